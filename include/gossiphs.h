@@ -0,0 +1,43 @@
+/*
+ * C interface of gossiphs, built by `cargo build --release --features capi`
+ * into libgossiphs.so, libgossiphs.dylib or gossiphs.dll.
+ *
+ * Results are json strings owned by the caller and freed by gossiphs_string_free.
+ * Functions return NULL on failure, gossiphs_last_error tells why.
+ */
+#ifndef GOSSIPHS_H
+#define GOSSIPHS_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct GossiphsGraph GossiphsGraph;
+
+/* the graph of the repo at project_path, with the default config */
+GossiphsGraph *gossiphs_graph_new(const char *project_path);
+
+/* the graph saved by `gossiphs index`, much faster than building it */
+GossiphsGraph *gossiphs_graph_load(const char *index_path);
+
+void gossiphs_graph_free(GossiphsGraph *graph);
+
+/* json array of the files related to file, the strongest first */
+char *gossiphs_related_files(const GossiphsGraph *graph, const char *file);
+
+/* json object of the symbols, commits and issues of file */
+char *gossiphs_file_metadata(const GossiphsGraph *graph, const char *file);
+
+/* sorted json array of the files of the graph */
+char *gossiphs_files(const GossiphsGraph *graph);
+
+void gossiphs_string_free(char *s);
+
+/* message of the last failure in this thread, valid until the next failure, NULL if none */
+const char *gossiphs_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -2,8 +2,7 @@ import networkx as nx
 from gossiphs import GraphConfig, create_graph, Graph
 
 # Create the graph using gossiphs
-config = GraphConfig()
-config.project_path = "../.."
+config = GraphConfig(project_path="../..")
 graph: Graph = create_graph(config)
 
 # Create a NetworkX directed graph
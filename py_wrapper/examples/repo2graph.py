@@ -1,8 +1,7 @@
 import networkx as nx
 from gossiphs import GraphConfig, create_graph, Graph
 
-config = GraphConfig()
-config.project_path = "../.."
+config = GraphConfig(project_path="../..")
 graph: Graph = create_graph(config)
 
 nx_graph = nx.DiGraph()
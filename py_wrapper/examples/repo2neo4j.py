@@ -3,8 +3,7 @@ from neo4j import GraphDatabase
 
 from gossiphs import GraphConfig, create_graph, Graph
 
-config = GraphConfig()
-config.project_path = "../.."
+config = GraphConfig(project_path="../..")
 graph: Graph = create_graph(config)
 
 nx_graph = nx.DiGraph()
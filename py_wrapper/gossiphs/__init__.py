@@ -1,5 +1,66 @@
 from gossiphs import _rust_api
 
 GraphConfig = _rust_api.GraphConfig
+ScoringConfig = _rust_api.ScoringConfig
 create_graph = _rust_api.create_graph
 Graph = _rust_api.Graph
+SymbolType = _rust_api.SymbolType
+CycleScope = _rust_api.CycleScope
+RelationDirection = _rust_api.RelationDirection
+GossiphsError = _rust_api.GossiphsError
+
+
+def _relations(graph):
+    relations = graph.list_all_relations()
+    files = {node.id: node.name for node in relations.file_nodes}
+    symbols = {node.id: node.name for node in relations.symbol_nodes}
+    return relations, files, symbols
+
+
+def to_networkx(self):
+    """Nodes and edges of the file relations, for networkx without depending on it.
+
+    An edge goes from a file to a file related to it, like `related_files`::
+
+        nodes, edges = graph.to_networkx()
+        g = nx.DiGraph()
+        g.add_nodes_from(nodes)
+        g.add_edges_from(edges)
+
+    `nx.from_edgelist(edges, create_using=nx.DiGraph)` also works, without the files related to no other.
+    """
+    relations, files, symbols = _relations(self)
+    nodes = [
+        (node.name, {"defs": node.defs, "refs": node.refs, "issues": node.issues})
+        for node in relations.file_nodes
+    ]
+    edges = [
+        (
+            files[relation.src],
+            files[relation.dst],
+            {
+                "score": relation.score,
+                "symbols": sorted(symbols[each] for each in relation.symbols),
+            },
+        )
+        for relation in relations.file_relations
+    ]
+    return nodes, edges
+
+
+def relations_dataframe(self):
+    """The file relations as records, one dict per relation, for `pandas.DataFrame(records)`."""
+    relations, files, symbols = _relations(self)
+    return [
+        {
+            "src": files[relation.src],
+            "dst": files[relation.dst],
+            "score": relation.score,
+            "symbols": sorted(symbols[each] for each in relation.symbols),
+        }
+        for relation in relations.file_relations
+    ]
+
+
+Graph.to_networkx = to_networkx
+Graph.relations_dataframe = relations_dataframe
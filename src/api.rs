@@ -1,209 +1,2142 @@
-use crate::graph::{Graph, RelatedSymbol};
+use crate::graph::{
+    extractor_of_extension, is_cross_language, is_test_file, normalize_path, progress_bar,
+    with_threads, Graph, RelatedSymbol,
+};
+use crate::history;
 use crate::symbol::{DefRefPair, RangeWrapper, Symbol, SymbolKind};
-use indicatif::ProgressBar;
-use pyo3::{pyclass, pymethods};
+use petgraph::algo::tarjan_scc;
+use petgraph::graph::{DiGraph, NodeIndex};
+use petgraph::visit::EdgeRef;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyType};
 use rayon::iter::IntoParallelRefIterator;
 use rayon::iter::ParallelIterator;
 use serde::{Deserialize, Serialize};
 use std::cmp::Reverse;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::Arc;
+use tracing::warn;
 
 #[derive(Serialize, Deserialize, Clone)]
 #[pyclass]
 pub struct RelatedFileContext {
     #[pyo3(get)]
     pub name: String,
+    #[pyo3(get)]
     pub score: usize,
+    #[pyo3(get)]
     pub defs: usize,
+    #[pyo3(get)]
     pub refs: usize,
 
     #[pyo3(get)]
     pub related_symbols: Vec<RelatedSymbol>,
 }
 
-#[derive(Serialize, Deserialize)]
+/// Keep the files scoring at least `min_score`, then the first `top` of them.
+/// `related_files` is sorted by score already, so these are the top ones.
+pub fn limit_related_files(
+    files: &mut Vec<RelatedFileContext>,
+    top: Option<usize>,
+    min_score: Option<usize>,
+) {
+    if let Some(min_score) = min_score {
+        files.retain(|each| each.score >= min_score);
+    }
+    if let Some(top) = top {
+        files.truncate(top);
+    }
+}
+
+/// Files of a directory related to another directory, see `Graph::related_directories`
+#[derive(Serialize, Deserialize, Clone)]
 #[pyclass]
-pub struct FileMetadata {
+pub struct RelatedDirectoryContext {
     #[pyo3(get)]
-    pub path: String,
+    pub name: String,
 
+    /// sum of the scores of the file pairs
     #[pyo3(get)]
-    pub commits: Vec<String>,
+    pub score: usize,
 
+    /// count of the related file pairs
     #[pyo3(get)]
-    pub symbols: Vec<Symbol>,
+    pub relations: usize,
+}
 
+/// Relations from the files of a package to the files of another one, see `Graph::package_relations`
+#[derive(Serialize, Deserialize, Clone)]
+#[pyclass]
+pub struct PackageRelation {
+    /// the package referencing the defs
     #[pyo3(get)]
-    pub issues: Vec<String>,
+    pub src: String,
+
+    /// the package defining them
+    #[pyo3(get)]
+    pub dst: String,
+
+    /// sum of the scores of the file pairs
+    #[pyo3(get)]
+    pub score: usize,
+
+    /// count of the related file pairs
+    #[pyo3(get)]
+    pub relations: usize,
 }
 
+/// A file ranked by `Graph::rank_files`
 #[derive(Serialize, Deserialize, Clone)]
 #[pyclass]
-pub enum LineKind {
-    FileNode,
-    FileRelation,
-    SymbolNode,
+pub struct FileRank {
+    #[pyo3(get)]
+    pub name: String,
+
+    /// pagerank over the file relations, sums up to 1 over all the files
+    #[pyo3(get)]
+    pub rank: f64,
+
+    /// sum of the scores of the files referencing this file
+    #[pyo3(get)]
+    pub in_score: usize,
+
+    /// sum of the scores of the files this file references
+    #[pyo3(get)]
+    pub out_score: usize,
 }
 
+/// A file ranked by `Graph::hotspots`
 #[derive(Serialize, Deserialize, Clone)]
 #[pyclass]
-pub struct FileNode {
+pub struct Hotspot {
+    #[pyo3(get)]
+    pub name: String,
+
+    /// count of the commits touching this file
+    #[pyo3(get)]
+    pub churn: usize,
+
+    /// count of the files referencing it or referenced by it
+    #[pyo3(get)]
+    pub coupled_files: usize,
+
+    /// sum of the scores of these relations
+    #[pyo3(get)]
+    pub coupling: usize,
+
+    /// churn * coupling, changed often and many files to break
+    #[pyo3(get)]
+    pub score: usize,
+}
+
+/// A file using the same symbols as another one, see `Graph::similar_files`
+#[derive(Serialize, Deserialize, Clone)]
+#[pyclass]
+pub struct SimilarFile {
+    #[pyo3(get)]
+    pub name: String,
+
+    /// jaccard index of the names referenced by both files, from 0 to 1
+    #[pyo3(get)]
+    pub similarity: f64,
+
+    /// the names referenced by both, sorted
+    #[pyo3(get)]
+    pub shared: Vec<String>,
+}
+
+const PAGERANK_DAMPING: f64 = 0.85;
+const PAGERANK_MAX_ITERATIONS: usize = 100;
+const PAGERANK_TOLERANCE: f64 = 1e-9;
+
+// weighted pagerank, `edges` are (src, dst, weight) with src pointing to dst
+fn pagerank(size: usize, edges: &[(usize, usize, usize)]) -> Vec<f64> {
+    if size == 0 {
+        return Vec::new();
+    }
+    let mut out_weights = vec![0usize; size];
+    for &(src, _, weight) in edges {
+        out_weights[src] += weight;
+    }
+
+    let base = 1.0 / size as f64;
+    let mut ranks = vec![base; size];
+    for _ in 0..PAGERANK_MAX_ITERATIONS {
+        // the files referencing nothing spread their rank over all the files
+        let dangling: f64 = (0..size)
+            .filter(|&i| out_weights[i] == 0)
+            .map(|i| ranks[i])
+            .sum();
+        let mut next = vec![(1.0 - PAGERANK_DAMPING + PAGERANK_DAMPING * dangling) * base; size];
+        for &(src, dst, weight) in edges {
+            next[dst] += PAGERANK_DAMPING * ranks[src] * weight as f64 / out_weights[src] as f64;
+        }
+        let delta: f64 = next.iter().zip(&ranks).map(|(a, b)| (a - b).abs()).sum();
+        ranks = next;
+        if delta < PAGERANK_TOLERANCE {
+            break;
+        }
+    }
+    ranks
+}
+
+/// Files grouped by `Graph::detect_communities`
+#[derive(Serialize, Deserialize, Clone)]
+#[pyclass]
+pub struct Community {
+    #[pyo3(get)]
+    pub id: usize,
+
+    #[pyo3(get)]
+    pub files: Vec<String>,
+
+    /// sum of the scores of the relations inside this community
+    #[pyo3(get)]
+    pub internal_score: usize,
+
+    /// sum of the scores of the relations crossing its border
+    #[pyo3(get)]
+    pub external_score: usize,
+}
+
+/// A file reached by `Graph::impacted_files`
+#[derive(Serialize, Deserialize, Clone)]
+#[pyclass]
+pub struct ImpactedFile {
+    #[pyo3(get)]
+    pub name: String,
+
+    /// the weakest relation score on the path, multiplied by the decay for each hop after the first
+    #[pyo3(get)]
+    pub score: f64,
+
+    /// 1 for the files in `related_files`
+    #[pyo3(get)]
+    pub hops: usize,
+
+    /// the file of the previous hop, referenced by this file
+    #[pyo3(get)]
+    pub via: String,
+}
+
+/// A file related to some changed files, see `Graph::impact`
+#[derive(Serialize, Deserialize, Clone)]
+#[pyclass]
+pub struct ImpactContext {
+    #[pyo3(get)]
+    pub name: String,
+
+    /// the sum of its scores to each changed file
+    #[pyo3(get)]
+    pub score: usize,
+
+    /// the changed files it relates to
+    #[pyo3(get)]
+    pub sources: Vec<String>,
+
+    /// defs of the changed files it references, sorted
+    #[pyo3(get)]
+    pub symbols: Vec<String>,
+}
+
+/// An author suggested by `Graph::suggest_reviewers`
+#[derive(Serialize, Deserialize, Clone)]
+#[pyclass]
+pub struct Reviewer {
+    /// `name <email>` of the commits
+    #[pyo3(get)]
+    pub name: String,
+
+    /// sum of the coupling scores over the commits touching the changed and related files
+    #[pyo3(get)]
+    pub score: usize,
+
+    /// count of these commits
+    #[pyo3(get)]
+    pub commits: usize,
+
+    /// the changed and related files this author committed to, sorted
+    #[pyo3(get)]
+    pub files: Vec<String>,
+}
+
+/// Authors owning a file, see `Graph::file_owners`
+#[derive(Serialize, Deserialize, Clone)]
+#[pyclass]
+pub struct FileOwnership {
+    #[pyo3(get)]
+    pub name: String,
+
+    /// count of the commits touching this file
+    #[pyo3(get)]
+    pub commits: usize,
+
+    /// (`name <email>`, share of the commits), the dominant first
+    #[pyo3(get)]
+    pub owners: Vec<(String, f64)>,
+}
+
+/// A file at risk found by `Graph::bus_factor`
+#[derive(Serialize, Deserialize, Clone)]
+#[pyclass]
+pub struct BusFactorRisk {
+    #[pyo3(get)]
+    pub name: String,
+
+    /// `name <email>` of the dominant author
+    #[pyo3(get)]
+    pub author: String,
+
+    /// share of the commits by this author, over the file and its coupled files
+    #[pyo3(get)]
+    pub share: f64,
+
+    /// count of these commits
+    #[pyo3(get)]
+    pub commits: usize,
+
+    /// count of the files related to it
+    #[pyo3(get)]
+    pub coupled_files: usize,
+}
+
+/// A def of a file referenced by another file, see `Graph::explain_relation`
+#[derive(Serialize, Deserialize, Clone)]
+#[pyclass]
+pub struct SymbolContribution {
+    #[pyo3(get)]
+    pub def: Symbol,
+
+    #[pyo3(get)]
+    pub reference: Symbol,
+
+    /// weight of the link, from the shared commits when the graph is built
+    #[pyo3(get)]
+    pub weight: usize,
+
+    /// multiplier of the symbol name, see `ScoringConfig::idf_damping`
+    #[pyo3(get)]
+    pub idf: f64,
+
+    /// weight divided by the count of defs in the file, at least `ScoringConfig::symbol_weight`,
+    /// multiplied by idf
+    #[pyo3(get)]
+    pub score: usize,
+}
+
+/// A commit changing both files, see `Graph::explain_relation`
+#[derive(Serialize, Deserialize, Clone)]
+#[pyclass]
+pub struct SharedCommit {
+    #[pyo3(get)]
+    pub sha: String,
+
+    /// count of the files changed by this commit
+    #[pyo3(get)]
+    pub files: usize,
+
+    /// multiplier by the age of the commit, see `ScoringConfig::half_life_days`
+    #[pyo3(get)]
+    pub decay: f64,
+
+    /// ((files of the graph - files of the commit) / files of the graph) ^ `large_commit_penalty`
+    /// * decay, 0 for large commits
+    #[pyo3(get)]
+    pub ratio: f64,
+}
+
+/// How the score of a relation is made up, see `Graph::explain_relation`
+#[derive(Serialize, Deserialize, Clone)]
+#[pyclass]
+pub struct RelationExplanation {
+    /// the file defining the symbols
+    #[pyo3(get)]
+    pub src: String,
+
+    /// the file referencing them
+    #[pyo3(get)]
+    pub dst: String,
+
+    /// same as the score of `dst` in `related_files(src)`
+    #[pyo3(get)]
+    pub score: usize,
+
+    /// the symbols linking them, the strongest first
+    #[pyo3(get)]
+    pub symbols: Vec<SymbolContribution>,
+
+    /// count of the defs of `src`, the weights are divided by it
+    #[pyo3(get)]
+    pub definitions: usize,
+
+    /// score of changing together without symbols, see `GraphConfig::co_change_weight`
+    #[pyo3(get)]
+    pub co_change: usize,
+
+    /// the commits changing both files
+    #[pyo3(get)]
+    pub commits: Vec<SharedCommit>,
+
+    /// count of the files of the graph
+    #[pyo3(get)]
+    pub files: usize,
+
+    /// commits changing this count of files or more are too large to count
+    #[pyo3(get)]
+    pub commit_size_limit: usize,
+
+    /// sum of the ratios of the commits
+    #[pyo3(get)]
+    pub ratio: f64,
+
+    /// `ScoringConfig::commit_weight`
     #[pyo3(get)]
-    id: usize,
+    pub commit_weight: f64,
+
+    /// count of the refs in `src`, complex files have lower ratios
+    #[pyo3(get)]
+    pub src_refs: usize,
+
+    /// `GraphConfig::cross_language_ratio` if they are in different languages, else 1
+    #[pyo3(get)]
+    pub language_ratio: f64,
+
+    /// `ScoringConfig::same_directory_boost` if they are in the same directory, else 1
+    #[pyo3(get)]
+    pub directory_boost: f64,
+
+    /// ratio * commit_weight / src_refs * language_ratio, at least 1: the weight of each symbol link.
+    /// 0 without any shared commit, then the symbols are only linked by names
+    #[pyo3(get)]
+    pub weight: usize,
+}
+
+/// Sizes of the graph, to sanity check it before trusting the numbers built on it, see `Graph::stats`
+#[derive(Serialize, Deserialize, Clone)]
+#[pyclass]
+pub struct GraphStats {
+    #[pyo3(get)]
+    pub files: usize,
+
+    #[pyo3(get)]
+    pub commits: usize,
+
+    // symbols in the symbol graph by kind, `DEF`, `REF` and `NAMESPACE`
+    #[pyo3(get)]
+    pub symbols: BTreeMap<String, usize>,
+
+    // symbols in the symbol graph by the extractor of their files
+    #[pyo3(get)]
+    pub languages: BTreeMap<String, usize>,
+
+    // links between the symbols
+    #[pyo3(get)]
+    pub symbol_links: usize,
+
+    // file pairs related in either direction
+    #[pyo3(get)]
+    pub relations: usize,
+
+    // related files of a file on average, in either direction
+    #[pyo3(get)]
+    pub average_degree: f64,
+
+    // files of the largest group connected by relations
+    #[pyo3(get)]
+    pub largest_component: usize,
+
+    // (stage, seconds) of building the graph, kept in the index
+    #[pyo3(get)]
+    pub build_timings: Vec<(String, f64)>,
+}
+
+/// A name defined in more files than a ref can link to, see `Graph::ambiguous_symbols`
+#[derive(Serialize, Deserialize, Clone)]
+#[pyclass]
+pub struct AmbiguousSymbol {
+    #[pyo3(get)]
+    pub name: String,
+
+    #[pyo3(get)]
+    pub definitions: usize,
+
+    // the files defining it, sorted
+    #[pyo3(get)]
+    pub files: Vec<String>,
+
+    // refs with this name, each linked to `def_limit` of the defs only
+    #[pyo3(get)]
+    pub references: usize,
+}
+
+/// A chain of relations between two files, see `Graph::paths_between_files`
+#[derive(Serialize, Deserialize, Clone)]
+#[pyclass]
+pub struct RelationPath {
+    /// from the defining file to the referencing file at the end
+    #[pyo3(get)]
+    pub files: Vec<String>,
+
+    /// `hops[i]` are the defs of `files[i]` referenced by `files[i + 1]`
+    #[pyo3(get)]
+    pub hops: Vec<Vec<DefRefPair>>,
+
+    /// the weakest relation score on the path
+    #[pyo3(get)]
+    pub score: usize,
+}
+
+// `paths_between_files` returns at most this many, the shortest first
+const MAX_RELATION_PATHS: usize = 32;
+
+// simple paths of exactly `left` more hops from `node` to `dst`
+fn walk_paths(
+    next: &[Vec<(usize, usize)>],
+    distance: &[usize],
+    dst: usize,
+    left: usize,
+    path: &mut Vec<(usize, usize)>,
+    found: &mut Vec<Vec<(usize, usize)>>,
+) {
+    let node = path.last().unwrap().0;
+    if left == 0 {
+        if node == dst {
+            found.push(path.clone());
+        }
+        return;
+    }
+    for &(neighbor, score) in &next[node] {
+        if found.len() >= MAX_RELATION_PATHS {
+            return;
+        }
+        let reachable = distance[neighbor] < left && (neighbor != dst || left == 1);
+        if reachable && !path.iter().any(|(visited, _)| *visited == neighbor) {
+            path.push((neighbor, score));
+            walk_paths(next, distance, dst, left - 1, path, found);
+            path.pop();
+        }
+    }
+}
+
+/// Granularity of `Graph::find_cycles`
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[pyclass]
+pub enum CycleScope {
+    FILE,
+    /// files merged into their directories
+    DIR,
+}
+
+/// Which way the related files point, see `Graph::related_files_towards`
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[pyclass]
+pub enum RelationDirection {
+    /// the files referencing this file, `Graph::related_files`
+    IN,
+    /// the files this file references, `Graph::outgoing_related_files`
+    OUT,
+    /// both of them, the scores of a file found in both are summed up
+    BOTH,
+}
+
+/// Files or directories depending on each other, see `Graph::find_cycles`
+#[derive(Serialize, Deserialize, Clone)]
+#[pyclass]
+pub struct Cycle {
+    #[pyo3(get)]
+    pub nodes: Vec<String>,
+
+    /// (referencing, defining, score) inside the cycle, the weakest first as they are the cheapest to break
+    #[pyo3(get)]
+    pub edges: Vec<(String, String, usize)>,
+
+    /// sum of the scores of the edges
+    #[pyo3(get)]
+    pub score: usize,
+}
+
+// louvain: move the nodes between communities while the modularity grows,
+// then merge each community into a node and start over.
+// relations are taken as undirected, nodes are visited in order so the result is stable.
+fn louvain(size: usize, edges: &[(usize, usize, usize)]) -> Vec<usize> {
+    // symmetric, self loops hold the weights inside the merged nodes
+    let mut adjacency: Vec<HashMap<usize, f64>> = vec![HashMap::new(); size];
+    for &(src, dst, weight) in edges {
+        *adjacency[src].entry(dst).or_default() += weight as f64;
+        *adjacency[dst].entry(src).or_default() += weight as f64;
+    }
+    // file -> node of the current level
+    let mut membership: Vec<usize> = (0..size).collect();
+
+    loop {
+        let communities = louvain_level(&adjacency);
+        let count = communities.iter().max().map_or(0, |max| max + 1);
+        if count == adjacency.len() {
+            break;
+        }
+        for node in membership.iter_mut() {
+            *node = communities[*node];
+        }
+        let mut merged: Vec<HashMap<usize, f64>> = vec![HashMap::new(); count];
+        for (node, neighbors) in adjacency.iter().enumerate() {
+            for (neighbor, weight) in neighbors {
+                *merged[communities[node]]
+                    .entry(communities[*neighbor])
+                    .or_default() += weight;
+            }
+        }
+        adjacency = merged;
+    }
+    membership
+}
+
+// one level of louvain, returns the community of each node, numbered from 0
+fn louvain_level(adjacency: &[HashMap<usize, f64>]) -> Vec<usize> {
+    let size = adjacency.len();
+    let degrees: Vec<f64> = adjacency
+        .iter()
+        .map(|neighbors| neighbors.values().sum())
+        .collect();
+    let total: f64 = degrees.iter().sum();
+    let mut communities: Vec<usize> = (0..size).collect();
+    if total == 0.0 {
+        return communities;
+    }
+    let mut community_degrees = degrees.clone();
+
+    let mut moved = true;
+    while moved {
+        moved = false;
+        for node in 0..size {
+            let current = communities[node];
+            community_degrees[current] -= degrees[node];
+
+            let mut links: HashMap<usize, f64> = HashMap::new();
+            for (neighbor, weight) in &adjacency[node] {
+                if *neighbor != node {
+                    *links.entry(communities[*neighbor]).or_default() += weight;
+                }
+            }
+            let gain = |community: usize, link: f64| {
+                link - community_degrees[community] * degrees[node] / total
+            };
+            let mut best = current;
+            let mut best_gain = gain(current, links.get(&current).copied().unwrap_or(0.0));
+            let mut candidates: Vec<(usize, f64)> = links.into_iter().collect();
+            candidates.sort_by_key(|(community, _)| *community);
+            for (community, link) in candidates {
+                let community_gain = gain(community, link);
+                if community_gain > best_gain + 1e-12 {
+                    best = community;
+                    best_gain = community_gain;
+                }
+            }
+
+            community_degrees[best] += degrees[node];
+            if best != current {
+                communities[node] = best;
+                moved = true;
+            }
+        }
+    }
+
+    let mut ids: HashMap<usize, usize> = HashMap::new();
+    communities
+        .iter()
+        .map(|community| {
+            let next = ids.len();
+            *ids.entry(*community).or_insert(next)
+        })
+        .collect()
+}
+
+// `.` for the files in the root
+fn parent_dir(file: &str) -> &str {
+    file.rsplit_once('/').map(|(dir, _)| dir).unwrap_or(".")
+}
+
+/// Whether the file is under the normalized dir, `.` or empty for the root.
+pub fn in_dir(file: &str, dir: &str) -> bool {
+    dir.is_empty()
+        || dir == "."
+        || file
+            .strip_prefix(dir)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+#[derive(Serialize, Deserialize)]
+#[pyclass]
+pub struct FileMetadata {
+    #[pyo3(get)]
+    pub path: String,
+
+    #[pyo3(get)]
+    pub commits: Vec<String>,
+
+    #[pyo3(get)]
+    pub symbols: Vec<Symbol>,
+
+    #[pyo3(get)]
+    pub issues: Vec<String>,
+
+    // over `GraphConfig::symbol_limit`, only a part of its symbols are kept
+    #[pyo3(get)]
+    pub truncated: bool,
+}
+
+/// A commit touching a file, see `Graph::file_commits_detailed`
+#[derive(Serialize, Deserialize, Clone)]
+#[pyclass]
+pub struct CommitInfo {
+    #[pyo3(get)]
+    pub sha: String,
+
+    /// `name <email>`
+    #[pyo3(get)]
+    pub author: String,
+
+    /// seconds since the epoch
+    #[pyo3(get)]
+    pub timestamp: i64,
+
+    /// first line of the message
+    #[pyo3(get)]
+    pub subject: String,
+}
+
+// function A refers to function B
+#[derive(Serialize, Deserialize, Clone)]
+#[pyclass]
+pub struct FunctionEdge {
+    #[pyo3(get)]
+    pub src: Symbol,
+
+    #[pyo3(get)]
+    pub dst: Symbol,
+
+    #[pyo3(get)]
+    pub weight: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[pyclass]
+pub enum LineKind {
+    FileNode,
+    FileRelation,
+    SymbolNode,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[pyclass]
+pub struct FileNode {
+    #[pyo3(get)]
+    pub id: usize,
+
+    #[pyo3(get)]
+    pub kind: LineKind,
+
+    #[pyo3(get)]
+    pub name: String,
+
+    #[pyo3(get)]
+    pub defs: usize,
+
+    #[pyo3(get)]
+    pub refs: usize,
+
+    #[pyo3(get)]
+    pub issues: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[pyclass]
+pub struct FileRelation {
+    #[pyo3(get)]
+    pub id: usize,
+
+    #[pyo3(get)]
+    pub kind: LineKind,
+
+    #[pyo3(get)]
+    pub src: usize,
+
+    #[pyo3(get)]
+    pub dst: usize,
+
+    #[pyo3(get)]
+    pub score: usize,
+
+    #[pyo3(get)]
+    pub symbols: Vec<usize>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[pyclass]
+pub struct SymbolNode {
+    #[pyo3(get)]
+    pub id: usize,
+
+    #[pyo3(get)]
+    pub kind: LineKind,
+
+    #[pyo3(get)]
+    pub name: String,
+
+    #[pyo3(get)]
+    pub range: RangeWrapper,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[pyclass]
+pub struct RelationList {
+    #[pyo3(get)]
+    pub file_nodes: Vec<FileNode>,
+
+    #[pyo3(get)]
+    pub file_relations: Vec<FileRelation>,
+
+    #[pyo3(get)]
+    pub symbol_nodes: Vec<SymbolNode>,
+}
+
+/// One line of `list_all_relations`, serialized as the inner struct.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum RelationLine {
+    FileNode(FileNode),
+    FileRelation(FileRelation),
+    SymbolNode(SymbolNode),
+}
+
+// files whose related files are kept in memory at the same time
+const RELATION_CHUNK_SIZE: usize = 256;
+
+impl Graph {
+    /// All files which pointed to this file
+    pub fn related_files(&self, file_name: String) -> Vec<RelatedFileContext> {
+        let file_name = self.normalize_path(&file_name);
+        match &self.related_index {
+            Some(index) => index.get(&file_name).cloned().unwrap_or_default(),
+            None => self.compute_related_files(&file_name),
+        }
+    }
+
+    /// `related_files` of each file, by the file names as given.
+    pub fn related_files_batch(
+        &self,
+        file_names: Vec<String>,
+    ) -> HashMap<String, Vec<RelatedFileContext>> {
+        with_threads(self.threads, || {
+            file_names
+                .par_iter()
+                .map(|file_name| (file_name.clone(), self.related_files(file_name.clone())))
+                .collect()
+        })
+    }
+
+    pub fn list_all_relations(&self) -> RelationList {
+        // https://github.com/williamfzc/gossiphs/issues/38
+        // node: file, symbol
+        // edge: file relation
+        let mut ret = RelationList {
+            file_nodes: Vec::new(),
+            file_relations: Vec::new(),
+            symbol_nodes: Vec::new(),
+        };
+        self.for_each_relation(|line| match line {
+            RelationLine::FileNode(node) => ret.file_nodes.push(node),
+            RelationLine::FileRelation(relation) => ret.file_relations.push(relation),
+            RelationLine::SymbolNode(node) => ret.symbol_nodes.push(node),
+        });
+        ret
+    }
+
+    /// Walk the relations without holding all of them: file nodes first,
+    /// then each symbol node right before the first relation referring to it.
+    pub fn for_each_relation<F>(&self, mut visit: F)
+    where
+        F: FnMut(RelationLine),
+    {
+        let mut files: Vec<String> = self.files().into_iter().collect();
+        files.sort();
+        let file_id_map: HashMap<&String, usize> = files
+            .iter()
+            .enumerate()
+            .map(|(i, file)| (file, i))
+            .collect();
+
+        for (id, file) in files.iter().enumerate() {
+            visit(RelationLine::FileNode(FileNode {
+                id,
+                kind: LineKind::FileNode,
+                name: file.to_string(),
+                defs: self.symbol_graph.list_definitions(file).len(),
+                refs: self.symbol_graph.list_references(file).len(),
+                issues: self.list_file_issues(file.to_string()),
+            }));
+        }
+
+        let mut symbol_ids: HashMap<String, usize> = HashMap::new();
+        let mut cur_id = files.len();
+        let pb = progress_bar(self.show_progress, files.len());
+        for chunk in files.chunks(RELATION_CHUNK_SIZE) {
+            let results: Vec<Vec<RelatedFileContext>> = with_threads(self.threads, || {
+                chunk
+                    .par_iter()
+                    .map(|file| {
+                        pb.inc(1);
+                        self.related_files(file.clone())
+                    })
+                    .collect()
+            });
+
+            for (file, related_files) in chunk.iter().zip(results) {
+                let src_id = file_id_map[file];
+                for related_file in related_files {
+                    let dst_id = match file_id_map.get(&related_file.name) {
+                        Some(&dst_id) => dst_id,
+                        None => continue,
+                    };
+                    let mut symbols: HashSet<usize> = HashSet::new();
+                    for s in related_file
+                        .related_symbols
+                        .iter()
+                        .filter(|s| s.symbol.kind == SymbolKind::DEF)
+                    {
+                        let symbol_id = s.symbol.id();
+                        let id = match symbol_ids.get(&symbol_id) {
+                            Some(&id) => id,
+                            None => {
+                                let id = cur_id;
+                                cur_id += 1;
+                                symbol_ids.insert(symbol_id, id);
+                                visit(RelationLine::SymbolNode(SymbolNode {
+                                    id,
+                                    kind: LineKind::SymbolNode,
+                                    name: s.symbol.name.to_string(),
+                                    range: s.symbol.range,
+                                }));
+                                id
+                            }
+                        };
+                        symbols.insert(id);
+                    }
+                    visit(RelationLine::FileRelation(FileRelation {
+                        id: cur_id,
+                        kind: LineKind::FileRelation,
+                        src: src_id,
+                        dst: dst_id,
+                        score: related_file.score,
+                        symbols: symbols.into_iter().collect(),
+                    }));
+                    cur_id += 1;
+                }
+            }
+        }
+        pb.finish_and_clear();
+    }
+
+    // sorted files, and the relations between them as (referencing, defining, score)
+    fn file_edges(&self) -> (Vec<String>, Vec<(usize, usize, usize)>) {
+        let mut files: Vec<String> = self.files().into_iter().collect();
+        files.sort();
+        let ids: HashMap<&str, usize> = files
+            .iter()
+            .enumerate()
+            .map(|(i, file)| (file.as_str(), i))
+            .collect();
+
+        let pb = progress_bar(self.show_progress, files.len());
+        let related: Vec<Vec<RelatedFileContext>> = with_threads(self.threads, || {
+            files
+                .par_iter()
+                .map(|file| {
+                    pb.inc(1);
+                    self.related_files(file.clone())
+                })
+                .collect()
+        });
+        pb.finish_and_clear();
+
+        let mut edges = Vec::new();
+        for (dst, related_files) in related.iter().enumerate() {
+            for each in related_files {
+                if let Some(&src) = ids.get(each.name.as_str()) {
+                    edges.push((src, dst, each.score));
+                }
+            }
+        }
+        (files, edges)
+    }
+
+    // (author, share of these commits), the dominant first
+    fn author_shares(&self, commits: &[String]) -> Vec<(String, f64)> {
+        let mut counter: HashMap<String, usize> = HashMap::new();
+        for commit in commits {
+            for author in self
+                ._relation_graph
+                .commit_related_authors(commit)
+                .unwrap_or_default()
+            {
+                *counter.entry(author).or_default() += 1;
+            }
+        }
+        let mut shares: Vec<(String, f64)> = counter
+            .into_iter()
+            .map(|(author, count)| (author, count as f64 / commits.len() as f64))
+            .collect();
+        shares.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        shares
+    }
+
+    // the deepest package containing the normalized file
+    fn package_dir(&self, file_name: &str) -> &str {
+        self.packages
+            .iter()
+            .filter(|package| in_dir(file_name, package))
+            .max_by_key(|package| package.len())
+            .map_or(".", |package| package.as_str())
+    }
+
+    /// Multiplier of the symbol name, see `ScoringConfig::idf_damping`
+    fn idf_of(&self, name: &str) -> f64 {
+        self.symbol_idf.get(name).copied().unwrap_or(1.0)
+    }
+
+    fn compute_related_files(&self, file_name: &str) -> Vec<RelatedFileContext> {
+        if !self.symbol_graph.file_mapping.contains_key(file_name) {
+            return Vec::new();
+        }
+
+        // find all the defs in this file
+        // and tracking all the references and theirs
+        let mut file_counter = HashMap::new();
+        let mut file_ref_mapping: HashMap<Arc<str>, Vec<RelatedSymbol>> = HashMap::new();
+
+        // other files -> this file
+        let definitions_in_file = self.symbol_graph.list_definitions(file_name);
+        let definition_count = definitions_in_file.len();
+
+        definitions_in_file.iter().for_each(|def| {
+            self.symbol_graph
+                .list_references_by_definition(&def.id())
+                .iter()
+                .for_each(|(each_ref, weight)| {
+                    let real_weight =
+                        self.scoring
+                            .link_score(*weight, definition_count, self.idf_of(&def.name));
+
+                    file_counter.entry(each_ref.file.clone()).or_insert(0);
+                    file_counter
+                        .entry(each_ref.file.clone())
+                        .and_modify(|w| *w += real_weight)
+                        .or_insert(real_weight);
+
+                    file_ref_mapping
+                        .entry(each_ref.file.clone())
+                        .and_modify(|v| {
+                            v.push(RelatedSymbol {
+                                symbol: each_ref.clone(),
+                                weight: real_weight,
+                            })
+                        })
+                        .or_insert(vec![RelatedSymbol {
+                            symbol: each_ref.clone(),
+                            weight: real_weight,
+                        }]);
+                });
+        });
+
+        definitions_in_file.iter().for_each(|def| {
+            self.symbol_graph
+                .list_references_by_definition(&def.id())
+                .into_iter()
+                .map(|s| s.0.file)
+                .for_each(|f| {
+                    file_ref_mapping
+                        .entry(f.clone())
+                        .and_modify(|v| {
+                            v.push(RelatedSymbol {
+                                symbol: def.clone(),
+                                weight: 0,
+                            })
+                        })
+                        .or_insert(vec![RelatedSymbol {
+                            symbol: def.clone(),
+                            weight: 0,
+                        }]);
+                });
+        });
+
+        // this file -> other files, see `outgoing_related_files`
+
+        // changed together, no symbols involved
+        if let Some(co_changes) = self.co_changes.get(file_name) {
+            for (other, score) in co_changes {
+                *file_counter.entry(Arc::from(other.as_str())).or_insert(0) += score;
+            }
+        }
+
+        // remove itself
+        file_counter.remove(file_name);
+
+        if self.scoring.same_directory_boost != 1.0 {
+            let dir = parent_dir(file_name);
+            file_counter
+                .iter_mut()
+                .filter(|(other, _)| parent_dir(other) == dir)
+                .for_each(|(_, score)| {
+                    *score = (*score as f64 * self.scoring.same_directory_boost) as usize
+                });
+        }
+
+        let mut contexts = file_counter
+            .iter()
+            .map(|(k, v)| {
+                let related_symbols = file_ref_mapping.get(k).cloned().unwrap_or_default();
+                RelatedFileContext {
+                    name: k.to_string(),
+                    score: *v,
+                    defs: self.symbol_graph.list_definitions(k).len(),
+                    refs: self.symbol_graph.list_references(k).len(),
+                    related_symbols,
+                }
+            })
+            .collect::<Vec<_>>();
+        contexts.sort_by_key(|context| Reverse(context.score));
+        contexts
+    }
+}
+
+// Read API v1
+#[pymethods]
+impl Graph {
+    /// The repo of the graph, the local clone for a remote one
+    #[getter]
+    pub fn project_path(&self) -> &str {
+        &self.project_path
+    }
+
+    /// The analyzed commit, none for the working tree
+    #[getter(commit_id)]
+    fn py_commit_id(&self) -> Option<String> {
+        self.commit_id.clone()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Graph(project_path={:?}, files={})",
+            self.project_path,
+            self.file_contexts.len()
+        )
+    }
+
+    /// Write the graph to a binary index file, `Graph.load` reads it back without analysis
+    #[pyo3(name = "save")]
+    fn py_save(&self, py: Python<'_>, path: String) -> PyResult<()> {
+        py.allow_threads(|| self.save(&path))?;
+        Ok(())
+    }
+
+    #[classmethod]
+    #[pyo3(name = "load")]
+    fn py_load(_cls: &Bound<'_, PyType>, py: Python<'_>, path: String) -> PyResult<Graph> {
+        let g = py.allow_threads(|| Graph::load(&path))?;
+        Ok(g)
+    }
+
+    /// The content of the index file, see `save`
+    #[pyo3(name = "to_bytes")]
+    fn py_to_bytes(&self, py: Python<'_>) -> PyResult<Py<PyBytes>> {
+        let content = py.allow_threads(|| self.to_bytes())?;
+        Ok(PyBytes::new_bound(py, &content).unbind())
+    }
+
+    #[classmethod]
+    #[pyo3(name = "from_bytes")]
+    fn py_from_bytes(_cls: &Bound<'_, PyType>, py: Python<'_>, content: &[u8]) -> PyResult<Graph> {
+        let g = py.allow_threads(|| Graph::from_bytes(content))?;
+        Ok(g)
+    }
+
+    // pickled as the index, the class is found as `gossiphs.Graph`
+    fn __reduce__(&self, py: Python<'_>) -> PyResult<(PyObject, (Py<PyBytes>,))> {
+        let from_bytes = py.get_type_bound::<Graph>().getattr("from_bytes")?;
+        Ok((from_bytes.unbind(), (self.py_to_bytes(py)?,)))
+    }
+
+    pub fn files(&self) -> HashSet<String> {
+        self.file_contexts
+            .iter()
+            .map(|each| each.path.clone())
+            .collect()
+    }
+
+    /// The key of a file in this graph, see `graph::normalize_path`
+    pub fn normalize_path(&self, file_name: &str) -> String {
+        normalize_path(&self.project_roots, file_name)
+    }
+
+    /// All files which pointed to this file, without holding the GIL
+    #[pyo3(name = "related_files")]
+    fn py_related_files(&self, py: Python<'_>, file_name: String) -> Vec<RelatedFileContext> {
+        py.allow_threads(|| self.related_files(file_name))
+    }
+
+    /// `related_files` of each file, computed in parallel, by the file names as given
+    #[pyo3(name = "related_files_batch")]
+    fn py_related_files_batch(
+        &self,
+        py: Python<'_>,
+        file_names: Vec<String>,
+    ) -> HashMap<String, Vec<RelatedFileContext>> {
+        py.allow_threads(|| self.related_files_batch(file_names))
+    }
+
+    /// All files which this file pointed to, the ones defining the symbols it references.
+    /// The scores and symbols are the ones of this file in their `related_files`.
+    pub fn outgoing_related_files(&self, file_name: String) -> Vec<RelatedFileContext> {
+        let file_name = self.normalize_path(&file_name);
+        let mut targets: HashSet<Arc<str>> = self
+            .symbol_graph
+            .list_references(&file_name)
+            .iter()
+            .flat_map(|each_ref| {
+                self.symbol_graph
+                    .list_definitions_by_reference(&each_ref.id())
+                    .into_keys()
+                    .map(|def| def.file)
+            })
+            .collect();
+        if let Some(co_changes) = self.co_changes.get(&file_name) {
+            targets.extend(co_changes.keys().map(|other| Arc::from(other.as_str())));
+        }
+        targets.remove(file_name.as_str());
+
+        let mut contexts: Vec<RelatedFileContext> = targets
+            .into_iter()
+            .filter_map(|target| {
+                let incoming = self
+                    .related_files(target.to_string())
+                    .into_iter()
+                    .find(|each| each.name == file_name)?;
+                Some(RelatedFileContext {
+                    name: target.to_string(),
+                    score: incoming.score,
+                    defs: self.symbol_graph.list_definitions(&target).len(),
+                    refs: self.symbol_graph.list_references(&target).len(),
+                    related_symbols: incoming.related_symbols,
+                })
+            })
+            .collect();
+        contexts.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.name.cmp(&b.name)));
+        contexts
+    }
+
+    /// `related_files`, `outgoing_related_files` or both of them merged, by `direction`
+    pub fn related_files_towards(
+        &self,
+        file_name: String,
+        direction: RelationDirection,
+    ) -> Vec<RelatedFileContext> {
+        match direction {
+            RelationDirection::IN => self.related_files(file_name),
+            RelationDirection::OUT => self.outgoing_related_files(file_name),
+            RelationDirection::BOTH => {
+                let mut merged: HashMap<String, RelatedFileContext> = HashMap::new();
+                for each in self
+                    .related_files(file_name.clone())
+                    .into_iter()
+                    .chain(self.outgoing_related_files(file_name))
+                {
+                    match merged.get_mut(&each.name) {
+                        Some(existing) => {
+                            existing.score += each.score;
+                            existing.related_symbols.extend(each.related_symbols);
+                        }
+                        None => {
+                            merged.insert(each.name.clone(), each);
+                        }
+                    }
+                }
+                let mut contexts: Vec<RelatedFileContext> = merged.into_values().collect();
+                contexts.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.name.cmp(&b.name)));
+                contexts
+            }
+        }
+    }
+
+    /// All directories which pointed to the files under this directory,
+    /// the scores of the file pairs summed up by the directory of the referencing file.
+    /// Relations inside this directory are left out.
+    pub fn related_directories(&self, dir: String) -> Vec<RelatedDirectoryContext> {
+        let dir = self.normalize_path(&dir).trim_end_matches('/').to_string();
+        let files: Vec<String> = self
+            .files()
+            .into_iter()
+            .filter(|file| in_dir(file, &dir))
+            .collect();
+        let related: Vec<Vec<RelatedFileContext>> = with_threads(self.threads, || {
+            files
+                .par_iter()
+                .map(|file| self.related_files(file.clone()))
+                .collect()
+        });
+
+        let mut counter: HashMap<&str, (usize, usize)> = HashMap::new();
+        for each in related.iter().flatten() {
+            if in_dir(&each.name, &dir) {
+                continue;
+            }
+            let entry = counter.entry(parent_dir(&each.name)).or_default();
+            entry.0 += each.score;
+            entry.1 += 1;
+        }
+        let mut contexts: Vec<RelatedDirectoryContext> = counter
+            .into_iter()
+            .map(|(name, (score, relations))| RelatedDirectoryContext {
+                name: name.to_string(),
+                score,
+                relations,
+            })
+            .collect();
+        contexts.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.name.cmp(&b.name)));
+        contexts
+    }
+
+    /// Counts of the files, symbols and relations of this graph, and the time of building it.
+    pub fn stats(&self) -> GraphStats {
+        let mut symbols: BTreeMap<String, usize> = BTreeMap::new();
+        let mut languages: BTreeMap<String, usize> = BTreeMap::new();
+        for symbol in self
+            .symbol_graph
+            .g
+            .node_weights()
+            .filter_map(|node| node.get_symbol())
+        {
+            *symbols.entry(format!("{:?}", symbol.kind)).or_default() += 1;
+            let file_extension = symbol.file.split('.').next_back().unwrap_or_default();
+            if let Some(extractor) = extractor_of_extension(&file_extension.to_lowercase()) {
+                *languages.entry(format!("{:?}", extractor)).or_default() += 1;
+            }
+        }
+
+        let (files, edges) = self.file_edges();
+        let pairs: HashSet<(usize, usize)> = edges
+            .iter()
+            .map(|&(src, dst, _)| (src.min(dst), src.max(dst)))
+            .collect();
+        // union-find over the related pairs
+        let mut parents: Vec<usize> = (0..files.len()).collect();
+        fn root(parents: &mut [usize], mut node: usize) -> usize {
+            while parents[node] != node {
+                parents[node] = parents[parents[node]];
+                node = parents[node];
+            }
+            node
+        }
+        for &(a, b) in &pairs {
+            let (root_a, root_b) = (root(&mut parents, a), root(&mut parents, b));
+            parents[root_a] = root_b;
+        }
+        let mut sizes: HashMap<usize, usize> = HashMap::new();
+        for node in 0..files.len() {
+            *sizes.entry(root(&mut parents, node)).or_default() += 1;
+        }
+
+        GraphStats {
+            files: files.len(),
+            commits: self._relation_graph.commits().len(),
+            symbols,
+            languages,
+            symbol_links: self.symbol_graph.g.edge_count(),
+            relations: pairs.len(),
+            average_degree: match files.len() {
+                0 => 0.0,
+                n => 2.0 * pairs.len() as f64 / n as f64,
+            },
+            largest_component: sizes.values().copied().max().unwrap_or_default(),
+            build_timings: self.build_timings.clone(),
+        }
+    }
+
+    /// Root directories of the packages found by their manifests (`Cargo.toml`, `package.json`,
+    /// `go.mod`, `pom.xml`), `.` for the root of the repo.
+    pub fn packages(&self) -> Vec<String> {
+        self.packages.clone()
+    }
+
+    /// The package this file belongs to: the deepest package directory containing it, `.` if none.
+    pub fn package_of(&self, file_name: String) -> String {
+        self.package_dir(&self.normalize_path(&file_name))
+            .to_string()
+    }
+
+    /// The file relations crossing packages, summed up by the pair of packages, the strongest first.
+    pub fn package_relations(&self) -> Vec<PackageRelation> {
+        let (files, edges) = self.file_edges();
+        let packages: Vec<&str> = files.iter().map(|file| self.package_dir(file)).collect();
+
+        let mut counter: HashMap<(&str, &str), (usize, usize)> = HashMap::new();
+        for &(src, dst, score) in &edges {
+            if packages[src] == packages[dst] {
+                continue;
+            }
+            let entry = counter.entry((packages[src], packages[dst])).or_default();
+            entry.0 += score;
+            entry.1 += 1;
+        }
+        let mut relations: Vec<PackageRelation> = counter
+            .into_iter()
+            .map(|((src, dst), (score, relations))| PackageRelation {
+                src: src.to_string(),
+                dst: dst.to_string(),
+                score,
+                relations,
+            })
+            .collect();
+        relations.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then_with(|| a.src.cmp(&b.src))
+                .then_with(|| a.dst.cmp(&b.dst))
+        });
+        relations
+    }
+
+    /// Files ranked by pagerank, the files the most used by the other important files first.
+    /// A relation is a link from the referencing file to the defining file, weighted by its score.
+    pub fn rank_files(&self) -> Vec<FileRank> {
+        let (files, edges) = self.file_edges();
+        let mut in_scores = vec![0; files.len()];
+        let mut out_scores = vec![0; files.len()];
+        for &(src, dst, score) in &edges {
+            in_scores[dst] += score;
+            out_scores[src] += score;
+        }
+
+        let ranks = pagerank(files.len(), &edges);
+        let mut ret: Vec<FileRank> = files
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| FileRank {
+                name,
+                rank: ranks[i],
+                in_score: in_scores[i],
+                out_score: out_scores[i],
+            })
+            .collect();
+        ret.sort_by(|a, b| b.rank.total_cmp(&a.rank).then_with(|| a.name.cmp(&b.name)));
+        ret
+    }
+
+    /// Files referencing the same names as this file, the most similar first, `top_n` of them at most.
+    /// Only the vocabulary counts, not the history, so copy-pasted modules are found even if they
+    /// were never changed together.
+    pub fn similar_files(&self, file_name: String, top_n: usize) -> Vec<SimilarFile> {
+        let file_name = self.normalize_path(&file_name);
+        let vocabulary = |file: &str| -> HashSet<Arc<str>> {
+            self.symbol_graph
+                .list_references(file)
+                .into_iter()
+                .map(|symbol| symbol.name)
+                .collect()
+        };
+        let names = vocabulary(&file_name);
+        if names.is_empty() {
+            return Vec::new();
+        }
+
+        let files: Vec<String> = self.files().into_iter().collect();
+        let mut similar: Vec<SimilarFile> = with_threads(self.threads, || {
+            files
+                .par_iter()
+                .filter(|file| *file != &file_name)
+                .filter_map(|file| {
+                    let others = vocabulary(file);
+                    let mut shared: Vec<String> = names
+                        .intersection(&others)
+                        .map(|name| name.to_string())
+                        .collect();
+                    if shared.is_empty() {
+                        return None;
+                    }
+                    shared.sort();
+                    let union = names.len() + others.len() - shared.len();
+                    Some(SimilarFile {
+                        name: file.clone(),
+                        similarity: shared.len() as f64 / union as f64,
+                        shared,
+                    })
+                })
+                .collect()
+        });
+        similar.sort_by(|a, b| {
+            b.similarity
+                .total_cmp(&a.similarity)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        similar.truncate(top_n);
+        similar
+    }
+
+    /// Names with more defs than `GraphConfig::def_limit`, the most defined first.
+    /// A ref of these names links to the `def_limit` defs sharing the most commits with it, the relations to
+    /// the other defs are dropped. Raise `def_limit` or add the generic ones to `symbol_stopwords`.
+    pub fn ambiguous_symbols(&self) -> Vec<AmbiguousSymbol> {
+        let mut defs: HashMap<Arc<str>, (usize, BTreeSet<String>)> = HashMap::new();
+        let mut refs: HashMap<Arc<str>, usize> = HashMap::new();
+        for symbol in self
+            .symbol_graph
+            .g
+            .node_weights()
+            .filter_map(|node| node.get_symbol())
+        {
+            match symbol.kind {
+                SymbolKind::DEF => {
+                    let entry = defs.entry(symbol.name.clone()).or_default();
+                    entry.0 += 1;
+                    entry.1.insert(symbol.file.to_string());
+                }
+                SymbolKind::REF => *refs.entry(symbol.name.clone()).or_default() += 1,
+                SymbolKind::NAMESPACE => {}
+            }
+        }
+
+        let mut ambiguous: Vec<AmbiguousSymbol> = defs
+            .into_iter()
+            .filter(|(_, (definitions, _))| *definitions > self.def_limit)
+            .map(|(name, (definitions, files))| AmbiguousSymbol {
+                references: refs.get(&name).copied().unwrap_or_default(),
+                name: name.to_string(),
+                definitions,
+                files: files.into_iter().collect(),
+            })
+            .collect();
+        ambiguous.sort_by(|a, b| {
+            b.definitions
+                .cmp(&a.definitions)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        ambiguous
+    }
+
+    /// Files no other file relates to, sorted. Entry points and tests are usually among them,
+    /// the others may be dead code.
+    pub fn orphan_files(&self) -> Vec<String> {
+        let files: Vec<String> = self.files().into_iter().collect();
+        let mut orphans: Vec<String> = with_threads(self.threads, || {
+            files
+                .par_iter()
+                .filter(|file| {
+                    self.related_files(file.to_string())
+                        .iter()
+                        .all(|each| each.score == 0)
+                })
+                .cloned()
+                .collect()
+        });
+        orphans.sort();
+        orphans
+    }
 
-    #[pyo3(get)]
-    kind: LineKind,
+    /// Defs referenced nowhere, by the file and the position.
+    /// Names used only in the same file, or called in a way the extractors miss, are included.
+    pub fn unreferenced_definitions(&self) -> Vec<Symbol> {
+        let mut defs: Vec<Symbol> = self
+            .file_contexts
+            .iter()
+            .flat_map(|file_context| self.symbol_graph.list_definitions(&file_context.path))
+            .filter(|def| {
+                self.symbol_graph
+                    .list_references_by_definition(&def.id())
+                    .is_empty()
+            })
+            .collect();
+        defs.sort_by(|a, b| a.file.cmp(&b.file).then(a.range.cmp(&b.range)));
+        defs
+    }
 
-    #[pyo3(get)]
-    name: String,
+    /// Files changed often and coupled with many others, the riskiest first.
+    /// Churn counts the commits in the history window of the graph.
+    pub fn hotspots(&self) -> Vec<Hotspot> {
+        let (files, edges) = self.file_edges();
+        let mut neighbors: Vec<HashSet<usize>> = vec![HashSet::new(); files.len()];
+        let mut coupling = vec![0; files.len()];
+        for &(src, dst, score) in &edges {
+            if src == dst || score == 0 {
+                continue;
+            }
+            neighbors[src].insert(dst);
+            neighbors[dst].insert(src);
+            coupling[src] += score;
+            coupling[dst] += score;
+        }
 
-    #[pyo3(get)]
-    issues: Vec<String>,
-}
+        let mut ret: Vec<Hotspot> = files
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let churn = self.list_file_commits(name.clone()).len();
+                Hotspot {
+                    name,
+                    churn,
+                    coupled_files: neighbors[i].len(),
+                    coupling: coupling[i],
+                    score: churn * coupling[i],
+                }
+            })
+            .collect();
+        ret.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then_with(|| b.churn.cmp(&a.churn))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        ret
+    }
 
-#[derive(Serialize, Deserialize, Clone)]
-#[pyclass]
-pub struct FileRelation {
-    #[pyo3(get)]
-    id: usize,
+    /// Strongly connected components of the relations, from the referencing file to the defining one.
+    /// With `CycleScope::DIR`, a directory referencing its own files is not a cycle.
+    pub fn find_cycles(&self, scope: CycleScope) -> Vec<Cycle> {
+        let (files, edges) = self.file_edges();
+        let (names, edges): (Vec<String>, Vec<(usize, usize, usize)>) = match scope {
+            CycleScope::FILE => (files, edges),
+            CycleScope::DIR => {
+                let mut dirs: Vec<&str> = files.iter().map(|file| parent_dir(file)).collect();
+                dirs.sort();
+                dirs.dedup();
+                let ids: HashMap<&str, usize> =
+                    dirs.iter().enumerate().map(|(i, dir)| (*dir, i)).collect();
+                let mut scores: HashMap<(usize, usize), usize> = HashMap::new();
+                for (src, dst, score) in edges {
+                    let src = ids[parent_dir(&files[src])];
+                    let dst = ids[parent_dir(&files[dst])];
+                    *scores.entry((src, dst)).or_default() += score;
+                }
+                let edges = scores
+                    .into_iter()
+                    .map(|((src, dst), score)| (src, dst, score))
+                    .collect();
+                (dirs.into_iter().map(String::from).collect(), edges)
+            }
+        };
 
-    #[pyo3(get)]
-    kind: LineKind,
+        let mut g: DiGraph<(), usize> = DiGraph::new();
+        let nodes: Vec<NodeIndex> = names.iter().map(|_| g.add_node(())).collect();
+        for &(src, dst, score) in &edges {
+            if src != dst {
+                g.add_edge(nodes[src], nodes[dst], score);
+            }
+        }
 
-    #[pyo3(get)]
-    src: usize,
+        let mut cycles: Vec<Cycle> = tarjan_scc(&g)
+            .into_iter()
+            .filter(|component| component.len() > 1)
+            .map(|component| {
+                let mut members: Vec<usize> = component.iter().map(|node| node.index()).collect();
+                members.sort();
+                let mut cycle_edges: Vec<(String, String, usize)> = g
+                    .edge_references()
+                    .filter(|edge| {
+                        component.contains(&edge.source()) && component.contains(&edge.target())
+                    })
+                    .map(|edge| {
+                        (
+                            names[edge.source().index()].clone(),
+                            names[edge.target().index()].clone(),
+                            *edge.weight(),
+                        )
+                    })
+                    .collect();
+                cycle_edges
+                    .sort_by(|a, b| a.2.cmp(&b.2).then_with(|| (&a.0, &a.1).cmp(&(&b.0, &b.1))));
+                Cycle {
+                    nodes: members.iter().map(|&i| names[i].clone()).collect(),
+                    score: cycle_edges.iter().map(|edge| edge.2).sum(),
+                    edges: cycle_edges,
+                }
+            })
+            .collect();
+        cycles.sort_by(|a, b| {
+            b.nodes
+                .len()
+                .cmp(&a.nodes.len())
+                .then_with(|| a.nodes.cmp(&b.nodes))
+        });
+        cycles
+    }
 
-    #[pyo3(get)]
-    dst: usize,
+    /// Why `dst_file` is in `related_files(src_file)`: the symbols linking them, the commits
+    /// changing both files and the numbers the weight of each link is computed from.
+    pub fn explain_relation(&self, src_file: String, dst_file: String) -> RelationExplanation {
+        let src = self.normalize_path(&src_file);
+        let dst = self.normalize_path(&dst_file);
 
-    #[pyo3(get)]
-    symbols: Vec<usize>,
-}
+        let definitions = self.symbol_graph.list_definitions(&src);
+        let mut symbols: Vec<SymbolContribution> = definitions
+            .iter()
+            .flat_map(|def| {
+                self.symbol_graph
+                    .list_references_by_definition(&def.id())
+                    .into_iter()
+                    .filter(|(reference, _)| *reference.file == *dst)
+                    .map(|(reference, weight)| SymbolContribution {
+                        def: def.clone(),
+                        reference,
+                        weight,
+                        idf: self.idf_of(&def.name),
+                        score: self.scoring.link_score(
+                            weight,
+                            definitions.len(),
+                            self.idf_of(&def.name),
+                        ),
+                    })
+            })
+            .collect();
+        symbols.sort_by(|a, b| {
+            b.weight
+                .cmp(&a.weight)
+                .then_with(|| a.def.name.cmp(&b.def.name))
+                .then_with(|| a.reference.id().cmp(&b.reference.id()))
+        });
 
-#[derive(Serialize, Deserialize, Clone)]
-#[pyclass]
-pub struct SymbolNode {
-    #[pyo3(get)]
-    id: usize,
+        let co_change = self
+            .co_changes
+            .get(&src)
+            .and_then(|each| each.get(&dst))
+            .copied()
+            .unwrap_or_default();
 
-    #[pyo3(get)]
-    kind: LineKind,
+        let dst_commits: HashSet<String> =
+            self.list_file_commits(dst.clone()).into_iter().collect();
+        let commits: Vec<SharedCommit> = self
+            .list_file_commits(src.clone())
+            .into_iter()
+            .filter(|each| dst_commits.contains(each))
+            .map(|sha| {
+                let files = self.commit_related_files(sha.clone()).len();
+                let decay = self.commit_decays.get(&sha).copied().unwrap_or(1.0);
+                let ratio = if files < self.commit_size_limit {
+                    self.scoring.commit_ratio(files, self.file_len) * decay
+                } else {
+                    0.0
+                };
+                SharedCommit {
+                    sha,
+                    files,
+                    decay,
+                    ratio,
+                }
+            })
+            .collect();
+        let ratio: f64 = commits.iter().map(|each| each.ratio).sum();
 
-    #[pyo3(get)]
-    name: String,
+        let src_refs = self.symbol_graph.list_references(&src).len();
+        let language_ratio = if is_cross_language(&dst, &src) {
+            self.cross_language_ratio
+        } else {
+            1.0
+        };
+        let mut weight = ratio * self.scoring.commit_weight;
+        if src_refs > 0 {
+            weight /= src_refs as f64;
+        }
+        weight *= language_ratio;
+        let directory_boost = if parent_dir(&src) == parent_dir(&dst) {
+            self.scoring.same_directory_boost
+        } else {
+            1.0
+        };
+        let score = symbols.iter().map(|each| each.score).sum::<usize>() + co_change;
 
-    #[pyo3(get)]
-    range: RangeWrapper,
-}
+        RelationExplanation {
+            score: (score as f64 * directory_boost) as usize,
+            src,
+            dst,
+            symbols,
+            definitions: definitions.len(),
+            co_change,
+            commits,
+            files: self.file_len,
+            commit_size_limit: self.commit_size_limit,
+            ratio,
+            commit_weight: self.scoring.commit_weight,
+            src_refs,
+            language_ratio,
+            directory_boost,
+            weight: if ratio > 0.0 {
+                std::cmp::max(weight as usize, 1)
+            } else {
+                0
+            },
+        }
+    }
 
-#[derive(Serialize, Deserialize, Clone)]
-#[pyclass]
-pub struct RelationList {
-    #[pyo3(get)]
-    pub file_nodes: Vec<FileNode>,
+    /// Chains of def/ref relations from `src_file` to `dst_file`, with at most `max_len` hops.
+    /// Each file references the defs of the one before it, so editing `src_file` may reach `dst_file` by them.
+    pub fn paths_between_files(
+        &self,
+        src_file: String,
+        dst_file: String,
+        max_len: usize,
+    ) -> Vec<RelationPath> {
+        let src_file = self.normalize_path(&src_file);
+        let dst_file = self.normalize_path(&dst_file);
+        let (files, edges) = self.file_edges();
+        let position = |file: &str| files.iter().position(|each| each == file);
+        let (src, dst) = match (position(&src_file), position(&dst_file)) {
+            (Some(src), Some(dst)) if src != dst => (src, dst),
+            _ => return Vec::new(),
+        };
 
-    #[pyo3(get)]
-    pub file_relations: Vec<FileRelation>,
+        // from the defining file to the referencing files, the strongest first
+        let mut next: Vec<Vec<(usize, usize)>> = vec![Vec::new(); files.len()];
+        let mut prev: Vec<Vec<usize>> = vec![Vec::new(); files.len()];
+        for &(referencing, defining, score) in &edges {
+            next[defining].push((referencing, score));
+            prev[referencing].push(defining);
+        }
+        for neighbors in next.iter_mut() {
+            neighbors.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        }
 
-    #[pyo3(get)]
-    pub symbol_nodes: Vec<SymbolNode>,
-}
+        // hops left to `dst`, to skip the files never reaching it
+        let mut distance = vec![usize::MAX; files.len()];
+        distance[dst] = 0;
+        let mut queue = std::collections::VecDeque::from([dst]);
+        while let Some(node) = queue.pop_front() {
+            for &before in &prev[node] {
+                if distance[before] == usize::MAX {
+                    distance[before] = distance[node] + 1;
+                    queue.push_back(before);
+                }
+            }
+        }
 
-// Read API v1
-#[pymethods]
-impl Graph {
-    pub fn files(&self) -> HashSet<String> {
-        self.file_contexts
-            .iter()
-            .map(|each| each.path.clone())
+        let mut found = Vec::new();
+        for len in 1..=max_len {
+            let mut path = vec![(src, 0)];
+            walk_paths(&next, &distance, dst, len, &mut path, &mut found);
+        }
+        found
+            .into_iter()
+            .map(|path| {
+                let hops = path
+                    .windows(2)
+                    .map(|pair| {
+                        self.symbol_graph
+                            .pairs_between_files(&files[pair[0].0], &files[pair[1].0])
+                    })
+                    .collect();
+                RelationPath {
+                    files: path.iter().map(|(node, _)| files[*node].clone()).collect(),
+                    hops,
+                    score: path[1..].iter().map(|(_, score)| *score).min().unwrap_or(0),
+                }
+            })
             .collect()
     }
 
-    /// All files which pointed to this file
-    pub fn related_files(&self, file_name: String) -> Vec<RelatedFileContext> {
-        if !self.symbol_graph.file_mapping.contains_key(&file_name) {
-            return Vec::new();
+    /// Files grouped by louvain over the file relations, suggesting the modules.
+    /// Files related to nothing are left alone in their own communities.
+    pub fn detect_communities(&self) -> Vec<Community> {
+        let (files, edges) = self.file_edges();
+        let labels = louvain(files.len(), &edges);
+
+        let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (i, label) in labels.iter().enumerate() {
+            groups.entry(*label).or_default().push(i);
+        }
+        let mut internal_scores: HashMap<usize, usize> = HashMap::new();
+        let mut external_scores: HashMap<usize, usize> = HashMap::new();
+        for &(src, dst, score) in &edges {
+            if labels[src] == labels[dst] {
+                *internal_scores.entry(labels[src]).or_default() += score;
+            } else {
+                *external_scores.entry(labels[src]).or_default() += score;
+                *external_scores.entry(labels[dst]).or_default() += score;
+            }
         }
 
-        // find all the defs in this file
-        // and tracking all the references and theirs
-        let mut file_counter = HashMap::new();
-        let mut file_ref_mapping: HashMap<String, Vec<RelatedSymbol>> = HashMap::new();
+        let mut communities: Vec<Community> = groups
+            .into_iter()
+            .map(|(label, members)| Community {
+                id: 0,
+                files: members.iter().map(|&i| files[i].clone()).collect(),
+                internal_score: internal_scores.get(&label).copied().unwrap_or(0),
+                external_score: external_scores.get(&label).copied().unwrap_or(0),
+            })
+            .collect();
+        // files are sorted, so are the files of each community
+        communities.sort_by(|a, b| {
+            b.files
+                .len()
+                .cmp(&a.files.len())
+                .then_with(|| a.files.cmp(&b.files))
+        });
+        for (id, community) in communities.iter_mut().enumerate() {
+            community.id = id;
+        }
+        communities
+    }
 
-        // other files -> this file
-        let definitions_in_file = self.symbol_graph.list_definitions(&file_name);
-        let definition_count = definitions_in_file.len();
+    /// Files reached by walking `related_files` transitively, up to `depth` hops.
+    /// Each file is kept at its fewest hops, with the best score among the paths of that length.
+    pub fn impacted_files(&self, file_name: String, depth: usize, decay: f64) -> Vec<ImpactedFile> {
+        let file_name = self.normalize_path(&file_name);
+        let mut visited: HashSet<String> = HashSet::from([file_name.clone()]);
+        // file and its score, the start file bounds nothing
+        let mut frontier: Vec<(String, f64)> = vec![(file_name, f64::INFINITY)];
+        let mut ret: Vec<ImpactedFile> = Vec::new();
 
-        definitions_in_file.iter().for_each(|def| {
-            self.symbol_graph
-                .list_references_by_definition(&def.id())
+        for hops in 1..=depth {
+            let related: Vec<Vec<RelatedFileContext>> = with_threads(self.threads, || {
+                frontier
+                    .par_iter()
+                    .map(|(file, _)| self.related_files(file.clone()))
+                    .collect()
+            });
+            let mut reached: HashMap<String, ImpactedFile> = HashMap::new();
+            for ((via, via_score), related_files) in frontier.iter().zip(related) {
+                for each in related_files {
+                    if each.score == 0 || visited.contains(&each.name) {
+                        continue;
+                    }
+                    let factor = if hops > 1 { decay } else { 1.0 };
+                    let score = via_score.min(each.score as f64) * factor;
+                    let better = reached
+                        .get(&each.name)
+                        .is_none_or(|prev| score > prev.score);
+                    if better {
+                        reached.insert(
+                            each.name.clone(),
+                            ImpactedFile {
+                                name: each.name,
+                                score,
+                                hops,
+                                via: via.clone(),
+                            },
+                        );
+                    }
+                }
+            }
+            if reached.is_empty() {
+                break;
+            }
+            let mut next: Vec<ImpactedFile> = reached.into_values().collect();
+            next.sort_by(|a, b| a.name.cmp(&b.name));
+            visited.extend(next.iter().map(|each| each.name.clone()));
+            frontier = next
                 .iter()
-                .for_each(|(each_ref, weight)| {
-                    let real_weight = std::cmp::max(weight / definition_count, 1);
-
-                    file_counter.entry(each_ref.file.clone()).or_insert(0);
-                    file_counter
-                        .entry(each_ref.file.clone())
-                        .and_modify(|w| *w += real_weight)
-                        .or_insert(real_weight);
-
-                    file_ref_mapping
-                        .entry(each_ref.file.clone())
-                        .and_modify(|v| {
-                            v.push(RelatedSymbol {
-                                symbol: each_ref.clone(),
-                                weight: real_weight,
-                            })
-                        })
-                        .or_insert(vec![RelatedSymbol {
-                            symbol: each_ref.clone(),
-                            weight: real_weight,
-                        }]);
-                });
+                .map(|each| (each.name.clone(), each.score))
+                .collect();
+            ret.extend(next);
+        }
+        ret.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.name.cmp(&b.name))
         });
+        ret
+    }
 
-        definitions_in_file.iter().for_each(|def| {
-            self.symbol_graph
-                .list_references_by_definition(&def.id())
-                .into_iter()
-                .map(|s| s.0.file)
-                .for_each(|f| {
-                    file_ref_mapping
-                        .entry(f.clone())
-                        .and_modify(|v| {
-                            v.push(RelatedSymbol {
-                                symbol: def.clone(),
-                                weight: 0,
-                            })
-                        })
-                        .or_insert(vec![RelatedSymbol {
-                            symbol: def.clone(),
-                            weight: 0,
-                        }]);
-                });
+    /// The union of `related_files` of the changed files, with the scores summed up.
+    /// The changed files themselves and the files not in the graph are skipped.
+    pub fn impact(&self, changed_files: Vec<String>) -> Vec<ImpactContext> {
+        let changed: BTreeSet<String> = changed_files
+            .iter()
+            .map(|file| self.normalize_path(file))
+            .filter(|file| self.symbol_graph.file_mapping.contains_key(file.as_str()))
+            .collect();
+        let related: Vec<Vec<RelatedFileContext>> = with_threads(self.threads, || {
+            changed
+                .par_iter()
+                .map(|file| self.related_files(file.clone()))
+                .collect()
         });
 
-        // this file -> other files
-        // TODO: need it?
+        let mut impacted: HashMap<String, ImpactContext> = HashMap::new();
+        for (source, related_files) in changed.iter().zip(related) {
+            for each in related_files {
+                if each.score == 0 || changed.contains(&each.name) {
+                    continue;
+                }
+                let ctx = impacted
+                    .entry(each.name.clone())
+                    .or_insert_with(|| ImpactContext {
+                        name: each.name.clone(),
+                        score: 0,
+                        sources: Vec::new(),
+                        symbols: Vec::new(),
+                    });
+                ctx.score += each.score;
+                ctx.sources.push(source.clone());
+                ctx.symbols.extend(
+                    each.related_symbols
+                        .iter()
+                        .filter(|related| *related.symbol.file == **source)
+                        .map(|related| related.symbol.name.to_string()),
+                );
+            }
+        }
+
+        let mut ret: Vec<ImpactContext> = impacted
+            .into_values()
+            .map(|mut ctx| {
+                ctx.symbols.sort();
+                ctx.symbols.dedup();
+                ctx
+            })
+            .collect();
+        ret.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.name.cmp(&b.name)));
+        ret
+    }
 
-        // remove itself
-        file_counter.remove(&file_name);
+    /// The test files in `impact` of the changed files, see `is_test_file` for the conventions.
+    /// The changed test files are left out like in `impact`, run them as well.
+    pub fn related_tests(&self, changed_files: Vec<String>) -> Vec<ImpactContext> {
+        let mut impacted = self.impact(changed_files);
+        impacted.retain(|each| is_test_file(&each.name));
+        impacted
+    }
 
-        let mut contexts = file_counter
+    /// Authors of the commits touching the changed files and the files coupled with them,
+    /// the best reviewers first. A commit counts for the score of its file in `impact`,
+    /// and for the top score when it touches a changed file.
+    /// Only the commits in the history window of the graph are considered.
+    pub fn suggest_reviewers(&self, changed_files: Vec<String>) -> Vec<Reviewer> {
+        let changed: BTreeSet<String> = changed_files
             .iter()
-            .map(|(k, v)| {
-                let related_symbols = file_ref_mapping[k].clone();
-                return RelatedFileContext {
-                    name: k.clone(),
-                    score: *v,
-                    defs: self.symbol_graph.list_definitions(k).len(),
-                    refs: self.symbol_graph.list_references(k).len(),
-                    related_symbols,
-                };
+            .map(|file| self.normalize_path(file))
+            .filter(|file| self.symbol_graph.file_mapping.contains_key(file.as_str()))
+            .collect();
+        let impacted = self.impact(changed.iter().cloned().collect());
+        let top_score = impacted.iter().map(|each| each.score).max().unwrap_or(1);
+        let weighted = changed
+            .into_iter()
+            .map(|file| (file, top_score))
+            .chain(impacted.into_iter().map(|each| (each.name, each.score)));
+
+        // author -> (score, commits, files)
+        let mut authors: HashMap<String, (usize, HashSet<String>, BTreeSet<String>)> =
+            HashMap::new();
+        for (file, score) in weighted {
+            for commit in self.list_file_commits(file.clone()) {
+                let commit_authors = self
+                    ._relation_graph
+                    .commit_related_authors(&commit)
+                    .unwrap_or_default();
+                for author in commit_authors {
+                    let entry = authors.entry(author).or_default();
+                    entry.0 += score;
+                    entry.1.insert(commit.clone());
+                    entry.2.insert(file.clone());
+                }
+            }
+        }
+
+        let mut ret: Vec<Reviewer> = authors
+            .into_iter()
+            .map(|(name, (score, commits, files))| Reviewer {
+                name,
+                score,
+                commits: commits.len(),
+                files: files.into_iter().collect(),
             })
-            .collect::<Vec<_>>();
-        contexts.sort_by_key(|context| Reverse(context.score));
-        contexts
+            .collect();
+        ret.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.name.cmp(&b.name)));
+        ret
+    }
+
+    /// Authors of the commits touching this file and their shares of the commits, the dominant first.
+    /// Only the commits in the history window of the graph are considered.
+    pub fn file_ownership(&self, file_name: String) -> FileOwnership {
+        let file_name = self.normalize_path(&file_name);
+        let commits = self.list_file_commits(file_name.clone());
+        FileOwnership {
+            owners: self.author_shares(&commits),
+            commits: commits.len(),
+            name: file_name,
+        }
+    }
+
+    /// Authors of at least `min_share` of the commits of each file, sorted by file name.
+    /// Files without any such author are left out.
+    pub fn file_owners(&self, min_share: f64) -> Vec<FileOwnership> {
+        let mut files: Vec<String> = self.files().into_iter().collect();
+        files.sort();
+        files
+            .into_iter()
+            .map(|file| self.file_ownership(file))
+            .filter_map(|mut ownership| {
+                ownership.owners.retain(|(_, share)| *share >= min_share);
+                (!ownership.owners.is_empty()).then_some(ownership)
+            })
+            .collect()
+    }
+
+    /// Files whose coupled files, themselves included, are mostly committed by a single author
+    /// taking at least `min_share` of the commits. The most dominated first.
+    pub fn bus_factor(&self, min_share: f64) -> Vec<BusFactorRisk> {
+        let mut files: Vec<String> = self.files().into_iter().collect();
+        files.sort();
+        let pb = progress_bar(self.show_progress, files.len());
+        let mut ret: Vec<BusFactorRisk> = with_threads(self.threads, || {
+            files
+                .par_iter()
+                .filter_map(|file| {
+                    pb.inc(1);
+                    let coupled: Vec<String> = self
+                        .related_files(file.clone())
+                        .into_iter()
+                        .filter(|each| each.score > 0)
+                        .map(|each| each.name)
+                        .collect();
+                    let mut commits: Vec<String> = std::iter::once(file)
+                        .chain(&coupled)
+                        .flat_map(|each| self.list_file_commits(each.clone()))
+                        .collect();
+                    commits.sort();
+                    commits.dedup();
+                    let (author, share) = self.author_shares(&commits).into_iter().next()?;
+                    (share >= min_share).then(|| BusFactorRisk {
+                        name: file.clone(),
+                        author,
+                        share,
+                        commits: commits.len(),
+                        coupled_files: coupled.len(),
+                    })
+                })
+                .collect()
+        });
+        pb.finish_and_clear();
+        ret.sort_by(|a, b| {
+            b.share
+                .total_cmp(&a.share)
+                .then_with(|| b.commits.cmp(&a.commits))
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        ret
+    }
+
+    /// Precompute `related_files` of all the files, then the queries are only lookups.
+    /// Worth it for long-running servers.
+    pub fn build_index(&mut self) {
+        let files: Vec<String> = self.files().into_iter().collect();
+        let pb = progress_bar(self.show_progress, files.len());
+        let index = with_threads(self.threads, || {
+            files
+                .par_iter()
+                .map(|file| {
+                    pb.inc(1);
+                    (file.clone(), self.compute_related_files(file))
+                })
+                .collect()
+        });
+        pb.finish_and_clear();
+        self.related_index = Some(index);
     }
 
     pub fn related_symbols(&self, symbol: Symbol) -> HashMap<Symbol, usize> {
@@ -223,12 +2156,8 @@ impl Graph {
     }
 
     pub fn file_metadata(&self, file_name: String) -> FileMetadata {
-        let symbols = self
-            .symbol_graph
-            .list_symbols(&file_name)
-            .iter()
-            .cloned()
-            .collect();
+        let file_name = self.normalize_path(&file_name);
+        let symbols = self.symbol_graph.list_symbols(&file_name).to_vec();
 
         let commit_sha_list = self
             ._relation_graph
@@ -240,15 +2169,35 @@ impl Graph {
             .file_related_issues(&file_name)
             .unwrap_or_default();
 
+        let truncated = self.truncated_files.contains(&file_name);
         FileMetadata {
             path: file_name,
             commits: commit_sha_list,
             issues: issue_list,
             symbols,
+            truncated,
         }
     }
 
+    /// Same as `FileMetadata.commits`, with the author, time and subject of each commit, the latest first.
+    /// Empty if the repo can not be read anymore, e.g. a graph loaded from an index elsewhere.
+    pub fn file_commits_detailed(&self, file_name: String) -> Vec<CommitInfo> {
+        let commits = self.list_file_commits(file_name);
+        let mut ret = history::commit_details(&self.project_path, &commits).unwrap_or_else(|err| {
+            warn!("Failed to read the commits: {}", err);
+            Vec::new()
+        });
+        ret.sort_by(|a, b| {
+            b.timestamp
+                .cmp(&a.timestamp)
+                .then_with(|| a.sha.cmp(&b.sha))
+        });
+        ret
+    }
+
     pub fn pairs_between_files(&self, src_file: String, dst_file: String) -> Vec<DefRefPair> {
+        let src_file = self.normalize_path(&src_file);
+        let dst_file = self.normalize_path(&dst_file);
         if !self.files().contains(&src_file) || !self.files().contains(&dst_file) {
             return Vec::new();
         }
@@ -256,97 +2205,114 @@ impl Graph {
     }
 
     pub fn list_file_issues(&self, file_name: String) -> Vec<String> {
+        let file_name = self.normalize_path(&file_name);
         let result = self._relation_graph.file_related_issues(&file_name);
         result.unwrap_or_default()
     }
 
     pub fn list_file_commits(&self, file_name: String) -> Vec<String> {
+        let file_name = self.normalize_path(&file_name);
         let result = self._relation_graph.file_related_commits(&file_name);
         result.unwrap_or_default()
     }
 
-    pub fn list_all_relations(&self) -> RelationList {
-        // https://github.com/williamfzc/gossiphs/issues/38
-        // node: file, symbol
-        // edge: file relation
-        let mut files: Vec<String> = self.files().into_iter().collect();
+    /// Files touched by this commit, sorted. `sha` is the full hash of the commit.
+    pub fn commit_related_files(&self, sha: String) -> Vec<String> {
+        let mut files = self
+            ._relation_graph
+            .commit_related_files(&sha)
+            .unwrap_or_default();
         files.sort();
-        let file_id_map: HashMap<&String, usize> = files
-            .iter()
-            .enumerate()
-            .map(|(i, file)| (file, i))
-            .collect();
+        files
+    }
 
-        let pb = ProgressBar::new(files.len() as u64);
-        let results: HashMap<&String, Vec<RelatedFileContext>> = files
-            .par_iter()
-            .map(|file| {
-                pb.inc(1);
-                let related_files: Vec<RelatedFileContext> =
-                    self.related_files(file.clone()).into_iter().collect();
-                return (file, related_files);
-            })
-            .collect();
-        pb.finish_and_clear();
+    /// Files touched by the commits mentioning this issue, sorted.
+    pub fn issue_related_files(&self, issue: String) -> Vec<String> {
+        let mut files = self
+            ._relation_graph
+            .issue_related_files(&issue)
+            .unwrap_or_default();
+        files.sort();
+        files
+    }
 
-        let mut file_nodes: Vec<FileNode> = Vec::new();
-        let mut file_relations: Vec<FileRelation> = Vec::new();
-        for (file, id) in &file_id_map {
-            file_nodes.push(FileNode {
-                id: id.clone(),
-                kind: LineKind::FileNode,
-                name: file.to_string(),
-                issues: self.list_file_issues(file.to_string()),
-            });
-        }
+    /// Function level edges: refs inside the body of A resolved to the def of B
+    pub fn call_graph(&self) -> Vec<FunctionEdge> {
+        let mut edges: HashMap<(String, String), FunctionEdge> = HashMap::new();
+        for file_context in &self.file_contexts {
+            let namespaces: Vec<&Symbol> = file_context
+                .symbols
+                .iter()
+                .filter(|each| each.kind == SymbolKind::NAMESPACE)
+                .collect();
+            let defs: Vec<&Symbol> = file_context
+                .symbols
+                .iter()
+                .filter(|each| each.kind == SymbolKind::DEF)
+                .collect();
 
-        let mut symbol_map: HashMap<String, SymbolNode> = HashMap::new();
-        let mut cur_id = file_nodes.len();
-        for (file, related_files) in &results {
-            let src_id = file_id_map[file];
-            for related_file in related_files {
-                if let Some(&dst_id) = file_id_map.get(&related_file.name) {
-                    let symbols: Vec<usize> = related_file
-                        .related_symbols
-                        .iter()
-                        .filter(|s| s.symbol.kind == SymbolKind::DEF)
-                        .map(|s| {
-                            let symbol_id = s.symbol.id();
-                            if !symbol_map.contains_key(&symbol_id) {
-                                symbol_map.insert(
-                                    symbol_id,
-                                    SymbolNode {
-                                        id: cur_id,
-                                        kind: LineKind::SymbolNode,
-                                        name: s.symbol.name.clone(),
-                                        range: s.symbol.range.clone(),
-                                    },
-                                );
-                                cur_id += 1;
-                                return cur_id - 1;
-                            } else {
-                                return symbol_map.get(&symbol_id).unwrap().id;
-                            }
-                        })
-                        .collect::<HashSet<_>>()
-                        .into_iter()
-                        .collect();
-                    file_relations.push(FileRelation {
-                        id: cur_id,
-                        kind: LineKind::FileRelation,
-                        src: src_id,
-                        dst: dst_id,
-                        symbols,
-                    });
-                    cur_id += 1;
+            for each_ref in file_context
+                .symbols
+                .iter()
+                .filter(|each| each.kind == SymbolKind::REF)
+            {
+                let owner = match owner_of(each_ref, &namespaces, &defs) {
+                    Some(owner) => owner,
+                    None => continue,
+                };
+                for (each_def, weight) in self
+                    .symbol_graph
+                    .list_definitions_by_reference(&each_ref.id())
+                {
+                    edges
+                        .entry((owner.id(), each_def.id()))
+                        .and_modify(|edge| edge.weight += weight)
+                        .or_insert(FunctionEdge {
+                            src: owner.clone(),
+                            dst: each_def,
+                            weight,
+                        });
                 }
             }
         }
 
-        RelationList {
-            file_nodes,
-            file_relations,
-            symbol_nodes: symbol_map.values().cloned().collect(),
+        let mut ret: Vec<FunctionEdge> = edges.into_values().collect();
+        ret.sort_by_key(|edge| Reverse(edge.weight));
+        ret
+    }
+
+    #[pyo3(name = "list_all_relations")]
+    fn py_list_all_relations(&self, py: Python<'_>) -> RelationList {
+        py.allow_threads(|| self.list_all_relations())
+    }
+}
+
+/// The def which owns the innermost namespace (function body) containing this ref.
+fn owner_of<'a>(
+    reference: &Symbol,
+    namespaces: &[&Symbol],
+    defs: &[&'a Symbol],
+) -> Option<&'a Symbol> {
+    let mut containers: Vec<&&Symbol> = namespaces
+        .iter()
+        .filter(|ns| {
+            ns.range.start_byte <= reference.range.start_byte
+                && reference.range.end_byte <= ns.range.end_byte
+        })
+        .collect();
+    // innermost first
+    containers.sort_by_key(|ns| ns.range.end_byte - ns.range.start_byte);
+
+    for ns in containers {
+        // the name of a declaration starts at the same line
+        let owner = defs.iter().find(|def| {
+            def.range.start_point.row == ns.range.start_point.row
+                && ns.range.start_byte <= def.range.start_byte
+                && def.range.end_byte <= ns.range.end_byte
+        });
+        if let Some(owner) = owner {
+            return Some(*owner);
         }
     }
+    None
 }
@@ -0,0 +1,194 @@
+use crate::extractor::Extractor;
+use crate::graph::{extractor_of_extension, FileContext};
+use git2::Oid;
+use serde::Serialize;
+use std::fs;
+use std::io::Result;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::debug;
+
+// entries written by another version may be extracted by different rules,
+// the suffix is bumped when the layout of `Symbol` changes
+const CACHE_VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), "-1");
+
+/// Extraction results keyed by git blob oid, stored in `<project>/.gossiphs/cache`.
+/// Unchanged files are never parsed twice.
+pub struct CacheManager {
+    dir: PathBuf,
+}
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub bytes: u64,
+}
+
+// links and module refs are resolved against the directory of the file,
+// and default exports are named after it, so these entries are never shared across paths
+fn path_dependent(ext: &str) -> bool {
+    matches!(
+        extractor_of_extension(ext),
+        Some(
+            Extractor::Markdown
+                | Extractor::TypeScript
+                | Extractor::JavaScript
+                | Extractor::Vue
+                | Extractor::Svelte
+        )
+    )
+}
+
+impl CacheManager {
+    pub fn new(project_path: &str) -> CacheManager {
+        CacheManager {
+            dir: Path::new(project_path).join(".gossiphs").join("cache"),
+        }
+    }
+
+    // the extractor depends on the extension, and signatures are optional
+    fn entry_path(&self, oid: &Oid, file_name: &str, capture_signatures: bool) -> PathBuf {
+        let ext = file_name
+            .split('.')
+            .next_back()
+            .unwrap_or_default()
+            .to_lowercase();
+        let mut key = format!("{}-{}", oid, ext);
+        if path_dependent(&ext) {
+            // a stable hash of the path, as a file name
+            let path_oid = Oid::hash_object(git2::ObjectType::Blob, file_name.as_bytes())
+                .map(|oid| oid.to_string())
+                .unwrap_or_default();
+            key.push('-');
+            key.push_str(&path_oid);
+        }
+        if capture_signatures {
+            key.push_str("-sig");
+        }
+        self.dir.join(key)
+    }
+
+    pub fn get(&self, oid: &Oid, file_name: &str, capture_signatures: bool) -> Option<FileContext> {
+        let content = fs::read(self.entry_path(oid, file_name, capture_signatures)).ok()?;
+        let (version, mut file_context): (String, FileContext) =
+            bincode::deserialize(&content).ok()?;
+        if version != CACHE_VERSION {
+            return None;
+        }
+
+        // the same blob can live under another path, for the extractors not depending on it
+        if file_context.path != file_name {
+            file_context.path = file_name.to_string();
+            let file: Arc<str> = Arc::from(file_name);
+            file_context
+                .symbols
+                .iter_mut()
+                .for_each(|symbol| symbol.file = file.clone());
+        }
+        Some(file_context)
+    }
+
+    pub fn put(&self, oid: &Oid, file_context: &FileContext, capture_signatures: bool) {
+        let entry_path = self.entry_path(oid, &file_context.path, capture_signatures);
+        let result = fs::create_dir_all(&self.dir).and_then(|_| {
+            // keep it out of `git status` and `--working-tree`
+            let ignore_file = self.dir.join(".gitignore");
+            if !ignore_file.exists() {
+                fs::write(ignore_file, "*\n")?;
+            }
+            let content = bincode::serialize(&(CACHE_VERSION, file_context))
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+            fs::write(&entry_path, content)
+        });
+        if let Err(err) = result {
+            debug!("Failed to write cache {:?}: {:?}", entry_path, err);
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let mut stats = CacheStats::default();
+        if let Ok(entries) = fs::read_dir(&self.dir) {
+            for entry in entries.flatten() {
+                if entry.file_name() == ".gitignore" {
+                    continue;
+                }
+                stats.entries += 1;
+                stats.bytes += entry.metadata().map(|meta| meta.len()).unwrap_or_default();
+            }
+        }
+        stats
+    }
+
+    pub fn clear(&self) -> Result<()> {
+        if !self.dir.exists() {
+            return Ok(());
+        }
+        fs::remove_dir_all(&self.dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::{CacheManager, CacheStats};
+    use crate::graph::{Graph, GraphConfig};
+
+    #[test]
+    fn cache_round_trip() {
+        let project_path =
+            std::env::temp_dir().join(format!("gossiphs-cache-{}", std::process::id()));
+        let cache = CacheManager::new(&project_path.to_string_lossy());
+        let g = Graph::from(GraphConfig::default());
+        let file_context = g
+            .file_contexts
+            .iter()
+            .find(|each| each.path == "src/graph.rs")
+            .unwrap();
+        let oid = git2::Oid::hash_object(git2::ObjectType::Blob, b"cache").unwrap();
+
+        assert!(cache.get(&oid, "src/graph.rs", false).is_none());
+        cache.put(&oid, file_context, false);
+        assert_eq!(cache.stats().entries, 1);
+        assert!(cache.get(&oid, "src/graph.rs", true).is_none());
+
+        let moved = cache.get(&oid, "src/moved.rs", false).unwrap();
+        assert_eq!(moved.path, "src/moved.rs");
+        assert_eq!(moved.symbols.len(), file_context.symbols.len());
+        assert!(moved
+            .symbols
+            .iter()
+            .all(|each| &*each.file == "src/moved.rs"));
+
+        cache.clear().unwrap();
+        assert_eq!(cache.stats(), CacheStats::default());
+        std::fs::remove_dir_all(&project_path).unwrap();
+    }
+
+    #[test]
+    fn cache_path_dependent() {
+        let project_path =
+            std::env::temp_dir().join(format!("gossiphs-cache-path-{}", std::process::id()));
+        let cache = CacheManager::new(&project_path.to_string_lossy());
+        // the same barrel in two directories re-exports two different modules
+        let content = "export * from './types';\n";
+        let files = vec![
+            (String::from("a/index.ts"), content.to_string()),
+            (String::from("b/index.ts"), content.to_string()),
+        ];
+        let g = Graph::from_sources(GraphConfig::default(), files).unwrap();
+        let context = |path: &str| g.file_contexts.iter().find(|each| each.path == path);
+        let oid = git2::Oid::hash_object(git2::ObjectType::Blob, content.as_bytes()).unwrap();
+
+        cache.put(&oid, context("a/index.ts").unwrap(), false);
+        assert!(cache.get(&oid, "a/index.ts", false).is_some());
+        assert!(cache.get(&oid, "b/index.ts", false).is_none());
+        cache.put(&oid, context("b/index.ts").unwrap(), false);
+        assert_eq!(cache.stats().entries, 2);
+
+        let cached = cache.get(&oid, "b/index.ts", false).unwrap();
+        assert_eq!(cached.path, "b/index.ts");
+        assert!(cached.symbols.iter().any(|each| &*each.name == "b/types"));
+
+        cache.clear().unwrap();
+        std::fs::remove_dir_all(&project_path).unwrap();
+    }
+}
@@ -0,0 +1,201 @@
+// C interface for the hosts which are not written in rust, see `include/gossiphs.h`.
+// Results are json strings owned by the caller, freed by `gossiphs_string_free`.
+use crate::graph::{Graph, GraphConfig};
+use serde::Serialize;
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+// no panic unwinds into the host, the errors are kept for `gossiphs_last_error`
+fn guard<T, F>(f: F) -> Option<T>
+where
+    F: FnOnce() -> Result<T, String>,
+{
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(ret)) => Some(ret),
+        Ok(Err(err)) => {
+            set_last_error(err);
+            None
+        }
+        Err(_) => {
+            set_last_error(String::from("panicked"));
+            None
+        }
+    }
+}
+
+unsafe fn read_str<'a>(s: *const c_char, name: &str) -> Result<&'a str, String> {
+    if s.is_null() {
+        return Err(format!("{} is null", name));
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| format!("{} is not utf-8", name))
+}
+
+unsafe fn read_graph<'a>(g: *const Graph) -> Result<&'a Graph, String> {
+    g.as_ref().ok_or_else(|| String::from("graph is null"))
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<*mut c_char, String> {
+    let json = serde_json::to_string(value).map_err(|err| err.to_string())?;
+    CString::new(json)
+        .map(CString::into_raw)
+        .map_err(|err| err.to_string())
+}
+
+/// Build the graph of the repo at `project_path`, with the default config.
+/// Null on failure, see `gossiphs_last_error`.
+///
+/// # Safety
+/// `project_path` is a nul-terminated utf-8 string.
+#[no_mangle]
+pub unsafe extern "C" fn gossiphs_graph_new(project_path: *const c_char) -> *mut Graph {
+    guard(|| {
+        let mut conf = GraphConfig::default();
+        conf.project_path = read_str(project_path, "project_path")?.to_string();
+        conf.show_progress = false;
+        Graph::try_from(conf).map_err(|err| err.to_string())
+    })
+    .map_or(ptr::null_mut(), |g| Box::into_raw(Box::new(g)))
+}
+
+/// Load the graph saved by `gossiphs index`, much faster than building it.
+/// Null on failure, see `gossiphs_last_error`.
+///
+/// # Safety
+/// `index_path` is a nul-terminated utf-8 string.
+#[no_mangle]
+pub unsafe extern "C" fn gossiphs_graph_load(index_path: *const c_char) -> *mut Graph {
+    guard(|| Graph::load(read_str(index_path, "index_path")?).map_err(|err| err.to_string()))
+        .map_or(ptr::null_mut(), |g| Box::into_raw(Box::new(g)))
+}
+
+/// Free a graph of `gossiphs_graph_new` or `gossiphs_graph_load`, null is ignored.
+///
+/// # Safety
+/// `g` is not used after, nor freed twice.
+#[no_mangle]
+pub unsafe extern "C" fn gossiphs_graph_free(g: *mut Graph) {
+    if !g.is_null() {
+        drop(Box::from_raw(g));
+    }
+}
+
+/// Files related to `file` as a json array, the strongest first, like `/file/relation` of the server.
+/// Null on failure, see `gossiphs_last_error`.
+///
+/// # Safety
+/// `g` is a live graph, and `file` a nul-terminated utf-8 string.
+#[no_mangle]
+pub unsafe extern "C" fn gossiphs_related_files(
+    g: *const Graph,
+    file: *const c_char,
+) -> *mut c_char {
+    guard(|| {
+        let g = read_graph(g)?;
+        to_json(&g.related_files(read_str(file, "file")?.to_string()))
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Symbols, commits and issues of `file` as a json object, like `/file/metadata` of the server.
+/// Null on failure, see `gossiphs_last_error`.
+///
+/// # Safety
+/// `g` is a live graph, and `file` a nul-terminated utf-8 string.
+#[no_mangle]
+pub unsafe extern "C" fn gossiphs_file_metadata(
+    g: *const Graph,
+    file: *const c_char,
+) -> *mut c_char {
+    guard(|| {
+        let g = read_graph(g)?;
+        to_json(&g.file_metadata(read_str(file, "file")?.to_string()))
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Files of the graph as a sorted json array.
+/// Null on failure, see `gossiphs_last_error`.
+///
+/// # Safety
+/// `g` is a live graph.
+#[no_mangle]
+pub unsafe extern "C" fn gossiphs_files(g: *const Graph) -> *mut c_char {
+    guard(|| {
+        let mut files: Vec<String> = read_graph(g)?.files().into_iter().collect();
+        files.sort();
+        to_json(&files)
+    })
+    .unwrap_or(ptr::null_mut())
+}
+
+/// Free a string returned by the other functions, null is ignored.
+///
+/// # Safety
+/// `s` comes from gossiphs, is not used after, nor freed twice.
+#[no_mangle]
+pub unsafe extern "C" fn gossiphs_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Message of the last failure in this thread, null if none.
+/// Owned by gossiphs, valid until the next call failing in this thread.
+#[no_mangle]
+pub extern "C" fn gossiphs_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |err| err.as_ptr())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::capi::{
+        gossiphs_file_metadata, gossiphs_graph_free, gossiphs_graph_new, gossiphs_last_error,
+        gossiphs_related_files, gossiphs_string_free,
+    };
+    use serde_json::Value;
+    use std::ffi::{CStr, CString};
+    use std::ptr;
+
+    #[test]
+    fn capi() {
+        unsafe {
+            let project_path = CString::new(".").unwrap();
+            let g = gossiphs_graph_new(project_path.as_ptr());
+            assert!(!g.is_null());
+
+            let file = CString::new("src/symbol.rs").unwrap();
+            let related = gossiphs_related_files(g, file.as_ptr());
+            let value: Value =
+                serde_json::from_str(CStr::from_ptr(related).to_str().unwrap()).unwrap();
+            assert!(!value.as_array().unwrap().is_empty());
+            gossiphs_string_free(related);
+
+            let metadata = gossiphs_file_metadata(g, ptr::null());
+            assert!(metadata.is_null());
+            let err = CStr::from_ptr(gossiphs_last_error()).to_str().unwrap();
+            assert_eq!(err, "file is null");
+            gossiphs_graph_free(g);
+
+            let missing = CString::new("/no/such/repo").unwrap();
+            assert!(gossiphs_graph_new(missing.as_ptr()).is_null());
+            assert!(!gossiphs_last_error().is_null());
+        }
+    }
+}
@@ -0,0 +1,691 @@
+use super::*;
+
+#[test]
+fn package_scope() {
+    let mut options = CommonOptions::default();
+    options.package = Some(String::from("./services/pay.ments/"));
+    assert_eq!(
+        options.graph_config().include_file_regex,
+        "^services/pay\\.ments/"
+    );
+    options.package = Some(String::from("."));
+    assert_eq!(options.graph_config().include_file_regex, "");
+}
+
+#[test]
+fn test_parse_time() {
+    assert_eq!(parse_time("1706659200"), Ok(1706659200));
+    assert_eq!(parse_time("2024-01-31"), Ok(1706659200));
+    assert_eq!(parse_time("1970-01-01"), Ok(0));
+    assert!(parse_time("6 months").unwrap() < parse_time("1 week ago").unwrap());
+    assert!(parse_time("yesterday").is_err());
+}
+
+#[test]
+fn test_handle_relate() {
+    let relate_cmd = RelateCommand {
+        common_options: CommonOptions::default(),
+        file: "src/extractor.rs".to_string(),
+        file_txt: "".to_string(),
+        dir: "".to_string(),
+        json: None,
+        format: RelateFormat::Json,
+        ignore_zero: true,
+        top: None,
+        hops: 1,
+        decay: 0.5,
+        min_score: None,
+        direction: RelateDirection::In,
+        load_index: None,
+    };
+    handle_relate(relate_cmd).unwrap();
+}
+
+#[test]
+fn test_handle_relate_files() {
+    let relate_cmd = RelateCommand {
+        common_options: CommonOptions::default(),
+        file: "src/extractor.rs;src/main.rs;src/graph.rs".to_string(),
+        file_txt: "".to_string(),
+        dir: "".to_string(),
+        json: None,
+        format: RelateFormat::Json,
+        ignore_zero: true,
+        top: None,
+        hops: 1,
+        decay: 0.5,
+        min_score: None,
+        direction: RelateDirection::In,
+        load_index: None,
+    };
+    handle_relate(relate_cmd).unwrap();
+}
+
+#[test]
+fn test_handle_relate_files_strict() {
+    let relate_cmd = RelateCommand {
+        common_options: CommonOptions::default(),
+        file: "src/extractor.rs;src/rule.rs;src/main.rs;src/graph.rs".to_string(),
+        file_txt: "".to_string(),
+        dir: "".to_string(),
+        json: None,
+        format: RelateFormat::Json,
+        ignore_zero: true,
+        top: None,
+        hops: 1,
+        decay: 0.5,
+        min_score: None,
+        direction: RelateDirection::In,
+        load_index: None,
+    };
+    handle_relate(relate_cmd).unwrap();
+}
+
+#[test]
+fn test_handle_relate_mermaid() {
+    let relate_cmd = RelateCommand {
+        common_options: CommonOptions::default(),
+        file: "src/graph.rs".to_string(),
+        file_txt: "".to_string(),
+        dir: "".to_string(),
+        json: None,
+        format: RelateFormat::Mermaid,
+        ignore_zero: true,
+        top: None,
+        hops: 1,
+        decay: 0.5,
+        min_score: None,
+        direction: RelateDirection::In,
+        load_index: None,
+    };
+    handle_relate(relate_cmd).unwrap();
+}
+
+#[test]
+fn expand_file_patterns_test() {
+    let known_files: HashSet<String> = ["src/main.rs", "src/a/b.rs", "README.md"]
+        .iter()
+        .map(|each| each.to_string())
+        .collect();
+    let expand = |patterns: &[&str]| {
+        expand_file_patterns(
+            patterns.iter().map(|each| each.to_string()).collect(),
+            &known_files,
+        )
+        .unwrap()
+    };
+    assert_eq!(expand(&["src/**/*.rs"]), vec!["src/a/b.rs", "src/main.rs"]);
+    assert_eq!(
+        expand(&["src/*.rs", "README.md"]),
+        vec!["src/main.rs", "README.md"]
+    );
+    assert_eq!(expand(&["*.go"]), vec!["*.go"]);
+    assert!(expand_file_patterns(vec!["src/[".to_string()], &known_files).is_err());
+}
+
+#[test]
+fn test_handle_relate_top() {
+    let output = std::env::temp_dir()
+        .join(format!(
+            "gossiphs-cli-relate-top-{}.json",
+            std::process::id()
+        ))
+        .to_string_lossy()
+        .into_owned();
+    handle_relate(RelateCommand {
+        common_options: CommonOptions::default(),
+        file: "src/symbol.rs".to_string(),
+        file_txt: "".to_string(),
+        dir: "".to_string(),
+        json: Some(output.clone()),
+        format: RelateFormat::Json,
+        ignore_zero: true,
+        top: Some(2),
+        hops: 1,
+        decay: 0.5,
+        min_score: Some(1),
+        direction: RelateDirection::In,
+        load_index: None,
+    })
+    .unwrap();
+    let result: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&output).unwrap()).unwrap();
+    let related = result[0]["related"].as_array().unwrap();
+    assert!(!related.is_empty() && related.len() <= 2);
+    assert!(related
+        .iter()
+        .all(|each| each["score"].as_u64().unwrap() >= 1));
+    fs::remove_file(output).unwrap();
+}
+
+#[test]
+fn test_handle_relate_direction() {
+    for direction in [RelateDirection::Out, RelateDirection::Both] {
+        handle_relate(RelateCommand {
+            common_options: CommonOptions::default(),
+            file: "src/export.rs".to_string(),
+            file_txt: "".to_string(),
+            dir: "".to_string(),
+            json: None,
+            format: RelateFormat::Mermaid,
+            ignore_zero: true,
+            top: None,
+            hops: 1,
+            decay: 0.5,
+            min_score: None,
+            direction,
+            load_index: None,
+        })
+        .unwrap();
+    }
+}
+
+#[test]
+fn test_handle_relate_table() {
+    for format in [RelateFormat::Md, RelateFormat::Table] {
+        handle_relate(RelateCommand {
+            common_options: CommonOptions::default(),
+            file: "src/export.rs".to_string(),
+            file_txt: "".to_string(),
+            dir: "".to_string(),
+            json: None,
+            format,
+            ignore_zero: true,
+            top: Some(3),
+            hops: 1,
+            decay: 0.5,
+            min_score: None,
+            direction: RelateDirection::In,
+            load_index: None,
+        })
+        .unwrap();
+    }
+}
+
+#[test]
+fn test_handle_relate_dir() {
+    let output = std::env::temp_dir()
+        .join(format!(
+            "gossiphs-cli-relate-dir-{}.json",
+            std::process::id()
+        ))
+        .to_string_lossy()
+        .into_owned();
+    handle_relate(RelateCommand {
+        common_options: CommonOptions::default(),
+        file: "".to_string(),
+        file_txt: "".to_string(),
+        dir: "src/".to_string(),
+        json: Some(output.clone()),
+        format: RelateFormat::Table,
+        ignore_zero: true,
+        top: None,
+        hops: 1,
+        decay: 0.5,
+        min_score: None,
+        direction: RelateDirection::In,
+        load_index: None,
+    })
+    .unwrap();
+    let result: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&output).unwrap()).unwrap();
+    let related = result[0]["related"].as_array().unwrap();
+    assert!(related.iter().any(|each| each["name"] == "examples"));
+    fs::remove_file(output).unwrap();
+}
+
+#[test]
+fn test_handle_relate_hops() {
+    let output = std::env::temp_dir()
+        .join(format!(
+            "gossiphs-cli-relate-hops-{}.json",
+            std::process::id()
+        ))
+        .to_string_lossy()
+        .into_owned();
+    handle_relate(RelateCommand {
+        common_options: CommonOptions::default(),
+        file: "src/rule.rs".to_string(),
+        file_txt: "".to_string(),
+        dir: "".to_string(),
+        json: Some(output.clone()),
+        format: RelateFormat::Json,
+        ignore_zero: true,
+        top: None,
+        hops: 2,
+        decay: 0.5,
+        min_score: None,
+        direction: RelateDirection::In,
+        load_index: None,
+    })
+    .unwrap();
+    let result: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&output).unwrap()).unwrap();
+    let impacted = result[0]["impacted"].as_array().unwrap();
+    assert!(impacted.iter().any(|each| each["hops"] == 2));
+    fs::remove_file(output).unwrap();
+}
+
+#[test]
+#[ignore]
+fn test_handle_relate_file_txt() {
+    let relate_cmd = RelateCommand {
+        common_options: CommonOptions::default(),
+        file: "".to_string(),
+        file_txt: "./aa.txt".to_string(),
+        dir: "".to_string(),
+        json: None,
+        format: RelateFormat::Json,
+        ignore_zero: true,
+        top: None,
+        hops: 1,
+        decay: 0.5,
+        min_score: None,
+        direction: RelateDirection::In,
+        load_index: None,
+    };
+    handle_relate(relate_cmd).unwrap();
+}
+
+#[test]
+fn index_test() {
+    let output = std::env::temp_dir()
+        .join(format!("gossiphs-cli-index-{}.bin", std::process::id()))
+        .to_string_lossy()
+        .into_owned();
+    handle_index(IndexCommand {
+        common_options: CommonOptions::default(),
+        output: output.clone(),
+    })
+    .unwrap();
+    handle_relate(RelateCommand {
+        common_options: CommonOptions::default(),
+        file: "src/extractor.rs".to_string(),
+        file_txt: "".to_string(),
+        dir: "".to_string(),
+        json: None,
+        format: RelateFormat::Json,
+        ignore_zero: true,
+        top: None,
+        hops: 1,
+        decay: 0.5,
+        min_score: None,
+        direction: RelateDirection::In,
+        load_index: Some(output.clone()),
+    })
+    .unwrap();
+    handle_diff(DiffCommand {
+        common_options: CommonOptions::default(),
+        target: "HEAD~1".to_string(),
+        source: "HEAD".to_string(),
+        json: false,
+        format: DiffFormat::Tree,
+        load_index: Some(output.clone()),
+    })
+    .unwrap();
+    for format in [ClustersFormat::Json, ClustersFormat::Table] {
+        handle_stats(StatsCommand {
+            common_options: CommonOptions::default(),
+            format,
+            load_index: Some(output.clone()),
+        })
+        .unwrap();
+    }
+    fs::remove_file(&output).unwrap();
+}
+
+#[test]
+fn multi_test() {
+    let output = std::env::temp_dir()
+        .join(format!("gossiphs-cli-multi-{}.bin", std::process::id()))
+        .to_string_lossy()
+        .into_owned();
+    handle_multi(MultiCommand {
+        common_options: CommonOptions::default(),
+        repos: ".".to_string(),
+        output: output.clone(),
+    })
+    .unwrap();
+    let name = repo_name(".");
+    assert_ne!(name, ".");
+    let g = Graph::load(&output).unwrap();
+    assert!(g.files().contains(&format!("{}/src/graph.rs", name)));
+    fs::remove_file(&output).unwrap();
+
+    assert!(handle_multi(MultiCommand {
+        common_options: CommonOptions::default(),
+        repos: ".,./".to_string(),
+        output: output.clone(),
+    })
+    .is_err());
+}
+
+#[test]
+#[ignore]
+fn server_test() {
+    handle_server(ServerCommand {
+        common_options: CommonOptions::default(),
+        host: String::from("127.0.0.1"),
+        port: 9411,
+        load_index: None,
+        watch: false,
+        projects: vec![],
+        cors_origins: vec![],
+        token: None,
+    })
+    .unwrap()
+}
+
+#[test]
+#[ignore]
+fn obsidian_test() {
+    handle_obsidian(ObsidianCommand {
+        common_options: CommonOptions::default(),
+        vault_dir: "./vault".to_string(),
+        canvas: true,
+    })
+    .unwrap()
+}
+
+#[test]
+fn diff_test() {
+    handle_diff(DiffCommand {
+        common_options: CommonOptions::default(),
+        target: "HEAD~10".to_string(),
+        source: "HEAD".to_string(),
+        json: false,
+        format: DiffFormat::Tree,
+        load_index: None,
+    })
+    .unwrap();
+
+    // missing in shallow clones
+    if let Err(err) = handle_diff(DiffCommand {
+        common_options: CommonOptions::default(),
+        target: "d18a5db39752d244664a23f74e174448b66b5b7e".to_string(),
+        source: "HEAD".to_string(),
+        json: false,
+        format: DiffFormat::Tree,
+        load_index: None,
+    }) {
+        assert_eq!(err.code, EXIT_BAD_REV);
+    }
+}
+
+#[test]
+fn export_test() {
+    let output = std::env::temp_dir()
+        .join(format!("gossiphs-cli-export-{}.dot", std::process::id()))
+        .to_string_lossy()
+        .into_owned();
+    handle_export(ExportCommand {
+        common_options: CommonOptions::default(),
+        format: ExportFormat::Dot,
+        output: Some(output.clone()),
+        symbols: true,
+    })
+    .unwrap();
+    assert!(fs::read_to_string(&output).unwrap().starts_with("digraph"));
+    fs::remove_file(&output).unwrap();
+}
+
+#[test]
+fn rank_test() {
+    let output = std::env::temp_dir()
+        .join(format!("gossiphs-cli-rank-{}.csv", std::process::id()))
+        .to_string_lossy()
+        .into_owned();
+    handle_rank(RankCommand {
+        common_options: CommonOptions::default(),
+        format: RankFormat::Csv,
+        output: Some(output.clone()),
+        top: Some(5),
+    })
+    .unwrap();
+    let content = fs::read_to_string(&output).unwrap();
+    let lines: Vec<&str> = content.lines().collect();
+    assert_eq!(lines[0], "name,rank,in_score,out_score");
+    assert_eq!(lines.len(), 6);
+    fs::remove_file(output).unwrap();
+}
+
+#[test]
+fn hotspots_test() {
+    let output = std::env::temp_dir()
+        .join(format!("gossiphs-cli-hotspots-{}.csv", std::process::id()))
+        .to_string_lossy()
+        .into_owned();
+    handle_hotspots(RankCommand {
+        common_options: CommonOptions::default(),
+        format: RankFormat::Csv,
+        output: Some(output.clone()),
+        top: Some(3),
+    })
+    .unwrap();
+    let content = fs::read_to_string(&output).unwrap();
+    let lines: Vec<&str> = content.lines().collect();
+    assert_eq!(lines[0], "name,churn,coupled_files,coupling,score");
+    assert_eq!(lines.len(), 4);
+    fs::remove_file(output).unwrap();
+}
+
+#[test]
+fn deadsymbols_test() {
+    let output = std::env::temp_dir()
+        .join(format!(
+            "gossiphs-cli-deadsymbols-{}.csv",
+            std::process::id()
+        ))
+        .to_string_lossy()
+        .into_owned();
+    handle_dead_symbols(RankCommand {
+        common_options: CommonOptions::default(),
+        format: RankFormat::Csv,
+        output: Some(output.clone()),
+        top: Some(3),
+    })
+    .unwrap();
+    let content = fs::read_to_string(&output).unwrap();
+    let lines: Vec<&str> = content.lines().collect();
+    assert_eq!(lines[0], "file,name,symbol_type,line");
+    assert_eq!(lines.len(), 4);
+    fs::remove_file(output).unwrap();
+
+    handle_orphans(RankCommand {
+        common_options: CommonOptions::default(),
+        format: RankFormat::Json,
+        output: None,
+        top: None,
+    })
+    .unwrap();
+}
+
+#[test]
+fn busfactor_test() {
+    let output = std::env::temp_dir()
+        .join(format!("gossiphs-cli-busfactor-{}.csv", std::process::id()))
+        .to_string_lossy()
+        .into_owned();
+    handle_busfactor(BusFactorCommand {
+        common_options: CommonOptions::default(),
+        min_share: 0.0,
+        format: RankFormat::Csv,
+        output: Some(output.clone()),
+    })
+    .unwrap();
+    let content = fs::read_to_string(&output).unwrap();
+    let lines: Vec<&str> = content.lines().collect();
+    assert_eq!(lines[0], "name,author,share,commits,coupled_files");
+    assert!(lines.len() > 1);
+    fs::remove_file(output).unwrap();
+}
+
+#[test]
+fn clusters_test() {
+    let output = std::env::temp_dir()
+        .join(format!("gossiphs-cli-clusters-{}.json", std::process::id()))
+        .to_string_lossy()
+        .into_owned();
+    handle_clusters(ClustersCommand {
+        common_options: CommonOptions::default(),
+        format: ClustersFormat::Json,
+        output: Some(output.clone()),
+        min_size: 2,
+    })
+    .unwrap();
+    let clusters: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&output).unwrap()).unwrap();
+    let clusters = clusters.as_array().unwrap();
+    assert!(!clusters.is_empty());
+    assert!(clusters
+        .iter()
+        .all(|each| each["files"].as_array().unwrap().len() >= 2));
+    fs::remove_file(output).unwrap();
+}
+
+#[test]
+fn cycles_test() {
+    let output = std::env::temp_dir()
+        .join(format!("gossiphs-cli-cycles-{}.json", std::process::id()))
+        .to_string_lossy()
+        .into_owned();
+    handle_cycles(CyclesCommand {
+        common_options: CommonOptions::default(),
+        scope: CyclesScope::Dir,
+        format: ClustersFormat::Json,
+        output: Some(output.clone()),
+    })
+    .unwrap();
+    let cycles: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&output).unwrap()).unwrap();
+    assert!(cycles
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|each| each["nodes"].as_array().unwrap().len() > 1));
+    fs::remove_file(output).unwrap();
+}
+
+#[test]
+fn impact_test() {
+    let output = std::env::temp_dir()
+        .join(format!("gossiphs-cli-impact-{}.json", std::process::id()))
+        .to_string_lossy()
+        .into_owned();
+    handle_impact(
+        ImpactCommand {
+            common_options: CommonOptions::default(),
+            change_options: ChangeOptions {
+                files: Some(String::from("src/rule.rs;src/history.rs")),
+                range: None,
+            },
+            format: ImpactFormat::Json,
+            output: Some(output.clone()),
+        },
+        false,
+    )
+    .unwrap();
+    let impacted: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&output).unwrap()).unwrap();
+    assert!(impacted
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|each| each["name"] == "src/graph.rs"));
+    fs::remove_file(output).unwrap();
+}
+
+#[test]
+fn viz_test() {
+    let output = std::env::temp_dir()
+        .join(format!("gossiphs-cli-viz-{}.html", std::process::id()))
+        .to_string_lossy()
+        .into_owned();
+    handle_viz(VizCommand {
+        common_options: CommonOptions::default(),
+        output: output.clone(),
+    })
+    .unwrap();
+    assert!(fs::read_to_string(&output)
+        .unwrap()
+        .contains("src/graph.rs"));
+    fs::remove_file(&output).unwrap();
+}
+
+#[test]
+fn relation_test() {
+    let mut config = CommonOptions::default();
+    config.exclude_file_regex = Some("".parse().unwrap());
+    config.project_path = ".".parse().unwrap();
+    handle_relation(RelationCommand {
+        common_options: config,
+        csv: "ok.csv".to_string(),
+        format: RelationFormat::Matrix,
+        symbol_csv: "ok1.csv".to_string(),
+        json: None,
+        index_file: "".to_string(),
+    })
+    .unwrap();
+    let symbols = fs::read_to_string("ok1.csv").unwrap();
+    assert!(symbols.contains("@src/graph.rs:"));
+    assert!(symbols.contains("(function)") || symbols.contains("(method)"));
+}
+
+#[test]
+fn relation_edges_test() {
+    let output = std::env::temp_dir()
+        .join(format!("gossiphs-cli-edges-{}.csv", std::process::id()))
+        .to_string_lossy()
+        .into_owned();
+    handle_relation(RelationCommand {
+        common_options: CommonOptions::default(),
+        csv: output.clone(),
+        format: RelationFormat::Edges,
+        symbol_csv: "".to_string(),
+        json: None,
+        index_file: "".to_string(),
+    })
+    .unwrap();
+    assert!(fs::read_to_string(&output)
+        .unwrap()
+        .starts_with("src,dst,score,symbols"));
+    fs::remove_file(&output).unwrap();
+}
+
+#[test]
+fn relation_json_test() {
+    let output = std::env::temp_dir()
+        .join(format!("gossiphs-cli-relation-{}.json", std::process::id()))
+        .to_string_lossy()
+        .into_owned();
+    handle_relation(RelationCommand {
+        common_options: CommonOptions::default(),
+        csv: "".to_string(),
+        format: RelationFormat::Edges,
+        symbol_csv: "".to_string(),
+        json: Some(output.clone()),
+        index_file: "".to_string(),
+    })
+    .unwrap();
+    let relations: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&output).unwrap()).unwrap();
+    assert!(!relations["file_nodes"].as_array().unwrap().is_empty());
+    assert!(!relations["file_relations"].as_array().unwrap().is_empty());
+    fs::remove_file(&output).unwrap();
+}
+
+#[test]
+fn relation_v2_test() {
+    let mut config = CommonOptions::default();
+    config.exclude_file_regex = Some("".parse().unwrap());
+    config.project_path = ".".parse().unwrap();
+    handle_relation_v2(RelationCommand {
+        common_options: config,
+        csv: "".to_string(),
+        format: RelationFormat::Edges,
+        symbol_csv: "".to_string(),
+        json: None,
+        index_file: "hello.index".to_string(),
+    })
+    .unwrap()
+}
@@ -0,0 +1,231 @@
+use crate::graph::Graph;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{BufRead, BufReader, Result, Write};
+use std::sync::{Arc, RwLock};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::sync::Notify;
+use tracing::warn;
+
+/// One request per line, in json.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum Request {
+    Related { file: String },
+    Metadata { file: String },
+    Files,
+    Stop,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Response {
+    Ok(Value),
+    Error(String),
+}
+
+pub struct DaemonConfig {
+    // unix socket path, or named pipe name on windows
+    pub address: String,
+    pub graph: Graph,
+}
+
+impl DaemonConfig {
+    pub fn new(g: Graph, address: String) -> DaemonConfig {
+        DaemonConfig { address, graph: g }
+    }
+}
+
+#[cfg(unix)]
+pub fn default_address(project_path: &str) -> String {
+    std::path::Path::new(project_path)
+        .join(".gossiphs")
+        .join("daemon.sock")
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[cfg(windows)]
+pub fn default_address(project_path: &str) -> String {
+    use std::hash::{Hash, Hasher};
+
+    // pipes live in a global namespace, one for each project
+    let project_path = std::fs::canonicalize(project_path)
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| project_path.to_string());
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    project_path.hash(&mut hasher);
+    format!(r"\\.\pipe\gossiphs-{:x}", hasher.finish())
+}
+
+/// Hold the graph and answer the queries from `query` until a `Stop` request.
+#[tokio::main]
+pub async fn daemon_main(daemon_conf: DaemonConfig) -> Result<()> {
+    let graph = Arc::new(RwLock::new(daemon_conf.graph));
+    let stop = Arc::new(Notify::new());
+
+    #[cfg(unix)]
+    {
+        use std::path::Path;
+        use tokio::net::UnixListener;
+
+        let address = Path::new(&daemon_conf.address);
+        if address.exists() {
+            if std::os::unix::net::UnixStream::connect(address).is_ok() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::AddrInUse,
+                    format!("a daemon is already running at {}", daemon_conf.address),
+                ));
+            }
+            // left by a killed daemon
+            std::fs::remove_file(address)?;
+        }
+        if let Some(parent) = address.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let listener = UnixListener::bind(address)?;
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => {
+                    let (stream, _) = accepted?;
+                    tokio::spawn(serve(stream, graph.clone(), stop.clone()));
+                }
+                _ = stop.notified() => break,
+            }
+        }
+        std::fs::remove_file(address)?;
+    }
+
+    #[cfg(windows)]
+    {
+        use tokio::net::windows::named_pipe::ServerOptions;
+
+        let mut server = ServerOptions::new()
+            .first_pipe_instance(true)
+            .create(&daemon_conf.address)?;
+        loop {
+            tokio::select! {
+                connected = server.connect() => {
+                    connected?;
+                    // the next client connects to a new instance
+                    let client = std::mem::replace(
+                        &mut server,
+                        ServerOptions::new().create(&daemon_conf.address)?,
+                    );
+                    tokio::spawn(serve(client, graph.clone(), stop.clone()));
+                }
+                _ = stop.notified() => break,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn serve<S>(stream: S, graph: Arc<RwLock<Graph>>, stop: Arc<Notify>)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut lines = tokio::io::BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let request = serde_json::from_str::<Request>(&line);
+        let response = match &request {
+            Ok(Request::Stop) => Response::Ok(Value::Null),
+            Ok(request) => handle(&graph.read().unwrap(), request),
+            Err(err) => Response::Error(format!("invalid request: {}", err)),
+        };
+
+        let mut content = serde_json::to_string(&response).unwrap();
+        content.push('\n');
+        if let Err(err) = writer.write_all(content.as_bytes()).await {
+            warn!("Failed to write response: {:?}", err);
+            return;
+        }
+        if let Ok(Request::Stop) = request {
+            stop.notify_one();
+            return;
+        }
+    }
+}
+
+fn handle(g: &Graph, request: &Request) -> Response {
+    let ret = match request {
+        Request::Related { file } => serde_json::to_value(g.related_files(file.clone())),
+        Request::Metadata { file } => serde_json::to_value(g.file_metadata(file.clone())),
+        Request::Files => {
+            let mut files: Vec<String> = g.files().into_iter().collect();
+            files.sort();
+            serde_json::to_value(files)
+        }
+        Request::Stop => Ok(Value::Null),
+    };
+    match ret {
+        Ok(value) => Response::Ok(value),
+        Err(err) => Response::Error(err.to_string()),
+    }
+}
+
+/// Send a request to the daemon listening on `address`.
+pub fn query(address: &str, request: &Request) -> Result<Response> {
+    #[cfg(unix)]
+    let stream = std::os::unix::net::UnixStream::connect(address)?;
+    #[cfg(windows)]
+    let stream = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(address)?;
+
+    let mut content = serde_json::to_string(request)?;
+    content.push('\n');
+    (&stream).write_all(content.as_bytes())?;
+
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    Ok(serde_json::from_str(&line)?)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use crate::daemon::{daemon_main, query, DaemonConfig, Request, Response};
+    use crate::graph::{Graph, GraphConfig};
+    use std::time::Duration;
+
+    #[test]
+    fn daemon_query() {
+        let address = std::env::temp_dir()
+            .join(format!("gossiphs-daemon-{}.sock", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        let g = Graph::from(GraphConfig::default());
+        let daemon_conf = DaemonConfig::new(g, address.clone());
+        let daemon = std::thread::spawn(move || daemon_main(daemon_conf));
+
+        let mut files = None;
+        for _ in 0..50 {
+            if let Ok(Response::Ok(value)) = query(&address, &Request::Files) {
+                files = Some(value);
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        assert!(!files.unwrap().as_array().unwrap().is_empty());
+
+        let related = query(
+            &address,
+            &Request::Related {
+                file: String::from("./src/graph.rs"),
+            },
+        )
+        .unwrap();
+        match related {
+            Response::Ok(value) => assert!(!value.as_array().unwrap().is_empty()),
+            Response::Error(err) => panic!("{}", err),
+        }
+
+        query(&address, &Request::Stop).unwrap();
+        daemon.join().unwrap().unwrap();
+        assert!(!std::path::Path::new(&address).exists());
+    }
+}
@@ -0,0 +1,173 @@
+use crate::generated;
+use crate::graph::{create_cupido_graph, extractor_of_extension, GraphConfig};
+use git2::Repository;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Environment report explaining why a graph may end up empty.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Diagnosis {
+    pub project_path: String,
+    pub head: Option<String>,
+    pub shallow: bool,
+    // commits reachable from HEAD, limited by `depth`
+    pub history_depth: usize,
+    pub collected_commits: usize,
+    pub collected_files: usize,
+    pub excluded_files: usize,
+    // extractor name -> file count
+    pub files_per_extractor: BTreeMap<String, usize>,
+    pub unsupported_files: usize,
+    pub hints: Vec<String>,
+}
+
+pub fn diagnose(conf: &GraphConfig) -> Diagnosis {
+    let mut diagnosis = Diagnosis {
+        project_path: conf.project_path.clone(),
+        head: None,
+        shallow: false,
+        history_depth: 0,
+        collected_commits: 0,
+        collected_files: 0,
+        excluded_files: 0,
+        files_per_extractor: BTreeMap::new(),
+        unsupported_files: 0,
+        hints: Vec::new(),
+    };
+
+    let repo = match Repository::open(&conf.project_path) {
+        Ok(repo) => repo,
+        Err(err) => {
+            diagnosis.hints.push(format!(
+                "{} is not a git repository ({}), pass the repo root by `--project-path`",
+                conf.project_path,
+                err.message()
+            ));
+            return diagnosis;
+        }
+    };
+    diagnosis.shallow = repo.is_shallow();
+    if diagnosis.shallow {
+        diagnosis.hints.push(String::from(
+            "shallow clone detected, co-change scores need history: `git fetch --unshallow` or `--auto-unshallow`",
+        ));
+    }
+
+    let head = match repo.head().and_then(|head| head.peel_to_commit()) {
+        Ok(commit) => commit,
+        Err(_) => {
+            diagnosis.hints.push(String::from(
+                "HEAD has no commit yet, commit your files first",
+            ));
+            return diagnosis;
+        }
+    };
+    diagnosis.head = Some(head.id().to_string());
+    if let Ok(mut revwalk) = repo.revwalk() {
+        if revwalk.push(head.id()).is_ok() {
+            diagnosis.history_depth = revwalk.take(conf.depth as usize).count();
+        }
+    }
+
+    let mut history_conf = conf.clone();
+    // only report it
+    history_conf.auto_unshallow = false;
+    let relation_graph = match create_cupido_graph(&history_conf) {
+        Ok(relation_graph) => relation_graph,
+        Err(err) => {
+            diagnosis.hints.push(err.to_string());
+            return diagnosis;
+        }
+    };
+    diagnosis.collected_commits = relation_graph.commit_size();
+    diagnosis.collected_files = relation_graph.file_size();
+    if diagnosis.collected_commits == 0 {
+        diagnosis.hints.push(String::from(
+            "no commits collected, check `--depth` and `--exclude-author-regex`",
+        ));
+    } else if diagnosis.collected_commits == 1 {
+        diagnosis.hints.push(String::from(
+            "only one commit collected, files never changed together so all the scores will be 0",
+        ));
+    }
+
+    let mut files = relation_graph.files();
+    if !conf.include_file_regex.is_empty() {
+        match Regex::new(&conf.include_file_regex) {
+            Ok(re) => files.retain(|file| re.is_match(file)),
+            Err(err) => diagnosis
+                .hints
+                .push(format!("invalid `--include-file-regex`: {}", err)),
+        }
+        diagnosis.excluded_files = diagnosis.collected_files - files.len();
+        if files.is_empty() && diagnosis.collected_files > 0 {
+            diagnosis.hints.push(String::from(
+                "`--include-file-regex` matches none of the files",
+            ));
+        }
+    }
+    if !conf.exclude_file_regex.is_empty() {
+        match Regex::new(&conf.exclude_file_regex) {
+            Ok(re) => files.retain(|file| !re.is_match(file)),
+            Err(err) => diagnosis
+                .hints
+                .push(format!("invalid `--exclude-file-regex`: {}", err)),
+        }
+        diagnosis.excluded_files = diagnosis.collected_files - files.len();
+        if files.is_empty() && diagnosis.collected_files > 0 {
+            diagnosis.hints.push(String::from(
+                "`--exclude-file-regex` excludes all the files",
+            ));
+        }
+    }
+    if conf.detect_generated {
+        files.retain(|file| !generated::is_generated_path(file));
+        diagnosis.excluded_files = diagnosis.collected_files - files.len();
+    }
+
+    for file in &files {
+        let file_extension = file.split('.').next_back().unwrap_or_default();
+        match extractor_of_extension(file_extension.to_lowercase().as_str()) {
+            Some(extractor) => {
+                *diagnosis
+                    .files_per_extractor
+                    .entry(format!("{:?}", extractor))
+                    .or_default() += 1;
+            }
+            None => diagnosis.unsupported_files += 1,
+        }
+    }
+    if !files.is_empty() && diagnosis.files_per_extractor.is_empty() && conf.co_change_weight == 0 {
+        diagnosis.hints.push(String::from(
+            "none of the files is written in a supported language, see README for the list, \
+             or relate them by the history only with `--co-change-weight`",
+        ));
+    }
+
+    diagnosis
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::doctor::diagnose;
+    use crate::graph::GraphConfig;
+
+    #[test]
+    fn diagnose_self() {
+        let config = GraphConfig::default();
+        let diagnosis = diagnose(&config);
+        assert!(diagnosis.head.is_some());
+        assert!(diagnosis.collected_commits > 0);
+        assert!(diagnosis.files_per_extractor.contains_key("Rust"));
+    }
+
+    #[test]
+    fn diagnose_not_a_repo() {
+        let mut config = GraphConfig::default();
+        config.project_path = String::from("/");
+        let diagnosis = diagnose(&config);
+        assert!(diagnosis.head.is_none());
+        assert_eq!(diagnosis.hints.len(), 1);
+    }
+}
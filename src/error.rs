@@ -0,0 +1,69 @@
+use std::fmt;
+
+/// Errors of building a graph, mostly caused by the repo or the config.
+#[derive(Debug)]
+pub enum GossiphsError {
+    // not a git repository, or not readable
+    Repository { path: String, source: git2::Error },
+    Rev { rev: String, source: git2::Error },
+    BareRepository(String),
+    Regex { name: String, source: regex::Error },
+    Git(git2::Error),
+}
+
+impl fmt::Display for GossiphsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GossiphsError::Repository { path, source } => {
+                write!(
+                    f,
+                    "failed to open repository {}: {}",
+                    path,
+                    source.message()
+                )
+            }
+            GossiphsError::Rev { rev, source } => {
+                write!(f, "failed to resolve rev {}: {}", rev, source.message())
+            }
+            GossiphsError::BareRepository(path) => {
+                write!(f, "{} is a bare repository, no working tree to read", path)
+            }
+            GossiphsError::Regex { name, source } => write!(f, "invalid {}: {}", name, source),
+            GossiphsError::Git(source) => write!(f, "git error: {}", source.message()),
+        }
+    }
+}
+
+impl std::error::Error for GossiphsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GossiphsError::Repository { source, .. }
+            | GossiphsError::Rev { source, .. }
+            | GossiphsError::Git(source) => Some(source),
+            GossiphsError::Regex { source, .. } => Some(source),
+            GossiphsError::BareRepository(_) => None,
+        }
+    }
+}
+
+impl From<git2::Error> for GossiphsError {
+    fn from(err: git2::Error) -> Self {
+        GossiphsError::Git(err)
+    }
+}
+
+impl GossiphsError {
+    pub(crate) fn open(path: &str) -> impl FnOnce(git2::Error) -> GossiphsError + '_ {
+        move |source| GossiphsError::Repository {
+            path: path.to_string(),
+            source,
+        }
+    }
+
+    pub(crate) fn regex(name: &str) -> impl FnOnce(regex::Error) -> GossiphsError + '_ {
+        move |source| GossiphsError::Regex {
+            name: name.to_string(),
+            source,
+        }
+    }
+}
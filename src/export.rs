@@ -0,0 +1,904 @@
+use crate::api::{FileNode, FileOwnership, FileRelation, RelationLine, RelationList};
+use crate::graph::Graph;
+use parquet::data_type::{ByteArray, ByteArrayType, Int64Type};
+use parquet::errors::Result as ParquetResult;
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+// files and relations in a stable order, with names resolved
+struct Sorted<'a> {
+    files: Vec<&'a FileNode>,
+    relations: Vec<&'a FileRelation>,
+    file_names: HashMap<usize, &'a str>,
+    symbol_names: HashMap<usize, &'a str>,
+}
+
+impl<'a> Sorted<'a> {
+    fn new(relations: &'a RelationList) -> Sorted<'a> {
+        let file_names: HashMap<usize, &str> = relations
+            .file_nodes
+            .iter()
+            .map(|node| (node.id, node.name.as_str()))
+            .collect();
+        let symbol_names: HashMap<usize, &str> = relations
+            .symbol_nodes
+            .iter()
+            .map(|node| (node.id, node.name.as_str()))
+            .collect();
+
+        let mut files: Vec<&FileNode> = relations.file_nodes.iter().collect();
+        files.sort_by_key(|node| node.name.as_str());
+        let mut file_relations: Vec<&FileRelation> = relations.file_relations.iter().collect();
+        file_relations
+            .sort_by_key(|relation| (file_names[&relation.dst], file_names[&relation.src]));
+
+        Sorted {
+            files,
+            relations: file_relations,
+            file_names,
+            symbol_names,
+        }
+    }
+
+    // the referencing file comes first
+    fn ends(&self, relation: &FileRelation) -> (&'a str, &'a str) {
+        (
+            self.file_names[&relation.dst],
+            self.file_names[&relation.src],
+        )
+    }
+
+    fn symbols(&self, relation: &FileRelation) -> Vec<&'a str> {
+        let mut symbols: Vec<&str> = relation
+            .symbols
+            .iter()
+            .filter_map(|id| self.symbol_names.get(id).copied())
+            .collect();
+        symbols.sort();
+        symbols
+    }
+
+    fn max_score(&self) -> usize {
+        self.relations
+            .iter()
+            .map(|relation| relation.score)
+            .max()
+            .unwrap_or(1)
+            .max(1)
+    }
+}
+
+/// Render the file relations as a graphviz digraph.
+/// An edge `a -> b` means `a` references the symbols defined in `b`, thicker for higher scores.
+pub fn to_dot(relations: &RelationList, with_symbols: bool) -> String {
+    let sorted = Sorted::new(relations);
+    let max_score = sorted.max_score();
+
+    let mut ret = String::from("digraph gossiphs {\n");
+    ret.push_str("    rankdir=LR;\n");
+    ret.push_str("    node [shape=box];\n");
+    for file in &sorted.files {
+        writeln!(ret, "    {};", quote(&file.name)).unwrap();
+    }
+    for relation in &sorted.relations {
+        let (src, dst) = sorted.ends(relation);
+        let mut label = relation.score.to_string();
+        if with_symbols {
+            for symbol in sorted.symbols(relation) {
+                label.push('\n');
+                label.push_str(symbol);
+            }
+        }
+        // 1.0 ~ 5.0
+        let penwidth = 1.0 + 4.0 * relation.score as f64 / max_score as f64;
+        writeln!(
+            ret,
+            "    {} -> {} [weight={}, penwidth={:.2}, label={}];",
+            quote(src),
+            quote(dst),
+            relation.score,
+            penwidth,
+            quote(&label),
+        )
+        .unwrap();
+    }
+    ret.push_str("}\n");
+    ret
+}
+
+/// Render the file relations as GraphML, for yEd, Gephi and networkx.
+pub fn to_graphml(relations: &RelationList, with_symbols: bool) -> String {
+    let sorted = Sorted::new(relations);
+
+    let mut ret = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    ret.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+    ret.push_str("  <key id=\"label\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>\n");
+    ret.push_str("  <key id=\"defs\" for=\"node\" attr.name=\"defs\" attr.type=\"int\"/>\n");
+    ret.push_str("  <key id=\"refs\" for=\"node\" attr.name=\"refs\" attr.type=\"int\"/>\n");
+    ret.push_str("  <key id=\"issues\" for=\"node\" attr.name=\"issues\" attr.type=\"string\"/>\n");
+    ret.push_str("  <key id=\"weight\" for=\"edge\" attr.name=\"weight\" attr.type=\"int\"/>\n");
+    if with_symbols {
+        ret.push_str(
+            "  <key id=\"symbols\" for=\"edge\" attr.name=\"symbols\" attr.type=\"string\"/>\n",
+        );
+    }
+    ret.push_str("  <graph id=\"gossiphs\" edgedefault=\"directed\">\n");
+    for file in &sorted.files {
+        writeln!(ret, "    <node id=\"{}\">", escape_xml(&file.name)).unwrap();
+        writeln!(
+            ret,
+            "      <data key=\"label\">{}</data>",
+            escape_xml(&file.name)
+        )
+        .unwrap();
+        writeln!(ret, "      <data key=\"defs\">{}</data>", file.defs).unwrap();
+        writeln!(ret, "      <data key=\"refs\">{}</data>", file.refs).unwrap();
+        writeln!(
+            ret,
+            "      <data key=\"issues\">{}</data>",
+            escape_xml(&file.issues.join(";"))
+        )
+        .unwrap();
+        ret.push_str("    </node>\n");
+    }
+    for (i, relation) in sorted.relations.iter().enumerate() {
+        let (src, dst) = sorted.ends(relation);
+        writeln!(
+            ret,
+            "    <edge id=\"e{}\" source=\"{}\" target=\"{}\">",
+            i,
+            escape_xml(src),
+            escape_xml(dst)
+        )
+        .unwrap();
+        writeln!(ret, "      <data key=\"weight\">{}</data>", relation.score).unwrap();
+        if with_symbols {
+            writeln!(
+                ret,
+                "      <data key=\"symbols\">{}</data>",
+                escape_xml(&sorted.symbols(relation).join(";"))
+            )
+            .unwrap();
+        }
+        ret.push_str("    </edge>\n");
+    }
+    ret.push_str("  </graph>\n");
+    ret.push_str("</graphml>\n");
+    ret
+}
+
+/// Render the file relations as GEXF 1.2, the native format of Gephi.
+pub fn to_gexf(relations: &RelationList, with_symbols: bool) -> String {
+    let sorted = Sorted::new(relations);
+
+    let mut ret = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    ret.push_str("<gexf xmlns=\"http://www.gexf.net/1.2draft\" version=\"1.2\">\n");
+    ret.push_str("  <graph mode=\"static\" defaultedgetype=\"directed\">\n");
+    ret.push_str("    <attributes class=\"node\">\n");
+    ret.push_str("      <attribute id=\"defs\" title=\"defs\" type=\"integer\"/>\n");
+    ret.push_str("      <attribute id=\"refs\" title=\"refs\" type=\"integer\"/>\n");
+    ret.push_str("      <attribute id=\"issues\" title=\"issues\" type=\"string\"/>\n");
+    ret.push_str("    </attributes>\n");
+    if with_symbols {
+        ret.push_str("    <attributes class=\"edge\">\n");
+        ret.push_str("      <attribute id=\"symbols\" title=\"symbols\" type=\"string\"/>\n");
+        ret.push_str("    </attributes>\n");
+    }
+
+    ret.push_str("    <nodes>\n");
+    for file in &sorted.files {
+        let name = escape_xml(&file.name);
+        writeln!(ret, "      <node id=\"{}\" label=\"{}\">", name, name).unwrap();
+        ret.push_str("        <attvalues>\n");
+        writeln!(
+            ret,
+            "          <attvalue for=\"defs\" value=\"{}\"/>",
+            file.defs
+        )
+        .unwrap();
+        writeln!(
+            ret,
+            "          <attvalue for=\"refs\" value=\"{}\"/>",
+            file.refs
+        )
+        .unwrap();
+        writeln!(
+            ret,
+            "          <attvalue for=\"issues\" value=\"{}\"/>",
+            escape_xml(&file.issues.join(";"))
+        )
+        .unwrap();
+        ret.push_str("        </attvalues>\n");
+        ret.push_str("      </node>\n");
+    }
+    ret.push_str("    </nodes>\n");
+
+    ret.push_str("    <edges>\n");
+    for (i, relation) in sorted.relations.iter().enumerate() {
+        let (src, dst) = sorted.ends(relation);
+        write!(
+            ret,
+            "      <edge id=\"{}\" source=\"{}\" target=\"{}\" weight=\"{}\"",
+            i,
+            escape_xml(src),
+            escape_xml(dst),
+            relation.score
+        )
+        .unwrap();
+        if with_symbols {
+            ret.push_str(">\n        <attvalues>\n");
+            writeln!(
+                ret,
+                "          <attvalue for=\"symbols\" value=\"{}\"/>",
+                escape_xml(&sorted.symbols(relation).join(";"))
+            )
+            .unwrap();
+            ret.push_str("        </attvalues>\n      </edge>\n");
+        } else {
+            ret.push_str("/>\n");
+        }
+    }
+    ret.push_str("    </edges>\n");
+    ret.push_str("  </graph>\n");
+    ret.push_str("</gexf>\n");
+    ret
+}
+
+/// The `nodes` / `links` schema of d3-force, `source` references the symbols defined in `target`.
+pub fn to_d3(relations: &RelationList, with_symbols: bool) -> Value {
+    let sorted = Sorted::new(relations);
+    let nodes: Vec<Value> = sorted
+        .files
+        .iter()
+        .map(|file| {
+            json!({
+                "id": file.name,
+                "defs": file.defs,
+                "refs": file.refs,
+                "issues": file.issues,
+            })
+        })
+        .collect();
+    let links: Vec<Value> = sorted
+        .relations
+        .iter()
+        .map(|relation| {
+            let (src, dst) = sorted.ends(relation);
+            let mut link = json!({
+                "source": src,
+                "target": dst,
+                "value": relation.score,
+            });
+            if with_symbols {
+                link["symbols"] = json!(sorted.symbols(relation));
+            }
+            link
+        })
+        .collect();
+    json!({ "nodes": nodes, "links": links })
+}
+
+/// A self-contained page drawing the relations with a force-directed layout.
+pub fn to_html(relations: &RelationList) -> String {
+    // no `</script>` inside the script
+    let data = to_d3(relations, false).to_string().replace("</", "<\\/");
+    include_str!("viz.html").replace("__GOSSIPHS_DATA__", &data)
+}
+
+/// Stream the relations as json lines, one `FileNode`, `FileRelation` or `SymbolNode` per line.
+pub fn to_jsonl<W: io::Write>(g: &Graph, mut writer: W) -> io::Result<()> {
+    let mut ret = Ok(());
+    g.for_each_relation(|line| {
+        if ret.is_err() {
+            return;
+        }
+        ret = serde_json::to_writer(&mut writer, &line)
+            .map_err(io::Error::from)
+            .and_then(|_| writer.write_all(b"\n"));
+    });
+    ret?;
+    writer.flush()
+}
+
+/// Stream the relations as csv rows of `src,dst,score,symbols`,
+/// `src` references the symbols defined in `dst` and the symbols are joined by `|`.
+pub fn to_edge_csv<W: io::Write>(g: &Graph, writer: W) -> csv::Result<()> {
+    let mut wtr = csv::Writer::from_writer(writer);
+    wtr.write_record(["src", "dst", "score", "symbols"])?;
+
+    let mut file_names: HashMap<usize, String> = HashMap::new();
+    let mut symbol_names: HashMap<usize, String> = HashMap::new();
+    let mut ret = Ok(());
+    g.for_each_relation(|line| match line {
+        RelationLine::FileNode(node) => {
+            file_names.insert(node.id, node.name);
+        }
+        RelationLine::SymbolNode(node) => {
+            symbol_names.insert(node.id, node.name);
+        }
+        RelationLine::FileRelation(relation) => {
+            if ret.is_err() {
+                return;
+            }
+            let mut symbols: Vec<&str> = relation
+                .symbols
+                .iter()
+                .filter_map(|id| symbol_names.get(id).map(|name| name.as_str()))
+                .collect();
+            symbols.sort();
+            ret = wtr.write_record([
+                file_names[&relation.dst].as_str(),
+                file_names[&relation.src].as_str(),
+                relation.score.to_string().as_str(),
+                symbols.join("|").as_str(),
+            ]);
+        }
+    });
+    ret?;
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Write the relations to `files.parquet`, `edges.parquet` and `symbols.parquet` in `dir`.
+/// Ids are shared across the three, and `src` of an edge references the symbols defined in `dst`.
+pub fn to_parquet(relations: &RelationList, dir: &Path) -> ParquetResult<()> {
+    std::fs::create_dir_all(dir)?;
+    let sorted = Sorted::new(relations);
+
+    write_parquet(
+        &dir.join("files.parquet"),
+        "message files {
+            required int64 id;
+            required binary name (UTF8);
+            required int64 defs;
+            required int64 refs;
+            repeated binary issues (UTF8);
+        }",
+        vec![
+            Column::Int64(sorted.files.iter().map(|f| f.id as i64).collect()),
+            Column::Utf8(sorted.files.iter().map(|f| f.name.clone()).collect()),
+            Column::Int64(sorted.files.iter().map(|f| f.defs as i64).collect()),
+            Column::Int64(sorted.files.iter().map(|f| f.refs as i64).collect()),
+            Column::Utf8List(sorted.files.iter().map(|f| f.issues.clone()).collect()),
+        ],
+    )?;
+
+    write_parquet(
+        &dir.join("edges.parquet"),
+        "message edges {
+            required int64 id;
+            required int64 src;
+            required int64 dst;
+            required int64 score;
+            repeated int64 symbols;
+        }",
+        vec![
+            Column::Int64(sorted.relations.iter().map(|r| r.id as i64).collect()),
+            Column::Int64(sorted.relations.iter().map(|r| r.dst as i64).collect()),
+            Column::Int64(sorted.relations.iter().map(|r| r.src as i64).collect()),
+            Column::Int64(sorted.relations.iter().map(|r| r.score as i64).collect()),
+            Column::Int64List(
+                sorted
+                    .relations
+                    .iter()
+                    .map(|r| r.symbols.iter().map(|id| *id as i64).collect())
+                    .collect(),
+            ),
+        ],
+    )?;
+
+    let mut symbols: Vec<_> = relations.symbol_nodes.iter().collect();
+    symbols.sort_by_key(|node| node.id);
+    write_parquet(
+        &dir.join("symbols.parquet"),
+        "message symbols {
+            required int64 id;
+            required binary name (UTF8);
+            required int64 start_row;
+            required int64 start_column;
+            required int64 end_row;
+            required int64 end_column;
+        }",
+        vec![
+            Column::Int64(symbols.iter().map(|s| s.id as i64).collect()),
+            Column::Utf8(symbols.iter().map(|s| s.name.clone()).collect()),
+            Column::Int64(
+                symbols
+                    .iter()
+                    .map(|s| s.range.start_point.row as i64)
+                    .collect(),
+            ),
+            Column::Int64(
+                symbols
+                    .iter()
+                    .map(|s| s.range.start_point.column as i64)
+                    .collect(),
+            ),
+            Column::Int64(
+                symbols
+                    .iter()
+                    .map(|s| s.range.end_point.row as i64)
+                    .collect(),
+            ),
+            Column::Int64(
+                symbols
+                    .iter()
+                    .map(|s| s.range.end_point.column as i64)
+                    .collect(),
+            ),
+        ],
+    )
+}
+
+// in the same order as the schema
+enum Column {
+    Int64(Vec<i64>),
+    Utf8(Vec<String>),
+    Int64List(Vec<Vec<i64>>),
+    Utf8List(Vec<Vec<String>>),
+}
+
+fn write_parquet(path: &Path, message_type: &str, columns: Vec<Column>) -> ParquetResult<()> {
+    let schema = Arc::new(parse_message_type(message_type)?);
+    let props = Arc::new(WriterProperties::builder().build());
+    let mut writer = SerializedFileWriter::new(File::create(path)?, schema, props)?;
+    let mut row_group = writer.next_row_group()?;
+    for column in columns {
+        let mut column_writer = match row_group.next_column()? {
+            Some(column_writer) => column_writer,
+            None => break,
+        };
+        match column {
+            Column::Int64(values) => {
+                column_writer
+                    .typed::<Int64Type>()
+                    .write_batch(&values, None, None)?;
+            }
+            Column::Utf8(values) => {
+                let values: Vec<ByteArray> =
+                    values.iter().map(|v| ByteArray::from(v.as_str())).collect();
+                column_writer
+                    .typed::<ByteArrayType>()
+                    .write_batch(&values, None, None)?;
+            }
+            Column::Int64List(lists) => {
+                let (values, def_levels, rep_levels) = flatten(lists);
+                column_writer.typed::<Int64Type>().write_batch(
+                    &values,
+                    Some(&def_levels),
+                    Some(&rep_levels),
+                )?;
+            }
+            Column::Utf8List(lists) => {
+                let (values, def_levels, rep_levels) = flatten(lists);
+                let values: Vec<ByteArray> =
+                    values.iter().map(|v| ByteArray::from(v.as_str())).collect();
+                column_writer.typed::<ByteArrayType>().write_batch(
+                    &values,
+                    Some(&def_levels),
+                    Some(&rep_levels),
+                )?;
+            }
+        }
+        column_writer.close()?;
+    }
+    row_group.close()?;
+    writer.close()?;
+    Ok(())
+}
+
+// values with the definition and repetition levels of a repeated column
+fn flatten<T>(lists: Vec<Vec<T>>) -> (Vec<T>, Vec<i16>, Vec<i16>) {
+    let mut values = Vec::new();
+    let mut def_levels = Vec::new();
+    let mut rep_levels = Vec::new();
+    for list in lists {
+        if list.is_empty() {
+            def_levels.push(0);
+            rep_levels.push(0);
+            continue;
+        }
+        for (i, value) in list.into_iter().enumerate() {
+            values.push(value);
+            def_levels.push(1);
+            rep_levels.push(if i == 0 { 0 } else { 1 });
+        }
+    }
+    (values, def_levels, rep_levels)
+}
+
+/// A draft CODEOWNERS, a directory takes a single line when all of its files have the same owners.
+/// Owners are the emails of the authors, map them to github users or teams before use.
+pub fn to_codeowners(ownerships: &[FileOwnership]) -> String {
+    let files: BTreeMap<&str, Vec<&str>> = ownerships
+        .iter()
+        .map(|each| {
+            let mut owners: Vec<&str> = each
+                .owners
+                .iter()
+                .map(|(author, _)| email(author))
+                .collect();
+            owners.sort();
+            owners.dedup();
+            (each.name.as_str(), owners)
+        })
+        .collect();
+    let mut ret =
+        String::from("# draft generated by `gossiphs codeowners` from the commit history\n");
+    write_codeowners(&files, "", &mut ret);
+    ret
+}
+
+// `dir` is empty for the root, or ends with `/`
+fn write_codeowners(files: &BTreeMap<&str, Vec<&str>>, dir: &str, ret: &mut String) {
+    let under: Vec<(&str, &Vec<&str>)> = files
+        .range(dir..)
+        .take_while(|(file, _)| file.starts_with(dir))
+        .map(|(file, owners)| (*file, owners))
+        .collect();
+    let Some((_, first)) = under.first() else {
+        return;
+    };
+    if under.iter().all(|(_, owners)| owners == first) {
+        let pattern = match dir {
+            "" => String::from("*"),
+            _ => format!("/{}", dir),
+        };
+        writeln!(ret, "{} {}", escape_codeowners(&pattern), first.join(" ")).unwrap();
+        return;
+    }
+
+    let mut sub_dirs: Vec<&str> = Vec::new();
+    for (file, owners) in &under {
+        match file[dir.len()..].split_once('/') {
+            Some((sub_dir, _)) => {
+                if sub_dirs.last() != Some(&sub_dir) {
+                    sub_dirs.push(sub_dir);
+                }
+            }
+            None => {
+                writeln!(
+                    ret,
+                    "{} {}",
+                    escape_codeowners(&format!("/{}", file)),
+                    owners.join(" ")
+                )
+                .unwrap();
+            }
+        }
+    }
+    for sub_dir in sub_dirs {
+        write_codeowners(files, &format!("{}{}/", dir, sub_dir), ret);
+    }
+}
+
+// `name <email>` of git, or as is without an email
+fn email(author: &str) -> &str {
+    author
+        .rsplit_once('<')
+        .and_then(|(_, rest)| rest.strip_suffix('>'))
+        .unwrap_or(author)
+}
+
+fn escape_codeowners(pattern: &str) -> String {
+    pattern.replace(' ', "\\ ")
+}
+
+/// Builds a mermaid `graph TD` snippet, for pasting into markdown.
+#[derive(Default)]
+pub struct Mermaid {
+    ids: HashMap<String, usize>,
+    lines: Vec<String>,
+}
+
+impl Mermaid {
+    pub fn new() -> Mermaid {
+        Mermaid::default()
+    }
+
+    /// Declare the file on first use, and return its node id.
+    pub fn node(&mut self, file: &str) -> String {
+        if let Some(id) = self.ids.get(file) {
+            return format!("n{}", id);
+        }
+        let id = self.ids.len();
+        self.ids.insert(file.to_string(), id);
+        self.lines
+            .push(format!("n{}[\"{}\"]", id, escape_mermaid(file)));
+        format!("n{}", id)
+    }
+
+    /// A dotted edge for the relations going away.
+    pub fn edge(&mut self, src: &str, dst: &str, label: &str, dotted: bool) {
+        let src = self.node(src);
+        let dst = self.node(dst);
+        let arrow = if dotted { "-.->" } else { "-->" };
+        self.lines.push(format!(
+            "{} {}|\"{}\"| {}",
+            src,
+            arrow,
+            escape_mermaid(label),
+            dst
+        ));
+    }
+
+    pub fn render(&self) -> String {
+        let mut ret = String::from("graph TD\n");
+        for line in &self.lines {
+            writeln!(ret, "    {}", line).unwrap();
+        }
+        ret
+    }
+}
+
+/// Rows rendered as a markdown table, or as aligned columns for terminals.
+pub struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(headers: &[&str]) -> Table {
+        Table {
+            headers: headers.iter().map(|each| each.to_string()).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn row(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+
+    pub fn render_markdown(&self) -> String {
+        let escape = |cell: &String| cell.replace('|', "\\|");
+        let mut ret = String::new();
+        writeln!(ret, "| {} |", self.headers.join(" | ")).unwrap();
+        writeln!(ret, "|{}", "---|".repeat(self.headers.len())).unwrap();
+        for row in &self.rows {
+            let cells: Vec<String> = row.iter().map(escape).collect();
+            writeln!(ret, "| {} |", cells.join(" | ")).unwrap();
+        }
+        ret
+    }
+
+    pub fn render_plain(&self) -> String {
+        let mut widths: Vec<usize> = self
+            .headers
+            .iter()
+            .map(|each| each.chars().count())
+            .collect();
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        let mut ret = String::new();
+        for row in std::iter::once(&self.headers).chain(&self.rows) {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect();
+            writeln!(ret, "{}", cells.join("  ").trim_end()).unwrap();
+        }
+        ret
+    }
+}
+
+fn escape_mermaid(s: &str) -> String {
+    s.replace('"', "#quot;")
+}
+
+fn quote(s: &str) -> String {
+    let escaped = s
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("\"{}\"", escaped)
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::FileOwnership;
+    use crate::export::{
+        escape_xml, flatten, quote, to_codeowners, to_d3, to_dot, to_edge_csv, to_gexf, to_graphml,
+        to_html, to_jsonl, to_parquet, Mermaid, Table,
+    };
+    use crate::graph::{Graph, GraphConfig};
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use std::fs::File;
+
+    #[test]
+    fn dot() {
+        let g = Graph::from(GraphConfig::default());
+        let dot = to_dot(&g.list_all_relations(), true);
+        assert!(dot.starts_with("digraph gossiphs {"));
+        assert!(dot.contains("\"src/graph.rs\";"));
+        assert!(dot.contains("\"src/main.rs\" -> \"src/graph.rs\""));
+        assert!(dot.trim_end().ends_with('}'));
+    }
+
+    #[test]
+    fn graphml_and_gexf() {
+        let g = Graph::from(GraphConfig::default());
+        let relations = g.list_all_relations();
+
+        let graphml = to_graphml(&relations, true);
+        assert!(graphml.contains("<node id=\"src/graph.rs\">"));
+        assert!(graphml.contains("source=\"src/main.rs\" target=\"src/graph.rs\""));
+        assert!(graphml.contains("<data key=\"symbols\">"));
+        assert!(graphml.trim_end().ends_with("</graphml>"));
+
+        let gexf = to_gexf(&relations, false);
+        assert!(gexf.contains("<node id=\"src/graph.rs\" label=\"src/graph.rs\">"));
+        assert!(gexf.contains("source=\"src/main.rs\" target=\"src/graph.rs\""));
+        assert!(!gexf.contains("for=\"symbols\""));
+        assert!(gexf.trim_end().ends_with("</gexf>"));
+    }
+
+    #[test]
+    fn parquet() {
+        let g = Graph::from(GraphConfig::default());
+        let relations = g.list_all_relations();
+        let dir = std::env::temp_dir().join(format!("gossiphs-parquet-{}", std::process::id()));
+        to_parquet(&relations, &dir).unwrap();
+
+        // decode all the rows, the repeated columns included
+        let rows = |name: &str| {
+            let file = File::open(dir.join(name)).unwrap();
+            SerializedFileReader::new(file)
+                .unwrap()
+                .get_row_iter(None)
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap()
+                .len()
+        };
+        assert_eq!(rows("files.parquet"), relations.file_nodes.len());
+        assert_eq!(rows("edges.parquet"), relations.file_relations.len());
+        assert_eq!(rows("symbols.parquet"), relations.symbol_nodes.len());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn jsonl() {
+        let g = Graph::from(GraphConfig::default());
+        let relations = g.list_all_relations();
+        let mut content = Vec::new();
+        to_jsonl(&g, &mut content).unwrap();
+
+        let lines: Vec<serde_json::Value> = String::from_utf8(content)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let count = |kind: &str| lines.iter().filter(|line| line["kind"] == kind).count();
+        assert_eq!(count("FileNode"), relations.file_nodes.len());
+        assert_eq!(count("FileRelation"), relations.file_relations.len());
+        assert_eq!(count("SymbolNode"), relations.symbol_nodes.len());
+    }
+
+    #[test]
+    fn d3_and_html() {
+        let g = Graph::from(GraphConfig::default());
+        let relations = g.list_all_relations();
+
+        let d3 = to_d3(&relations, true);
+        assert_eq!(
+            d3["nodes"].as_array().unwrap().len(),
+            relations.file_nodes.len()
+        );
+        assert!(d3["links"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|link| link["source"] == "src/main.rs" && link["target"] == "src/graph.rs"));
+
+        let html = to_html(&relations);
+        assert!(!html.contains("__GOSSIPHS_DATA__"));
+        assert!(html.contains("\"src/graph.rs\""));
+    }
+
+    #[test]
+    fn edge_csv() {
+        let g = Graph::from(GraphConfig::default());
+        let relations = g.list_all_relations();
+        let mut content = Vec::new();
+        to_edge_csv(&g, &mut content).unwrap();
+
+        let content = String::from_utf8(content).unwrap();
+        let mut lines = content.lines();
+        assert_eq!(lines.next(), Some("src,dst,score,symbols"));
+        assert_eq!(lines.count(), relations.file_relations.len());
+        assert!(content.contains("\nsrc/main.rs,src/graph.rs,"));
+    }
+
+    #[test]
+    fn levels() {
+        let (values, def_levels, rep_levels) = flatten(vec![vec![1, 2], vec![], vec![3]]);
+        assert_eq!(values, vec![1, 2, 3]);
+        assert_eq!(def_levels, vec![1, 1, 0, 1]);
+        assert_eq!(rep_levels, vec![0, 1, 0, 0]);
+    }
+
+    #[test]
+    fn mermaid() {
+        let mut mermaid = Mermaid::new();
+        mermaid.edge("src/main.rs", "src/graph.rs", "23", false);
+        mermaid.edge("src/api.rs", "src/graph.rs", "DELETED", true);
+        assert_eq!(
+            mermaid.render(),
+            "graph TD\n    n0[\"src/main.rs\"]\n    n1[\"src/graph.rs\"]\n    n0 -->|\"23\"| n1\n    n2[\"src/api.rs\"]\n    n2 -.->|\"DELETED\"| n1\n"
+        );
+    }
+
+    #[test]
+    fn table() {
+        let mut table = Table::new(&["file", "score"]);
+        table.row(vec!["src/main.rs".to_string(), "23".to_string()]);
+        table.row(vec!["a|b.rs".to_string(), "1".to_string()]);
+        assert_eq!(
+            table.render_markdown(),
+            "| file | score |\n|---|---|\n| src/main.rs | 23 |\n| a\\|b.rs | 1 |\n"
+        );
+        assert_eq!(
+            table.render_plain(),
+            "file         score\nsrc/main.rs  23\na|b.rs       1\n"
+        );
+    }
+
+    #[test]
+    fn escaped() {
+        assert_eq!(quote("a\"b\\c\nd"), "\"a\\\"b\\\\c\\nd\"");
+        assert_eq!(escape_xml("<a & 'b'>"), "&lt;a &amp; &apos;b&apos;&gt;");
+    }
+
+    #[test]
+    fn codeowners() {
+        let ownership = |name: &str, owners: &[&str]| FileOwnership {
+            name: name.to_string(),
+            commits: 2,
+            owners: owners
+                .iter()
+                .map(|owner| (owner.to_string(), 0.5))
+                .collect(),
+        };
+        let alice = "alice <alice@example.com>";
+        let bob = "bob <bob@example.com>";
+        let ownerships = vec![
+            ownership("README.md", &[bob]),
+            ownership("src/api.rs", &[alice, bob]),
+            ownership("src/graph.rs", &[bob, alice]),
+            ownership("web/app.ts", &[alice]),
+            ownership("web/my app.ts", &[bob]),
+        ];
+        assert_eq!(
+            to_codeowners(&ownerships),
+            "# draft generated by `gossiphs codeowners` from the commit history\n\
+             /README.md bob@example.com\n\
+             /src/ alice@example.com bob@example.com\n\
+             /web/app.ts alice@example.com\n\
+             /web/my\\ app.ts bob@example.com\n"
+        );
+        assert_eq!(
+            to_codeowners(&ownerships[1..3]).lines().nth(1),
+            Some("* alice@example.com bob@example.com")
+        );
+    }
+}
@@ -1,8 +1,12 @@
 use crate::rule::{get_rule, Rule};
-use crate::symbol::Symbol;
+use crate::symbol::{assign_occurrences, Interner, Symbol, SymbolType};
+use regex::Regex;
 use std::collections::HashMap;
-use tree_sitter::{Language, Parser, Query, QueryCursor};
+use std::path::Path;
+use std::sync::Arc;
+use tree_sitter::{Language, Node, Parser, Point, Query, QueryCursor, Range};
 
+#[derive(Debug)]
 pub enum Extractor {
     Rust,
     TypeScript,
@@ -12,66 +16,401 @@ pub enum Extractor {
     Java,
     Kotlin,
     Swift,
+    Vue,
+    Svelte,
+    Proto,
+    Thrift,
+    Markdown,
 }
 
 const DEFAULT_NAMESPACE_REPR: &str = "<NS>";
 
+lazy_static::lazy_static! {
+    static ref SCRIPT_BLOCK_REGEX: Regex =
+        Regex::new(r"(?s)<script\b[^>]*>(.*?)</script>").unwrap();
+    static ref PROTO_REGEX_RULE: RegexRule = RegexRule::compile(&Extractor::Proto);
+    static ref THRIFT_REGEX_RULE: RegexRule = RegexRule::compile(&Extractor::Thrift);
+    static ref MARKDOWN_REGEX_RULE: RegexRule = RegexRule::compile(&Extractor::Markdown);
+}
+
+/// Each line of a regex grammar is a regex, the first group is the symbol name
+/// and the group name (if any) is its type.
+fn grammar_regexes(grammar: &str) -> Result<Vec<Regex>, regex::Error> {
+    grammar
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .map(Regex::new)
+        .collect()
+}
+
+/// The compiled grammars of a rule without tree-sitter, built once for all the files.
+struct RegexRule {
+    export_regexes: Vec<Regex>,
+    import_regexes: Vec<Regex>,
+}
+
+impl RegexRule {
+    fn compile(extractor: &Extractor) -> RegexRule {
+        let rule = get_rule(extractor);
+        let compile = |grammar| {
+            grammar_regexes(grammar)
+                .unwrap_or_else(|err| panic!("invalid regex rule of {:?}: {}", extractor, err))
+        };
+        RegexRule {
+            export_regexes: compile(rule.export_grammar),
+            import_regexes: compile(rule.import_grammar),
+        }
+    }
+}
+
+/// Resolve a relative link of a document to a path from the repo root.
+/// Urls and anchors are ignored.
+fn resolve_link(f: &str, link: &str) -> Option<String> {
+    if link.contains("://") || link.starts_with('#') || link.starts_with("mailto:") {
+        return None;
+    }
+
+    let mut parts: Vec<&str> = Vec::new();
+    if !link.starts_with('/') {
+        parts.extend(f.split('/'));
+        // file name itself
+        parts.pop();
+    }
+    for part in link.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            _ => parts.push(part),
+        }
+    }
+    if parts.is_empty() {
+        return None;
+    }
+    Some(parts.join("/"))
+}
+
+/// Names of the declarations enclosing a def node, outermost first, joined by `::`.
+fn scope_of_def(node: Node, source: &[u8]) -> String {
+    let mut names = Vec::new();
+    let mut cur = node.parent();
+    while let Some(ancestor) = cur {
+        let name_node = ancestor.child_by_field_name("name").or_else(|| {
+            // rust: `impl Graph { ... }`
+            if ancestor.kind() == "impl_item" {
+                ancestor.child_by_field_name("type")
+            } else {
+                None
+            }
+        });
+        if let Some(name_node) = name_node {
+            // skip the declaration of this def itself
+            if name_node.id() != node.id() {
+                if let Ok(text) = name_node.utf8_text(source) {
+                    // drop generics
+                    let name = text.split('<').next().unwrap_or_default().trim();
+                    if !name.is_empty() {
+                        names.push(name.to_string());
+                    }
+                }
+            }
+        }
+        cur = ancestor.parent();
+    }
+    names.reverse();
+    names.join("::")
+}
+
+/// Parameter list (and return type if any) of a declaration, as written in source.
+fn signature_of(declaration: Node, source: &[u8]) -> Option<String> {
+    let parameters = declaration
+        .child_by_field_name("parameters")
+        .or_else(|| declaration.child_by_field_name("parameter"))?;
+    let end = declaration
+        .child_by_field_name("return_type")
+        .or_else(|| declaration.child_by_field_name("result"))
+        .map_or(parameters.end_byte(), |ret| ret.end_byte());
+    let text = std::str::from_utf8(&source[parameters.start_byte()..end]).ok()?;
+    Some(text.to_string())
+}
+
+/// Comments right above a declaration, or the docstring of python.
+fn doc_of(declaration: Node, source: &[u8]) -> Option<String> {
+    let mut anchor = declaration;
+    while let Some(parent) = anchor.parent() {
+        match parent.kind() {
+            "export_statement" | "decorated_definition" => anchor = parent,
+            _ => break,
+        }
+    }
+
+    let mut lines = Vec::new();
+    let mut expected_row = anchor.start_position().row;
+    let mut sibling = anchor.prev_sibling();
+    while let Some(node) = sibling {
+        if node.kind() == "attribute_item" {
+            // rust: #[derive(...)]
+            expected_row = node.start_position().row;
+        } else if node.kind().contains("comment") {
+            // a blank line breaks the doc
+            if node.end_position().row + 1 < expected_row {
+                break;
+            }
+            lines.push(node.utf8_text(source).ok()?.trim().to_string());
+            expected_row = node.start_position().row;
+        } else {
+            break;
+        }
+        sibling = node.prev_sibling();
+    }
+    if !lines.is_empty() {
+        lines.reverse();
+        return Some(lines.join("\n"));
+    }
+
+    // python docstring
+    let first_statement = declaration
+        .child_by_field_name("body")?
+        .named_child(0)
+        .filter(|node| node.kind() == "expression_statement")?;
+    let docstring = first_statement
+        .named_child(0)
+        .filter(|node| node.kind() == "string")?;
+    Some(docstring.utf8_text(source).ok()?.to_string())
+}
+
+/// Qualifier written right before a ref, e.g. `Graph` in `Graph::from` or `graph.from`.
+fn qualifier_of_ref(source: &str, start_byte: usize) -> String {
+    let before = &source[..start_byte];
+    let before = match before
+        .strip_suffix("::")
+        .or_else(|| before.strip_suffix('.'))
+    {
+        Some(before) => before,
+        None => return String::new(),
+    };
+    let start = before
+        .char_indices()
+        .rev()
+        .find(|(_, c)| !(c.is_alphanumeric() || *c == '_'))
+        .map_or(0, |(i, c)| i + c.len_utf8());
+    before[start..].to_string()
+}
+
+/// Keep only the `<script>` blocks of a single-file component (vue/svelte).
+/// Everything else is replaced by spaces (newlines are kept),
+/// so byte offsets and rows of symbols still point to the original file.
+fn mask_sfc_script(s: &str) -> String {
+    let mut masked: Vec<u8> = s
+        .bytes()
+        .map(|b| if b == b'\n' { b'\n' } else { b' ' })
+        .collect();
+    for cap in SCRIPT_BLOCK_REGEX.captures_iter(s) {
+        if let Some(script) = cap.get(1) {
+            masked[script.start()..script.end()].copy_from_slice(script.as_str().as_bytes());
+        }
+    }
+    // only ascii spaces and complete script blocks, always valid utf8
+    String::from_utf8(masked).unwrap_or_default()
+}
+
 impl Extractor {
     pub fn get_rule(&self) -> Rule {
         get_rule(self)
     }
-    pub fn extract(&self, f: &String, s: &String) -> Vec<Symbol> {
+
+    /// Language family of the extracted files, sharing one runtime.
+    /// IDL and docs are `None`, they can be linked from any language.
+    pub fn language(&self) -> Option<&'static str> {
         match self {
+            Extractor::Rust => Some("rust"),
+            Extractor::TypeScript | Extractor::JavaScript | Extractor::Vue | Extractor::Svelte => {
+                Some("javascript")
+            }
+            Extractor::Go => Some("go"),
+            Extractor::Python => Some("python"),
+            Extractor::Java | Extractor::Kotlin => Some("jvm"),
+            Extractor::Swift => Some("swift"),
+            Extractor::Proto | Extractor::Thrift | Extractor::Markdown => None,
+        }
+    }
+    #[cfg(test)]
+    pub fn extract(&self, f: &String, s: &String) -> Vec<Symbol> {
+        self.extract_with_signatures(f, s, false)
+    }
+
+    /// Also capture the signatures and doc comments of defs if `capture_signatures`
+    pub fn extract_with_signatures(
+        &self,
+        f: &String,
+        s: &String,
+        capture_signatures: bool,
+    ) -> Vec<Symbol> {
+        let mut symbols = match self {
             Extractor::Rust => {
                 let lang = &tree_sitter_rust::language();
-                self._extract(f, s, lang)
+                self._extract(f, s, lang, capture_signatures)
             }
             Extractor::TypeScript => {
                 let lang = &tree_sitter_typescript::language_typescript();
-                self._extract(f, s, lang)
+                self._extract(f, s, lang, capture_signatures)
             }
             Extractor::Go => {
                 let lang = &tree_sitter_go::language();
-                self._extract(f, s, lang)
+                self._extract(f, s, lang, capture_signatures)
                     .into_iter()
-                    .filter(|each| {
-                        return each.name != "_";
-                    })
+                    .filter(|each| &*each.name != "_")
                     .collect()
             }
             Extractor::Python => {
                 let lang = &tree_sitter_python::language();
-                self._extract(f, s, lang)
+                self._extract(f, s, lang, capture_signatures)
             }
             Extractor::JavaScript => {
                 let lang = &tree_sitter_javascript::language();
-                self._extract(f, s, lang)
+                self._extract(f, s, lang, capture_signatures)
             }
             Extractor::Java => {
                 let lang = &tree_sitter_javascript::language();
-                self._extract(f, s, lang)
+                self._extract(f, s, lang, capture_signatures)
             }
             Extractor::Kotlin => {
                 let lang = &tree_sitter_kotlin::language();
-                self._extract(f, s, lang)
+                self._extract(f, s, lang, capture_signatures)
             }
             Extractor::Swift => {
                 let lang = &tree_sitter_swift::language();
-                self._extract(f, s, lang)
+                self._extract(f, s, lang, capture_signatures)
+            }
+            Extractor::Vue | Extractor::Svelte => {
+                let lang = &tree_sitter_typescript::language_typescript();
+                self._extract(f, &mask_sfc_script(s), lang, capture_signatures)
+            }
+            // no tree-sitter grammar, rules are regex
+            Extractor::Proto | Extractor::Thrift => self._extract_by_regex(f, s),
+            Extractor::Markdown => self
+                ._extract_by_regex(f, s)
+                .into_iter()
+                .filter_map(|mut each| {
+                    // code spans are plain identifiers, others are file links
+                    if !each.name.contains(['.', '/']) {
+                        return Some(each);
+                    }
+                    each.name = resolve_link(f, &each.name)?.into();
+                    Some(each)
+                })
+                .collect(),
+        };
+        assign_occurrences(&mut symbols);
+        symbols
+    }
+
+    fn _extract_by_regex(&self, f: &str, s: &str) -> Vec<Symbol> {
+        let rule: &RegexRule = match self {
+            Extractor::Proto => &PROTO_REGEX_RULE,
+            Extractor::Thrift => &THRIFT_REGEX_RULE,
+            Extractor::Markdown => &MARKDOWN_REGEX_RULE,
+            _ => unreachable!("{:?} has a tree-sitter grammar", self),
+        };
+        let line_starts: Vec<usize> = std::iter::once(0)
+            .chain(s.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        let to_point = |offset: usize| -> Point {
+            let row = match line_starts.binary_search(&offset) {
+                Ok(row) => row,
+                Err(row) => row - 1,
+            };
+            Point {
+                row,
+                column: offset - line_starts[row],
+            }
+        };
+        let to_range = |start_byte: usize, end_byte: usize| -> Range {
+            Range {
+                start_byte,
+                end_byte,
+                start_point: to_point(start_byte),
+                end_point: to_point(end_byte),
+            }
+        };
+
+        let file: Arc<str> = Arc::from(f);
+        let mut interner = Interner::default();
+        let mut ret = Vec::new();
+        let mut taken = HashMap::new();
+
+        // defs
+        for re in &rule.export_regexes {
+            let symbol_type = re
+                .capture_names()
+                .nth(1)
+                .flatten()
+                .map_or(SymbolType::UNKNOWN, SymbolType::from_capture);
+            for cap in re.captures_iter(s) {
+                if let Some(name) = cap.get(1) {
+                    let mut def_node = Symbol::new_def(
+                        file.clone(),
+                        interner.intern(name.as_str()),
+                        to_range(name.start(), name.end()),
+                    );
+                    def_node.symbol_type = symbol_type;
+                    if taken.contains_key(&def_node.range.start_byte) {
+                        continue;
+                    }
+                    taken.insert(def_node.range.start_byte, ());
+                    ret.push(def_node);
+                }
             }
         }
+
+        // refs
+        for re in &rule.import_regexes {
+            let symbol_type = re
+                .capture_names()
+                .nth(1)
+                .flatten()
+                .map_or(SymbolType::UNKNOWN, SymbolType::from_capture);
+            for cap in re.captures_iter(s) {
+                if let Some(name) = cap.get(1) {
+                    let mut ref_node = Symbol::new_ref(
+                        file.clone(),
+                        interner.intern(name.as_str()),
+                        to_range(name.start(), name.end()),
+                    );
+                    ref_node.symbol_type = symbol_type;
+                    if taken.contains_key(&ref_node.range.start_byte) {
+                        continue;
+                    }
+                    taken.insert(ref_node.range.start_byte, ());
+                    ret.push(ref_node);
+                }
+            }
+        }
+
+        ret
     }
 
-    fn _extract(&self, f: &String, s: &String, language: &Language) -> Vec<Symbol> {
+    fn _extract(
+        &self,
+        f: &String,
+        s: &String,
+        language: &Language,
+        capture_signatures: bool,
+    ) -> Vec<Symbol> {
         let mut parser = Parser::new();
         parser
             .set_language(language)
             .expect("Error loading grammar");
         let tree = parser.parse(s, None).unwrap();
 
-        let rule = get_rule(&self);
+        let rule = get_rule(self);
+        let file: Arc<str> = Arc::from(f.as_str());
+        let mut interner = Interner::default();
         let mut ret = Vec::new();
         let mut taken = HashMap::new();
+        let mut ref_index: HashMap<usize, usize> = HashMap::new();
 
         // defs
         {
@@ -81,11 +420,38 @@ impl Extractor {
             for mat in matches {
                 let matched_node = mat.captures[0].node;
                 let range = matched_node.range();
+                let capture_name = &query.capture_names()[mat.captures[0].index as usize];
 
                 if let Ok(str_slice) = matched_node.utf8_text(s.as_bytes()) {
-                    let string = str_slice.to_string();
-                    let def_node = Symbol::new_def(f.clone(), string, range);
-                    taken.insert(def_node.id(), ());
+                    let mut string = str_slice.to_string();
+                    let mut symbol_type = SymbolType::from_capture(capture_name);
+                    if *capture_name == "default" {
+                        // importers usually name it after the file
+                        string = Path::new(f)
+                            .file_stem()
+                            .and_then(|stem| stem.to_str())
+                            .unwrap_or_default()
+                            .to_string();
+                        symbol_type = match matched_node.kind() {
+                            "class" => SymbolType::CLASS,
+                            _ => SymbolType::FUNCTION,
+                        };
+                    }
+                    let mut def_node =
+                        Symbol::new_def(file.clone(), interner.intern(&string), range);
+                    // the same node can be matched by more than one pattern
+                    if taken.contains_key(&def_node.range.start_byte) {
+                        continue;
+                    }
+                    def_node.symbol_type = symbol_type;
+                    def_node.scope = scope_of_def(matched_node, s.as_bytes());
+                    if capture_signatures {
+                        if let Some(declaration) = matched_node.parent() {
+                            def_node.signature = signature_of(declaration, s.as_bytes());
+                            def_node.doc = doc_of(declaration, s.as_bytes());
+                        }
+                    }
+                    taken.insert(def_node.range.start_byte, ());
                     ret.push(def_node);
                 }
             }
@@ -99,13 +465,34 @@ impl Extractor {
             for mat in matches {
                 let matched_node = mat.captures[0].node;
                 let range = matched_node.range();
+                let capture_name = &query.capture_names()[mat.captures[0].index as usize];
 
                 if let Ok(str_slice) = matched_node.utf8_text(s.as_bytes()) {
-                    let string = str_slice.to_string();
-                    let ref_node = Symbol::new_ref(f.clone(), string, range);
-                    if taken.contains_key(&ref_node.id()) {
+                    let mut string = str_slice.to_string();
+                    if *capture_name == "module" {
+                        // links to the whole file, like markdown links
+                        string = match resolve_link(f, &string) {
+                            Some(path) => path,
+                            None => continue,
+                        };
+                    }
+                    let mut ref_node =
+                        Symbol::new_ref(file.clone(), interner.intern(&string), range);
+                    ref_node.symbol_type = SymbolType::from_capture(capture_name);
+                    ref_node.scope = qualifier_of_ref(s, range.start_byte);
+                    if taken.contains_key(&ref_node.range.start_byte) {
+                        continue;
+                    }
+                    // the same node can be matched by more than one pattern,
+                    // the more specific type wins
+                    if let Some(&index) = ref_index.get(&ref_node.range.start_byte) {
+                        let existing: &mut Symbol = &mut ret[index];
+                        if existing.symbol_type == SymbolType::UNKNOWN {
+                            existing.symbol_type = ref_node.symbol_type;
+                        }
                         continue;
                     }
+                    ref_index.insert(ref_node.range.start_byte, ret.len());
                     ret.push(ref_node);
                 }
             }
@@ -122,12 +509,12 @@ impl Extractor {
                     let range = matched_node.range();
 
                     let ref_node = Symbol::new_namespace(
-                        f.clone(),
+                        file.clone(),
                         // empty string will break some func
-                        String::from(DEFAULT_NAMESPACE_REPR),
+                        interner.intern(DEFAULT_NAMESPACE_REPR),
                         range,
                     );
-                    if taken.contains_key(&ref_node.id()) {
+                    if taken.contains_key(&ref_node.range.start_byte) {
                         continue;
                     }
                     ret.push(ref_node);
@@ -141,10 +528,52 @@ impl Extractor {
 
 #[cfg(test)]
 mod tests {
-    use crate::extractor::Extractor;
+    use crate::extractor::{grammar_regexes, Extractor};
+    use crate::symbol::{SymbolKind, SymbolType};
     use std::fs;
     use tracing::info;
 
+    #[test]
+    fn stable_ids() {
+        let file = String::from("calc.go");
+        let before = String::from(
+            "package calc\n\nfunc Add(a, b int) int {\n\treturn a + b\n}\n\nfunc Run() {\n\tAdd(1, 2)\n\tAdd(3, 4)\n}\n",
+        );
+        let after = before.replace(
+            "package calc\n",
+            "package calc\n\nfunc Sub(a, b int) int {\n\treturn a - b\n}\n",
+        );
+        let ids = |content: &String, name: &str, kind: SymbolKind| -> Vec<(String, usize)> {
+            Extractor::Go
+                .extract(&file, content)
+                .into_iter()
+                .filter(|each| &*each.name == name && each.kind == kind)
+                .map(|each| (each.id(), each.range.start_byte))
+                .collect()
+        };
+
+        let (defs_before, defs_after) = (
+            ids(&before, "Add", SymbolKind::DEF),
+            ids(&after, "Add", SymbolKind::DEF),
+        );
+        assert_eq!(defs_before.len(), 1);
+        assert_eq!(defs_before[0].0, defs_after[0].0);
+        assert_ne!(defs_before[0].1, defs_after[0].1);
+
+        let refs: Vec<String> = ids(&before, "Add", SymbolKind::REF)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(refs.len(), 2);
+        assert_ne!(refs[0], refs[1]);
+        assert_ne!(refs[0], defs_before[0].0);
+        let refs_after: Vec<String> = ids(&after, "Add", SymbolKind::REF)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(refs, refs_after);
+    }
+
     #[test]
     fn extract_rust() {
         let symbols = Extractor::Rust.extract(
@@ -183,7 +612,17 @@ impl Extractor {
         );
         symbols.iter().for_each(|each| {
             info!("symbol: {:?}", each);
-        })
+        });
+        let def = symbols
+            .iter()
+            .find(|each| &*each.name == "extract" && each.kind == SymbolKind::DEF)
+            .unwrap();
+        assert_eq!(def.qualified_name(), "Extractor::extract");
+        let reference = symbols
+            .iter()
+            .find(|each| &*each.name == "new" && each.kind == SymbolKind::REF)
+            .unwrap();
+        assert_eq!(reference.scope, "Parser");
     }
 
     #[test]
@@ -258,6 +697,11 @@ package abc
 type Parser struct {
 	*Headless
 	engine *sitter.Parser
+	Timeout int
+}
+
+type Engine interface {
+	Parse(content []byte) error
 }
 
 func NormalFunc(lang *sitter.Language) string {
@@ -285,7 +729,17 @@ type c = d
         );
         symbols.iter().for_each(|each| {
             info!("symbol: {:?}", each);
-        })
+        });
+        let find_def = |name: &str| {
+            symbols
+                .iter()
+                .find(|each| &*each.name == name && each.kind == SymbolKind::DEF)
+                .map(|each| each.symbol_type)
+        };
+        assert_eq!(find_def("Parse"), Some(SymbolType::METHOD));
+        assert_eq!(find_def("Timeout"), Some(SymbolType::VARIABLE));
+        // unexported field
+        assert_eq!(find_def("engine"), None);
     }
 
     #[test]
@@ -334,7 +788,12 @@ class BaseStep(object):
         );
         symbols.iter().for_each(|each| {
             info!("symbol: {:?}", each);
-        })
+        });
+        let class_def = symbols
+            .iter()
+            .find(|each| &*each.name == "BaseStep" && each.kind == SymbolKind::DEF)
+            .unwrap();
+        assert_eq!(class_def.symbol_type, SymbolType::CLASS);
     }
 
     #[test]
@@ -489,4 +948,408 @@ class AppDelegate: UIResponder, UIApplicationDelegate {
             info!("symbol: {:?}", each);
         })
     }
+
+    #[test]
+    fn extract_vue() {
+        let symbols = Extractor::Vue.extract(
+            &String::from("abc"),
+            &String::from(
+                r#"
+<template>
+  <div @click="handleClick">{{ message }}</div>
+</template>
+
+<script lang="ts">
+import { defineComponent } from 'vue';
+
+export function handleClick() {
+  console.log(formatMessage("clicked"));
+}
+</script>
+            "#,
+            ),
+        );
+        symbols.iter().for_each(|each| {
+            info!("symbol: {:?}", each);
+        });
+        let def = symbols
+            .iter()
+            .find(|each| &*each.name == "handleClick" && each.kind == SymbolKind::DEF)
+            .unwrap();
+        assert_eq!(def.range.start_point.row, 8);
+    }
+
+    #[test]
+    fn extract_proto() {
+        let symbols = Extractor::Proto.extract(
+            &String::from("abc"),
+            &String::from(
+                r#"
+syntax = "proto3";
+
+package user.v1;
+
+message GetUserRequest {
+  string user_id = 1;
+}
+
+message GetUserResponse {
+  User user = 1;
+  repeated google.protobuf.Any details = 2;
+}
+
+enum Role {
+  ROLE_UNSPECIFIED = 0;
+}
+
+service UserService {
+  rpc GetUser(GetUserRequest) returns (GetUserResponse);
+  rpc WatchUser(stream GetUserRequest) returns (stream user.v1.GetUserResponse);
+}
+            "#,
+            ),
+        );
+        symbols.iter().for_each(|each| {
+            info!("symbol: {:?}", each);
+        });
+        let def = symbols
+            .iter()
+            .find(|each| &*each.name == "GetUser" && each.kind == SymbolKind::DEF)
+            .unwrap();
+        assert_eq!(def.range.start_point.row, 19);
+        assert!(symbols
+            .iter()
+            .any(|each| &*each.name == "User" && each.kind == SymbolKind::REF));
+    }
+
+    #[test]
+    fn extract_thrift() {
+        let symbols = Extractor::Thrift.extract(
+            &String::from("abc"),
+            &String::from(
+                r#"
+namespace go user
+
+struct User {
+  1: required string name,
+  2: optional Profile profile,
+}
+
+exception NotFound {
+  1: string message,
+}
+
+service UserService {
+  User getUser(1: string id) throws (1: NotFound err),
+  oneway void ping(),
+}
+            "#,
+            ),
+        );
+        symbols.iter().for_each(|each| {
+            info!("symbol: {:?}", each);
+        });
+        assert!(symbols
+            .iter()
+            .any(|each| &*each.name == "getUser" && each.kind == SymbolKind::DEF));
+        assert!(symbols
+            .iter()
+            .any(|each| &*each.name == "Profile" && each.kind == SymbolKind::REF));
+    }
+
+    #[test]
+    fn regex_grammars() {
+        for extractor in [Extractor::Proto, Extractor::Thrift, Extractor::Markdown] {
+            let rule = extractor.get_rule();
+            for grammar in [rule.export_grammar, rule.import_grammar] {
+                let regexes = grammar_regexes(grammar)
+                    .unwrap_or_else(|err| panic!("{:?}: {}", extractor, err));
+                for re in regexes {
+                    // the symbol name
+                    assert!(re.captures_len() > 1, "{:?}: {}", extractor, re);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn extract_markdown() {
+        let symbols = Extractor::Markdown.extract(
+            &String::from("docs/usage.md"),
+            &String::from(
+                r#"
+## Usage
+
+Call `related_files` (or `Graph::file_metadata()`) after `create_graph`.
+
+See [rules](../src/rule.rs), [the example](./mini.rs#L10) and [homepage](https://github.com/williamfzc/gossiphs).
+            "#,
+            ),
+        );
+        symbols.iter().for_each(|each| {
+            info!("symbol: {:?}", each);
+        });
+        let names: Vec<&str> = symbols.iter().map(|each| &*each.name).collect();
+        assert!(names.contains(&"related_files"));
+        assert!(names.contains(&"file_metadata"));
+        assert!(names.contains(&"src/rule.rs"));
+        assert!(names.contains(&"docs/mini.rs"));
+        assert_eq!(names.len(), 5);
+    }
+
+    #[test]
+    fn extract_signatures() {
+        let symbols = Extractor::Rust.extract_with_signatures(
+            &String::from("abc"),
+            &String::from(
+                r#"
+/// Create a graph from config.
+/// It walks the git history.
+#[inline]
+pub fn create_graph(config: GraphConfig) -> Graph {
+    Graph::from(config)
+}
+"#,
+            ),
+            true,
+        );
+        let def = symbols
+            .iter()
+            .find(|each| &*each.name == "create_graph" && each.kind == SymbolKind::DEF)
+            .unwrap();
+        assert_eq!(
+            def.signature.as_deref(),
+            Some("(config: GraphConfig) -> Graph")
+        );
+        assert_eq!(
+            def.doc.as_deref(),
+            Some("/// Create a graph from config.\n/// It walks the git history.")
+        );
+
+        let symbols = Extractor::Python.extract_with_signatures(
+            &String::from("abc"),
+            &String::from(
+                r#"
+def name(self) -> str:
+    """step name"""
+    raise NotImplementedError
+"#,
+            ),
+            true,
+        );
+        let def = symbols
+            .iter()
+            .find(|each| &*each.name == "name" && each.kind == SymbolKind::DEF)
+            .unwrap();
+        assert_eq!(def.signature.as_deref(), Some("(self) -> str"));
+        assert_eq!(def.doc.as_deref(), Some("\"\"\"step name\"\"\""));
+    }
+
+    #[test]
+    fn extract_rust_repo_files() {
+        // this repo itself
+        let find_def = |file: &str, name: &str| {
+            let content = fs::read_to_string(file).unwrap();
+            Extractor::Rust
+                .extract(&String::from(file), &content)
+                .into_iter()
+                .find(|each| &*each.name == name && each.kind == SymbolKind::DEF)
+                .map(|each| each.symbol_type)
+        };
+
+        assert_eq!(find_def("src/symbol.rs", "Symbol"), Some(SymbolType::CLASS));
+        assert_eq!(
+            find_def("src/symbol.rs", "SymbolKind"),
+            Some(SymbolType::TYPE)
+        );
+        assert_eq!(
+            find_def("src/symbol.rs", "list_definitions"),
+            Some(SymbolType::METHOD)
+        );
+        assert_eq!(
+            find_def("src/graph.rs", "create_cupido_graph"),
+            Some(SymbolType::FUNCTION)
+        );
+        assert_eq!(
+            find_def("src/server.rs", "VERSION"),
+            Some(SymbolType::VARIABLE)
+        );
+    }
+
+    #[test]
+    fn extract_typescript_exports() {
+        let symbols = Extractor::TypeScript.extract(
+            &String::from("src/hooks/useAuth.ts"),
+            &String::from(
+                r#"
+export enum AuthState {
+  Anonymous,
+  LoggedIn,
+}
+
+export * from './types';
+export * as helpers from '../helpers';
+
+export default () => {
+  return AuthState.Anonymous;
+};
+            "#,
+            ),
+        );
+        symbols.iter().for_each(|each| {
+            info!("symbol: {:?}", each);
+        });
+        let find = |name: &str, kind: SymbolKind| {
+            symbols
+                .iter()
+                .find(|each| &*each.name == name && each.kind == kind)
+                .map(|each| each.symbol_type)
+        };
+        assert_eq!(find("AuthState", SymbolKind::DEF), Some(SymbolType::TYPE));
+        assert_eq!(find("useAuth", SymbolKind::DEF), Some(SymbolType::FUNCTION));
+        assert_eq!(find("helpers", SymbolKind::DEF), Some(SymbolType::VARIABLE));
+        assert!(find("src/hooks/types", SymbolKind::REF).is_some());
+    }
+
+    #[test]
+    fn extract_python_definitions() {
+        let symbols = Extractor::Python.extract(
+            &String::from("abc"),
+            &String::from(
+                r#"
+import dataclasses
+
+DEFAULT_TIMEOUT = 30
+logger = get_logger()
+
+@dataclasses.dataclass
+class StepConfig:
+    name: str
+    timeout: int = DEFAULT_TIMEOUT
+
+@register_step("build")
+def build(config: StepConfig):
+    pass
+            "#,
+            ),
+        );
+        symbols.iter().for_each(|each| {
+            info!("symbol: {:?}", each);
+        });
+        let find = |name: &str, kind: SymbolKind| {
+            symbols
+                .iter()
+                .find(|each| &*each.name == name && each.kind == kind)
+                .map(|each| each.symbol_type)
+        };
+        assert_eq!(
+            find("DEFAULT_TIMEOUT", SymbolKind::DEF),
+            Some(SymbolType::VARIABLE)
+        );
+        assert_eq!(find("logger", SymbolKind::DEF), None);
+        assert_eq!(find("timeout", SymbolKind::DEF), Some(SymbolType::VARIABLE));
+        assert_eq!(
+            find("register_step", SymbolKind::REF),
+            Some(SymbolType::FUNCTION)
+        );
+        assert_eq!(
+            find("dataclass", SymbolKind::REF),
+            Some(SymbolType::FUNCTION)
+        );
+    }
+
+    #[test]
+    fn extract_kt_objects() {
+        let symbols = Extractor::Kotlin.extract(
+            &String::from("abc"),
+            &String::from(
+                r#"
+package com.example.core
+
+const val DEFAULT_PAGE_SIZE = 20
+val dispatcher = Dispatchers.IO
+
+object NetworkModule {
+    val baseUrl = "https://example.com"
+}
+
+class UserRepository {
+    companion object Factory {
+        fun create(): UserRepository = UserRepository()
+    }
+}
+            "#,
+            ),
+        );
+        symbols.iter().for_each(|each| {
+            info!("symbol: {:?}", each);
+        });
+        let find_def = |name: &str| {
+            symbols
+                .iter()
+                .find(|each| &*each.name == name && each.kind == SymbolKind::DEF)
+                .map(|each| each.symbol_type)
+        };
+        assert_eq!(find_def("DEFAULT_PAGE_SIZE"), Some(SymbolType::VARIABLE));
+        assert_eq!(find_def("dispatcher"), Some(SymbolType::VARIABLE));
+        assert_eq!(find_def("NetworkModule"), Some(SymbolType::CLASS));
+        assert_eq!(find_def("baseUrl"), Some(SymbolType::VARIABLE));
+        assert_eq!(find_def("Factory"), Some(SymbolType::CLASS));
+    }
+
+    #[test]
+    fn extract_swift_types() {
+        let symbols = Extractor::Swift.extract(
+            &String::from("abc"),
+            &String::from(
+                r#"
+protocol Storage {
+    func save(_ key: String)
+}
+
+struct Point {
+    var x: Int
+}
+
+enum Direction {
+    case up, down
+}
+
+class DiskStorage: Storage {
+    func save(_ key: String) {
+        let encoder = JSONEncoder()
+        encoder.encode(key)
+    }
+}
+
+extension Point: Equatable {}
+            "#,
+            ),
+        );
+        symbols.iter().for_each(|each| {
+            info!("symbol: {:?}", each);
+        });
+        let find = |name: &str, kind: SymbolKind| {
+            symbols
+                .iter()
+                .find(|each| &*each.name == name && each.kind == kind)
+                .map(|each| each.symbol_type)
+        };
+        assert_eq!(find("Storage", SymbolKind::DEF), Some(SymbolType::TYPE));
+        assert_eq!(find("Point", SymbolKind::DEF), Some(SymbolType::CLASS));
+        assert_eq!(find("Direction", SymbolKind::DEF), Some(SymbolType::TYPE));
+        assert_eq!(
+            find("DiskStorage", SymbolKind::DEF),
+            Some(SymbolType::CLASS)
+        );
+        assert_eq!(find("save", SymbolKind::DEF), Some(SymbolType::METHOD));
+        assert_eq!(find("Storage", SymbolKind::REF), Some(SymbolType::CLASS));
+        assert_eq!(
+            find("JSONEncoder", SymbolKind::REF),
+            Some(SymbolType::FUNCTION)
+        );
+        assert_eq!(find("encode", SymbolKind::REF), Some(SymbolType::METHOD));
+        // plain locals are no longer references
+        assert_eq!(find("encoder", SymbolKind::REF), None);
+    }
 }
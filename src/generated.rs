@@ -0,0 +1,111 @@
+// linguist-style heuristics of the files not written by hand,
+// their symbols and commits tie together files that have nothing in common
+
+// directories of dependencies checked into the repo
+const VENDORED_DIRS: [&str; 7] = [
+    "node_modules",
+    "vendor",
+    "third_party",
+    "third-party",
+    "bower_components",
+    "Pods",
+    "site-packages",
+];
+
+const LOCKFILES: [&str; 12] = [
+    "Cargo.lock",
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "go.sum",
+    "poetry.lock",
+    "Pipfile.lock",
+    "composer.lock",
+    "Gemfile.lock",
+    "Podfile.lock",
+    "pubspec.lock",
+];
+
+const GENERATED_SUFFIXES: [&str; 10] = [
+    ".min.js",
+    ".min.css",
+    ".bundle.js",
+    ".pb.go",
+    ".pb.h",
+    ".pb.cc",
+    "_pb2.py",
+    "_pb2_grpc.py",
+    ".g.dart",
+    ".freezed.dart",
+];
+
+// in the first lines of generated files
+const GENERATED_HEADERS: [&str; 5] = [
+    "code generated",
+    "do not edit",
+    "@generated",
+    "autogenerated",
+    "auto-generated",
+];
+
+// the average line length of minified files, hand-written code is far below it
+const MINIFIED_LINE_LEN: usize = 300;
+
+/// Vendored dependencies, lockfiles and the outputs of code generators, by their paths.
+pub fn is_generated_path(path: &str) -> bool {
+    let (dirs, name) = path.rsplit_once('/').unwrap_or(("", path));
+    dirs.split('/').any(|dir| VENDORED_DIRS.contains(&dir))
+        || LOCKFILES.contains(&name)
+        || GENERATED_SUFFIXES
+            .iter()
+            .any(|suffix| name.ends_with(suffix))
+        || name.contains(".generated.")
+}
+
+/// Files marked as generated in their headers, and minified ones.
+pub fn is_generated_content(content: &str) -> bool {
+    let marked = content.lines().take(5).any(|line| {
+        let line = line.to_lowercase();
+        GENERATED_HEADERS.iter().any(|header| line.contains(header))
+    });
+    let lines = content.lines().count();
+    marked || (lines > 0 && content.len() / lines > MINIFIED_LINE_LEN)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::generated::{is_generated_content, is_generated_path};
+
+    #[test]
+    fn generated_path() {
+        assert!(is_generated_path("node_modules/react/index.js"));
+        assert!(is_generated_path("web/node_modules/react/index.js"));
+        assert!(is_generated_path("vendor/github.com/pkg/errors/errors.go"));
+        assert!(is_generated_path("Cargo.lock"));
+        assert!(is_generated_path("web/package-lock.json"));
+        assert!(is_generated_path("static/app.min.js"));
+        assert!(is_generated_path("api/user.pb.go"));
+        assert!(is_generated_path("api/user_pb2.py"));
+        assert!(is_generated_path("src/schema.generated.ts"));
+
+        assert!(!is_generated_path("src/graph.rs"));
+        assert!(!is_generated_path("src/vendors.rs"));
+        assert!(!is_generated_path("Cargo.toml"));
+    }
+
+    #[test]
+    fn generated_content() {
+        assert!(is_generated_content(
+            "// Code generated by protoc-gen-go. DO NOT EDIT.\npackage api\n"
+        ));
+        assert!(is_generated_content("# @generated by pants\nimport os\n"));
+        assert!(is_generated_content(&format!(
+            "var a={};",
+            "1,".repeat(1000)
+        )));
+
+        assert!(!is_generated_content(include_str!("generated.rs")));
+        assert!(!is_generated_content(""));
+    }
+}
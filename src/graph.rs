@@ -1,20 +1,30 @@
+use crate::api::RelatedFileContext;
+use crate::cache::CacheManager;
+use crate::error::GossiphsError;
 use crate::extractor::Extractor;
-use crate::symbol::{Symbol, SymbolGraph, SymbolKind};
-use cupido::collector::config::Collect;
-use cupido::collector::config::{get_collector, Config};
+use crate::generated;
+use crate::history;
+use crate::remote;
+use crate::snapshot;
+use crate::symbol::{Interner, Symbol, SymbolGraph, SymbolKind};
 use cupido::relation::graph::RelationGraph as CupidoRelationGraph;
-use git2::Repository;
+use git2::{ObjectType, Oid, Repository, StatusOptions, Tree};
 use indicatif::ProgressBar;
-use pyo3::{pyclass, pymethods};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
 use rayon::iter::IntoParallelRefIterator;
 use rayon::iter::ParallelIterator;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
 use std::path::Path;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
 use std::time::Instant;
 use tracing::{debug, info, warn};
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct FileContext {
     pub path: String,
     pub symbols: Vec<Symbol>,
@@ -40,11 +50,94 @@ impl<'a> NamespaceManager<'a> {
     }
 }
 
-#[pyclass]
+type SymbolTable = HashMap<Arc<str>, Vec<Symbol>>;
+
+/// Where the contents of files are read from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FileSource {
+    /// files on disk, uncommitted changes and untracked (not ignored) files included
+    WorkingTree,
+    /// blobs of HEAD
+    Head,
+    /// blobs of a commit (branch, tag, sha ...), without checking it out
+    Rev(String),
+}
+
+impl FileSource {
+    /// The commit the history is walked from, None for HEAD.
+    pub fn rev(&self) -> Option<&str> {
+        match self {
+            FileSource::Rev(rev) => Some(rev),
+            FileSource::WorkingTree | FileSource::Head => None,
+        }
+    }
+}
+
+/// Stage of a running build and the files done in it, see `GraphConfig.progress`
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BuildStatus {
+    // clone, history, extraction or linking, the stages of `Graph.build_timings`
+    pub phase: String,
+    pub files_processed: usize,
+    pub files_total: usize,
+}
+
+/// A handle for watching a build from another thread, the clones share the same status.
+#[derive(Clone, Debug, Default)]
+pub struct BuildProgress(Arc<Mutex<BuildStatus>>);
+
+impl BuildProgress {
+    pub fn status(&self) -> BuildStatus {
+        self.0.lock().unwrap().clone()
+    }
+
+    fn start(&self, phase: &str, files_total: usize) {
+        *self.0.lock().unwrap() = BuildStatus {
+            phase: phase.to_string(),
+            files_processed: 0,
+            files_total,
+        };
+    }
+
+    fn inc(&self) {
+        self.0.lock().unwrap().files_processed += 1;
+    }
+}
+
+#[pyclass(module = "gossiphs")]
 pub struct Graph {
+    pub(crate) project_path: String,
+    // prefixes stripped from the queried files, see `project_roots`
+    pub(crate) project_roots: Vec<String>,
+    pub(crate) commit_id: Option<String>,
     pub(crate) file_contexts: Vec<FileContext>,
     pub(crate) _relation_graph: CupidoRelationGraph,
     pub(crate) symbol_graph: SymbolGraph,
+    // file -> co-changed file -> score, see `GraphConfig::co_change_weight`
+    pub(crate) co_changes: HashMap<String, HashMap<String, usize>>,
+    // file -> related files, see `Graph::build_index`
+    pub(crate) related_index: Option<HashMap<String, Vec<RelatedFileContext>>>,
+    // inputs of the symbol weights, see `Graph::explain_relation`
+    pub(crate) file_len: usize,
+    pub(crate) commit_size_limit: usize,
+    pub(crate) cross_language_ratio: f64,
+    // same as `GraphConfig`, see `Graph::ambiguous_symbols`
+    pub(crate) def_limit: usize,
+    // tuning of the symbol weights and the file scores, see `ScoringConfig`
+    pub(crate) scoring: ScoringConfig,
+    // commit -> multiplier by its age, empty if `ScoringConfig::half_life_days` is off
+    pub(crate) commit_decays: HashMap<String, f64>,
+    // symbol name -> multiplier by its spread, empty if `ScoringConfig::idf_damping` is off
+    pub(crate) symbol_idf: HashMap<Arc<str>, f64>,
+    // root directories of the packages, see `Graph::package_of`
+    pub(crate) packages: Vec<String>,
+    // files over `GraphConfig::symbol_limit` keeping a part of their symbols
+    pub(crate) truncated_files: HashSet<String>,
+    // (stage, seconds) of `Graph::try_from`, see `Graph::stats`
+    pub(crate) build_timings: Vec<(String, f64)>,
+    // same as `GraphConfig`, for the queries over all the files
+    pub(crate) show_progress: bool,
+    pub(crate) threads: usize,
 }
 
 impl Graph {
@@ -52,8 +145,9 @@ impl Graph {
         file_name: &String,
         file_content: &String,
         _symbol_limit: usize,
+        capture_signatures: bool,
     ) -> Option<FileContext> {
-        let file_extension = match file_name.split('.').last() {
+        let file_extension = match file_name.split('.').next_back() {
             Some(ext) => ext.to_lowercase(),
             None => {
                 debug!("File {} has no extension, skipping...", file_name);
@@ -61,23 +155,9 @@ impl Graph {
             }
         };
 
-        let extractor_mapping: HashMap<&str, &Extractor> = [
-            ("rs", &Extractor::Rust),
-            ("ts", &Extractor::TypeScript),
-            ("tsx", &Extractor::TypeScript),
-            ("go", &Extractor::Go),
-            ("py", &Extractor::Python),
-            ("js", &Extractor::JavaScript),
-            ("jsx", &Extractor::JavaScript),
-            ("java", &Extractor::Java),
-            ("kt", &Extractor::Kotlin),
-            ("swift", &Extractor::Swift),
-        ]
-        .into_iter()
-        .collect();
-
-        if let Some(extractor) = extractor_mapping.get(file_extension.as_str()) {
-            let symbols = extractor.extract(file_name, file_content);
+        if let Some(extractor) = extractor_of_extension(file_extension.as_str()) {
+            let symbols =
+                extractor.extract_with_signatures(file_name, file_content, capture_signatures);
             let mut file_context = FileContext {
                 // use the relative path as key
                 path: file_name.clone(),
@@ -106,27 +186,19 @@ impl Graph {
             file_context.symbols = file_context
                 .symbols
                 .iter()
-                .filter_map(|symbol| {
+                .filter(|symbol| {
+                    // keep namespaces for locating the bodies of functions
                     if symbol.kind == SymbolKind::NAMESPACE {
-                        return None;
+                        return true;
                     }
 
                     let line = symbol.range.start_point.row;
                     let depth = namespace_manager.get_line_depth(line);
 
-                    match symbol.kind {
-                        SymbolKind::DEF => {
-                            // nested def
-                            if depth >= rule.namespace_filter_level {
-                                return None;
-                            }
-
-                            return Some(symbol);
-                        }
-                        _ => Some(symbol),
-                    }
+                    // nested def
+                    symbol.kind != SymbolKind::DEF || depth < rule.namespace_filter_level
                 })
-                .map(|f| f.clone())
+                .cloned()
                 .collect();
 
             Some(file_context)
@@ -135,17 +207,102 @@ impl Graph {
         }
     }
 
+    // the extracted files and the truncated ones of them
     fn extract_file_contexts(
-        root: &String,
+        conf: &GraphConfig,
         files: Vec<String>,
-        symbol_limit: usize,
-    ) -> Vec<FileContext> {
-        let repo = Repository::open(root).unwrap();
-        let head = repo.head().unwrap();
-        let commit = head.peel_to_commit().unwrap();
-        let tree = commit.tree().unwrap();
+    ) -> Result<(Vec<FileContext>, HashSet<String>), GossiphsError> {
+        let repo = Repository::open(&conf.project_path)
+            .map_err(GossiphsError::open(&conf.project_path))?;
+        let mut file_content_pairs = match &conf.source {
+            FileSource::Head | FileSource::Rev(_) => {
+                let commit = history::resolve_commit(&repo, conf.source.rev())?;
+                Self::read_tree_files(&repo, &commit.tree()?, files, conf.lossy_decode)
+            }
+            FileSource::WorkingTree => {
+                let workdir = repo
+                    .workdir()
+                    .ok_or_else(|| GossiphsError::BareRepository(conf.project_path.clone()))?;
+                Self::read_working_tree_files(workdir, files, conf.lossy_decode)
+            }
+        };
+        if conf.detect_generated {
+            file_content_pairs.retain(|(_, _, content)| !generated::is_generated_content(content));
+        }
+
+        let cache = conf
+            .enable_cache
+            .then(|| CacheManager::new(&conf.project_path));
+        let pb = progress_bar(conf.show_progress, file_content_pairs.len());
+        conf.progress.start("extraction", file_content_pairs.len());
+        let extract = || -> Vec<FileContext> {
+            file_content_pairs
+                .par_iter()
+                .map(|(file_path, oid, file_content)| {
+                    pb.inc(1);
+                    conf.progress.inc();
+                    if let Some(file_context) = cache
+                        .as_ref()
+                        .and_then(|cache| cache.get(oid, file_path, conf.capture_signatures))
+                    {
+                        return Some(file_context);
+                    }
+                    let file_context = Graph::extract_file_context(
+                        file_path,
+                        file_content,
+                        conf.symbol_limit,
+                        conf.capture_signatures,
+                    );
+                    if let (Some(cache), Some(file_context)) = (&cache, &file_context) {
+                        cache.put(oid, file_context, conf.capture_signatures);
+                    }
+                    file_context
+                })
+                .filter(|ctx| ctx.is_some())
+                .map(|ctx| ctx.unwrap())
+                .collect()
+        };
+        let file_contexts = with_threads(conf.threads, extract);
+        pb.finish_and_clear();
+        Ok(Self::limit_file_contexts(conf, file_contexts))
+    }
+
+    // truncate or drop the large files, then intern the names
+    fn limit_file_contexts(
+        conf: &GraphConfig,
+        mut file_contexts: Vec<FileContext>,
+    ) -> (Vec<FileContext>, HashSet<String>) {
+        file_contexts
+            .retain(|ctx| conf.truncate_large_files || ctx.symbols.len() < conf.symbol_limit);
+        let mut truncated_files = HashSet::new();
+        for file_context in &mut file_contexts {
+            if file_context.symbols.len() >= conf.symbol_limit {
+                let symbols = std::mem::take(&mut file_context.symbols);
+                file_context.symbols =
+                    truncate_symbols(symbols, conf.symbol_limit.saturating_sub(1));
+                truncated_files.insert(file_context.path.clone());
+            }
+        }
+        if !truncated_files.is_empty() {
+            info!("truncated files: {}", truncated_files.len());
+        }
+
+        // files are extracted in parallel, share the same names across them
+        let mut interner = Interner::default();
+        file_contexts
+            .iter_mut()
+            .flat_map(|file_context| file_context.symbols.iter_mut())
+            .for_each(|symbol| interner.intern_symbol(symbol));
+        (file_contexts, truncated_files)
+    }
 
-        let file_content_pairs: Vec<_> = files
+    fn read_tree_files(
+        repo: &Repository,
+        tree: &Tree,
+        files: Vec<String>,
+        lossy_decode: bool,
+    ) -> Vec<(String, Oid, String)> {
+        files
             .into_iter()
             .filter_map(|file_path| {
                 let tree_entry = match tree.get_path(Path::new(&file_path)) {
@@ -156,7 +313,7 @@ impl Graph {
                     }
                 };
 
-                let object = match tree_entry.to_object(&repo) {
+                let object = match tree_entry.to_object(repo) {
                     Ok(obj) => obj,
                     Err(err) => {
                         warn!("Failed to get object for {:?}: {:?}", file_path, err);
@@ -166,7 +323,10 @@ impl Graph {
                 let blob = match object.peel_to_blob() {
                     Ok(blob) => blob,
                     Err(err) => {
-                        warn!("Failed to peel object to blob for {:?}: {:?}", file_path, err);
+                        warn!(
+                            "Failed to peel object to blob for {:?}: {:?}",
+                            file_path, err
+                        );
                         return None;
                     }
                 };
@@ -174,40 +334,68 @@ impl Graph {
                     return None;
                 }
 
-                match std::str::from_utf8(blob.content()) {
-                    Ok(content) => Some((file_path, content.to_string())),
+                decode(&file_path, blob.content(), lossy_decode)
+                    .map(|content| (file_path, blob.id(), content))
+            })
+            .collect()
+    }
+
+    fn read_working_tree_files(
+        workdir: &Path,
+        files: Vec<String>,
+        lossy_decode: bool,
+    ) -> Vec<(String, Oid, String)> {
+        files
+            .into_iter()
+            .filter_map(|file_path| {
+                let content = match fs::read(workdir.join(&file_path)) {
+                    Ok(content) => content,
                     Err(err) => {
-                        warn!("Invalid UTF-8 content in file {:?}: {:?}", file_path, err);
-                        None
+                        // removed from disk but still in history
+                        debug!("Failed to read {:?}: {:?}", file_path, err);
+                        return None;
                     }
+                };
+                // same as git, a NUL byte means binary
+                if content.contains(&0) {
+                    return None;
                 }
-            })
-            .collect();
 
-        let pb = ProgressBar::new(file_content_pairs.len() as u64);
-        let file_contexts: Vec<FileContext> = file_content_pairs
-            .par_iter()
-            .map(|(file_path, file_content)| {
-                pb.inc(1);
-                return Graph::extract_file_context(file_path, file_content, symbol_limit);
+                // the oid it would have once committed, for the cache
+                let oid = Oid::hash_object(ObjectType::Blob, &content).ok()?;
+                decode(&file_path, &content, lossy_decode).map(|content| (file_path, oid, content))
             })
-            .filter(|ctx| ctx.is_some())
-            .map(|ctx| ctx.unwrap())
-            .filter(|ctx| ctx.symbols.len() < symbol_limit)
+            .collect()
+    }
+
+    /// Files on disk which are not ignored, including the uncommitted ones.
+    fn list_working_tree_files(project_path: &str) -> Result<Vec<String>, GossiphsError> {
+        let repo = Repository::open(project_path).map_err(GossiphsError::open(project_path))?;
+        let mut files: Vec<String> = repo
+            .index()?
+            .iter()
+            .filter_map(|entry| String::from_utf8(entry.path).ok())
             .collect();
-        pb.finish_and_clear();
-        file_contexts
+
+        let mut opts = StatusOptions::new();
+        opts.include_untracked(true)
+            .recurse_untracked_dirs(true)
+            .include_ignored(false);
+        if let Ok(statuses) = repo.statuses(Some(&mut opts)) {
+            statuses
+                .iter()
+                .filter(|each| each.status().is_wt_new())
+                .filter_map(|each| each.path().map(String::from))
+                .for_each(|each| files.push(each));
+        }
+        Ok(files)
     }
 
     fn build_global_symbol_table(
         file_contexts: &[FileContext],
-    ) -> (
-        HashMap<String, Vec<Symbol>>,
-        HashMap<String, Vec<Symbol>>,
-        HashMap<String, Vec<Symbol>>,
-    ) {
-        let mut global_def_symbol_table: HashMap<String, Vec<Symbol>> = HashMap::new();
-        let mut global_ref_symbol_table: HashMap<String, Vec<Symbol>> = HashMap::new();
+    ) -> (SymbolTable, SymbolTable, SymbolTable) {
+        let mut global_def_symbol_table: SymbolTable = HashMap::new();
+        let mut global_ref_symbol_table: SymbolTable = HashMap::new();
 
         file_contexts
             .iter()
@@ -217,13 +405,13 @@ impl Graph {
                     SymbolKind::DEF => {
                         global_def_symbol_table
                             .entry(symbol.name.clone())
-                            .or_insert_with(Vec::new)
+                            .or_default()
                             .push(symbol.clone());
                     }
                     SymbolKind::REF => {
                         global_ref_symbol_table
                             .entry(symbol.name.clone())
-                            .or_insert_with(Vec::new)
+                            .or_default()
                             .push(symbol.clone());
                     }
                     // ignore
@@ -251,10 +439,19 @@ impl Graph {
 
     fn filter_pointless_symbols(
         file_contexts: &Vec<FileContext>,
-        global_def_symbol_table: &HashMap<String, Vec<Symbol>>,
-        global_ref_symbol_table: &HashMap<String, Vec<Symbol>>,
+        global_def_symbol_table: &SymbolTable,
+        global_ref_symbol_table: &SymbolTable,
         symbol_len_limit: usize,
+        symbol_stopwords: &[String],
+        include_symbol_regex: Option<&Regex>,
+        exclude_symbol_regex: Option<&Regex>,
     ) -> Vec<FileContext> {
+        let file_names: HashSet<&str> = file_contexts
+            .iter()
+            .map(|each| each.path.as_str())
+            .collect();
+        let is_file_link =
+            |name: &str| resolve_file_link(name, |candidate| file_names.contains(candidate));
         let mut filtered_file_contexts = Vec::new();
         for file_context in file_contexts {
             let filtered_symbols = file_context
@@ -262,22 +459,26 @@ impl Graph {
                 .iter()
                 .filter(|symbol| {
                     // ref but no def
-                    if !global_def_symbol_table.contains_key(&symbol.name) {
+                    // file links (e.g. from markdown) point to a file instead of a def
+                    if !global_def_symbol_table.contains_key(&symbol.name)
+                        && is_file_link(&symbol.name).is_none()
+                    {
                         return false;
                     }
-                    return true;
+                    true
                 })
                 .filter(|symbol| {
                     // def but no ref
                     if !global_ref_symbol_table.contains_key(&symbol.name) {
                         return false;
                     }
-                    return true;
+                    true
                 })
-                .filter(|symbol| {
-                    return symbol.name.len() > symbol_len_limit;
-                })
-                .map(|symbol| symbol.clone())
+                .filter(|symbol| symbol.name.len() > symbol_len_limit)
+                .filter(|symbol| !symbol_stopwords.iter().any(|each| **each == *symbol.name))
+                .filter(|symbol| include_symbol_regex.is_none_or(|re| re.is_match(&symbol.name)))
+                .filter(|symbol| !exclude_symbol_regex.is_some_and(|re| re.is_match(&symbol.name)))
+                .cloned()
                 .collect();
 
             filtered_file_contexts.push(FileContext {
@@ -290,39 +491,276 @@ impl Graph {
 
     pub fn empty() -> Graph {
         Graph {
+            project_path: String::from("."),
+            project_roots: project_roots("."),
+            commit_id: None,
             file_contexts: Vec::new(),
             _relation_graph: CupidoRelationGraph::new(),
             symbol_graph: SymbolGraph::new(),
+            co_changes: HashMap::new(),
+            related_index: None,
+            file_len: 0,
+            commit_size_limit: 0,
+            cross_language_ratio: 1.0,
+            def_limit: GraphConfig::default().def_limit,
+            scoring: ScoringConfig::default(),
+            commit_decays: HashMap::new(),
+            symbol_idf: HashMap::new(),
+            packages: Vec::new(),
+            truncated_files: HashSet::new(),
+            build_timings: Vec::new(),
+            show_progress: true,
+            threads: 0,
         }
     }
 
+    /// Same as `Graph::try_from`, but panics on errors.
     pub fn from(conf: GraphConfig) -> Graph {
+        Self::try_from(conf).unwrap_or_else(|err| panic!("Failed to build graph: {}", err))
+    }
+
+    pub fn try_from(mut conf: GraphConfig) -> Result<Graph, GossiphsError> {
         let start_time = Instant::now();
+        let mut build_timings = Vec::new();
+        let mut stage_time = Instant::now();
+        if remote::is_remote(&conf.project_path) {
+            conf.progress.start("clone", 0);
+            conf.project_path = remote::clone_or_fetch(&conf.project_path, conf.clone_depth)?;
+            lap(&mut build_timings, &mut stage_time, "clone");
+        }
         // 1. call cupido
         // 2. extract symbols
         // 3. building def and ref relations
-        let relation_graph = create_cupido_graph(
-            &conf.project_path,
-            conf.depth,
-            conf.exclude_author_regex,
-            conf.exclude_commit_regex,
-            conf.issue_regex,
-        );
+        conf.progress.start("history", 0);
+        let relation_graph = create_cupido_graph(&conf)?;
         let size = relation_graph.size();
         info!("relation graph ready, size: {:?}", size);
+        lap(&mut build_timings, &mut stage_time, "history");
+
+        let files = Self::list_files(&conf, &relation_graph)?;
+        let commit_id = match conf.source {
+            FileSource::Head | FileSource::Rev(_) => Repository::open(&conf.project_path)
+                .and_then(|repo| {
+                    repo.revparse_single(conf.source.rev().unwrap_or("HEAD"))?
+                        .peel_to_commit()
+                        .map(|commit| commit.id().to_string())
+                })
+                .ok(),
+            FileSource::WorkingTree => None,
+        };
+        let packages = detect_packages(&conf, &files)?;
+        let file_len = files.len();
+        let (file_contexts, truncated_files) = Self::extract_files(&conf, files)?;
+        info!("symbol extract finished, files: {}", file_contexts.len());
+        lap(&mut build_timings, &mut stage_time, "extraction");
+
+        let sources = Sources {
+            relation_graph,
+            file_contexts,
+            truncated_files,
+            commit_id,
+            packages,
+            file_len,
+            history: true,
+        };
+        Self::link(&conf, sources, build_timings, start_time)
+    }
+
+    /// Rebuild the graph after the `changed` paths (files or directories) of the working tree changed,
+    /// without walking the history again. Only the changed files are extracted, the others keep
+    /// their symbols, then all the files are linked again. New commits and checkouts need `Graph::try_from`.
+    pub fn update_files(
+        &self,
+        conf: &GraphConfig,
+        changed: &[String],
+    ) -> Result<Graph, GossiphsError> {
+        Self::update_sources(conf, self.sources(), changed)
+    }
+
+    // the inputs of linking this graph, with a copy of its history
+    pub(crate) fn sources(&self) -> Sources {
+        Sources {
+            relation_graph: snapshot::copy_relation_graph(&self._relation_graph),
+            file_contexts: self.file_contexts.clone(),
+            truncated_files: self.truncated_files.clone(),
+            commit_id: self.commit_id.clone(),
+            packages: self.packages.clone(),
+            file_len: self.file_len,
+            history: !self._relation_graph.commits().is_empty(),
+        }
+    }
+
+    // see `Graph::update_files`, `sources` can be taken without keeping the graph locked
+    pub(crate) fn update_sources(
+        conf: &GraphConfig,
+        sources: Sources,
+        changed: &[String],
+    ) -> Result<Graph, GossiphsError> {
+        let start_time = Instant::now();
+        let mut build_timings = Vec::new();
+        let mut stage_time = Instant::now();
+        let is_changed = |file: &str| {
+            changed.iter().any(|path| {
+                file.strip_prefix(path.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+        };
+
+        let files = Self::list_files(conf, &sources.relation_graph)?;
+        let mut file_contexts = sources.file_contexts;
+        let mut truncated_files = sources.truncated_files;
+        file_contexts.retain(|file_context| !is_changed(&file_context.path));
+        truncated_files.retain(|file| !is_changed(file));
+        let changed_files: Vec<String> = files
+            .iter()
+            .filter(|file| is_changed(file))
+            .cloned()
+            .collect();
+        info!("extracting {} changed files", changed_files.len());
+        let (changed_contexts, changed_truncated) = Self::extract_files(conf, changed_files)?;
+        file_contexts.extend(changed_contexts);
+        truncated_files.extend(changed_truncated);
+        lap(&mut build_timings, &mut stage_time, "extraction");
+
+        let sources = Sources {
+            file_contexts,
+            truncated_files,
+            packages: detect_packages(conf, &files)?,
+            file_len: files.len(),
+            ..sources
+        };
+        Self::link(conf, sources, build_timings, start_time)
+    }
 
+    // the files of the history, and the ones on disk for the working tree, after the file filters
+    fn list_files(
+        conf: &GraphConfig,
+        relation_graph: &CupidoRelationGraph,
+    ) -> Result<Vec<String>, GossiphsError> {
         let mut files = relation_graph.files();
+        if conf.source == FileSource::WorkingTree {
+            let known: HashSet<String> = files.iter().cloned().collect();
+            Self::list_working_tree_files(&conf.project_path)?
+                .into_iter()
+                .filter(|each| !known.contains(each))
+                .for_each(|each| files.push(each));
+        }
+        if !conf.include_file_regex.is_empty() {
+            let re = Regex::new(&conf.include_file_regex)
+                .map_err(GossiphsError::regex("include_file_regex"))?;
+            files.retain(|file| re.is_match(file));
+        }
         if !conf.exclude_file_regex.is_empty() {
-            let re = Regex::new(&conf.exclude_file_regex).expect("Invalid regex");
+            let re = Regex::new(&conf.exclude_file_regex)
+                .map_err(GossiphsError::regex("exclude_file_regex"))?;
             files.retain(|file| !re.is_match(file));
         }
+        if conf.detect_generated {
+            files.retain(|file| !generated::is_generated_path(file));
+        }
+        Ok(files)
+    }
+
+    // the extracted files and the truncated ones of them, with the files only linked by the history
+    fn extract_files(
+        conf: &GraphConfig,
+        files: Vec<String>,
+    ) -> Result<(Vec<FileContext>, HashSet<String>), GossiphsError> {
+        // no symbols to link, only the history
+        let co_change_only: Vec<String> = match conf.co_change_weight {
+            0 => Vec::new(),
+            _ => files
+                .iter()
+                .filter(|file| {
+                    let file_extension = file.split('.').next_back().unwrap_or_default();
+                    extractor_of_extension(&file_extension.to_lowercase()).is_none()
+                })
+                .cloned()
+                .collect(),
+        };
+        let (mut file_contexts, truncated_files) = Self::extract_file_contexts(conf, files)?;
+        file_contexts.extend(co_change_only.into_iter().map(|path| FileContext {
+            path,
+            symbols: Vec::new(),
+        }));
+        Ok((file_contexts, truncated_files))
+    }
 
+    /// Build the graph from the contents of the files instead of a repo, like a playground in the browser.
+    /// `files` are the paths relative to the project and the contents. Without history, the symbols are
+    /// linked by their names alone and the commits and issues are empty. The repo options of `conf`,
+    /// like `project_path` and `source`, are ignored.
+    pub fn from_sources(
+        conf: GraphConfig,
+        files: Vec<(String, String)>,
+    ) -> Result<Graph, GossiphsError> {
+        let start_time = Instant::now();
+        let mut build_timings = Vec::new();
+        let mut stage_time = Instant::now();
         let file_len = files.len();
-        let file_contexts =
-            Self::extract_file_contexts(&conf.project_path, files, conf.symbol_limit);
-        info!("symbol extract finished, files: {}", file_contexts.len());
+        let extract = || -> Vec<FileContext> {
+            files
+                .par_iter()
+                .filter_map(|(file_path, file_content)| {
+                    Graph::extract_file_context(
+                        file_path,
+                        file_content,
+                        conf.symbol_limit,
+                        conf.capture_signatures,
+                    )
+                })
+                .collect()
+        };
+        let (file_contexts, truncated_files) =
+            Self::limit_file_contexts(&conf, with_threads(conf.threads, extract));
+        lap(&mut build_timings, &mut stage_time, "extraction");
+
+        let sources = Sources {
+            relation_graph: CupidoRelationGraph::new(),
+            file_contexts,
+            truncated_files,
+            commit_id: None,
+            packages: Vec::new(),
+            file_len,
+            history: false,
+        };
+        Self::link(&conf, sources, build_timings, start_time)
+    }
+
+    fn link(
+        conf: &GraphConfig,
+        sources: Sources,
+        mut build_timings: Vec<(String, f64)>,
+        start_time: Instant,
+    ) -> Result<Graph, GossiphsError> {
+        let mut stage_time = Instant::now();
+        let Sources {
+            relation_graph,
+            file_contexts,
+            truncated_files,
+            commit_id,
+            packages,
+            file_len,
+            history,
+        } = sources;
+        // reduce the impact of large commits
+        let commit_size_limit = ((file_len as f32) * conf.commit_size_limit_ratio) as usize;
 
         // filter pointless REF
+        let include_symbol_regex = conf
+            .include_symbol_regex
+            .as_ref()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(GossiphsError::regex("include_symbol_regex"))
+            })
+            .transpose()?;
+        let exclude_symbol_regex = conf
+            .exclude_symbol_regex
+            .as_ref()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(GossiphsError::regex("exclude_symbol_regex"))
+            })
+            .transpose()?;
         let (global_def_symbol_table, global_ref_symbol_table, global_unique_def_symbol_table) =
             Self::build_global_symbol_table(&file_contexts);
         let final_file_contexts = Self::filter_pointless_symbols(
@@ -330,16 +768,28 @@ impl Graph {
             &global_def_symbol_table,
             &global_ref_symbol_table,
             conf.symbol_len_limit,
+            &conf.symbol_stopwords,
+            include_symbol_regex.as_ref(),
+            exclude_symbol_regex.as_ref(),
         );
+        let symbol_idf = if conf.scoring.idf_damping {
+            symbol_idf(&final_file_contexts)
+        } else {
+            HashMap::new()
+        };
 
         // building graph
         // 1. file - symbols
         // 2. symbols - symbols
         info!("start building symbol graph ...");
-        let pb = ProgressBar::new(final_file_contexts.len() as u64);
+        let pb = progress_bar(conf.show_progress, final_file_contexts.len());
+        // files are walked twice, adding the symbols then linking them
+        conf.progress
+            .start("linking", final_file_contexts.len() * 2);
         let mut symbol_graph = SymbolGraph::new();
         for file_context in &final_file_contexts {
             pb.inc(1);
+            conf.progress.inc();
             symbol_graph.add_file(&file_context.path);
             for symbol in &file_context.symbols {
                 symbol_graph.add_symbol(symbol.clone());
@@ -354,18 +804,17 @@ impl Graph {
         let mut file_commit_cache: HashMap<String, HashSet<String>> = HashMap::new();
         let mut commit_file_cache: HashMap<String, HashSet<String>> = HashMap::new();
         let mut related_commits = |f: String| -> HashSet<String> {
-            return if let Some(ref_commits) = file_commit_cache.get(&f) {
+            if let Some(ref_commits) = file_commit_cache.get(&f) {
                 ref_commits.clone()
             } else {
+                // files which are not committed yet have no history
                 let file_commits: HashSet<String> = relation_graph
                     .file_related_commits(&f)
-                    .unwrap()
+                    .unwrap_or_default()
                     .into_iter()
                     .filter(|each| {
-                        // reduce the impact of large commits
-                        return if let Some(ref_files) = commit_file_cache.get(each) {
-                            ref_files.len()
-                                < ((file_len as f32) * conf.commit_size_limit_ratio) as usize
+                        if let Some(ref_files) = commit_file_cache.get(each) {
+                            ref_files.len() < commit_size_limit
                         } else {
                             let ref_files: HashSet<String> = relation_graph
                                 .commit_related_files(each)
@@ -374,32 +823,39 @@ impl Graph {
                                 .collect();
 
                             commit_file_cache.insert(each.clone(), ref_files.clone());
-                            ref_files.len()
-                                < ((file_len as f32) * conf.commit_size_limit_ratio) as usize
-                        };
+                            ref_files.len() < commit_size_limit
+                        }
                     })
-                    .into_iter()
                     .collect();
 
                 file_commit_cache.insert(f.clone(), file_commits.clone());
                 file_commits
-            };
+            }
         };
 
         let mut symbol_mapping: HashMap<String, usize> = HashMap::new();
-        let mut symbol_count = |f: &String, g: &SymbolGraph| -> usize {
-            return if let Some(count) = symbol_mapping.get(f) {
+        let mut symbol_count = |f: &str, g: &SymbolGraph| -> usize {
+            if let Some(count) = symbol_mapping.get(f) {
                 *count
             } else {
-                let count = g.list_references(&f).len();
-                symbol_mapping.insert(f.clone(), count);
+                let count = g.list_references(f).len();
+                symbol_mapping.insert(f.to_string(), count);
                 count
-            };
+            }
+        };
+
+        let commit_decays = match conf.scoring.half_life_days {
+            days if days > 0.0 && history => {
+                commit_decays(&conf.project_path, &relation_graph, days)?
+            }
+            _ => HashMap::new(),
         };
+        let decay = |commit: &str| commit_decays.get(commit).copied().unwrap_or(1.0);
 
         let mut commit_file_cache2: HashMap<String, HashSet<String>> = HashMap::new();
         for file_context in &final_file_contexts {
             pb.inc(1);
+            conf.progress.inc();
             let def_related_commits = related_commits(file_context.path.clone());
             for symbol in &file_context.symbols {
                 if symbol.kind != SymbolKind::REF {
@@ -407,26 +863,39 @@ impl Graph {
                 }
 
                 // all the possible definitions of this reference
-                let defs = global_def_symbol_table.get(&symbol.name).unwrap();
+                let defs = match global_def_symbol_table.get(&symbol.name) {
+                    Some(defs) => resolve_definitions(symbol, defs),
+                    // file link, handled below
+                    None => continue,
+                };
 
                 let mut ratio_map: BTreeMap<usize, Vec<&Symbol>> = BTreeMap::new();
                 for def in defs {
-                    let f = def.file.clone();
-                    let ref_related_commits = related_commits(f);
+                    let cross_language = is_cross_language(&symbol.file, &def.file);
+                    if cross_language && !conf.cross_language {
+                        continue;
+                    }
+
+                    let ref_related_commits = related_commits(def.file.to_string());
                     // calc the diff of two set
                     let commit_intersection: HashSet<String> = ref_related_commits
                         .intersection(&def_related_commits)
                         .cloned()
                         .collect();
 
-                    let mut ratio = 0.0;
+                    // without history, any def of the name is a match
+                    let mut ratio = match history {
+                        true => 0.0,
+                        false => 1.0,
+                    };
                     commit_intersection.iter().for_each(|each_commit| {
                         // different range commits should have different scores
                         // large commit has less score
 
                         // how many files has been referenced
                         if let Some(commit_ref_files) = commit_file_cache2.get(each_commit) {
-                            ratio += (file_len - commit_ref_files.len()) as f64 / (file_len as f64);
+                            ratio += conf.scoring.commit_ratio(commit_ref_files.len(), file_len)
+                                * decay(each_commit);
                         } else {
                             let commit_ref_files: HashSet<String> = relation_graph
                                 .commit_related_files(each_commit)
@@ -435,31 +904,33 @@ impl Graph {
                                 .collect();
                             commit_file_cache2
                                 .insert(each_commit.clone(), commit_ref_files.clone());
-                            ratio += (file_len - commit_ref_files.len()) as f64 / (file_len as f64);
+                            ratio += conf.scoring.commit_ratio(commit_ref_files.len(), file_len)
+                                * decay(each_commit);
                         };
                     });
 
                     if ratio > 0.0 {
+                        ratio *= conf.scoring.commit_weight;
                         // complex file has lower ratio
-                        let ref_count_in_file = symbol_count(&def.file.clone(), &symbol_graph);
+                        let ref_count_in_file = symbol_count(&def.file, &symbol_graph);
                         if ref_count_in_file > 0 {
-                            ratio = ratio / ref_count_in_file as f64;
+                            ratio /= ref_count_in_file as f64;
+                        }
+                        if cross_language {
+                            ratio *= conf.cross_language_ratio;
                         }
                         if ratio < 1.0 {
                             ratio = 1.0;
                         }
 
-                        ratio_map
-                            .entry(ratio as usize)
-                            .or_insert(Vec::new())
-                            .push(def);
+                        ratio_map.entry(ratio as usize).or_default().push(def);
                     }
                 }
 
                 let mut def_count = 0;
                 for (&ratio, defs) in ratio_map.iter().rev() {
                     for def in defs {
-                        symbol_graph.link_symbol_to_symbol(&symbol, &def);
+                        symbol_graph.link_symbol_to_symbol(symbol, def);
                         symbol_graph.enhance_symbol_to_symbol(&symbol.id(), &def.id(), ratio);
 
                         def_count += 1;
@@ -475,6 +946,27 @@ impl Graph {
         }
         pb.finish_and_clear();
 
+        // file links: a ref named by a file path points to all the defs of that file
+        for file_context in &final_file_contexts {
+            for symbol in &file_context.symbols {
+                if symbol.kind != SymbolKind::REF
+                    || global_def_symbol_table.contains_key(&symbol.name)
+                {
+                    continue;
+                }
+                let target = match resolve_file_link(&symbol.name, |candidate| {
+                    symbol_graph.file_mapping.contains_key(candidate)
+                }) {
+                    Some(target) => target,
+                    None => continue,
+                };
+                for def in symbol_graph.list_definitions(&target) {
+                    symbol_graph.link_symbol_to_symbol(symbol, &def);
+                    symbol_graph.enhance_symbol_to_symbol(&symbol.id(), &def.id(), 1);
+                }
+            }
+        }
+
         // check the graph and do some fallbacks
         for file_context in &final_file_contexts {
             let def_symbols: Vec<&Symbol> = file_context
@@ -499,6 +991,10 @@ impl Graph {
                             .get(&each_def.name)
                             .unwrap_or(&Vec::new())
                             .iter()
+                            .filter(|r| {
+                                conf.cross_language
+                                    || !is_cross_language(&r.file, &fallback_def.file)
+                            })
                             .for_each(|r| {
                                 symbol_graph.link_symbol_to_symbol(&fallback_def, r);
                             })
@@ -507,19 +1003,250 @@ impl Graph {
             }
         }
 
+        let co_changes = match conf.co_change_weight {
+            0 => HashMap::new(),
+            weight => co_changes(&relation_graph, &symbol_graph, commit_size_limit, weight),
+        };
+
         info!(
             "symbol graph ready, nodes: {}, edges: {}",
             symbol_graph.symbol_mapping.len(),
             symbol_graph.g.edge_count(),
         );
         info!("total time cost: {:?}", start_time.elapsed());
+        lap(&mut build_timings, &mut stage_time, "linking");
+        build_timings.push((String::from("total"), start_time.elapsed().as_secs_f64()));
 
-        Graph {
+        Ok(Graph {
+            project_path: conf.project_path.clone(),
+            project_roots: project_roots(&conf.project_path),
+            commit_id,
             file_contexts,
             _relation_graph: relation_graph,
             symbol_graph,
+            co_changes,
+            related_index: None,
+            file_len,
+            commit_size_limit,
+            cross_language_ratio: conf.cross_language_ratio,
+            def_limit: conf.def_limit,
+            scoring: conf.scoring.clone(),
+            commit_decays,
+            symbol_idf,
+            packages,
+            truncated_files,
+            build_timings,
+            show_progress: conf.show_progress,
+            threads: conf.threads,
+        })
+    }
+}
+
+// what `Graph::link` needs from the repo
+pub(crate) struct Sources {
+    relation_graph: CupidoRelationGraph,
+    file_contexts: Vec<FileContext>,
+    truncated_files: HashSet<String>,
+    commit_id: Option<String>,
+    packages: Vec<String>,
+    file_len: usize,
+    // false for `Graph::from_sources`
+    history: bool,
+}
+
+/// Commits changing both files of each pair, multiplied by `weight`.
+/// Like the symbol links, the commits touching `commit_size_limit` files or more are skipped.
+fn co_changes(
+    relation_graph: &CupidoRelationGraph,
+    symbol_graph: &SymbolGraph,
+    commit_size_limit: usize,
+    weight: usize,
+) -> HashMap<String, HashMap<String, usize>> {
+    let mut ret: HashMap<String, HashMap<String, usize>> = HashMap::new();
+    for commit in relation_graph.commits() {
+        let commit_files = relation_graph
+            .commit_related_files(&commit)
+            .unwrap_or_default();
+        if commit_files.len() >= commit_size_limit {
+            continue;
+        }
+        let files: Vec<&String> = commit_files
+            .iter()
+            .filter(|file| symbol_graph.file_mapping.contains_key(file.as_str()))
+            .collect();
+        for (i, a) in files.iter().enumerate() {
+            for b in &files[i + 1..] {
+                *ret.entry(a.to_string())
+                    .or_default()
+                    .entry(b.to_string())
+                    .or_default() += weight;
+                *ret.entry(b.to_string())
+                    .or_default()
+                    .entry(a.to_string())
+                    .or_default() += weight;
+            }
+        }
+    }
+    ret
+}
+
+// manifests marking the root of a package in a monorepo
+const PACKAGE_MANIFESTS: [&str; 4] = ["Cargo.toml", "package.json", "go.mod", "pom.xml"];
+
+fn manifests(dir: &str) -> Vec<String> {
+    PACKAGE_MANIFESTS
+        .iter()
+        .map(|manifest| match dir {
+            "" => manifest.to_string(),
+            _ => format!("{}/{}", dir, manifest),
+        })
+        .collect()
+}
+
+// the time since the last stage, as the stage `name`
+fn lap(timings: &mut Vec<(String, f64)>, since: &mut Instant, name: &str) {
+    timings.push((name.to_string(), since.elapsed().as_secs_f64()));
+    *since = Instant::now();
+}
+
+// invalid bytes (latin-1 or gbk comments ...) are replaced with U+FFFD if `lossy_decode`,
+// otherwise the file is skipped
+fn decode(file_path: &str, content: &[u8], lossy_decode: bool) -> Option<String> {
+    match std::str::from_utf8(content) {
+        Ok(content) => Some(content.to_string()),
+        Err(_) if lossy_decode => {
+            debug!(
+                "Invalid UTF-8 content in file {:?}, decoded lossily",
+                file_path
+            );
+            Some(String::from_utf8_lossy(content).into_owned())
+        }
+        Err(err) => {
+            warn!("Invalid UTF-8 content in file {:?}: {:?}", file_path, err);
+            None
+        }
+    }
+}
+
+// the first defs of a file, then its other symbols until `limit`, in their original order
+fn truncate_symbols(symbols: Vec<Symbol>, limit: usize) -> Vec<Symbol> {
+    let defs = symbols
+        .iter()
+        .filter(|symbol| symbol.kind == SymbolKind::DEF)
+        .count()
+        .min(limit);
+    let mut others = limit - defs;
+    let mut kept_defs = 0;
+    symbols
+        .into_iter()
+        .filter(|symbol| {
+            if symbol.kind == SymbolKind::DEF {
+                kept_defs += 1;
+                kept_defs <= defs
+            } else if others > 0 {
+                others -= 1;
+                true
+            } else {
+                false
+            }
+        })
+        .collect()
+}
+
+/// Directories of the files holding a package manifest, sorted, `.` for the root.
+/// Workspaces and their members both have one, a file belongs to the deepest of them.
+fn detect_packages(conf: &GraphConfig, files: &[String]) -> Result<Vec<String>, GossiphsError> {
+    let mut dirs: HashSet<&str> = HashSet::from([""]);
+    for file in files {
+        let mut dir = file.as_str();
+        while let Some((parent, _)) = dir.rsplit_once('/') {
+            // the parents of it are collected already
+            if !dirs.insert(parent) {
+                break;
+            }
+            dir = parent;
+        }
+    }
+
+    let repo =
+        Repository::open(&conf.project_path).map_err(GossiphsError::open(&conf.project_path))?;
+    let mut packages: Vec<String> = match conf.source {
+        FileSource::Head | FileSource::Rev(_) => {
+            let tree = history::resolve_commit(&repo, conf.source.rev())?.tree()?;
+            dirs.into_iter()
+                .filter(|dir| {
+                    manifests(dir)
+                        .iter()
+                        .any(|path| tree.get_path(Path::new(path)).is_ok())
+                })
+                .collect::<Vec<&str>>()
+        }
+        FileSource::WorkingTree => {
+            let workdir = repo
+                .workdir()
+                .ok_or_else(|| GossiphsError::BareRepository(conf.project_path.clone()))?;
+            dirs.into_iter()
+                .filter(|dir| {
+                    manifests(dir)
+                        .iter()
+                        .any(|path| workdir.join(path).is_file())
+                })
+                .collect::<Vec<&str>>()
+        }
+    }
+    .into_iter()
+    .map(|dir| match dir {
+        "" => String::from("."),
+        _ => dir.to_string(),
+    })
+    .collect();
+    packages.sort();
+    Ok(packages)
+}
+
+/// Multiplier of each commit by its age, halved every `half_life_days` before the latest commit.
+fn commit_decays(
+    project_path: &str,
+    relation_graph: &CupidoRelationGraph,
+    half_life_days: f64,
+) -> Result<HashMap<String, f64>, GossiphsError> {
+    let commits = history::commit_details(project_path, &relation_graph.commits())?;
+    let latest = commits
+        .iter()
+        .map(|each| each.timestamp)
+        .max()
+        .unwrap_or_default();
+    Ok(commits
+        .into_iter()
+        .map(|each| {
+            let days = (latest - each.timestamp) as f64 / 86400.0;
+            (each.sha, 0.5_f64.powf(days / half_life_days))
+        })
+        .collect())
+}
+
+/// Inverse document frequency of each symbol name: 1 for the names in a single file,
+/// down to 1 / (1 + ln(files)) for the names in every file, like `new` or `get`.
+fn symbol_idf(file_contexts: &[FileContext]) -> HashMap<Arc<str>, f64> {
+    let mut counter: HashMap<Arc<str>, usize> = HashMap::new();
+    for file_context in file_contexts {
+        let names: HashSet<&Arc<str>> = file_context
+            .symbols
+            .iter()
+            .map(|symbol| &symbol.name)
+            .collect();
+        for name in names {
+            *counter.entry(name.clone()).or_default() += 1;
         }
     }
+    let files = file_contexts.len() as f64;
+    counter
+        .into_iter()
+        .map(|(name, count)| {
+            let idf = (1.0 + (files / count as f64).ln()) / (1.0 + files.ln());
+            (name, idf)
+        })
+        .collect()
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -532,92 +1259,562 @@ pub struct RelatedSymbol {
     pub weight: usize,
 }
 
-fn create_cupido_graph(
-    project_path: &String,
-    depth: u32,
-    exclude_author_regex: Option<String>,
-    exclude_commit_regex: Option<String>,
-    issue_regex: Option<String>,
-) -> CupidoRelationGraph {
-    let mut conf = Config::default();
-    conf.repo_path = project_path.parse().unwrap();
-    conf.depth = depth;
-    conf.author_exclude_regex = exclude_author_regex;
-    conf.commit_exclude_regex = exclude_commit_regex;
-    if issue_regex.is_some() {
-        conf.issue_regex = issue_regex.unwrap();
+pub(crate) fn extractor_of_extension(file_extension: &str) -> Option<&'static Extractor> {
+    match file_extension {
+        "rs" => Some(&Extractor::Rust),
+        "ts" | "tsx" => Some(&Extractor::TypeScript),
+        "go" => Some(&Extractor::Go),
+        "py" => Some(&Extractor::Python),
+        "js" | "jsx" => Some(&Extractor::JavaScript),
+        "java" => Some(&Extractor::Java),
+        "kt" => Some(&Extractor::Kotlin),
+        "swift" => Some(&Extractor::Swift),
+        "vue" => Some(&Extractor::Vue),
+        "svelte" => Some(&Extractor::Svelte),
+        "proto" => Some(&Extractor::Proto),
+        "thrift" => Some(&Extractor::Thrift),
+        "md" => Some(&Extractor::Markdown),
+        _ => None,
     }
+}
 
-    let collector = get_collector();
-    let graph = collector.walk(conf);
-    graph
+fn language_of(file_name: &str) -> Option<&'static str> {
+    let file_extension = file_name.split('.').next_back()?.to_lowercase();
+    extractor_of_extension(file_extension.as_str()).and_then(|extractor| extractor.language())
 }
 
-#[pyclass]
-#[derive(Clone)]
-pub struct GraphConfig {
-    #[pyo3(get, set)]
-    pub project_path: String,
+// directories holding tests, `src/test/java` of maven included
+const TEST_DIRS: [&str; 5] = ["test", "tests", "__tests__", "spec", "testing"];
 
-    // if a def has been referenced over `def_limit` times, it will be ignored.
-    #[pyo3(get, set)]
-    pub def_limit: usize,
+/// Whether the file is a test by the conventions of its language,
+/// e.g. `*_test.go`, `test_*.py`, `*.spec.ts`, `*Test.java` or anything under `tests/`.
+pub fn is_test_file(file_name: &str) -> bool {
+    let file_name = file_name.replace('\\', "/");
+    let (dirs, base) = file_name.rsplit_once('/').unwrap_or(("", &file_name));
+    if dirs.split('/').any(|dir| TEST_DIRS.contains(&dir)) {
+        return true;
+    }
+    let (stem, extension) = match base.rsplit_once('.') {
+        Some(parts) => parts,
+        None => return false,
+    };
+    match extension {
+        "go" => stem.ends_with("_test"),
+        "py" => stem.starts_with("test_") || stem.ends_with("_test") || stem == "conftest",
+        "ts" | "tsx" | "js" | "jsx" | "vue" | "svelte" => {
+            stem.ends_with(".test") || stem.ends_with(".spec")
+        }
+        "java" | "kt" | "swift" => stem.ends_with("Test") || stem.ends_with("Tests"),
+        _ => false,
+    }
+}
 
-    // commit size limit
-    // reduce the impact of large commits
-    // large commit: edit more than xx% files once
-    // default to 1.0, do nothing
-    // set to 0.3, means 30%
-    #[pyo3(get, set)]
-    pub commit_size_limit_ratio: f32,
+/// Whether a ref in `ref_file` and a def in `def_file` live in different languages.
+pub(crate) fn is_cross_language(ref_file: &str, def_file: &str) -> bool {
+    match (language_of(ref_file), language_of(def_file)) {
+        (Some(ref_language), Some(def_language)) => ref_language != def_language,
+        _ => false,
+    }
+}
 
-    // commit history search depth
-    #[pyo3(get, set)]
-    pub depth: u32,
+// forward slashes, with `.` and `..` resolved by the components rather than the filesystem
+fn clean_path(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." if parts.last().is_some_and(|last| *last != "..") => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+    match path.starts_with('/') {
+        true => format!("/{}", parts.join("/")),
+        false => parts.join("/"),
+    }
+}
 
-    // symbol limit of each file, for ignoring large files
-    #[pyo3(get, set)]
-    pub symbol_limit: usize,
+/// The prefixes `normalize_path` strips: the project path as given and its canonical form.
+/// This is the only part touching the filesystem, so a graph computes it once.
+pub fn project_roots(project_path: &str) -> Vec<String> {
+    let mut roots = vec![clean_path(project_path)];
+    if let Ok(root) = Path::new(project_path).canonicalize() {
+        // windows canonical paths start with `\\?\`
+        let root = root.to_string_lossy().replacen("\\\\?\\", "", 1);
+        roots.push(clean_path(&root));
+    }
+    roots.retain(|root| !root.is_empty() && root != "/");
+    roots.dedup();
+    roots
+}
 
-    // if a symbol len <= `symbol_len_limit`, it will be ignored.
-    #[pyo3(get, set)]
-    pub symbol_len_limit: usize,
+/// Repo relative path with forward slashes, which is how files are keyed in the graph.
+/// `src\main.rs`, `./src/main.rs` and `/path/to/repo/src/main.rs` all become `src/main.rs`.
+pub fn normalize_path(project_roots: &[String], file_name: &str) -> String {
+    let file_name = clean_path(file_name);
+    for root in project_roots {
+        if let Some(rest) = file_name
+            .strip_prefix(root.as_str())
+            .and_then(|rest| rest.strip_prefix('/'))
+        {
+            return rest.to_string();
+        }
+    }
+    file_name
+}
 
-    #[pyo3(get, set)]
-    pub exclude_file_regex: String,
-    #[pyo3(get, set)]
-    pub exclude_author_regex: Option<String>,
-    #[pyo3(get, set)]
-    pub exclude_commit_regex: Option<String>,
+// module paths can omit the extension, like `export * from './types'`
+const MODULE_SUFFIXES: [&str; 7] = [
+    ".ts",
+    ".tsx",
+    ".js",
+    ".jsx",
+    "/index.ts",
+    "/index.tsx",
+    "/index.js",
+];
+
+/// The real file a file link (markdown link, module path ...) points to.
+fn resolve_file_link(name: &str, exists: impl Fn(&str) -> bool) -> Option<String> {
+    if exists(name) {
+        return Some(name.to_string());
+    }
+    // plain identifiers never point to files
+    if !name.contains('/') {
+        return None;
+    }
+    MODULE_SUFFIXES
+        .iter()
+        .map(|suffix| format!("{}{}", name, suffix))
+        .find(|candidate| exists(candidate))
+}
 
-    #[pyo3(get, set)]
-    pub issue_regex: Option<String>,
+/// A qualified reference (e.g. `Graph::from`) only matches the defs whose scope ends with
+/// its qualifier. Falls back to all the candidates if none of them matches.
+pub(crate) fn resolve_definitions<'a>(reference: &Symbol, defs: &'a [Symbol]) -> Vec<&'a Symbol> {
+    if reference.scope.is_empty() {
+        return defs.iter().collect();
+    }
+    let suffix = format!("::{}", reference.scope);
+    let matched: Vec<&Symbol> = defs
+        .iter()
+        .filter(|def| def.scope == reference.scope || def.scope.ends_with(&suffix))
+        .collect();
+    if matched.is_empty() {
+        return defs.iter().collect();
+    }
+    matched
 }
 
-#[pymethods]
-impl GraphConfig {
-    #[new]
-    pub fn default() -> GraphConfig {
-        GraphConfig {
+/// A progress bar on stderr, or a hidden one if `show_progress` is off.
+pub(crate) fn progress_bar(show_progress: bool, len: usize) -> ProgressBar {
+    if show_progress {
+        ProgressBar::new(len as u64)
+    } else {
+        ProgressBar::hidden()
+    }
+}
+
+/// Run `op` in a pool of `threads` threads, 0 means the global pool of rayon.
+pub(crate) fn with_threads<R, OP>(threads: usize, op: OP) -> R
+where
+    R: Send,
+    OP: FnOnce() -> R + Send,
+{
+    if threads == 0 {
+        return op();
+    }
+    match rayon::ThreadPoolBuilder::new().num_threads(threads).build() {
+        Ok(pool) => pool.install(op),
+        Err(err) => {
+            warn!("Failed to build thread pool: {:?}", err);
+            op()
+        }
+    }
+}
+
+/// Fetch the full history of a shallow clone (CI usually clones with `--depth 1`).
+fn unshallow(project_path: &str) -> bool {
+    info!("fetching the full history of {} ...", project_path);
+    match Command::new("git")
+        .args(["fetch", "--unshallow"])
+        .current_dir(project_path)
+        .status()
+    {
+        Ok(status) if status.success() => true,
+        Ok(status) => {
+            warn!("git fetch --unshallow exited with {}", status);
+            false
+        }
+        Err(err) => {
+            warn!("failed to run git fetch --unshallow: {:?}", err);
+            false
+        }
+    }
+}
+
+pub(crate) fn create_cupido_graph(
+    conf: &GraphConfig,
+) -> Result<CupidoRelationGraph, GossiphsError> {
+    let project_path = conf.project_path.as_str();
+    let shallow = Repository::open(project_path)
+        .map(|repo| repo.is_shallow())
+        .unwrap_or(false);
+    if shallow && !(conf.auto_unshallow && unshallow(project_path)) {
+        // the cli prints it even if logging is off, see `build_graph` of main.rs
+        warn!(
+            "{} is a shallow clone, the commit history is incomplete and most scores will be 0",
+            project_path
+        );
+    }
+
+    history::walk(conf)
+}
+
+/// Weights of the scoring heuristics, the defaults keep the original ranking.
+#[pyclass]
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ScoringConfig {
+    // multiplier of the weight a symbol link gets from the commits changing both files
+    #[pyo3(get, set)]
+    pub commit_weight: f64,
+
+    // the least score of a symbol link, raise it to favor the count of shared symbols over the history
+    #[pyo3(get, set)]
+    pub symbol_weight: f64,
+
+    // each shared commit counts (files - commit files) / files to the power of it,
+    // raise it to punish large commits harder, 0 counts all the commits as 1
+    #[pyo3(get, set)]
+    pub large_commit_penalty: f64,
+
+    // divide the weights of the links by the count of defs in the file,
+    // so files defining a lot do not relate to everything
+    #[pyo3(get, set)]
+    pub divide_by_definitions: bool,
+
+    // multiplier of the scores of the files in the same directory
+    #[pyo3(get, set)]
+    pub same_directory_boost: f64,
+
+    // a commit counts half as much every `half_life_days` before the latest commit,
+    // default to 0, all the commits count the same
+    #[pyo3(get, set)]
+    pub half_life_days: f64,
+
+    // symbols named in many files (`new`, `get` ...) count less, see `symbol_idf`
+    #[pyo3(get, set)]
+    pub idf_damping: bool,
+}
+
+#[pymethods]
+impl ScoringConfig {
+    #[new]
+    #[allow(clippy::should_implement_trait)]
+    pub fn default() -> ScoringConfig {
+        ScoringConfig {
+            commit_weight: 1.0,
+            symbol_weight: 1.0,
+            large_commit_penalty: 1.0,
+            divide_by_definitions: true,
+            same_directory_boost: 1.0,
+            half_life_days: 0.0,
+            idf_damping: false,
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        crate::pyapi::repr("ScoringConfig", self)
+    }
+
+    fn __eq__(&self, other: &Self) -> bool {
+        crate::pyapi::same(self, other)
+    }
+
+    fn to_dict(&self, py: Python<'_>) -> PyResult<PyObject> {
+        crate::pyapi::to_dict(py, self)
+    }
+}
+
+impl ScoringConfig {
+    /// How much a commit changing `commit_files` of the `file_len` files counts.
+    pub(crate) fn commit_ratio(&self, commit_files: usize, file_len: usize) -> f64 {
+        let ratio = file_len.saturating_sub(commit_files) as f64 / file_len as f64;
+        ratio.powf(self.large_commit_penalty)
+    }
+
+    /// Score of a symbol link of `weight` from a file with `definitions` defs,
+    /// `idf` is the multiplier of the symbol name, see `symbol_idf`.
+    pub(crate) fn link_score(&self, weight: usize, definitions: usize, idf: f64) -> usize {
+        let mut score = weight as f64;
+        if self.divide_by_definitions && definitions > 0 {
+            score /= definitions as f64;
+        }
+        // the least score is kept for the common names as well
+        (score * idf).max(self.symbol_weight) as usize
+    }
+}
+
+#[pyclass]
+#[derive(Clone)]
+pub struct GraphConfig {
+    // a local directory, or the url of a remote repo cloned into a cache directory
+    #[pyo3(get, set)]
+    pub project_path: String,
+
+    // commits fetched when cloning a remote repo, 0 for the full history
+    #[pyo3(get, set)]
+    pub clone_depth: u32,
+
+    // if a def has been referenced over `def_limit` times, it will be ignored.
+    // see `Graph::ambiguous_symbols` for the names defined more than `def_limit` times
+    #[pyo3(get, set)]
+    pub def_limit: usize,
+
+    // commit size limit
+    // reduce the impact of large commits
+    // large commit: edit more than xx% files once
+    // default to 1.0, do nothing
+    // set to 0.3, means 30%
+    #[pyo3(get, set)]
+    pub commit_size_limit_ratio: f32,
+
+    // commit history search depth
+    #[pyo3(get, set)]
+    pub depth: u32,
+
+    // symbol limit of each file, for ignoring large files
+    #[pyo3(get, set)]
+    pub symbol_limit: usize,
+
+    // replace the invalid UTF-8 bytes instead of skipping the file, e.g. a latin-1 comment
+    #[pyo3(get, set)]
+    pub lossy_decode: bool,
+
+    // keep the files over `symbol_limit` with their first defs instead of ignoring them,
+    // see `FileMetadata::truncated`
+    #[pyo3(get, set)]
+    pub truncate_large_files: bool,
+
+    // if a symbol len <= `symbol_len_limit`, it will be ignored.
+    #[pyo3(get, set)]
+    pub symbol_len_limit: usize,
+
+    // symbols with these names are ignored, e.g. `new`, `init`, `run`
+    #[pyo3(get, set)]
+    pub symbol_stopwords: Vec<String>,
+
+    // only the symbols whose names match it are kept, then the ones matching the exclude one are dropped,
+    // e.g. `^Test` or `^(get|set|has|clear)_` of protobuf accessors
+    #[pyo3(get, set)]
+    pub include_symbol_regex: Option<String>,
+    #[pyo3(get, set)]
+    pub exclude_symbol_regex: Option<String>,
+
+    // only the files matching it are analyzed, e.g. `^services/payments/`, empty for all
+    #[pyo3(get, set)]
+    pub include_file_regex: String,
+    #[pyo3(get, set)]
+    pub exclude_file_regex: String,
+    // leave out vendored dependencies, lockfiles, minified and generated code, see `generated.rs`
+    #[pyo3(get, set)]
+    pub detect_generated: bool,
+    #[pyo3(get, set)]
+    pub exclude_author_regex: Option<String>,
+    #[pyo3(get, set)]
+    pub exclude_commit_regex: Option<String>,
+
+    #[pyo3(get, set)]
+    pub issue_regex: Option<String>,
+
+    // capture signatures and doc comments of defs, costs more memory
+    #[pyo3(get, set)]
+    pub capture_signatures: bool,
+
+    // allow a ref to resolve to a def written in another language,
+    // e.g. python calling a rust function exported by pyo3
+    #[pyo3(get, set)]
+    pub cross_language: bool,
+
+    // score multiplier of cross language links, lower it to prefer same language defs
+    #[pyo3(get, set)]
+    pub cross_language_ratio: f64,
+
+    // score of each commit changing two files together, added on top of the symbol links.
+    // files without an extractor (yaml, sql ...) join the graph with these scores only.
+    // default to 0, do nothing
+    #[pyo3(get, set)]
+    pub co_change_weight: usize,
+
+    #[pyo3(get, set)]
+    pub scoring: ScoringConfig,
+
+    // HEAD by default, the history is walked from the rev or HEAD in any case
+    pub source: FileSource,
+
+    // only the commits in this time window (unix timestamps) are collected
+    #[pyo3(get, set)]
+    pub since: Option<i64>,
+    #[pyo3(get, set)]
+    pub until: Option<i64>,
+
+    // merge commits repeat the changes of the merged branch as one large commit
+    #[pyo3(get, set)]
+    pub exclude_merge_commits: bool,
+
+    // skip the commits of dependency bots and CI, on top of `exclude_author_regex`
+    #[pyo3(get, set)]
+    pub exclude_bot_commits: bool,
+
+    // keep the history of renamed files under their current path
+    #[pyo3(get, set)]
+    pub follow_renames: bool,
+
+    // fetch the full history before analysis if the repo is a shallow clone
+    #[pyo3(get, set)]
+    pub auto_unshallow: bool,
+
+    // reuse the extraction results of unchanged files, see `CacheManager`
+    #[pyo3(get, set)]
+    pub enable_cache: bool,
+
+    // progress bars on stderr, turn it off when embedded in other programs
+    #[pyo3(get, set)]
+    pub show_progress: bool,
+
+    // threads for extraction and the queries over all the files, 0 means all the cores
+    #[pyo3(get, set)]
+    pub threads: usize,
+
+    // the stage and the files done of the build, for watching it from another thread
+    pub progress: BuildProgress,
+}
+
+impl GraphConfig {
+    #[allow(clippy::should_implement_trait)]
+    pub fn default() -> GraphConfig {
+        GraphConfig {
             project_path: String::from("."),
+            clone_depth: 0,
             def_limit: 16,
             commit_size_limit_ratio: 1.0,
             depth: 10240,
             symbol_limit: 4096,
+            lossy_decode: false,
+            truncate_large_files: false,
             symbol_len_limit: 0,
+            symbol_stopwords: Vec::new(),
+            include_symbol_regex: None,
+            exclude_symbol_regex: None,
+            include_file_regex: String::new(),
             exclude_file_regex: String::new(),
+            detect_generated: true,
             exclude_author_regex: None,
             exclude_commit_regex: None,
             issue_regex: None,
+            capture_signatures: false,
+            cross_language: true,
+            cross_language_ratio: 1.0,
+            co_change_weight: 0,
+            scoring: ScoringConfig::default(),
+            source: FileSource::Head,
+            since: None,
+            until: None,
+            exclude_merge_commits: false,
+            exclude_bot_commits: false,
+            follow_renames: true,
+            auto_unshallow: false,
+            enable_cache: false,
+            show_progress: true,
+            threads: 0,
+            progress: BuildProgress::default(),
         }
     }
 }
 
+#[pymethods]
+impl GraphConfig {
+    /// `GraphConfig(project_path=".", depth=500, strict=True)` in python, the fields not given keep their defaults
+    #[new]
+    #[pyo3(signature = (**kwargs))]
+    fn py_new(py: Python<'_>, kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<GraphConfig> {
+        let config = Bound::new(py, GraphConfig::default())?;
+        if let Some(kwargs) = kwargs {
+            // `strict` first, then a `def_limit` given with it wins, like in the cli
+            if let Some(strict) = kwargs.get_item("strict")? {
+                config.setattr("strict", strict)?;
+            }
+            for (key, value) in kwargs.iter() {
+                let key: String = key.extract()?;
+                if key != "strict" {
+                    config.setattr(key.as_str(), value)?;
+                }
+            }
+        }
+        let config = config.borrow().clone();
+        Ok(config)
+    }
+
+    /// precise-first analysis, the same as `--strict` of the cli: a def referenced more than once is ignored
+    #[getter]
+    fn strict(&self) -> bool {
+        self.def_limit == 1
+    }
+
+    #[setter]
+    fn set_strict(&mut self, strict: bool) {
+        if strict {
+            self.def_limit = 1;
+        } else if self.def_limit == 1 {
+            self.def_limit = GraphConfig::default().def_limit;
+        }
+    }
+
+    /// `source` in python, files on disk instead of the blobs of HEAD
+    #[getter]
+    fn working_tree(&self) -> bool {
+        self.source == FileSource::WorkingTree
+    }
+
+    #[setter]
+    fn set_working_tree(&mut self, working_tree: bool) {
+        if working_tree {
+            self.source = FileSource::WorkingTree;
+        } else if self.source == FileSource::WorkingTree {
+            self.source = FileSource::Head;
+        }
+    }
+
+    /// `source` in python, the commit to analyze (branch, tag, sha ...) instead of HEAD
+    #[getter]
+    fn rev(&self) -> Option<String> {
+        self.source.rev().map(String::from)
+    }
+
+    #[setter]
+    fn set_rev(&mut self, rev: Option<String>) {
+        self.source = match rev {
+            Some(rev) => FileSource::Rev(rev),
+            None => FileSource::Head,
+        };
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::graph::{Graph, GraphConfig};
-    use crate::symbol::DefRefPair;
+    use crate::api::{CycleScope, RelatedFileContext, RelationDirection};
+    use crate::error::GossiphsError;
+    use crate::graph::{
+        decode, is_cross_language, is_test_file, normalize_path, project_roots,
+        resolve_definitions, Graph, GraphConfig,
+    };
+    use crate::symbol::{DefRefPair, Symbol, SymbolKind};
+    use git2::Repository;
     use petgraph::visit::EdgeRef;
+    use std::path::Path;
+    use std::sync::Arc;
     use tracing::{debug, info};
 
     #[test]
@@ -719,9 +1916,962 @@ mod tests {
             );
         });
 
+        g.call_graph().iter().take(10).for_each(|edge| {
+            info!(
+                "{} {} -> {} {}: {}",
+                edge.src.file, edge.src.name, edge.dst.file, edge.dst.name, edge.weight
+            );
+        });
+
         let issues = g.list_file_issues(String::from("src/extractor.rs"));
         let commits = g.list_file_commits(String::from("src/graph.rs"));
-        assert!(issues.len() > 0);
-        assert!(commits.len() > 0);
+        assert!(!issues.is_empty());
+        assert!(!commits.is_empty());
+    }
+
+    #[test]
+    fn single_thread_without_progress() {
+        let mut config = GraphConfig::default();
+        config.show_progress = false;
+        config.threads = 1;
+        let g = Graph::from(config);
+        assert!(!g.show_progress);
+        assert_eq!(g.files(), Graph::from(GraphConfig::default()).files());
+        assert!(!g.list_all_relations().file_nodes.is_empty());
+    }
+
+    #[test]
+    fn related_index() {
+        let mut g = Graph::from(GraphConfig::default());
+        let file = String::from("src/graph.rs");
+        let scores = |g: &Graph| {
+            let mut scores = g
+                .related_files(file.clone())
+                .into_iter()
+                .map(|each| (each.name, each.score))
+                .collect::<Vec<_>>();
+            scores.sort();
+            scores
+        };
+        let expected = scores(&g);
+        g.build_index();
+        assert!(g.related_index.is_some());
+        assert_eq!(scores(&g), expected);
+        assert!(g.related_files(String::from("not/exist.rs")).is_empty());
+    }
+
+    #[test]
+    fn related_files_batch() {
+        let g = Graph::from(GraphConfig::default());
+        let files = vec![String::from("src/graph.rs"), String::from("not/exist.rs")];
+        let batch = g.related_files_batch(files.clone());
+        assert_eq!(batch.len(), 2);
+        for file in files {
+            let expected: Vec<String> = g
+                .related_files(file.clone())
+                .into_iter()
+                .map(|each| each.name)
+                .collect();
+            let names: Vec<String> = batch[&file].iter().map(|each| each.name.clone()).collect();
+            assert_eq!(names, expected);
+        }
+    }
+
+    #[test]
+    fn outgoing_related_files() {
+        let g = Graph::from(GraphConfig::default());
+        let outgoing = g.outgoing_related_files(String::from("src/api.rs"));
+        assert!(outgoing.iter().any(|each| each.name == "src/graph.rs"));
+        assert!(outgoing.iter().all(|each| each.name != "src/api.rs"));
+        assert!(outgoing
+            .windows(2)
+            .all(|pair| pair[0].score >= pair[1].score));
+        // the same relation seen from the other side
+        for each in &outgoing {
+            let incoming = g.related_files(each.name.clone());
+            let api = incoming.iter().find(|other| other.name == "src/api.rs");
+            assert_eq!(api.map(|other| other.score), Some(each.score));
+        }
+
+        let both = g.related_files_towards(String::from("src/api.rs"), RelationDirection::BOTH);
+        let score_of = |files: &[RelatedFileContext], name: &str| {
+            files
+                .iter()
+                .find(|each| each.name == name)
+                .map_or(0, |each| each.score)
+        };
+        let incoming = g.related_files(String::from("src/api.rs"));
+        assert_eq!(
+            score_of(&both, "src/graph.rs"),
+            score_of(&incoming, "src/graph.rs") + score_of(&outgoing, "src/graph.rs")
+        );
+    }
+
+    #[test]
+    fn orphans_and_dead_code() {
+        let g = Graph::from(GraphConfig::default());
+        let orphans = g.orphan_files();
+        assert!(orphans.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(!orphans.contains(&String::from("src/graph.rs")));
+        for orphan in &orphans {
+            assert!(g
+                .related_files(orphan.clone())
+                .iter()
+                .all(|each| each.score == 0));
+        }
+
+        let defs = g.unreferenced_definitions();
+        assert!(!defs.is_empty());
+        for def in &defs {
+            assert_eq!(def.kind, SymbolKind::DEF);
+            assert!(g
+                .symbol_graph
+                .list_references_by_definition(&def.id())
+                .is_empty());
+        }
+    }
+
+    #[test]
+    fn similar_files() {
+        let g = Graph::from(GraphConfig::default());
+        let similar = g.similar_files(String::from("./src/api.rs"), 5);
+        assert!(!similar.is_empty() && similar.len() <= 5);
+        assert!(similar
+            .windows(2)
+            .all(|pair| pair[0].similarity >= pair[1].similarity));
+        for each in &similar {
+            assert_ne!(each.name, "src/api.rs");
+            assert!(each.similarity > 0.0 && each.similarity <= 1.0);
+            assert!(each.shared.windows(2).all(|pair| pair[0] < pair[1]));
+        }
+        assert!(g.similar_files(String::from("not/exist.rs"), 5).is_empty());
+    }
+
+    #[test]
+    fn ambiguous_symbols() {
+        let g = Graph::from(GraphConfig::default());
+        assert!(g
+            .ambiguous_symbols()
+            .iter()
+            .all(|each| each.definitions > 16));
+
+        let mut config = GraphConfig::default();
+        config.def_limit = 1;
+        let g = Graph::from(config);
+        let ambiguous = g.ambiguous_symbols();
+        assert!(!ambiguous.is_empty());
+        assert!(ambiguous
+            .windows(2)
+            .all(|pair| pair[0].definitions >= pair[1].definitions));
+        for each in &ambiguous {
+            assert!(each.definitions > 1);
+            assert!(!each.files.is_empty() && each.files.len() <= each.definitions);
+            assert!(each.files.windows(2).all(|pair| pair[0] < pair[1]));
+        }
+    }
+
+    #[test]
+    fn stats() {
+        let g = Graph::from(GraphConfig::default());
+        let stats = g.stats();
+        assert_eq!(stats.files, g.files().len());
+        assert!(stats.commits > 0);
+        assert!(stats.symbols["DEF"] > 0 && stats.symbols["REF"] > 0);
+        assert!(stats.languages["Rust"] > 0);
+        assert!(stats.symbol_links > 0 && stats.relations > 0);
+        assert!(stats.largest_component > 1 && stats.largest_component <= stats.files);
+        assert!(stats.average_degree > 0.0);
+        let stages: Vec<&str> = stats
+            .build_timings
+            .iter()
+            .map(|(stage, _)| stage.as_str())
+            .collect();
+        assert_eq!(stages, vec!["history", "extraction", "linking", "total"]);
+
+        let empty = Graph::empty().stats();
+        assert_eq!(
+            (empty.files, empty.relations, empty.largest_component),
+            (0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn related_directories() {
+        let g = Graph::from(GraphConfig::default());
+        let dirs = g.related_directories(String::from("src/"));
+        assert!(dirs.iter().all(|each| each.name != "src"));
+        assert!(dirs.iter().any(|each| each.name == "examples"));
+        assert!(dirs.windows(2).all(|pair| pair[0].score >= pair[1].score));
+
+        // the file pairs from `examples/` to `src/` add up
+        let expected: usize = g
+            .files()
+            .into_iter()
+            .filter(|file| file.starts_with("src/"))
+            .flat_map(|file| g.related_files(file))
+            .filter(|each| each.name.starts_with("examples/"))
+            .map(|each| each.score)
+            .sum();
+        let examples = dirs.iter().find(|each| each.name == "examples").unwrap();
+        assert_eq!(examples.score, expected);
+    }
+
+    #[test]
+    fn rank_files() {
+        let g = Graph::from(GraphConfig::default());
+        let ranks = g.rank_files();
+        assert_eq!(ranks.len(), g.files().len());
+        let total: f64 = ranks.iter().map(|each| each.rank).sum();
+        assert!((total - 1.0).abs() < 1e-6);
+        assert!(ranks.windows(2).all(|pair| pair[0].rank >= pair[1].rank));
+
+        // used by almost every file
+        let top: Vec<&str> = ranks
+            .iter()
+            .take(3)
+            .map(|each| each.name.as_str())
+            .collect();
+        assert!(top.contains(&"src/symbol.rs") || top.contains(&"src/graph.rs"));
+    }
+
+    #[test]
+    fn hotspots() {
+        let g = Graph::from(GraphConfig::default());
+        let hotspots = g.hotspots();
+        assert_eq!(hotspots.len(), g.files().len());
+        assert!(hotspots
+            .windows(2)
+            .all(|pair| pair[0].score >= pair[1].score));
+        for each in &hotspots {
+            assert_eq!(each.score, each.churn * each.coupling);
+            assert_eq!(each.churn, g.list_file_commits(each.name.clone()).len());
+            assert_eq!(each.coupled_files == 0, each.coupling == 0);
+        }
+        assert!(hotspots[0].score > 0);
+    }
+
+    #[test]
+    fn detect_communities() {
+        let g = Graph::from(GraphConfig::default());
+        let communities = g.detect_communities();
+        let mut files: Vec<String> = communities
+            .iter()
+            .flat_map(|each| each.files.clone())
+            .collect();
+        files.sort();
+        let mut expected: Vec<String> = g.files().into_iter().collect();
+        expected.sort();
+        assert_eq!(files, expected);
+
+        assert!(communities.iter().enumerate().all(|(i, each)| each.id == i));
+        assert!(communities[0].files.len() > 1);
+        assert!(communities[0].internal_score > 0);
+        // stable between runs
+        let again: Vec<Vec<String>> = g
+            .detect_communities()
+            .into_iter()
+            .map(|each| each.files)
+            .collect();
+        let first: Vec<Vec<String>> = communities.into_iter().map(|each| each.files).collect();
+        assert_eq!(again, first);
+    }
+
+    #[test]
+    fn find_cycles() {
+        let g = Graph::from(GraphConfig::default());
+        for scope in [CycleScope::FILE, CycleScope::DIR] {
+            for cycle in g.find_cycles(scope) {
+                assert!(cycle.nodes.len() > 1);
+                assert_eq!(
+                    cycle.score,
+                    cycle.edges.iter().map(|edge| edge.2).sum::<usize>()
+                );
+                // every node is reached from the other nodes of the cycle
+                for node in &cycle.nodes {
+                    assert!(cycle.edges.iter().any(|edge| &edge.0 == node));
+                    assert!(cycle.edges.iter().any(|edge| &edge.1 == node));
+                }
+            }
+        }
+        // most files of this repo reach each other by the common names like `new`
+        let cycles = g.find_cycles(CycleScope::FILE);
+        assert!(cycles
+            .iter()
+            .any(|cycle| cycle.nodes.contains(&String::from("src/graph.rs"))));
+    }
+
+    #[test]
+    fn impacted_files() {
+        let g = Graph::from(GraphConfig::default());
+        let file = String::from("src/rule.rs");
+
+        let mut direct: Vec<String> = g
+            .related_files(file.clone())
+            .into_iter()
+            .filter(|each| each.score > 0)
+            .map(|each| each.name)
+            .collect();
+        direct.sort();
+        let mut one_hop: Vec<String> = g
+            .impacted_files(file.clone(), 1, 0.5)
+            .into_iter()
+            .map(|each| each.name)
+            .collect();
+        one_hop.sort();
+        assert_eq!(one_hop, direct);
+
+        let impacted = g.impacted_files(file.clone(), 3, 0.5);
+        assert!(impacted.len() > direct.len());
+        assert!(impacted.iter().all(|each| each.name != file));
+        for each in impacted.iter().filter(|each| each.hops > 1) {
+            assert!(!direct.contains(&each.name));
+            let via = impacted.iter().find(|prev| prev.name == each.via).unwrap();
+            assert_eq!(via.hops, each.hops - 1);
+            assert!(each.score <= via.score * 0.5);
+        }
+    }
+
+    #[test]
+    fn impact() {
+        let g = Graph::from(GraphConfig::default());
+        let changed = vec![String::from("src/rule.rs"), String::from("src/history.rs")];
+        let impacted = g.impact(changed.clone());
+        assert!(!impacted.is_empty());
+        assert!(impacted.iter().all(|each| !changed.contains(&each.name)));
+
+        // graph.rs uses both of them
+        let graph = impacted
+            .iter()
+            .find(|each| each.name == "src/graph.rs")
+            .unwrap();
+        assert_eq!(graph.sources, vec!["src/history.rs", "src/rule.rs"]);
+        assert!(graph.symbols.contains(&String::from("walk")));
+        let score: usize = changed
+            .iter()
+            .flat_map(|file| g.related_files(file.clone()))
+            .filter(|each| each.name == "src/graph.rs")
+            .map(|each| each.score)
+            .sum();
+        assert_eq!(graph.score, score);
+
+        assert!(g.impact(vec![String::from("not/exist.rs")]).is_empty());
+    }
+
+    #[test]
+    fn suggest_reviewers() {
+        let g = Graph::from(GraphConfig::default());
+        let reviewers = g.suggest_reviewers(vec![String::from("src/history.rs")]);
+        assert!(!reviewers.is_empty());
+        assert!(reviewers
+            .windows(2)
+            .all(|pair| pair[0].score >= pair[1].score));
+        for each in &reviewers {
+            assert!(each.commits > 0);
+            assert!(!each.files.is_empty());
+        }
+        // the authors of the changed file itself
+        assert!(reviewers
+            .iter()
+            .any(|each| each.files.contains(&String::from("src/history.rs"))));
+
+        assert!(g
+            .suggest_reviewers(vec![String::from("not/exist.rs")])
+            .is_empty());
+    }
+
+    #[test]
+    fn file_owners() {
+        let g = Graph::from(GraphConfig::default());
+        let ownerships = g.file_owners(0.5);
+        assert!(!ownerships.is_empty());
+        assert!(ownerships
+            .windows(2)
+            .all(|pair| pair[0].name < pair[1].name));
+        for each in &ownerships {
+            assert!(each.commits > 0);
+            assert!(!each.owners.is_empty());
+            assert!(each
+                .owners
+                .iter()
+                .all(|(_, share)| *share >= 0.5 && *share <= 1.0));
+        }
+        // nobody owns more than all the commits
+        assert!(g.file_owners(1.1).is_empty());
+    }
+
+    #[test]
+    fn bus_factor() {
+        let g = Graph::from(GraphConfig::default());
+        let ownership = g.file_ownership(String::from("./src/graph.rs"));
+        assert_eq!(ownership.name, "src/graph.rs");
+        assert!(ownership.commits > 0);
+        // one author per commit
+        let total: f64 = ownership.owners.iter().map(|(_, share)| share).sum();
+        assert!((total - 1.0).abs() < 1e-6);
+
+        let risks = g.bus_factor(0.0);
+        assert!(!risks.is_empty());
+        assert!(risks.windows(2).all(|pair| pair[0].share >= pair[1].share));
+        let risk = risks
+            .iter()
+            .find(|each| each.name == "src/graph.rs")
+            .unwrap();
+        // the coupled files bring in more commits
+        assert!(risk.commits >= ownership.commits);
+        assert!(risk.coupled_files > 0);
+        assert!(g.bus_factor(1.1).is_empty());
+    }
+
+    #[test]
+    fn explain_relation() {
+        let g = Graph::from(GraphConfig::default());
+        let related = g.related_files(String::from("src/symbol.rs"));
+        assert!(!related.is_empty());
+        for each in related.iter().take(5) {
+            let explanation =
+                g.explain_relation(String::from("./src/symbol.rs"), each.name.clone());
+            assert_eq!(explanation.src, "src/symbol.rs");
+            assert_eq!(explanation.score, each.score);
+            assert!(explanation
+                .symbols
+                .iter()
+                .all(|symbol| *symbol.reference.file == *each.name));
+            let ratio: f64 = explanation.commits.iter().map(|commit| commit.ratio).sum();
+            assert!((explanation.ratio - ratio).abs() < 1e-6);
+        }
+
+        let explanation =
+            g.explain_relation(String::from("src/symbol.rs"), String::from("not-a-file"));
+        assert_eq!(explanation.score, 0);
+        assert!(explanation.symbols.is_empty());
+        assert!(explanation.commits.is_empty());
+    }
+
+    #[test]
+    fn scoring() {
+        let mut config = GraphConfig::default();
+        config.scoring.symbol_weight = 100.0;
+        config.scoring.same_directory_boost = 2.0;
+        let g = Graph::from(config);
+        let related = g.related_files(String::from("src/symbol.rs"));
+        assert!(!related.is_empty());
+        for each in &related {
+            assert!(each
+                .related_symbols
+                .iter()
+                .filter(|symbol| symbol.weight > 0)
+                .all(|symbol| symbol.weight >= 100));
+            let explanation = g.explain_relation(String::from("src/symbol.rs"), each.name.clone());
+            assert_eq!(explanation.score, each.score);
+            if each.name.starts_with("src/") && !each.name[4..].contains('/') {
+                assert_eq!(explanation.directory_boost, 2.0);
+            }
+        }
+    }
+
+    #[test]
+    fn half_life() {
+        let mut config = GraphConfig::default();
+        config.scoring.half_life_days = 30.0;
+        let g = Graph::from(config);
+        assert!(!g.commit_decays.is_empty());
+        assert!(g
+            .commit_decays
+            .values()
+            .all(|decay| *decay > 0.0 && *decay <= 1.0));
+        assert!(g.commit_decays.values().any(|decay| *decay == 1.0));
+
+        let related = g.related_files(String::from("src/symbol.rs"));
+        let explanation =
+            g.explain_relation(String::from("src/symbol.rs"), related[0].name.clone());
+        assert!(explanation
+            .commits
+            .iter()
+            .all(|commit| commit.decay == g.commit_decays[&commit.sha]));
+    }
+
+    #[test]
+    fn symbol_stopwords_and_idf() {
+        let mut config = GraphConfig::default();
+        config.symbol_stopwords = vec![String::from("from"), String::from("new")];
+        config.scoring.idf_damping = true;
+        let g = Graph::from(config);
+        assert!(g
+            .symbol_graph
+            .list_symbols("src/graph.rs")
+            .iter()
+            .all(|symbol| &*symbol.name != "from" && &*symbol.name != "new"));
+
+        assert!(!g.symbol_idf.is_empty());
+        assert!(g.symbol_idf.values().all(|idf| *idf > 0.0 && *idf <= 1.0));
+        let related = g.related_files(String::from("src/symbol.rs"));
+        for each in related.iter().take(5) {
+            let explanation = g.explain_relation(String::from("src/symbol.rs"), each.name.clone());
+            assert_eq!(explanation.score, each.score);
+            assert!(explanation
+                .symbols
+                .iter()
+                .all(|symbol| symbol.idf == g.symbol_idf[&symbol.def.name]));
+        }
+    }
+
+    #[test]
+    fn idf_keeps_symbol_weight() {
+        let mut config = GraphConfig::default();
+        config.scoring.idf_damping = true;
+        // every file has the name, so it gets the lowest idf
+        let mut files = vec![(
+            String::from("src/runner.rs"),
+            String::from("pub fn shared_runner() {}\n"),
+        )];
+        for i in 0..8 {
+            files.push((
+                format!("src/caller_{}.rs", i),
+                format!("fn caller_{}() {{ shared_runner(); }}\n", i),
+            ));
+        }
+        let g = Graph::from_sources(config, files).unwrap();
+        assert!(g.symbol_idf["shared_runner"] < 1.0);
+        let related = g.related_files(String::from("src/runner.rs"));
+        assert_eq!(related.len(), 8);
+        assert!(related.iter().all(|each| each.score > 0));
+        assert_eq!(g.scoring.link_score(1, 0, 0.1), 1);
+    }
+
+    #[test]
+    fn include_file_regex() {
+        let mut config = GraphConfig::default();
+        config.include_file_regex = String::from("^src/");
+        let g = Graph::from(config);
+        assert!(g.files().contains("src/graph.rs"));
+        assert!(g.files().iter().all(|file| file.starts_with("src/")));
+    }
+
+    #[test]
+    fn truncate_large_files() {
+        let mut config = GraphConfig::default();
+        config.symbol_limit = 64;
+        let g = Graph::from(config.clone());
+        assert!(!g.files().contains("src/graph.rs"));
+
+        config.truncate_large_files = true;
+        let g = Graph::from(config);
+        let metadata = g.file_metadata(String::from("src/graph.rs"));
+        assert!(metadata.truncated);
+        assert!(!metadata.symbols.is_empty() && metadata.symbols.len() < 64);
+        assert!(!g.related_files(String::from("src/graph.rs")).is_empty());
+    }
+
+    #[test]
+    fn packages() {
+        let mut g = Graph::from(GraphConfig::default());
+        assert_eq!(g.packages(), vec![String::from(".")]);
+        assert_eq!(g.package_of(String::from("./src/graph.rs")), ".");
+        assert!(g.package_relations().is_empty());
+
+        g.packages = vec![String::from("."), String::from("src")];
+        assert_eq!(g.package_of(String::from("src/graph.rs")), "src");
+        assert_eq!(g.package_of(String::from("docs/usage.md")), ".");
+        assert!(g
+            .package_relations()
+            .iter()
+            .all(|relation| relation.src != relation.dst && relation.relations > 0));
+    }
+
+    #[test]
+    fn symbol_regex() {
+        let mut config = GraphConfig::default();
+        config.include_symbol_regex = Some(String::from("^[A-Z]"));
+        config.exclude_symbol_regex = Some(String::from("^Graph"));
+        let g = Graph::from(config);
+        let symbols = g.symbol_graph.list_symbols("src/graph.rs");
+        assert!(!symbols.is_empty());
+        assert!(symbols
+            .iter()
+            .all(|symbol| symbol.name.starts_with(char::is_uppercase)
+                && !symbol.name.starts_with("Graph")));
+    }
+
+    #[test]
+    fn file_commits_detailed() {
+        let g = Graph::from(GraphConfig::default());
+        let commits = g.file_commits_detailed(String::from("./src/graph.rs"));
+        assert_eq!(
+            commits.len(),
+            g.list_file_commits(String::from("src/graph.rs")).len()
+        );
+        assert!(commits
+            .windows(2)
+            .all(|pair| pair[0].timestamp >= pair[1].timestamp));
+        assert!(commits
+            .iter()
+            .all(|each| !each.author.is_empty() && !each.subject.is_empty()));
+        assert!(g
+            .file_commits_detailed(String::from("not-a-file"))
+            .is_empty());
+    }
+
+    #[test]
+    fn commit_and_issue_related_files() {
+        let g = Graph::from(GraphConfig::default());
+        for commit in g.list_file_commits(String::from("src/graph.rs")) {
+            let files = g.commit_related_files(commit);
+            assert!(files.contains(&String::from("src/graph.rs")));
+            assert!(files.windows(2).all(|pair| pair[0] <= pair[1]));
+        }
+        for issue in g.list_file_issues(String::from("src/extractor.rs")) {
+            let files = g.issue_related_files(issue);
+            assert!(files.contains(&String::from("src/extractor.rs")));
+        }
+        assert!(g
+            .commit_related_files(String::from("not-a-commit"))
+            .is_empty());
+        assert!(g.issue_related_files(String::from("#0")).is_empty());
+    }
+
+    #[test]
+    fn paths_between_files() {
+        let g = Graph::from(GraphConfig::default());
+        let src = String::from("src/rule.rs");
+        let dst = String::from("src/api.rs");
+        assert!(g.pairs_between_files(src.clone(), dst.clone()).is_empty());
+
+        let paths = g.paths_between_files(src.clone(), dst.clone(), 3);
+        assert!(!paths.is_empty());
+        assert!(paths
+            .windows(2)
+            .all(|pair| pair[0].files.len() <= pair[1].files.len()));
+        for path in &paths {
+            assert_eq!(path.files.first(), Some(&src));
+            assert_eq!(path.files.last(), Some(&dst));
+            assert!(path.files.len() <= 4);
+            assert_eq!(path.hops.len(), path.files.len() - 1);
+            for (i, pairs) in path.hops.iter().enumerate() {
+                assert!(!pairs.is_empty());
+                assert!(pairs
+                    .iter()
+                    .all(|pair| *pair.src_symbol.file == path.files[i]
+                        && *pair.dst_symbol.file == path.files[i + 1]));
+            }
+        }
+        assert!(g
+            .paths_between_files(src, String::from("not/exist.rs"), 3)
+            .is_empty());
+    }
+
+    #[test]
+    fn interned_symbols() {
+        let g = Graph::from(GraphConfig::default());
+        let symbols: Vec<&Symbol> = g
+            .file_contexts
+            .iter()
+            .flat_map(|file_context| file_context.symbols.iter())
+            .filter(|symbol| &*symbol.name == "from")
+            .collect();
+        assert!(symbols.len() > 1);
+        assert!(symbols
+            .iter()
+            .all(|symbol| Arc::ptr_eq(&symbol.name, &symbols[0].name)));
+    }
+
+    #[test]
+    fn resolve_qualified_reference() {
+        let range = tree_sitter::Range {
+            start_byte: 0,
+            end_byte: 4,
+            start_point: tree_sitter::Point { row: 0, column: 0 },
+            end_point: tree_sitter::Point { row: 0, column: 4 },
+        };
+        let mut graph_from = Symbol::new_def(Arc::from("a.rs"), Arc::from("from"), range);
+        graph_from.scope = String::from("graph::Graph");
+        let mut other_from = Symbol::new_def(Arc::from("b.rs"), Arc::from("from"), range);
+        other_from.scope = String::from("RangeWrapper");
+        let defs = vec![graph_from, other_from];
+
+        let mut reference = Symbol::new_ref(Arc::from("c.rs"), Arc::from("from"), range);
+        reference.scope = String::from("Graph");
+        let resolved = resolve_definitions(&reference, &defs);
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(&*resolved[0].file, "a.rs");
+
+        // unknown qualifier, keep all the candidates
+        reference.scope = String::from("self");
+        assert_eq!(resolve_definitions(&reference, &defs).len(), 2);
+    }
+
+    #[test]
+    fn cross_language_files() {
+        assert!(is_cross_language(
+            "py_wrapper/gossiphs/__init__.py",
+            "src/api.rs"
+        ));
+        assert!(!is_cross_language("web/app.tsx", "web/utils.js"));
+        assert!(!is_cross_language("src/App.kt", "src/Util.java"));
+        // idl and docs can be referenced by any language
+        assert!(!is_cross_language("server/user.go", "idl/user.proto"));
+        assert!(!is_cross_language("src/main.rs", "README"));
+    }
+
+    #[test]
+    fn test_files() {
+        for file in [
+            "pkg/calc_test.go",
+            "tests/cli.rs",
+            "src/test/java/CalcTest.java",
+            "web/__tests__/app.js",
+            "web/app.spec.ts",
+            "web/app.test.tsx",
+            "py/test_calc.py",
+            "py/conftest.py",
+            "Sources/CalcTests.swift",
+        ] {
+            assert!(is_test_file(file), "{}", file);
+        }
+        for file in [
+            "pkg/calc.go",
+            "src/graph.rs",
+            "src/latest.rs",
+            "web/contest.ts",
+            "py/testing_utils.py",
+            "README",
+        ] {
+            assert!(!is_test_file(file), "{}", file);
+        }
+    }
+
+    #[test]
+    fn related_tests() {
+        let repo_path = std::env::temp_dir().join(format!("gossiphs-tests-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&repo_path);
+        let repo = Repository::init(&repo_path).unwrap();
+        let signature = git2::Signature::now("gossiphs", "gossiphs@example.com").unwrap();
+        let mut index = repo.index().unwrap();
+        let mut parents = Vec::new();
+        // the root commit is skipped by the history, so the files come in the second one
+        for files in [
+            vec![("README.md", "calc")],
+            vec![
+                (
+                    "calc.go",
+                    "package calc\n\nfunc Add(a, b int) int {\n\treturn a + b\n}\n",
+                ),
+                (
+                    "main.go",
+                    "package calc\n\nfunc Run() int {\n\treturn Add(1, 2)\n}\n",
+                ),
+                (
+                    "calc_test.go",
+                    "package calc\n\nfunc TestAdd(t *testing.T) {\n\tAdd(1, 2)\n}\n",
+                ),
+            ],
+        ] {
+            for (name, content) in files {
+                std::fs::write(repo_path.join(name), content).unwrap();
+                index.add_path(Path::new(name)).unwrap();
+            }
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let parent_refs: Vec<&git2::Commit> = parents.iter().collect();
+            let commit = repo
+                .commit(
+                    Some("HEAD"),
+                    &signature,
+                    &signature,
+                    "init",
+                    &tree,
+                    &parent_refs,
+                )
+                .unwrap();
+            parents = vec![repo.find_commit(commit).unwrap()];
+        }
+
+        let mut config = GraphConfig::default();
+        config.project_path = repo_path.to_string_lossy().into_owned();
+        let g = Graph::from(config);
+        let changed = vec![String::from("calc.go")];
+        let impacted: Vec<String> = g
+            .impact(changed.clone())
+            .into_iter()
+            .map(|each| each.name)
+            .collect();
+        assert!(impacted.contains(&String::from("main.go")));
+        let tests: Vec<String> = g
+            .related_tests(changed)
+            .into_iter()
+            .map(|each| each.name)
+            .collect();
+        assert_eq!(tests, vec!["calc_test.go"]);
+
+        std::fs::remove_dir_all(&repo_path).unwrap();
+    }
+
+    #[test]
+    fn co_change_weight() {
+        let g = Graph::from(GraphConfig::default());
+        assert!(!g.files().contains("Cargo.toml"));
+
+        let mut config = GraphConfig::default();
+        config.co_change_weight = 2;
+        let co_change = Graph::from(config);
+        assert!(co_change.files().contains("Cargo.toml"));
+        let related = co_change.related_files(String::from("Cargo.toml"));
+        assert!(!related.is_empty());
+        assert!(related.iter().all(|each| each.related_symbols.is_empty()));
+
+        // symbol scores plus the co-change ones
+        let file = String::from("src/history.rs");
+        let co_changes = &co_change.co_changes[&file];
+        for each in co_change.related_files(file.clone()) {
+            let symbol_score = g
+                .related_files(file.clone())
+                .into_iter()
+                .find(|prev| prev.name == each.name)
+                .map_or(0, |prev| prev.score);
+            let co_change_score = co_changes.get(&each.name).copied().unwrap_or_default();
+            assert_eq!(co_change_score % 2, 0);
+            assert_eq!(each.score, symbol_score + co_change_score);
+        }
+
+        let path = std::env::temp_dir()
+            .join(format!("gossiphs-co-change-{}.bin", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        co_change.save(&path).unwrap();
+        let loaded = Graph::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            loaded.related_files(String::from("Cargo.toml")).len(),
+            related.len()
+        );
+    }
+
+    #[test]
+    fn build_progress() {
+        let config = GraphConfig::default();
+        let progress = config.progress.clone();
+        let g = Graph::from(config);
+        let status = progress.status();
+        assert_eq!(status.phase, "linking");
+        assert_eq!(status.files_total, g.file_contexts.len() * 2);
+        assert_eq!(status.files_processed, status.files_total);
+    }
+
+    #[test]
+    fn from_sources() {
+        let files = vec![
+            (
+                String::from("src/helper.rs"),
+                String::from("pub fn shared_helper() -> usize { 1 }\n"),
+            ),
+            (
+                String::from("src/main.rs"),
+                String::from("fn main() { shared_helper(); }\n"),
+            ),
+            (String::from("README"), String::from("no extension")),
+        ];
+        let g = Graph::from_sources(GraphConfig::default(), files).unwrap();
+        assert_eq!(g.files().len(), 2);
+        let related = g.related_files(String::from("src/helper.rs"));
+        assert_eq!(related.len(), 1);
+        assert_eq!(related[0].name, "src/main.rs");
+        assert!(related[0].score > 0);
+        let metadata = g.file_metadata(String::from("src/main.rs"));
+        assert!(!metadata.symbols.is_empty());
+        assert!(metadata.commits.is_empty());
+    }
+
+    #[test]
+    fn lossy_decode() {
+        let content = b"// caf\xe9\nfunc Add(a, b int) int {}\n";
+        assert!(decode("calc.go", content, false).is_none());
+        let decoded = decode("calc.go", content, true).unwrap();
+        assert!(decoded.starts_with("// caf\u{FFFD}\n"));
+        assert!(decoded.contains("func Add"));
+        assert_eq!(decode("calc.go", b"func Add", false).unwrap(), "func Add");
+    }
+
+    #[test]
+    fn working_tree_files() {
+        let files = Graph::list_working_tree_files(".").unwrap();
+        assert!(files.contains(&String::from("src/graph.rs")));
+        // ignored files stay out
+        assert!(!files.iter().any(|each| each.starts_with("target/")));
+    }
+
+    #[test]
+    fn try_from_errors() {
+        let mut config = GraphConfig::default();
+        config.project_path = std::env::temp_dir().to_string_lossy().into_owned();
+        assert!(matches!(
+            Graph::try_from(config),
+            Err(GossiphsError::Repository { .. })
+        ));
+
+        let mut config = GraphConfig::default();
+        config.exclude_file_regex = String::from("(");
+        assert!(matches!(
+            Graph::try_from(config),
+            Err(GossiphsError::Regex { .. })
+        ));
+
+        let mut config = GraphConfig::default();
+        config.include_file_regex = String::from("(");
+        assert!(matches!(
+            Graph::try_from(config),
+            Err(GossiphsError::Regex { .. })
+        ));
+
+        let mut config = GraphConfig::default();
+        config.exclude_symbol_regex = Some(String::from("("));
+        assert!(matches!(
+            Graph::try_from(config),
+            Err(GossiphsError::Regex { .. })
+        ));
+    }
+
+    #[test]
+    fn normalize_paths() {
+        let roots = project_roots(".");
+        assert_eq!(normalize_path(&roots, "src\\graph.rs"), "src/graph.rs");
+        assert_eq!(normalize_path(&roots, "./src/graph.rs"), "src/graph.rs");
+        assert_eq!(
+            normalize_path(&project_roots("./repo"), "repo/src/graph.rs"),
+            "src/graph.rs"
+        );
+        assert_eq!(
+            normalize_path(&project_roots("C:\\repo\\"), "C:\\repo\\src\\graph.rs"),
+            "src/graph.rs"
+        );
+
+        let root = std::fs::canonicalize(".").unwrap();
+        let absolute = root.join("src").join("graph.rs");
+        assert_eq!(
+            normalize_path(&roots, &absolute.to_string_lossy()),
+            "src/graph.rs"
+        );
+
+        // files which do not exist, or no longer exist, are normalized the same
+        let absolute = root.join("src").join("..").join("gone").join("main.rs");
+        assert_eq!(
+            normalize_path(&roots, &absolute.to_string_lossy()),
+            "gone/main.rs"
+        );
+        assert_eq!(
+            normalize_path(&project_roots("missing/repo"), "./missing/repo/./a/../b.rs"),
+            "b.rs"
+        );
+    }
+
+    #[test]
+    fn strict_config() {
+        let mut config = GraphConfig::default();
+        config.set_strict(true);
+        assert!(config.strict());
+        assert_eq!(config.def_limit, 1);
+        config.set_strict(false);
+        assert_eq!(config.def_limit, GraphConfig::default().def_limit);
+
+        config.def_limit = 4;
+        config.set_strict(false);
+        assert_eq!(config.def_limit, 4);
     }
 }
@@ -0,0 +1,301 @@
+use crate::api::CommitInfo;
+use crate::graph::Graph;
+use crate::history;
+use crate::server::{graph_file, project_graph, ProjectParams};
+use crate::symbol::Symbol as GraphSymbol;
+use async_graphql::{EmptyMutation, EmptySubscription, Object, Result, Schema, SimpleObject};
+use async_graphql_axum::GraphQL;
+use axum::routing::post_service;
+use axum::Router;
+use std::sync::{Arc, RwLock};
+
+type SharedGraph = Arc<RwLock<Graph>>;
+
+fn graph_of(project: Option<String>) -> Result<SharedGraph> {
+    project_graph(ProjectParams { project }).map_err(|err| err.message.into())
+}
+
+fn files_of(graph: &SharedGraph, paths: Vec<String>) -> Vec<File> {
+    paths
+        .into_iter()
+        .map(|path| File {
+            graph: graph.clone(),
+            path,
+        })
+        .collect()
+}
+
+pub(crate) struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// A file of the graph, null if it is not in the graph.
+    async fn file(&self, path: String, project: Option<String>) -> Result<Option<File>> {
+        let graph = graph_of(project)?;
+        let path = graph_file(&graph.read().unwrap(), &path).ok();
+        Ok(path.map(|path| File { graph, path }))
+    }
+
+    /// Files of the graph, sorted by path.
+    async fn files(
+        &self,
+        project: Option<String>,
+        offset: Option<usize>,
+        limit: Option<usize>,
+    ) -> Result<Vec<File>> {
+        let graph = graph_of(project)?;
+        let mut paths: Vec<String> = graph.read().unwrap().files().into_iter().collect();
+        paths.sort();
+        let paths = paths
+            .into_iter()
+            .skip(offset.unwrap_or(0))
+            .take(limit.unwrap_or(usize::MAX))
+            .collect();
+        Ok(files_of(&graph, paths))
+    }
+
+    /// A commit by its full hash, null if it is not in the repo.
+    async fn commit(&self, sha: String, project: Option<String>) -> Result<Option<Commit>> {
+        let graph = graph_of(project)?;
+        let project_path = graph.read().unwrap().project_path.clone();
+        let info = history::commit_details(&project_path, &[sha])
+            .map_err(|err| err.to_string())?
+            .pop();
+        Ok(info.map(|info| Commit { graph, info }))
+    }
+
+    /// An issue mentioned by the commits, like `#123`.
+    async fn issue(&self, name: String, project: Option<String>) -> Result<Issue> {
+        Ok(Issue {
+            graph: graph_of(project)?,
+            name,
+        })
+    }
+
+    /// A symbol by its id, see `Symbol.id`.
+    async fn symbol(&self, id: String, project: Option<String>) -> Result<Option<Symbol>> {
+        let graph = graph_of(project)?;
+        let symbol = {
+            let g = graph.read().unwrap();
+            g.symbol_graph
+                .symbol_mapping
+                .get(id.as_str())
+                .and_then(|index| g.symbol_graph.g[*index].get_symbol())
+        };
+        Ok(symbol.map(|symbol| Symbol { graph, symbol }))
+    }
+}
+
+pub(crate) struct File {
+    graph: SharedGraph,
+    path: String,
+}
+
+#[Object]
+impl File {
+    async fn path(&self) -> &str {
+        &self.path
+    }
+
+    async fn symbols(&self) -> Vec<Symbol> {
+        let metadata = self.graph.read().unwrap().file_metadata(self.path.clone());
+        metadata
+            .symbols
+            .into_iter()
+            .map(|symbol| Symbol {
+                graph: self.graph.clone(),
+                symbol,
+            })
+            .collect()
+    }
+
+    /// Files related to this one, the strongest first.
+    async fn related(&self, top: Option<usize>, min_score: Option<usize>) -> Vec<Relation> {
+        let related = self.graph.read().unwrap().related_files(self.path.clone());
+        related
+            .into_iter()
+            .filter(|each| each.score >= min_score.unwrap_or(0))
+            .take(top.unwrap_or(usize::MAX))
+            .map(|each| Relation {
+                score: each.score,
+                symbols: each
+                    .related_symbols
+                    .iter()
+                    .map(|related| related.symbol.name.to_string())
+                    .collect(),
+                file: File {
+                    graph: self.graph.clone(),
+                    path: each.name,
+                },
+            })
+            .collect()
+    }
+
+    /// Commits changing this file, the latest first.
+    async fn commits(&self) -> Vec<Commit> {
+        let commits = self
+            .graph
+            .read()
+            .unwrap()
+            .file_commits_detailed(self.path.clone());
+        commits
+            .into_iter()
+            .map(|info| Commit {
+                graph: self.graph.clone(),
+                info,
+            })
+            .collect()
+    }
+
+    async fn issues(&self) -> Vec<Issue> {
+        let issues = self
+            .graph
+            .read()
+            .unwrap()
+            .list_file_issues(self.path.clone());
+        issues
+            .into_iter()
+            .map(|name| Issue {
+                graph: self.graph.clone(),
+                name,
+            })
+            .collect()
+    }
+}
+
+/// A file related to another one.
+#[derive(SimpleObject)]
+pub(crate) struct Relation {
+    file: File,
+    score: usize,
+    /// names of the symbols linking them
+    symbols: Vec<String>,
+}
+
+pub(crate) struct Symbol {
+    graph: SharedGraph,
+    symbol: GraphSymbol,
+}
+
+#[Object]
+impl Symbol {
+    async fn id(&self) -> String {
+        self.symbol.id()
+    }
+
+    async fn name(&self) -> &str {
+        &self.symbol.name
+    }
+
+    /// DEF, REF or NAMESPACE
+    async fn kind(&self) -> String {
+        format!("{:?}", self.symbol.kind)
+    }
+
+    /// 0-based
+    async fn line(&self) -> usize {
+        self.symbol.range.start_point.row
+    }
+
+    async fn file(&self) -> File {
+        File {
+            graph: self.graph.clone(),
+            path: self.symbol.file.to_string(),
+        }
+    }
+}
+
+pub(crate) struct Commit {
+    graph: SharedGraph,
+    info: CommitInfo,
+}
+
+#[Object]
+impl Commit {
+    async fn sha(&self) -> &str {
+        &self.info.sha
+    }
+
+    /// `name <email>`
+    async fn author(&self) -> &str {
+        &self.info.author
+    }
+
+    /// seconds since the epoch
+    async fn timestamp(&self) -> i64 {
+        self.info.timestamp
+    }
+
+    async fn subject(&self) -> &str {
+        &self.info.subject
+    }
+
+    async fn files(&self) -> Vec<File> {
+        let paths = self
+            .graph
+            .read()
+            .unwrap()
+            .commit_related_files(self.info.sha.clone());
+        files_of(&self.graph, paths)
+    }
+}
+
+pub(crate) struct Issue {
+    graph: SharedGraph,
+    name: String,
+}
+
+#[Object]
+impl Issue {
+    async fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Files changed by the commits mentioning this issue.
+    async fn files(&self) -> Vec<File> {
+        let paths = self
+            .graph
+            .read()
+            .unwrap()
+            .issue_related_files(self.name.clone());
+        files_of(&self.graph, paths)
+    }
+}
+
+pub(crate) type GraphSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+pub(crate) fn schema() -> GraphSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription).finish()
+}
+
+/// `POST /graphql`, over the same graphs as the rest routes.
+pub(crate) fn router() -> Router {
+    Router::new().route("/graphql", post_service(GraphQL::new(schema())))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::{Graph, GraphConfig};
+    use crate::graphql::schema;
+    use crate::server::GRAPH_INST;
+
+    #[tokio::test]
+    async fn graphql_query() {
+        *GRAPH_INST.write().unwrap() = Graph::from(GraphConfig::default());
+        let response = schema()
+            .execute(
+                r#"{ file(path: "src/symbol.rs") { path related(top: 2) { score file { path commits { author } } } } }"#,
+            )
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        let data = response.data.into_json().unwrap();
+        assert_eq!(data["file"]["path"], "src/symbol.rs");
+        let related = data["file"]["related"].as_array().unwrap();
+        assert!(!related.is_empty() && related.len() <= 2);
+
+        let response = schema()
+            .execute(r#"{ file(path: "no/such.rs") { path } }"#)
+            .await;
+        assert!(response.data.into_json().unwrap()["file"].is_null());
+    }
+}
@@ -0,0 +1,453 @@
+use crate::api::CommitInfo;
+use crate::error::GossiphsError;
+use crate::graph::GraphConfig;
+use cupido::relation::graph::RelationGraph as CupidoRelationGraph;
+use git2::{Commit, Delta, DiffFindOptions, DiffOptions, Oid, Repository};
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::warn;
+
+const DEFAULT_ISSUE_REGEX: &str = r"(#\d+)";
+
+lazy_static::lazy_static! {
+    // dependency bots, CI and formatters, their commits touch many unrelated files
+    static ref BOT_AUTHOR_REGEX: Regex = Regex::new(
+        r"(?i)(\[bot\]|dependabot|renovate|greenkeeper|snyk-bot|github-actions|pre-commit-ci|mergify|imgbot)"
+    )
+    .unwrap();
+}
+
+/// Walk the commit history and collect the co-change relations.
+/// Same as the native collector of cupido, but it can start from any rev
+/// so that nothing needs to be checked out.
+pub(crate) fn walk(conf: &GraphConfig) -> Result<CupidoRelationGraph, GossiphsError> {
+    let repo =
+        Repository::open(&conf.project_path).map_err(GossiphsError::open(&conf.project_path))?;
+    let start = resolve_commit(&repo, conf.source.rev())?;
+
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push(start.id())?;
+    // top to bottom, children always come before their parents for tracking renames
+    revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)?;
+    // only the first parent, for performance
+    revwalk.simplify_first_parent()?;
+
+    let issue_regex = Regex::new(conf.issue_regex.as_deref().unwrap_or(DEFAULT_ISSUE_REGEX))
+        .map_err(GossiphsError::regex("issue_regex"))?;
+    let commit_exclude_regex = conf
+        .exclude_commit_regex
+        .as_ref()
+        .map(|pattern| Regex::new(pattern).map_err(GossiphsError::regex("exclude_commit_regex")))
+        .transpose()?;
+    let author_exclude_regex = conf
+        .exclude_author_regex
+        .as_ref()
+        .map(|pattern| Regex::new(pattern).map_err(GossiphsError::regex("exclude_author_regex")))
+        .transpose()?;
+
+    let mut graph = CupidoRelationGraph::new();
+    // old path -> current path, collected from new to old
+    let mut renames: HashMap<String, String> = HashMap::new();
+    let mut counter = 0;
+    for commit_id in revwalk {
+        let commit = match commit_id.and_then(|commit_id| repo.find_commit(commit_id)) {
+            Ok(commit) => commit,
+            Err(err) => {
+                warn!("Failed to find commit: {:?}", err);
+                continue;
+            }
+        };
+
+        // time window. commits are sorted from new to old, but a rebased or cherry-picked commit
+        // can be older than its children, so the old ones are skipped instead of ending the walk
+        let commit_time = commit.time().seconds();
+        if conf.until.is_some_and(|until| commit_time > until) {
+            continue;
+        }
+        if conf.since.is_some_and(|since| commit_time < since) {
+            continue;
+        }
+
+        if let Some(regex) = &commit_exclude_regex {
+            if regex.is_match(commit.message().unwrap_or_default()) {
+                continue;
+            }
+        }
+        if conf.exclude_merge_commits && commit.parent_count() > 1 {
+            continue;
+        }
+        let author = commit.author().to_string();
+        if let Some(regex) = &author_exclude_regex {
+            if regex.is_match(&author) {
+                continue;
+            }
+        }
+        if conf.exclude_bot_commits && BOT_AUTHOR_REGEX.is_match(&author) {
+            continue;
+        }
+
+        let mut files: Vec<String> = changed_files(&repo, &commit, conf.follow_renames)?
+            .into_iter()
+            .map(|(path, old_path)| {
+                let current = renames.get(&path).cloned().unwrap_or(path);
+                if let Some(old_path) = old_path {
+                    renames.insert(old_path, current.clone());
+                }
+                current
+            })
+            .collect();
+        files.sort();
+        files.dedup();
+        if files.is_empty() {
+            continue;
+        }
+        let commit_name = commit.id().to_string();
+        graph.add_commit_node(&commit_name);
+        for file in &files {
+            graph.add_file_node(file);
+            graph.add_edge_file2commit(file, &commit_name);
+        }
+
+        for issue in issue_regex.find_iter(commit.message().unwrap_or_default()) {
+            let issue = issue.as_str().to_string();
+            graph.add_issue_node(&issue);
+            for file in &files {
+                graph.add_edge_file2issue(file, &issue);
+            }
+            graph.add_edge_commit2issue(&commit_name, &issue);
+        }
+
+        graph.add_author_node(&author);
+        graph.add_edge_author2commit(&author, &commit_name);
+
+        counter += 1;
+        if counter > conf.depth {
+            break;
+        }
+    }
+    Ok(graph)
+}
+
+/// The commit of `rev`, or HEAD if not specified.
+pub(crate) fn resolve_commit<'repo>(
+    repo: &'repo Repository,
+    rev: Option<&str>,
+) -> Result<Commit<'repo>, GossiphsError> {
+    let rev = rev.unwrap_or("HEAD");
+    repo.revparse_single(rev)
+        .and_then(|object| object.peel_to_commit())
+        .map_err(|source| GossiphsError::Rev {
+            rev: rev.to_string(),
+            source,
+        })
+}
+
+/// Author, time and subject of these commits of the repository at `project_path`.
+/// The commits not found in the repository are left out.
+pub(crate) fn commit_details(
+    project_path: &str,
+    shas: &[String],
+) -> Result<Vec<CommitInfo>, GossiphsError> {
+    let repo = Repository::open(project_path).map_err(GossiphsError::open(project_path))?;
+    Ok(shas
+        .iter()
+        .filter_map(|sha| {
+            let commit = Oid::from_str(sha)
+                .and_then(|oid| repo.find_commit(oid))
+                .ok()?;
+            let author = commit.author().to_string();
+            let subject = commit.summary().unwrap_or_default().to_string();
+            Some(CommitInfo {
+                sha: sha.clone(),
+                author,
+                timestamp: commit.time().seconds(),
+                subject,
+            })
+        })
+        .collect())
+}
+
+/// Files changed in the repository at `project_path`, sorted and deduplicated.
+/// Without a range they are the uncommitted changes (staged, unstaged and untracked).
+/// `A..B` compares the two commits, `A...B` compares B with the merge base,
+/// and a single rev is compared with its first parent.
+pub fn changed_paths(
+    project_path: &str,
+    range: Option<&str>,
+) -> Result<Vec<String>, GossiphsError> {
+    let repo = Repository::open(project_path).map_err(GossiphsError::open(project_path))?;
+    let mut opts = DiffOptions::new();
+    opts.include_unmodified(false)
+        .include_ignored(false)
+        .ignore_filemode(true);
+
+    let diff = match range {
+        None => {
+            opts.include_untracked(true).recurse_untracked_dirs(true);
+            let head_tree = resolve_commit(&repo, None)?.tree()?;
+            repo.diff_tree_to_workdir_with_index(Some(&head_tree), Some(&mut opts))?
+        }
+        Some(range) => {
+            let (old, new) = if let Some((old, new)) = range.split_once("...") {
+                let new = resolve_commit(&repo, Some(new))?;
+                let old = resolve_commit(&repo, Some(old))?;
+                let base = repo.merge_base(old.id(), new.id())?;
+                (Some(repo.find_commit(base)?), new)
+            } else if let Some((old, new)) = range.split_once("..") {
+                (
+                    Some(resolve_commit(&repo, Some(old))?),
+                    resolve_commit(&repo, Some(new))?,
+                )
+            } else {
+                let new = resolve_commit(&repo, Some(range))?;
+                (new.parent(0).ok(), new)
+            };
+            let old_tree = old.map(|commit| commit.tree()).transpose()?;
+            repo.diff_tree_to_tree(old_tree.as_ref(), Some(&new.tree()?), Some(&mut opts))?
+        }
+    };
+
+    let mut files: Vec<String> = diff
+        .deltas()
+        .flat_map(|delta| [delta.old_file().path(), delta.new_file().path()])
+        .flatten()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// Files changed by this commit, compared with its first parent.
+/// The old path is also returned if the file is renamed.
+fn changed_files(
+    repo: &Repository,
+    commit: &Commit,
+    follow_renames: bool,
+) -> Result<Vec<(String, Option<String>)>, GossiphsError> {
+    let parent = match commit.parent(0) {
+        Ok(parent) => parent,
+        // the root commit
+        Err(_) => return Ok(Vec::new()),
+    };
+    // missing objects of partial or shallow clones
+    let parent_tree = parent.tree()?;
+    let current_tree = commit.tree()?;
+
+    let mut opts = DiffOptions::new();
+    opts.minimal(true)
+        .include_unmodified(false)
+        .include_ignored(false)
+        .ignore_filemode(true)
+        .force_text(true);
+    let mut diff =
+        repo.diff_tree_to_tree(Some(&parent_tree), Some(&current_tree), Some(&mut opts))?;
+    if follow_renames {
+        let mut find_opts = DiffFindOptions::new();
+        find_opts.renames(true);
+        if let Err(err) = diff.find_similar(Some(&mut find_opts)) {
+            warn!("Failed to detect renames in {}: {:?}", commit.id(), err);
+        }
+    }
+
+    let to_string = |path: &Path| path.to_string_lossy().into_owned();
+    Ok(diff
+        .deltas()
+        .filter_map(|delta| {
+            let path = delta.new_file().path().map(to_string)?;
+            let old_path = match delta.status() {
+                Delta::Renamed => delta.old_file().path().map(to_string),
+                _ => None,
+            };
+            Some((path, old_path))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::GraphConfig;
+    use crate::history::{changed_paths, walk, BOT_AUTHOR_REGEX};
+    use git2::{Repository, Signature, Time};
+    use std::path::Path;
+
+    #[test]
+    fn walk_time_window() {
+        let config = GraphConfig::default();
+        assert!(walk(&config).unwrap().commit_size() > 0);
+
+        let mut config = GraphConfig::default();
+        config.until = Some(0);
+        assert_eq!(walk(&config).unwrap().commit_size(), 0);
+
+        let mut config = GraphConfig::default();
+        config.since = Some(i64::MAX);
+        assert_eq!(walk(&config).unwrap().commit_size(), 0);
+    }
+
+    #[test]
+    fn changed_paths_of_range() {
+        assert!(!changed_paths(".", Some("HEAD")).unwrap().is_empty());
+        assert!(changed_paths(".", Some("HEAD..HEAD")).unwrap().is_empty());
+        assert!(!changed_paths(".", Some("HEAD~1...HEAD"))
+            .unwrap()
+            .is_empty());
+        assert!(changed_paths(".", Some("not-a-rev")).is_err());
+    }
+
+    #[test]
+    fn bot_authors() {
+        for author in [
+            "dependabot[bot] <49699333+dependabot[bot]@users.noreply.github.com>",
+            "Renovate Bot <bot@renovateapp.com>",
+            "github-actions <41898282+github-actions[bot]@users.noreply.github.com>",
+        ] {
+            assert!(BOT_AUTHOR_REGEX.is_match(author));
+        }
+        assert!(!BOT_AUTHOR_REGEX.is_match("williamfzc <williamfzc@foxmail.com>"));
+    }
+
+    #[test]
+    fn walk_renames() {
+        let repo_path =
+            std::env::temp_dir().join(format!("gossiphs-renames-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&repo_path);
+        let repo = Repository::init(&repo_path).unwrap();
+        let signature = Signature::now("gossiphs", "gossiphs@example.com").unwrap();
+        let content = "fn main() {\n    println!(\"hello\");\n}\n".repeat(8);
+        let commit = |files: &[(&str, &str)], message: &str| {
+            let mut index = repo.index().unwrap();
+            index.clear().unwrap();
+            for (name, content) in files {
+                std::fs::write(repo_path.join(name), content).unwrap();
+                index.add_path(Path::new(name)).unwrap();
+            }
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let parents: Vec<_> = repo
+                .head()
+                .ok()
+                .map(|head| head.peel_to_commit().unwrap())
+                .into_iter()
+                .collect();
+            let parents: Vec<_> = parents.iter().collect();
+            repo.commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                message,
+                &tree,
+                &parents,
+            )
+            .unwrap();
+        };
+        commit(&[("README.md", "init")], "init");
+        commit(&[("README.md", "init"), ("a.rs", &content)], "add a");
+        commit(
+            &[("README.md", "init"), ("b.rs", &content)],
+            "rename a to b",
+        );
+        commit(
+            &[("README.md", "init"), ("b.rs", &(content.clone() + "\n"))],
+            "update b",
+        );
+
+        let mut config = GraphConfig::default();
+        config.project_path = repo_path.to_string_lossy().into_owned();
+        let graph = walk(&config).unwrap();
+        assert_eq!(graph.files(), vec![String::from("b.rs")]);
+        assert_eq!(
+            graph
+                .file_related_commits(&String::from("b.rs"))
+                .unwrap()
+                .len(),
+            3
+        );
+
+        config.follow_renames = false;
+        let graph = walk(&config).unwrap();
+        assert_eq!(
+            graph
+                .file_related_commits(&String::from("b.rs"))
+                .unwrap()
+                .len(),
+            2
+        );
+
+        std::fs::remove_dir_all(&repo_path).unwrap();
+    }
+
+    #[test]
+    fn walk_missing_objects() {
+        let repo_path =
+            std::env::temp_dir().join(format!("gossiphs-missing-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&repo_path);
+        let repo = Repository::init(&repo_path).unwrap();
+        let signature = Signature::now("gossiphs", "gossiphs@example.com").unwrap();
+        let mut parent = None;
+        let mut trees = Vec::new();
+        for content in ["a", "b"] {
+            std::fs::write(repo_path.join("a.rs"), content).unwrap();
+            let mut index = repo.index().unwrap();
+            index.add_path(Path::new("a.rs")).unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let parents: Vec<_> = parent.iter().collect();
+            let oid = repo
+                .commit(
+                    Some("HEAD"),
+                    &signature,
+                    &signature,
+                    content,
+                    &tree,
+                    &parents,
+                )
+                .unwrap();
+            trees.push(tree.id().to_string());
+            parent = Some(repo.find_commit(oid).unwrap());
+        }
+        // the tree of the first commit is gone, like in a partial clone
+        let (dir, file) = trees[0].split_at(2);
+        std::fs::remove_file(repo_path.join(".git/objects").join(dir).join(file)).unwrap();
+
+        let mut config = GraphConfig::default();
+        config.project_path = repo_path.to_string_lossy().into_owned();
+        assert!(walk(&config).is_err());
+        std::fs::remove_dir_all(&repo_path).unwrap();
+    }
+
+    #[test]
+    fn walk_since_out_of_order() {
+        let repo_path = std::env::temp_dir().join(format!("gossiphs-since-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&repo_path);
+        let repo = Repository::init(&repo_path).unwrap();
+        let mut parent = None;
+        // the third commit is cherry-picked with its old committer time
+        for (content, time) in [("a", 2000), ("b", 2000), ("c", 1000), ("d", 3000)] {
+            let signature =
+                Signature::new("gossiphs", "gossiphs@example.com", &Time::new(time, 0)).unwrap();
+            std::fs::write(repo_path.join("a.rs"), content).unwrap();
+            let mut index = repo.index().unwrap();
+            index.add_path(Path::new("a.rs")).unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let parents: Vec<_> = parent.iter().collect();
+            let oid = repo
+                .commit(
+                    Some("HEAD"),
+                    &signature,
+                    &signature,
+                    content,
+                    &tree,
+                    &parents,
+                )
+                .unwrap();
+            parent = Some(repo.find_commit(oid).unwrap());
+        }
+
+        let mut config = GraphConfig::default();
+        config.project_path = repo_path.to_string_lossy().into_owned();
+        config.since = Some(1500);
+        // the root commit changes nothing, "c" is out of the window
+        assert_eq!(walk(&config).unwrap().commit_size(), 2);
+        std::fs::remove_dir_all(&repo_path).unwrap();
+    }
+}
@@ -1,30 +1,81 @@
 pub mod api;
+pub mod cache;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod daemon;
+pub mod doctor;
+pub mod error;
+pub mod export;
 pub(crate) mod extractor;
+mod generated;
 pub mod graph;
+#[cfg(feature = "graphql")]
+mod graphql;
+pub mod history;
+pub mod lsp;
+pub mod mcp;
+mod merge;
+pub mod obsidian;
+mod openapi;
+pub mod remote;
 mod rule;
+mod snapshot;
 pub mod server;
 pub mod symbol;
+mod watch;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 // py wrapper
-use crate::graph::{Graph, GraphConfig, RelatedSymbol};
+use crate::graph::{Graph, GraphConfig, RelatedSymbol, ScoringConfig};
 use pyo3::prelude::*;
 
 mod pyapi;
 
-use crate::symbol::{DefRefPair, Symbol};
+use crate::symbol::{DefRefPair, Symbol, SymbolType};
 use pyo3_stub_gen::define_stub_info_gatherer;
-use crate::api::{FileMetadata, RelatedFileContext};
+use crate::api::{
+    AmbiguousSymbol, BusFactorRisk, CommitInfo, Community, Cycle, CycleScope, FileMetadata,
+    FileOwnership, FileRank, FunctionEdge, GraphStats, Hotspot, ImpactContext, ImpactedFile,
+    PackageRelation, RelatedDirectoryContext, RelatedFileContext, RelationDirection,
+    RelationExplanation, RelationPath, Reviewer, SharedCommit, SimilarFile, SymbolContribution,
+};
 
 #[pymodule]
 fn _rust_api(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(pyapi::create_graph, m)?)?;
+    m.add("GossiphsError", m.py().get_type_bound::<pyapi::GossiphsError>())?;
     m.add_class::<GraphConfig>()?;
+    m.add_class::<ScoringConfig>()?;
     m.add_class::<Graph>()?;
     m.add_class::<RelatedSymbol>()?;
     m.add_class::<DefRefPair>()?;
     m.add_class::<RelatedFileContext>()?;
+    m.add_class::<RelatedDirectoryContext>()?;
+    m.add_class::<PackageRelation>()?;
+    m.add_class::<GraphStats>()?;
+    m.add_class::<AmbiguousSymbol>()?;
+    m.add_class::<SimilarFile>()?;
+    m.add_class::<FileRank>()?;
+    m.add_class::<Hotspot>()?;
+    m.add_class::<Community>()?;
+    m.add_class::<Cycle>()?;
+    m.add_class::<CycleScope>()?;
+    m.add_class::<RelationDirection>()?;
+    m.add_class::<ImpactContext>()?;
+    m.add_class::<ImpactedFile>()?;
+    m.add_class::<RelationPath>()?;
+    m.add_class::<RelationExplanation>()?;
+    m.add_class::<SymbolContribution>()?;
+    m.add_class::<SharedCommit>()?;
+    m.add_class::<Reviewer>()?;
     m.add_class::<FileMetadata>()?;
+    m.add_class::<CommitInfo>()?;
+    m.add_class::<FileOwnership>()?;
+    m.add_class::<BusFactorRisk>()?;
+    m.add_class::<FunctionEdge>()?;
     m.add_class::<Symbol>()?;
+    m.add_class::<SymbolType>()?;
     Ok(())
 }
 
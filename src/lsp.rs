@@ -0,0 +1,301 @@
+use crate::graph::Graph;
+use crate::server::VERSION;
+use crate::symbol::{Point, Symbol, SymbolKind};
+use serde_json::{json, Value};
+use std::io::{BufRead, Error, ErrorKind, Read, Result, Write};
+use std::path::Path;
+
+// json-rpc error codes
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// One message framed by the `Content-Length` header, none at the end of the input.
+fn read_message<R: BufRead>(reader: &mut R) -> Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let length =
+        length.ok_or_else(|| Error::new(ErrorKind::InvalidData, "missing Content-Length"))?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+fn write_message<W: Write>(writer: &mut W, message: &Value) -> Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut ret = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| text.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                ret.push(byte);
+                i += 3;
+            }
+            None => {
+                ret.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&ret).into_owned()
+}
+
+/// The path of a `file://` uri relative to the repo, like the paths in the graph.
+fn uri_to_file(root: &Path, uri: &str) -> Option<String> {
+    let path = percent_decode(uri.strip_prefix("file://")?);
+    // `file:///C:/repo` on windows
+    let path = match path.get(2..3) {
+        Some(":") => path.trim_start_matches('/').to_string(),
+        _ => path,
+    };
+    let relative = Path::new(&path).strip_prefix(root).ok()?;
+    Some(relative.to_string_lossy().replace('\\', "/"))
+}
+
+fn file_to_uri(root: &Path, file: &str) -> String {
+    let path = root.join(file).to_string_lossy().replace('\\', "/");
+    match path.starts_with('/') {
+        true => format!("file://{}", path),
+        false => format!("file:///{}", path),
+    }
+}
+
+fn location(root: &Path, symbol: &Symbol) -> Value {
+    let point = |point: &Point| json!({ "line": point.row, "character": point.column });
+    json!({
+        "uri": file_to_uri(root, &symbol.file),
+        "range": {
+            "start": point(&symbol.range.start_point),
+            "end": point(&symbol.range.end_point),
+        },
+    })
+}
+
+// columns are bytes in tree-sitter and utf-16 units in lsp, the same for ascii names
+fn symbol_at(g: &Graph, root: &Path, params: &Value) -> Option<Symbol> {
+    let file = uri_to_file(root, params["textDocument"]["uri"].as_str()?)?;
+    let position = Point {
+        row: params["position"]["line"].as_u64()? as usize,
+        column: params["position"]["character"].as_u64()? as usize,
+    };
+    g.file_metadata(file).symbols.into_iter().find(|symbol| {
+        symbol.kind != SymbolKind::NAMESPACE
+            && symbol.range.start_point <= position
+            && position < symbol.range.end_point
+    })
+}
+
+// the definitions a symbol resolves to, itself for a definition
+fn definitions_of(g: &Graph, symbol: Symbol) -> Vec<Symbol> {
+    match symbol.kind {
+        SymbolKind::DEF => vec![symbol],
+        _ => g
+            .symbol_graph
+            .list_definitions_by_reference(&symbol.id())
+            .into_keys()
+            .collect(),
+    }
+}
+
+fn handle_request(g: &Graph, root: &Path, method: &str, params: &Value) -> Option<Value> {
+    Some(match method {
+        "initialize" => json!({
+            "capabilities": {
+                "referencesProvider": true,
+                "definitionProvider": true,
+            },
+            "serverInfo": { "name": "gossiphs", "version": VERSION },
+        }),
+        "shutdown" => Value::Null,
+        "textDocument/references" => {
+            let Some(symbol) = symbol_at(g, root, params) else {
+                return Some(Value::Array(Vec::new()));
+            };
+            let include_declaration = params["context"]["includeDeclaration"]
+                .as_bool()
+                .unwrap_or(false);
+            let mut locations = Vec::new();
+            for definition in definitions_of(g, symbol) {
+                let mut references: Vec<Symbol> = g
+                    .symbol_graph
+                    .list_references_by_definition(&definition.id())
+                    .into_keys()
+                    .collect();
+                references.sort_by(|a, b| (&a.file, a.range).cmp(&(&b.file, b.range)));
+                if include_declaration {
+                    locations.push(location(root, &definition));
+                }
+                locations.extend(references.iter().map(|each| location(root, each)));
+            }
+            Value::from(locations)
+        }
+        "textDocument/definition" => {
+            let locations: Vec<Value> = symbol_at(g, root, params)
+                .filter(|symbol| symbol.kind == SymbolKind::REF)
+                .map(|symbol| definitions_of(g, symbol))
+                .unwrap_or_default()
+                .iter()
+                .map(|each| location(root, each))
+                .collect();
+            Value::from(locations)
+        }
+        // files likely affected by editing this one, the strongest first
+        "gossiphs/relatedFiles" => {
+            let file = uri_to_file(root, params["textDocument"]["uri"].as_str()?)?;
+            let related: Vec<Value> = g
+                .related_files(file)
+                .into_iter()
+                .map(|each| {
+                    json!({
+                        "uri": file_to_uri(root, &each.name),
+                        "name": each.name,
+                        "score": each.score,
+                    })
+                })
+                .collect();
+            Value::from(related)
+        }
+        _ => return None,
+    })
+}
+
+/// Answer the Language Server Protocol requests until the `exit` notification or the end of the input.
+/// `root` is the absolute path of the repo, the uris of the documents are resolved against it.
+pub fn serve<R: BufRead, W: Write>(
+    g: &Graph,
+    root: &Path,
+    mut reader: R,
+    mut writer: W,
+) -> Result<()> {
+    while let Some(message) = read_message(&mut reader)? {
+        let method = message["method"].as_str().unwrap_or_default();
+        if method == "exit" {
+            break;
+        }
+        // notifications (didOpen, didChange ...) are ignored, the graph is the one of the commit
+        let Some(id) = message.get("id").cloned() else {
+            continue;
+        };
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        let response = match handle_request(g, root, method, &params) {
+            Some(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            None => {
+                let (code, message) = match method {
+                    "gossiphs/relatedFiles" => (INVALID_PARAMS, "invalid document uri".to_string()),
+                    _ => (METHOD_NOT_FOUND, format!("unknown method {}", method)),
+                };
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": { "code": code, "message": message },
+                })
+            }
+        };
+        write_message(&mut writer, &response)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::{Graph, GraphConfig};
+    use crate::lsp::{file_to_uri, percent_decode, read_message, serve, uri_to_file};
+    use serde_json::{json, Value};
+    use std::path::Path;
+
+    fn frame(message: Value) -> String {
+        let body = message.to_string();
+        format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+    }
+
+    #[test]
+    fn uri() {
+        let root = Path::new("/home/me/my repo");
+        assert_eq!(percent_decode("my%20repo%2"), "my repo%2");
+        assert_eq!(
+            uri_to_file(root, "file:///home/me/my%20repo/src/graph.rs").unwrap(),
+            "src/graph.rs"
+        );
+        assert!(uri_to_file(root, "file:///tmp/graph.rs").is_none());
+        assert_eq!(
+            file_to_uri(root, "src/graph.rs"),
+            "file:///home/me/my repo/src/graph.rs"
+        );
+    }
+
+    #[test]
+    fn lsp_session() {
+        let g = Graph::from(GraphConfig::default());
+        let root = Path::new(".").canonicalize().unwrap();
+        let uri = file_to_uri(&root, "src/symbol.rs");
+        // `pub struct SymbolGraph`
+        let def = g
+            .file_metadata(String::from("src/symbol.rs"))
+            .symbols
+            .into_iter()
+            .find(|each| &*each.name == "SymbolGraph")
+            .unwrap();
+        let position = json!({
+            "line": def.range.start_point.row,
+            "character": def.range.start_point.column,
+        });
+
+        let input = [
+            frame(json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}})),
+            frame(json!({"jsonrpc": "2.0", "method": "initialized", "params": {}})),
+            frame(json!({
+                "jsonrpc": "2.0", "id": 2, "method": "textDocument/references",
+                "params": {"textDocument": {"uri": uri}, "position": position},
+            })),
+            frame(json!({
+                "jsonrpc": "2.0", "id": 3, "method": "gossiphs/relatedFiles",
+                "params": {"textDocument": {"uri": uri}},
+            })),
+            frame(json!({"jsonrpc": "2.0", "id": 4, "method": "textDocument/hover", "params": {}})),
+            frame(json!({"jsonrpc": "2.0", "method": "exit"})),
+        ]
+        .concat();
+        let mut output = Vec::new();
+        serve(&g, &root, input.as_bytes(), &mut output).unwrap();
+
+        let mut reader = output.as_slice();
+        let mut responses = Vec::new();
+        while let Some(message) = read_message(&mut reader).unwrap() {
+            responses.push(message);
+        }
+        assert_eq!(responses.len(), 4);
+        assert_eq!(
+            responses[0]["result"]["capabilities"]["referencesProvider"],
+            true
+        );
+        let references = responses[1]["result"].as_array().unwrap();
+        assert!(references
+            .iter()
+            .any(|each| each["uri"] == file_to_uri(&root, "src/graph.rs")));
+        assert!(!responses[2]["result"].as_array().unwrap().is_empty());
+        assert_eq!(responses[3]["error"]["code"], -32601);
+    }
+}
@@ -1,22 +1,44 @@
 use clap::Parser;
 use csv::Writer;
-use git2::build::CheckoutBuilder;
-use git2::{Commit, DiffOptions, Error, Object, ObjectType, Repository, Status};
-use gossiphs::api::RelatedFileContext;
-use gossiphs::graph::{Graph, GraphConfig};
-use gossiphs::server::{server_main, ServerConfig};
+use git2::{Commit, DiffOptions, Error, Repository};
+use globset::GlobBuilder;
+use gossiphs::api::{
+    in_dir, limit_related_files, CycleScope, ImpactedFile, RelatedDirectoryContext,
+    RelatedFileContext, RelationDirection,
+};
+use gossiphs::cache::CacheManager;
+use gossiphs::daemon::{daemon_main, default_address, query, DaemonConfig, Request, Response};
+use gossiphs::doctor::diagnose;
+use gossiphs::error::GossiphsError;
+use gossiphs::export::{
+    to_codeowners, to_d3, to_dot, to_edge_csv, to_gexf, to_graphml, to_html, to_jsonl, to_parquet,
+    Mermaid, Table,
+};
+use gossiphs::graph::{FileSource, Graph, GraphConfig};
+use gossiphs::history::changed_paths;
+use gossiphs::lsp;
+use gossiphs::mcp;
+use gossiphs::obsidian::{module_canvas, sync_vault};
+use gossiphs::server::{query_server, server_main, ServerConfig};
+use gossiphs::symbol::{Symbol, SymbolKind};
 use indicatif::ProgressBar;
 use inquire::Text;
 use rayon::iter::ParallelIterator;
 use rayon::prelude::IntoParallelRefIterator;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
 use std::fs;
 use std::fs::File;
-use std::io::{BufWriter, Write};
+use std::io::{self, BufWriter};
 use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
 use termtree::Tree;
-use tracing::{debug, info};
+use tracing::info;
+
+#[cfg(test)]
+mod cli_tests;
 
 #[derive(Parser, Debug)]
 #[clap(
@@ -50,17 +72,110 @@ enum SubCommand {
     #[clap(name = "obsidian")]
     Obsidian(ObsidianCommand),
 
-    /// Diff analysis (will do some real checkout)
+    /// Diff analysis between two revs
     #[clap(name = "diff")]
     Diff(DiffCommand),
+
+    /// Check the repo and the config, explain why the output can be empty
+    #[clap(name = "doctor")]
+    Doctor(DoctorCommand),
+
+    /// Build the graph and save it to a binary index for `--load-index`
+    #[clap(name = "index")]
+    Index(IndexCommand),
+
+    /// Manage the extraction cache enabled by `--cache`
+    #[clap(name = "cache")]
+    Cache(CacheCommand),
+
+    /// Keep the graph in a background process for `query`
+    #[clap(name = "daemon")]
+    Daemon(DaemonCommand),
+
+    /// Query the graph held by `daemon`
+    #[clap(name = "query")]
+    Query(QueryCommand),
+
+    /// Serve the graph to AI assistants as Model Context Protocol tools over stdio
+    #[clap(name = "mcp")]
+    Mcp(StdioCommand),
+
+    /// Serve the references and the related files to editors as a language server over stdio
+    #[clap(name = "lsp")]
+    Lsp(StdioCommand),
+
+    /// Export the file relations as dot, graphml, gexf, d3, jsonl or parquet
+    #[clap(name = "export")]
+    Export(ExportCommand),
+
+    /// Draw the file relations in a self-contained html page
+    #[clap(name = "viz")]
+    Viz(VizCommand),
+
+    /// Rank the files by pagerank, the load-bearing files first
+    #[clap(name = "rank")]
+    Rank(RankCommand),
+
+    /// Group the closely related files, suggesting the modules
+    #[clap(name = "clusters")]
+    Clusters(ClustersCommand),
+
+    /// Find the files or directories depending on each other
+    #[clap(name = "cycles")]
+    Cycles(CyclesCommand),
+
+    /// Files related to a set of changed files, for reviewing a change in CI
+    #[clap(name = "impact")]
+    Impact(ImpactCommand),
+
+    /// Test files related to a set of changed files, to run only the affected tests
+    #[clap(name = "tests")]
+    Tests(ImpactCommand),
+
+    /// Suggest the reviewers of a change, the authors of the most coupled files
+    #[clap(name = "reviewers")]
+    Reviewers(ReviewersCommand),
+
+    /// Draft a CODEOWNERS from the dominant authors, `--since` keeps the recent ones only
+    #[clap(name = "codeowners")]
+    Codeowners(CodeownersCommand),
+
+    /// Rank the files by churn and coupling, the riskiest first
+    #[clap(name = "hotspots")]
+    Hotspots(RankCommand),
+
+    /// List the files no other file relates to
+    #[clap(name = "orphans")]
+    Orphans(RankCommand),
+
+    /// List the defs referenced nowhere
+    #[clap(name = "deadsymbols")]
+    DeadSymbols(RankCommand),
+
+    /// Find the files whose coupled files are mostly committed by a single author
+    #[clap(name = "busfactor")]
+    BusFactor(BusFactorCommand),
+
+    /// Build the graphs of several repos and merge them into one index for `--load-index`
+    #[clap(name = "multi")]
+    Multi(MultiCommand),
+
+    /// Counts of the files, symbols and relations, to sanity check a graph or an index
+    #[clap(name = "stats")]
+    Stats(StatsCommand),
 }
 
 #[derive(Parser, Debug)]
 struct CommonOptions {
+    /// a local repo, or the url of a remote one like `https://github.com/org/repo.git`
     #[clap(short, long)]
     #[clap(default_value = ".")]
     project_path: String,
 
+    /// commits fetched when `--project-path` is a url, 0 for the full history
+    #[clap(long)]
+    clone_depth: Option<u32>,
+
     /// precise-first analysis
     #[clap(long)]
     #[clap(default_value = "false")]
@@ -73,6 +188,15 @@ struct CommonOptions {
     #[clap(long)]
     depth: Option<u32>,
 
+    /// analyze only the files matching it, e.g. `^services/payments/`
+    #[clap(long)]
+    include_file_regex: Option<String>,
+
+    /// analyze only the files of this package, its root directory like `crates/core`,
+    /// see `Graph::packages`. Replaces `--include-file-regex`
+    #[clap(long)]
+    package: Option<String>,
+
     #[clap(long)]
     exclude_file_regex: Option<String>,
 
@@ -81,19 +205,196 @@ struct CommonOptions {
 
     #[clap(long)]
     symbol_len_limit: Option<usize>,
+
+    /// symbol names separated by `;` to ignore, e.g. `new;init;run`
+    #[clap(long)]
+    symbol_stopwords: Option<String>,
+
+    /// keep only the symbols whose names match it
+    #[clap(long)]
+    include_symbol_regex: Option<String>,
+
+    /// ignore the symbols whose names match it, e.g. generated ones
+    #[clap(long)]
+    exclude_symbol_regex: Option<String>,
+
+    /// keep vendored dependencies, lockfiles, minified and generated code in the graph
+    #[clap(long)]
+    #[clap(default_value = "false")]
+    keep_generated: bool,
+
+    /// replace the invalid UTF-8 bytes of the files (e.g. latin-1 comments) instead of leaving the files out
+    #[clap(long)]
+    #[clap(default_value = "false")]
+    lossy_decode: bool,
+
+    /// keep the files over the symbol limit with a part of their symbols instead of leaving them out
+    #[clap(long)]
+    #[clap(default_value = "false")]
+    truncate_large_files: bool,
+
+    /// analyze the files on disk (uncommitted and untracked ones included) instead of HEAD
+    #[clap(long)]
+    #[clap(default_value = "false")]
+    working_tree: bool,
+
+    /// fetch the full history first if the repo is a shallow clone
+    #[clap(long)]
+    #[clap(default_value = "false")]
+    auto_unshallow: bool,
+
+    /// only collect commits after this time: `2024-01-31`, `6 months` (ago) or a unix timestamp
+    #[clap(long, value_parser = parse_time)]
+    since: Option<i64>,
+
+    /// only collect commits before this time, same format as `--since`
+    #[clap(long, value_parser = parse_time)]
+    until: Option<i64>,
+
+    #[clap(long)]
+    #[clap(default_value = "false")]
+    exclude_merge_commits: bool,
+
+    /// skip the commits of dependabot, renovate, github-actions and so on
+    #[clap(long)]
+    #[clap(default_value = "false")]
+    exclude_bot_commits: bool,
+
+    /// reuse the extraction results of unchanged files, stored in `.gossiphs/cache`
+    #[clap(long)]
+    #[clap(default_value = "false")]
+    cache: bool,
+
+    /// score of each commit changing two files together, and keep the files without symbols
+    #[clap(long)]
+    co_change_weight: Option<usize>,
+
+    /// multiplier of the weights from the commits changing both files, see `ScoringConfig`
+    #[clap(long)]
+    commit_weight: Option<f64>,
+
+    /// the least score of each shared symbol
+    #[clap(long)]
+    symbol_weight: Option<f64>,
+
+    /// multiplier of the scores of the files in the same directory
+    #[clap(long)]
+    same_dir_boost: Option<f64>,
+
+    /// a commit counts half as much every N days before the latest one
+    #[clap(long)]
+    half_life_days: Option<f64>,
+
+    /// the symbols named in many files count less
+    #[clap(long)]
+    #[clap(default_value = "false")]
+    idf: bool,
 }
 
 impl CommonOptions {
+    fn graph_config(&self) -> GraphConfig {
+        let mut config = GraphConfig::default();
+        config.project_path = self.project_path.clone();
+        if self.working_tree {
+            config.source = FileSource::WorkingTree;
+        }
+        config.detect_generated = !self.keep_generated;
+        config.truncate_large_files = self.truncate_large_files;
+        config.lossy_decode = self.lossy_decode;
+        config.auto_unshallow = self.auto_unshallow;
+        config.since = self.since;
+        config.until = self.until;
+        config.exclude_merge_commits = self.exclude_merge_commits;
+        config.exclude_bot_commits = self.exclude_bot_commits;
+        config.enable_cache = self.cache;
+        if let Some(co_change_weight) = self.co_change_weight {
+            config.co_change_weight = co_change_weight;
+        }
+        if let Some(commit_weight) = self.commit_weight {
+            config.scoring.commit_weight = commit_weight;
+        }
+        if let Some(symbol_weight) = self.symbol_weight {
+            config.scoring.symbol_weight = symbol_weight;
+        }
+        if let Some(same_dir_boost) = self.same_dir_boost {
+            config.scoring.same_directory_boost = same_dir_boost;
+        }
+        if let Some(half_life_days) = self.half_life_days {
+            config.scoring.half_life_days = half_life_days;
+        }
+        config.scoring.idf_damping = self.idf;
+        if self.strict {
+            config.def_limit = 1;
+        }
+        if let Some(def_limit) = self.def_limit {
+            config.def_limit = def_limit;
+        }
+        if let Some(depth) = self.depth {
+            config.depth = depth;
+        }
+        if let Some(clone_depth) = self.clone_depth {
+            config.clone_depth = clone_depth;
+        }
+        if let Some(include) = &self.include_file_regex {
+            config.include_file_regex = include.clone();
+        }
+        if let Some(package) = &self.package {
+            let package = package.trim_start_matches("./").trim_end_matches('/');
+            if !package.is_empty() && package != "." {
+                config.include_file_regex = format!("^{}/", regex::escape(package));
+            }
+        }
+        if let Some(exclude) = &self.exclude_file_regex {
+            config.exclude_file_regex = exclude.clone();
+        }
+        config.exclude_author_regex = self.exclude_author_regex.clone();
+        if let Some(symbol_len_limit) = self.symbol_len_limit {
+            config.symbol_len_limit = symbol_len_limit;
+        }
+        if let Some(stopwords) = &self.symbol_stopwords {
+            config.symbol_stopwords = stopwords
+                .split(';')
+                .filter(|each| !each.is_empty())
+                .map(String::from)
+                .collect();
+        }
+        config.include_symbol_regex = self.include_symbol_regex.clone();
+        config.exclude_symbol_regex = self.exclude_symbol_regex.clone();
+        config
+    }
+
     #[cfg(test)]
     fn default() -> CommonOptions {
         CommonOptions {
             project_path: String::from("."),
+            clone_depth: None,
             strict: false,
             def_limit: None,
             depth: None,
+            include_file_regex: None,
+            package: None,
             exclude_file_regex: None,
             exclude_author_regex: None,
             symbol_len_limit: None,
+            symbol_stopwords: None,
+            include_symbol_regex: None,
+            exclude_symbol_regex: None,
+            keep_generated: false,
+            truncate_large_files: false,
+            lossy_decode: false,
+            working_tree: false,
+            auto_unshallow: false,
+            since: None,
+            until: None,
+            exclude_merge_commits: false,
+            exclude_bot_commits: false,
+            cache: false,
+            co_change_weight: None,
+            commit_weight: None,
+            symbol_weight: None,
+            same_dir_boost: None,
+            half_life_days: None,
+            idf: false,
         }
     }
 }
@@ -103,6 +404,7 @@ struct RelateCommand {
     #[clap(flatten)]
     common_options: CommonOptions,
 
+    /// files separated by `;`, globs like `src/**/*.rs`, or `-` to read them from stdin
     #[clap(long)]
     #[clap(default_value = "")]
     file: String,
@@ -111,13 +413,50 @@ struct RelateCommand {
     #[clap(default_value = "")]
     file_txt: String,
 
+    /// directories separated by `;`, related at the directory level instead of files
+    #[clap(long)]
+    #[clap(default_value = "")]
+    dir: String,
+
     #[clap(long)]
     #[clap(default_value = None)]
     json: Option<String>,
 
+    /// printed format, `--json` still saves the json to the file
+    #[clap(long, value_enum)]
+    #[clap(default_value = "json")]
+    format: RelateFormat,
+
     #[clap(long)]
     #[clap(default_value = "true")]
     ignore_zero: bool,
+
+    /// keep only the first N related files of each file
+    #[clap(long)]
+    top: Option<usize>,
+
+    /// walk the relations transitively, up to N hops, for the blast radius of a change
+    #[clap(long)]
+    #[clap(default_value = "1")]
+    hops: usize,
+
+    /// the score is multiplied by this for each hop after the first
+    #[clap(long)]
+    #[clap(default_value = "0.5")]
+    decay: f64,
+
+    /// drop the related files scoring lower than this
+    #[clap(long)]
+    min_score: Option<usize>,
+
+    /// `in`: the files referencing it, `out`: the files it references, `both`
+    #[clap(long, value_enum)]
+    #[clap(default_value = "in")]
+    direction: RelateDirection,
+
+    /// read the graph from an index built by `gossiphs index`
+    #[clap(long)]
+    load_index: Option<String>,
 }
 
 #[derive(Parser, Debug)]
@@ -129,10 +468,21 @@ struct RelationCommand {
     #[clap(default_value = "output.csv")]
     csv: String,
 
+    /// a row per related file pair, or the file x file matrix
+    #[clap(long, value_enum)]
+    #[clap(default_value = "edges")]
+    format: RelationFormat,
+
+    /// symbols between files, in matrix format only
     #[clap(long)]
     #[clap(default_value = "")]
     symbol_csv: String,
 
+    /// write the nodes and relations to this json file instead of csv
+    #[clap(long)]
+    #[clap(default_value = None)]
+    json: Option<String>,
+
     #[clap(long)]
     #[clap(default_value = "output.index")]
     index_file: String,
@@ -153,9 +503,36 @@ struct ServerCommand {
     #[clap(flatten)]
     common_options: CommonOptions,
 
+    /// address to listen on, `0.0.0.0` to serve the other machines as well
+    #[clap(long)]
+    #[clap(default_value = "127.0.0.1")]
+    host: String,
+
     #[clap(long)]
     #[clap(default_value = "9411")]
     port: u16,
+
+    /// read the graph from an index built by `gossiphs index`
+    #[clap(long)]
+    load_index: Option<String>,
+
+    /// keep the graph in sync with file edits and new commits, implies `--working-tree` and `--cache`
+    #[clap(long)]
+    #[clap(default_value = "false")]
+    watch: bool,
+
+    /// more repos to serve, as `name=path`, queried with `?project=name`. Repeat it for each repo
+    #[clap(long = "project")]
+    projects: Vec<String>,
+
+    /// origins allowed to call the api from browsers, `*` for any. Repeat it for each origin
+    #[clap(long = "cors-origin")]
+    cors_origins: Vec<String>,
+
+    /// require `Authorization: Bearer <token>` on all the routes but `/health`.
+    /// read from `GOSSIPHS_TOKEN` if not set, which keeps it out of the process list
+    #[clap(long)]
+    token: Option<String>,
 }
 
 #[derive(Parser, Debug)]
@@ -165,6 +542,11 @@ struct ObsidianCommand {
 
     #[clap(long)]
     vault_dir: String,
+
+    /// also write `modules.canvas`, an obsidian canvas of the top level directories
+    #[clap(long)]
+    #[clap(default_value = "false")]
+    canvas: bool,
 }
 
 #[derive(Parser, Debug)]
@@ -180,37 +562,489 @@ struct DiffCommand {
     #[clap(default_value = "HEAD")]
     source: String,
 
-    /// use json format for output, else use tree
+    /// use json format for output, same as `--format json`
     #[clap(long)]
     #[clap(default_value = "false")]
     json: bool,
+
+    #[clap(long, value_enum)]
+    #[clap(default_value = "tree")]
+    format: DiffFormat,
+
+    /// reuse an index built by `gossiphs index` for the side it was built from
+    #[clap(long)]
+    load_index: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+struct DoctorCommand {
+    #[clap(flatten)]
+    common_options: CommonOptions,
+
+    /// use json format for output
+    #[clap(long)]
+    #[clap(default_value = "false")]
+    json: bool,
+}
+
+#[derive(Parser, Debug)]
+struct CacheCommand {
+    #[clap(short, long)]
+    #[clap(default_value = ".")]
+    project_path: String,
+
+    #[clap(subcommand)]
+    action: CacheAction,
+}
+
+#[derive(Parser, Debug)]
+enum CacheAction {
+    /// Remove all the cached entries
+    #[clap(name = "clear")]
+    Clear,
+
+    /// Show the number and the size of the cached entries
+    #[clap(name = "stats")]
+    Stats,
+}
+
+#[derive(Parser, Debug)]
+struct DaemonCommand {
+    #[clap(flatten)]
+    common_options: CommonOptions,
+
+    /// unix socket path (named pipe name on windows), `.gossiphs/daemon.sock` by default
+    #[clap(long)]
+    socket: Option<String>,
+
+    /// read the graph from an index built by `gossiphs index`
+    #[clap(long)]
+    load_index: Option<String>,
+}
+
+// options of the servers speaking over stdio, `mcp` and `lsp`
+#[derive(Parser, Debug)]
+struct StdioCommand {
+    #[clap(flatten)]
+    common_options: CommonOptions,
+
+    /// read the graph from an index built by `gossiphs index`
+    #[clap(long)]
+    load_index: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+struct QueryCommand {
+    #[clap(short, long)]
+    #[clap(default_value = ".")]
+    project_path: String,
+
+    /// same as `daemon --socket`
+    #[clap(long)]
+    socket: Option<String>,
+
+    /// url of a running `server` to ask instead of the daemon, like `http://host:9411`
+    #[clap(long)]
+    server: Option<String>,
+
+    /// `--token` of the server, read from `GOSSIPHS_TOKEN` if not set
+    #[clap(long)]
+    token: Option<String>,
+
+    /// `--project` of the server, the repo of its `--project-path` if not set
+    #[clap(long)]
+    project: Option<String>,
+
+    #[clap(subcommand)]
+    action: QueryAction,
+}
+
+#[derive(Parser, Debug)]
+enum QueryAction {
+    /// Related files of a file
+    #[clap(name = "related")]
+    Related { file: String },
+
+    /// Commits and symbols of a file
+    #[clap(name = "metadata")]
+    Metadata { file: String },
+
+    /// All the files in the graph
+    #[clap(name = "files")]
+    Files,
+
+    /// Stop the daemon
+    #[clap(name = "stop")]
+    Stop,
+}
+
+#[derive(Parser, Debug)]
+struct IndexCommand {
+    #[clap(flatten)]
+    common_options: CommonOptions,
+
+    #[clap(long)]
+    #[clap(default_value = ".gossiphs/index.bin")]
+    output: String,
+}
+
+#[derive(Parser, Debug)]
+struct ExportCommand {
+    #[clap(flatten)]
+    common_options: CommonOptions,
+
+    #[clap(long, value_enum)]
+    #[clap(default_value = "dot")]
+    format: ExportFormat,
+
+    /// print to stdout if not set, required by parquet as the output dir
+    #[clap(long)]
+    output: Option<String>,
+
+    /// label the edges with the referenced symbols
+    #[clap(long)]
+    #[clap(default_value = "false")]
+    symbols: bool,
+}
+
+#[derive(Parser, Debug)]
+struct VizCommand {
+    #[clap(flatten)]
+    common_options: CommonOptions,
+
+    #[clap(long)]
+    #[clap(default_value = "gossiphs.html")]
+    output: String,
+}
+
+#[derive(Parser, Debug)]
+struct RankCommand {
+    #[clap(flatten)]
+    common_options: CommonOptions,
+
+    #[clap(long, value_enum)]
+    #[clap(default_value = "json")]
+    format: RankFormat,
+
+    /// print to stdout if not set
+    #[clap(long)]
+    output: Option<String>,
+
+    /// keep only the first N files
+    #[clap(long)]
+    top: Option<usize>,
+}
+
+#[derive(Parser, Debug)]
+struct ClustersCommand {
+    #[clap(flatten)]
+    common_options: CommonOptions,
+
+    #[clap(long, value_enum)]
+    #[clap(default_value = "json")]
+    format: ClustersFormat,
+
+    /// print to stdout if not set
+    #[clap(long)]
+    output: Option<String>,
+
+    /// leave out the clusters with fewer files, like the files related to nothing
+    #[clap(long)]
+    #[clap(default_value = "2")]
+    min_size: usize,
+}
+
+#[derive(Parser, Debug)]
+struct CyclesCommand {
+    #[clap(flatten)]
+    common_options: CommonOptions,
+
+    #[clap(long, value_enum)]
+    #[clap(default_value = "file")]
+    scope: CyclesScope,
+
+    #[clap(long, value_enum)]
+    #[clap(default_value = "json")]
+    format: ClustersFormat,
+
+    /// print to stdout if not set
+    #[clap(long)]
+    output: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+struct ChangeOptions {
+    /// changed files split by `;`, or `-` to read them from stdin.
+    /// detected from git if not set
+    #[clap(long)]
+    files: Option<String>,
+
+    /// `A..B`, `A...B` or a single commit. the uncommitted changes if not set
+    #[clap(long)]
+    range: Option<String>,
+}
+
+impl ChangeOptions {
+    fn changed_files(&self, project_path: &str) -> Result<Vec<String>, CliError> {
+        match self.files.as_deref() {
+            Some("-") => Ok(non_empty_lines(&io::read_to_string(io::stdin()).map_err(
+                |err| CliError::new(EXIT_FAILURE, format!("failed to read stdin: {}", err)),
+            )?)),
+            Some(files) => Ok(files.split(';').map(|each| each.to_string()).collect()),
+            None => Ok(changed_paths(project_path, self.range.as_deref())?),
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+struct ImpactCommand {
+    #[clap(flatten)]
+    common_options: CommonOptions,
+
+    #[clap(flatten)]
+    change_options: ChangeOptions,
+
+    #[clap(long, value_enum)]
+    #[clap(default_value = "json")]
+    format: ImpactFormat,
+
+    /// print to stdout if not set
+    #[clap(long)]
+    output: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+struct ReviewersCommand {
+    #[clap(flatten)]
+    common_options: CommonOptions,
+
+    #[clap(flatten)]
+    change_options: ChangeOptions,
+
+    /// skip the authors containing any of these, split by `;`. e.g. the author of the change
+    #[clap(long)]
+    exclude: Option<String>,
+
+    #[clap(long)]
+    #[clap(default_value = "3")]
+    top: usize,
+
+    /// print to stdout if not set
+    #[clap(long)]
+    output: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+struct CodeownersCommand {
+    #[clap(flatten)]
+    common_options: CommonOptions,
+
+    /// authors of at least this share of the commits of a file own it
+    #[clap(long)]
+    #[clap(default_value = "0.5")]
+    min_share: f64,
+
+    /// print to stdout if not set
+    #[clap(long)]
+    output: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+struct BusFactorCommand {
+    #[clap(flatten)]
+    common_options: CommonOptions,
+
+    /// flag the files when a single author takes at least this share of the commits
+    #[clap(long)]
+    #[clap(default_value = "0.8")]
+    min_share: f64,
+
+    #[clap(long, value_enum)]
+    #[clap(default_value = "json")]
+    format: RankFormat,
+
+    /// print to stdout if not set
+    #[clap(long)]
+    output: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+struct StatsCommand {
+    #[clap(flatten)]
+    common_options: CommonOptions,
+
+    #[clap(long, value_enum)]
+    #[clap(default_value = "json")]
+    format: ClustersFormat,
+
+    /// read the graph from an index built by `gossiphs index`
+    #[clap(long)]
+    load_index: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+struct MultiCommand {
+    /// `--project-path` is ignored, each repo is analyzed with the other options
+    #[clap(flatten)]
+    common_options: CommonOptions,
+
+    /// repos separated by `,`, their files are prefixed by the directory names
+    #[clap(long)]
+    repos: String,
+
+    #[clap(long)]
+    #[clap(default_value = ".gossiphs/multi.bin")]
+    output: String,
+}
+
+#[derive(clap::ValueEnum, Clone, Debug)]
+enum ImpactFormat {
+    Json,
+    Md,
+    /// the file names only, one per line
+    Plain,
+}
+
+#[derive(clap::ValueEnum, Clone, Debug)]
+enum CyclesScope {
+    File,
+    Dir,
+}
+
+#[derive(clap::ValueEnum, Clone, Debug)]
+enum ClustersFormat {
+    Json,
+    /// a row per file, or per edge of the cycles
+    Table,
+}
+
+#[derive(clap::ValueEnum, Clone, Debug)]
+enum RankFormat {
+    Json,
+    Csv,
+}
+
+#[derive(clap::ValueEnum, Clone, Debug)]
+enum RelationFormat {
+    Edges,
+    Matrix,
+}
+
+#[derive(clap::ValueEnum, Clone, Debug)]
+enum RelateFormat {
+    Json,
+    Mermaid,
+    /// a markdown table, for PR comments
+    Md,
+    /// aligned columns, for terminals
+    Table,
+}
+
+#[derive(clap::ValueEnum, Clone, Debug)]
+enum RelateDirection {
+    In,
+    Out,
+    Both,
+}
+
+#[derive(clap::ValueEnum, Clone, Debug)]
+enum DiffFormat {
+    Tree,
+    Json,
+    Mermaid,
+}
+
+#[derive(clap::ValueEnum, Clone, Debug)]
+enum ExportFormat {
+    Dot,
+    Graphml,
+    Gexf,
+    /// json of nodes and links, for d3-force
+    D3,
+    /// json lines, streamed without holding all the relations
+    Jsonl,
+    /// a directory of files, edges and symbols datasets
+    Parquet,
 }
 
 impl RelateCommand {
-    pub fn get_files(&self) -> Vec<String> {
-        if !self.file_txt.is_empty() {
-            let file_contents = match fs::read_to_string(&self.file_txt) {
-                Ok(contents) => contents,
-                Err(err) => {
-                    eprintln!("Error reading file {}: {}", self.file_txt, err);
-                    return Vec::new();
-                }
-            };
-            return file_contents
-                .clone()
-                .lines()
-                .filter(|each| !each.trim().is_empty())
-                .map(|each| each.to_string())
-                .collect();
+    /// `--file -` reads the paths from stdin, and globs are expanded against `known_files`
+    pub fn get_files(&self, known_files: &HashSet<String>) -> Result<Vec<String>, CliError> {
+        let patterns: Vec<String> = if !self.file_txt.is_empty() {
+            let file_contents = fs::read_to_string(&self.file_txt).map_err(|err| {
+                CliError::new(
+                    EXIT_FAILURE,
+                    format!("failed to read {}: {}", self.file_txt, err),
+                )
+            })?;
+            non_empty_lines(&file_contents)
+        } else if self.file == "-" {
+            let stdin = io::read_to_string(io::stdin()).map_err(|err| {
+                CliError::new(EXIT_FAILURE, format!("failed to read stdin: {}", err))
+            })?;
+            non_empty_lines(&stdin)
+        } else {
+            self.file.split(';').map(|each| each.to_string()).collect()
+        };
+        expand_file_patterns(patterns, known_files)
+    }
+}
+
+fn non_empty_lines(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .map(|each| each.trim())
+        .filter(|each| !each.is_empty())
+        .map(|each| each.to_string())
+        .collect()
+}
+
+// a glob matching nothing is kept as is, to be reported as a missing file
+fn expand_file_patterns(
+    patterns: Vec<String>,
+    known_files: &HashSet<String>,
+) -> Result<Vec<String>, CliError> {
+    let mut sorted_files: Vec<&String> = known_files.iter().collect();
+    sorted_files.sort();
+
+    let mut files = Vec::new();
+    for pattern in patterns {
+        if !pattern.contains(['*', '?', '[', '{']) {
+            files.push(pattern);
+            continue;
+        }
+        // `*` stays in a dir, `**` crosses them
+        let matcher = GlobBuilder::new(&pattern)
+            .literal_separator(true)
+            .build()
+            .map_err(|err| {
+                CliError::new(EXIT_FAILURE, format!("invalid glob {}: {}", pattern, err))
+            })?
+            .compile_matcher();
+        let matched: Vec<String> = sorted_files
+            .iter()
+            .filter(|each| matcher.is_match(each.as_str()))
+            .map(|each| each.to_string())
+            .collect();
+        if matched.is_empty() {
+            files.push(pattern);
+        } else {
+            files.extend(matched);
         }
-        self.file.split(';').map(|each| each.to_string()).collect()
     }
+    Ok(files)
 }
 
+// exit codes, for the tools wrapping gossiphs to tell the failures apart
+const EXIT_FAILURE: i32 = 1;
+const EXIT_NOT_A_REPO: i32 = 2;
+const EXIT_FILE_NOT_IN_GRAPH: i32 = 3;
+const EXIT_BAD_REV: i32 = 4;
+
 fn main() {
     let cli: Cli = Cli::parse();
 
-    match cli.cmd {
+    let ret = match cli.cmd {
         SubCommand::Relate(search_cmd) => handle_relate(search_cmd),
         SubCommand::Relation(relation_cmd) => handle_relation(relation_cmd),
         SubCommand::Relation2(relation_cmd) => handle_relation_v2(relation_cmd),
@@ -218,135 +1052,468 @@ fn main() {
         SubCommand::Server(server_cmd) => handle_server(server_cmd),
         SubCommand::Obsidian(obsidian_cmd) => handle_obsidian(obsidian_cmd),
         SubCommand::Diff(diff_cmd) => handle_diff(diff_cmd),
+        SubCommand::Doctor(doctor_cmd) => handle_doctor(doctor_cmd),
+        SubCommand::Index(index_cmd) => handle_index(index_cmd),
+        SubCommand::Cache(cache_cmd) => handle_cache(cache_cmd),
+        SubCommand::Daemon(daemon_cmd) => handle_daemon(daemon_cmd),
+        SubCommand::Query(query_cmd) => handle_query(query_cmd),
+        SubCommand::Mcp(mcp_cmd) => handle_mcp(mcp_cmd),
+        SubCommand::Lsp(lsp_cmd) => handle_lsp(lsp_cmd),
+        SubCommand::Export(export_cmd) => handle_export(export_cmd),
+        SubCommand::Viz(viz_cmd) => handle_viz(viz_cmd),
+        SubCommand::Rank(rank_cmd) => handle_rank(rank_cmd),
+        SubCommand::Clusters(clusters_cmd) => handle_clusters(clusters_cmd),
+        SubCommand::Cycles(cycles_cmd) => handle_cycles(cycles_cmd),
+        SubCommand::Impact(impact_cmd) => handle_impact(impact_cmd, false),
+        SubCommand::Tests(tests_cmd) => handle_impact(tests_cmd, true),
+        SubCommand::Reviewers(reviewers_cmd) => handle_reviewers(reviewers_cmd),
+        SubCommand::Codeowners(codeowners_cmd) => handle_codeowners(codeowners_cmd),
+        SubCommand::Hotspots(hotspots_cmd) => handle_hotspots(hotspots_cmd),
+        SubCommand::Orphans(orphans_cmd) => handle_orphans(orphans_cmd),
+        SubCommand::DeadSymbols(dead_cmd) => handle_dead_symbols(dead_cmd),
+        SubCommand::BusFactor(busfactor_cmd) => handle_busfactor(busfactor_cmd),
+        SubCommand::Multi(multi_cmd) => handle_multi(multi_cmd),
+        SubCommand::Stats(stats_cmd) => handle_stats(stats_cmd),
+    };
+    if let Err(err) = ret {
+        eprintln!("error: {}", err.msg);
+        std::process::exit(err.code);
     }
 }
 
-fn handle_relate(relate_cmd: RelateCommand) {
-    // result will be saved to file, so enable log
-    if !relate_cmd.json.is_none() {
-        tracing_subscriber::fmt::init();
+/// Printed by `main`, which exits with `code`.
+#[derive(Debug)]
+struct CliError {
+    code: i32,
+    msg: String,
+}
+
+impl CliError {
+    fn new(code: i32, msg: impl Display) -> CliError {
+        CliError {
+            code,
+            msg: msg.to_string(),
+        }
+    }
+}
+
+impl From<GossiphsError> for CliError {
+    fn from(err: GossiphsError) -> Self {
+        let code = match err {
+            GossiphsError::Repository { .. } | GossiphsError::BareRepository(_) => EXIT_NOT_A_REPO,
+            GossiphsError::Rev { .. } => EXIT_BAD_REV,
+            _ => EXIT_FAILURE,
+        };
+        CliError::new(code, err)
+    }
+}
+
+fn build_graph(config: GraphConfig) -> Result<Graph, CliError> {
+    let g = Graph::try_from(config)?;
+    // still shallow after `--auto-unshallow`, logging may be off so always print it
+    if Repository::open(g.project_path()).is_ok_and(|repo| repo.is_shallow()) {
+        eprintln!(
+            "{} is a shallow clone, the commit history is incomplete and most scores will be 0. \
+            Fetch the history by `git fetch --unshallow` or `--auto-unshallow`",
+            g.project_path()
+        );
+    }
+    Ok(g)
+}
+
+fn load_graph(index: &str) -> Result<Graph, CliError> {
+    Graph::load(index).map_err(|err| {
+        CliError::new(
+            EXIT_FAILURE,
+            format!("failed to load index {}: {}", index, err),
+        )
+    })
+}
+
+fn handle_relate(relate_cmd: RelateCommand) -> Result<(), CliError> {
+    // result will be saved to file, so enable log
+    // may be set already when called more than once in a process
+    if relate_cmd.json.is_some() {
+        let _ = tracing_subscriber::fmt().try_init();
+    }
+    let config = relate_cmd.common_options.graph_config();
+
+    let g = match &relate_cmd.load_index {
+        Some(index) => load_graph(index)?,
+        None => build_graph(config)?,
+    };
+    if !matches!(relate_cmd.direction, RelateDirection::In)
+        && (!relate_cmd.dir.is_empty() || relate_cmd.hops > 1)
+    {
+        return Err(CliError::new(
+            EXIT_FAILURE,
+            "`--direction` works with the files of one hop only, not with `--dir` or `--hops`",
+        ));
+    }
+    if !relate_cmd.dir.is_empty() {
+        return handle_relate_dirs(&g, &relate_cmd);
+    }
+
+    let known_files = g.files();
+    let files = relate_cmd.get_files(&known_files)?;
+    let mut missing = false;
+    for file in &files {
+        if !known_files.contains(&g.normalize_path(file)) {
+            missing = true;
+            eprintln!(
+                "{} is not in the graph, run `gossiphs doctor` for the possible reasons",
+                file
+            );
+        }
     }
-    let mut config = GraphConfig::default();
-    config.project_path = relate_cmd.common_options.project_path.clone();
-    if relate_cmd.common_options.strict {
-        config.def_limit = 1
+    if relate_cmd.hops > 1 {
+        handle_relate_hops(&g, &relate_cmd, &files)?;
+    } else {
+        print_related_files(&g, &relate_cmd, &files)?;
     }
-    if !relate_cmd.common_options.depth.is_none() {
-        config.depth = relate_cmd.common_options.depth.unwrap();
+    // the output is still complete, the missing files simply have nothing related
+    if missing {
+        return Err(CliError::new(
+            EXIT_FILE_NOT_IN_GRAPH,
+            "some files are not in the graph",
+        ));
     }
+    Ok(())
+}
 
-    let g = Graph::from(config);
-
+fn print_related_files(
+    g: &Graph,
+    relate_cmd: &RelateCommand,
+    files: &[String],
+) -> Result<(), CliError> {
+    let direction = match relate_cmd.direction {
+        RelateDirection::In => RelationDirection::IN,
+        RelateDirection::Out => RelationDirection::OUT,
+        RelateDirection::Both => RelationDirection::BOTH,
+    };
     let mut related_files_data = Vec::new();
-    let files = relate_cmd.get_files();
-    for file in &files {
-        let mut files = g.related_files(String::from(file));
+    for file in files {
+        let mut files = g.related_files_towards(String::from(file), direction);
         if relate_cmd.ignore_zero {
             files.retain(|each| each.score > 0);
         }
+        limit_related_files(&mut files, relate_cmd.top, relate_cmd.min_score);
         related_files_data.push(RelatedFileWrapper {
             name: file.to_string(),
             related: files,
         });
     }
     let json = serde_json::to_string(&related_files_data).unwrap();
-    if !relate_cmd.json.is_none() {
-        fs::write(relate_cmd.json.unwrap(), json).expect("");
-    } else {
-        println!("{}", json);
+    save_json(&relate_cmd.json, &json)?;
+    match relate_cmd.format {
+        RelateFormat::Json => {
+            if relate_cmd.json.is_none() {
+                println!("{}", json);
+            }
+        }
+        RelateFormat::Mermaid => {
+            let mut mermaid = Mermaid::new();
+            for each in &related_files_data {
+                mermaid.node(&each.name);
+                let incoming: HashSet<String> = match relate_cmd.direction {
+                    RelateDirection::Both => g
+                        .related_files(each.name.clone())
+                        .into_iter()
+                        .map(|related| related.name)
+                        .collect(),
+                    _ => HashSet::new(),
+                };
+                for related in &each.related {
+                    // from the file referencing the defs of the other one
+                    let referencing = match relate_cmd.direction {
+                        RelateDirection::In => true,
+                        RelateDirection::Out => false,
+                        RelateDirection::Both => incoming.contains(&related.name),
+                    };
+                    let score = related.score.to_string();
+                    if referencing {
+                        mermaid.edge(&related.name, &each.name, &score, false);
+                    } else {
+                        mermaid.edge(&each.name, &related.name, &score, false);
+                    }
+                }
+            }
+            print!("{}", mermaid.render());
+        }
+        RelateFormat::Md => print!("{}", relate_table(&related_files_data).render_markdown()),
+        RelateFormat::Table => print!("{}", relate_table(&related_files_data).render_plain()),
     }
+    Ok(())
 }
 
-fn handle_relation_v2(relation_cmd: RelationCommand) {
-    let mut config = GraphConfig::default();
-    config.project_path = relation_cmd.common_options.project_path.clone();
-    if relation_cmd.common_options.strict {
-        config.def_limit = 1;
-    }
-    if relation_cmd.common_options.def_limit.is_some() {
-        config.def_limit = relation_cmd.common_options.def_limit.unwrap();
+// `relate --hops`, the files impacted transitively
+fn handle_relate_hops(
+    g: &Graph,
+    relate_cmd: &RelateCommand,
+    files: &[String],
+) -> Result<(), CliError> {
+    let mut impacted_files_data = Vec::new();
+    for file in files {
+        let mut impacted = g.impacted_files(file.clone(), relate_cmd.hops, relate_cmd.decay);
+        if let Some(min_score) = relate_cmd.min_score {
+            impacted.retain(|each| each.score >= min_score as f64);
+        }
+        if let Some(top) = relate_cmd.top {
+            impacted.truncate(top);
+        }
+        impacted_files_data.push(ImpactedFileWrapper {
+            name: file.to_string(),
+            impacted,
+        });
     }
-
-    if let Some(depth) = relation_cmd.common_options.depth {
-        config.depth = depth;
+    let json = serde_json::to_string(&impacted_files_data).unwrap();
+    save_json(&relate_cmd.json, &json)?;
+
+    let mut table = Table::new(&["file", "impacted", "hops", "score", "via"]);
+    for each in &impacted_files_data {
+        for impacted in &each.impacted {
+            table.row(vec![
+                each.name.clone(),
+                impacted.name.clone(),
+                impacted.hops.to_string(),
+                format!("{:.2}", impacted.score),
+                impacted.via.clone(),
+            ]);
+        }
     }
-    if let Some(exclude) = relation_cmd.common_options.exclude_file_regex {
-        config.exclude_file_regex = exclude;
+    match relate_cmd.format {
+        RelateFormat::Json => {
+            if relate_cmd.json.is_none() {
+                println!("{}", json);
+            }
+        }
+        RelateFormat::Mermaid => {
+            let mut mermaid = Mermaid::new();
+            for each in &impacted_files_data {
+                mermaid.node(&each.name);
+                // the impacted file references the defs of the previous hop
+                for impacted in &each.impacted {
+                    mermaid.edge(
+                        &impacted.name,
+                        &impacted.via,
+                        &format!("{:.2}", impacted.score),
+                        impacted.hops > 1,
+                    );
+                }
+            }
+            print!("{}", mermaid.render());
+        }
+        RelateFormat::Md => print!("{}", table.render_markdown()),
+        RelateFormat::Table => print!("{}", table.render_plain()),
     }
-    config.exclude_author_regex = relation_cmd.common_options.exclude_author_regex.clone();
-
-    let g = Graph::from(config);
-    let relation_list = g.list_all_relations();
+    Ok(())
+}
 
-    let mut writer =
-        BufWriter::new(File::create(relation_cmd.index_file).expect("Unable to create file"));
-    for node in relation_list.file_nodes {
-        let serialized = serde_json::to_string(&node).expect("Failed to serialize FileNode");
-        writeln!(writer, "{}", serialized).expect("Unable to write data");
+// `relate --dir`, the same outputs with directories in place of files
+fn handle_relate_dirs(g: &Graph, relate_cmd: &RelateCommand) -> Result<(), CliError> {
+    let known_files = g.files();
+    let mut related_dirs_data = Vec::new();
+    let mut missing = false;
+    for dir in relate_cmd.dir.split(';') {
+        let normalized = g.normalize_path(dir).trim_end_matches('/').to_string();
+        if !known_files.iter().any(|file| in_dir(file, &normalized)) {
+            missing = true;
+            eprintln!("{} has no files in the graph", dir);
+        }
+        let mut related = g.related_directories(dir.to_string());
+        if let Some(min_score) = relate_cmd.min_score {
+            related.retain(|each| each.score >= min_score);
+        }
+        if let Some(top) = relate_cmd.top {
+            related.truncate(top);
+        }
+        related_dirs_data.push(RelatedDirectoryWrapper {
+            name: dir.to_string(),
+            related,
+        });
+    }
+    let json = serde_json::to_string(&related_dirs_data).unwrap();
+    save_json(&relate_cmd.json, &json)?;
+
+    let mut table = Table::new(&["dir", "related", "score", "relations"]);
+    for each in &related_dirs_data {
+        for related in &each.related {
+            table.row(vec![
+                each.name.clone(),
+                related.name.clone(),
+                related.score.to_string(),
+                related.relations.to_string(),
+            ]);
+        }
     }
-    for relation in relation_list.file_relations {
-        let serialized =
-            serde_json::to_string(&relation).expect("Failed to serialize FileRelation");
-        writeln!(writer, "{}", serialized).expect("Unable to write data");
+    match relate_cmd.format {
+        RelateFormat::Json => {
+            if relate_cmd.json.is_none() {
+                println!("{}", json);
+            }
+        }
+        RelateFormat::Mermaid => {
+            let mut mermaid = Mermaid::new();
+            for each in &related_dirs_data {
+                mermaid.node(&each.name);
+                for related in &each.related {
+                    mermaid.edge(&related.name, &each.name, &related.score.to_string(), false);
+                }
+            }
+            print!("{}", mermaid.render());
+        }
+        RelateFormat::Md => print!("{}", table.render_markdown()),
+        RelateFormat::Table => print!("{}", table.render_plain()),
     }
-    for node in relation_list.symbol_nodes {
-        let serialized = serde_json::to_string(&node).expect("Failed to serialize SymbolNode");
-        writeln!(writer, "{}", serialized).expect("Unable to write data");
+    if missing {
+        return Err(CliError::new(
+            EXIT_FILE_NOT_IN_GRAPH,
+            "some dirs have no files in the graph",
+        ));
     }
+    Ok(())
 }
 
-fn handle_relation(relation_cmd: RelationCommand) {
-    let mut config = GraphConfig::default();
-    config.project_path = relation_cmd.common_options.project_path.clone();
-    if relation_cmd.common_options.strict {
-        config.def_limit = 1;
-    }
-    if relation_cmd.common_options.def_limit.is_some() {
-        config.def_limit = relation_cmd.common_options.def_limit.unwrap();
+fn save_json(json_file: &Option<String>, json: &str) -> Result<(), CliError> {
+    if let Some(json_file) = json_file {
+        fs::write(json_file, json).map_err(|err| {
+            CliError::new(
+                EXIT_FAILURE,
+                format!("failed to write {}: {}", json_file, err),
+            )
+        })?;
     }
+    Ok(())
+}
 
-    if let Some(depth) = relation_cmd.common_options.depth {
-        config.depth = depth;
+// symbols shown in a row of the table, the most referenced first
+const TABLE_TOP_SYMBOLS: usize = 5;
+
+fn relate_table(related_files_data: &[RelatedFileWrapper]) -> Table {
+    let mut table = Table::new(&["file", "related", "score", "symbols"]);
+    for each in related_files_data {
+        for related in &each.related {
+            let mut weights: HashMap<&str, usize> = HashMap::new();
+            for s in &related.related_symbols {
+                if s.symbol.kind == SymbolKind::REF {
+                    *weights.entry(&s.symbol.name).or_default() += s.weight;
+                }
+            }
+            let mut symbols: Vec<(&str, usize)> = weights.into_iter().collect();
+            symbols.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+            let names: Vec<&str> = symbols
+                .iter()
+                .take(TABLE_TOP_SYMBOLS)
+                .map(|(name, _)| *name)
+                .collect();
+            table.row(vec![
+                each.name.clone(),
+                related.name.clone(),
+                related.score.to_string(),
+                names.join(", "),
+            ]);
+        }
     }
-    if let Some(exclude) = relation_cmd.common_options.exclude_file_regex {
-        config.exclude_file_regex = exclude;
+    table
+}
+
+fn handle_relation_v2(relation_cmd: RelationCommand) -> Result<(), CliError> {
+    let g = build_graph(relation_cmd.common_options.graph_config())?;
+    let ret =
+        File::create(&relation_cmd.index_file).and_then(|file| to_jsonl(&g, BufWriter::new(file)));
+    if let Err(err) = ret {
+        return Err(CliError::new(
+            EXIT_FAILURE,
+            format!("failed to write {}: {}", relation_cmd.index_file, err),
+        ));
     }
-    config.exclude_author_regex = relation_cmd.common_options.exclude_author_regex.clone();
-    if let Some(symbol_len_limit) = relation_cmd.common_options.symbol_len_limit {
-        config.symbol_len_limit = symbol_len_limit;
+    Ok(())
+}
+
+// `name@file:line(kind)`, so repeated names can still be traced back to code
+fn symbol_label(symbol: &Symbol) -> String {
+    format!(
+        "{}@{}:{}({})",
+        symbol.name,
+        symbol.file,
+        symbol.range.start_point.row + 1,
+        format!("{:?}", symbol.symbol_type).to_lowercase()
+    )
+}
+
+fn handle_relation(relation_cmd: RelationCommand) -> Result<(), CliError> {
+    let g = build_graph(relation_cmd.common_options.graph_config())?;
+
+    if let Some(json_file) = &relation_cmd.json {
+        let ret = File::create(json_file)
+            .map_err(serde_json::Error::io)
+            .and_then(|file| serde_json::to_writer(BufWriter::new(file), &g.list_all_relations()));
+        if let Err(err) = ret {
+            return Err(CliError::new(
+                EXIT_FAILURE,
+                format!("failed to write {}: {}", json_file, err),
+            ));
+        }
+        return Ok(());
     }
 
-    let g = Graph::from(config);
+    // streamed, the matrix grows with the square of the files
+    if let RelationFormat::Edges = relation_cmd.format {
+        let ret = File::create(&relation_cmd.csv)
+            .map_err(csv::Error::from)
+            .and_then(|file| to_edge_csv(&g, BufWriter::new(file)));
+        if let Err(err) = ret {
+            return Err(CliError::new(
+                EXIT_FAILURE,
+                format!("failed to write {}: {}", relation_cmd.csv, err),
+            ));
+        }
+        return Ok(());
+    }
 
     let mut files: Vec<String> = g.files().into_iter().collect();
     files.sort();
 
     // Create a new CSV writer
-    let wtr_result = Writer::from_path(relation_cmd.csv);
-    let mut wtr = match wtr_result {
+    let mut wtr = match Writer::from_path(&relation_cmd.csv) {
         Ok(writer) => writer,
-        Err(e) => panic!("Failed to create CSV writer: {}", e),
+        Err(e) => {
+            return Err(CliError::new(
+                EXIT_FAILURE,
+                format!("failed to create {}: {}", relation_cmd.csv, e),
+            ))
+        }
     };
     // Write the header row
     let mut header = vec!["".to_string()];
     header.extend(files.clone());
     if let Err(e) = wtr.write_record(&header) {
-        panic!("Failed to write CSV header: {}", e);
+        return Err(CliError::new(
+            EXIT_FAILURE,
+            format!("failed to write csv header: {}", e),
+        ));
     }
 
     let mut symbol_wtr_opts = None;
     if !relation_cmd.symbol_csv.is_empty() {
-        let symbol_wtr_result = Writer::from_path(relation_cmd.symbol_csv);
-        symbol_wtr_opts = match symbol_wtr_result {
+        symbol_wtr_opts = match Writer::from_path(&relation_cmd.symbol_csv) {
             Ok(writer) => Some(writer),
-            Err(e) => panic!("Failed to create CSV writer: {}", e),
+            Err(e) => {
+                return Err(CliError::new(
+                    EXIT_FAILURE,
+                    format!("failed to create {}: {}", relation_cmd.symbol_csv, e),
+                ))
+            }
         };
         let mut header = vec!["".to_string()];
         header.extend(files.clone());
         if let Some(symbol_wtr) = symbol_wtr_opts.as_mut() {
-            symbol_wtr
-                .write_record(&header)
-                .expect("Failed to write header to symbol_wtr");
+            symbol_wtr.write_record(&header).map_err(|e| {
+                CliError::new(
+                    EXIT_FAILURE,
+                    format!("failed to write {}: {}", relation_cmd.symbol_csv, e),
+                )
+            })?;
         }
     }
 
@@ -369,11 +1536,13 @@ fn handle_relation(relation_cmd: RelationCommand) {
                     if *score > 0 {
                         row.push(score.to_string());
                         if symbol_wtr_opts.is_some() {
-                            let pairs = g
+                            let mut pairs = g
                                 .pairs_between_files(file.clone(), related_file.clone())
                                 .iter()
-                                .map(|each| each.src_symbol.name.clone())
+                                .map(|each| symbol_label(&each.src_symbol))
                                 .collect::<Vec<String>>();
+                            // a def referenced many times shows up once
+                            pairs.dedup();
                             pair_row.push(pairs.join("|"));
                         }
                     } else {
@@ -398,34 +1567,37 @@ fn handle_relation(relation_cmd: RelationCommand) {
         .collect();
 
     for (row, pair_row) in sorted_results {
-        wtr.write_record(&row).expect("Failed to write record");
+        wtr.write_record(&row).map_err(|e| {
+            CliError::new(
+                EXIT_FAILURE,
+                format!("failed to write {}: {}", relation_cmd.csv, e),
+            )
+        })?;
         if let Some(symbol_wtr) = symbol_wtr_opts.as_mut() {
-            symbol_wtr
-                .write_record(&pair_row)
-                .expect("Failed to write pair_row to symbol_wtr");
+            symbol_wtr.write_record(&pair_row).map_err(|e| {
+                CliError::new(
+                    EXIT_FAILURE,
+                    format!("failed to write {}: {}", relation_cmd.symbol_csv, e),
+                )
+            })?;
         }
     }
 
     // Flush the writer to ensure all data is written
     if let Err(e) = wtr.flush() {
-        panic!("Failed to flush CSV writer: {}", e);
+        return Err(CliError::new(
+            EXIT_FAILURE,
+            format!("failed to flush csv writer: {}", e),
+        ));
     }
+    Ok(())
 }
 
-fn handle_interactive(interactive_cmd: InteractiveCommand) {
-    let mut config = GraphConfig::default();
-    config.project_path = interactive_cmd.common_options.project_path.clone();
-    if interactive_cmd.common_options.strict {
-        config.def_limit = 1
-    }
-    if !interactive_cmd.common_options.depth.is_none() {
-        config.depth = interactive_cmd.common_options.depth.unwrap();
-    }
-
-    let g = Graph::from(config);
+fn handle_interactive(interactive_cmd: InteractiveCommand) -> Result<(), CliError> {
+    let g = build_graph(interactive_cmd.common_options.graph_config())?;
 
     if interactive_cmd.dry {
-        return;
+        return Ok(());
     }
 
     loop {
@@ -443,6 +1615,19 @@ fn handle_interactive(interactive_cmd: InteractiveCommand) {
             Err(_) => break,
         }
     }
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize)]
+struct ImpactedFileWrapper {
+    pub name: String,
+    pub impacted: Vec<ImpactedFile>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RelatedDirectoryWrapper {
+    pub name: String,
+    pub related: Vec<RelatedDirectoryContext>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -451,192 +1636,711 @@ struct RelatedFileWrapper {
     pub related: Vec<RelatedFileContext>,
 }
 
-fn handle_server(server_cmd: ServerCommand) {
-    tracing_subscriber::fmt::init();
-    let mut config = GraphConfig::default();
-    config.project_path = server_cmd.common_options.project_path.clone();
-    if server_cmd.common_options.strict {
-        config.def_limit = 1
+fn handle_index(index_cmd: IndexCommand) -> Result<(), CliError> {
+    let g = build_graph(index_cmd.common_options.graph_config())?;
+    if let Err(err) = g.save(&index_cmd.output) {
+        return Err(CliError::new(
+            EXIT_FAILURE,
+            format!("failed to save index {}: {}", index_cmd.output, err),
+        ));
+    }
+    println!("index saved to {}", index_cmd.output);
+    Ok(())
+}
+
+fn handle_stats(stats_cmd: StatsCommand) -> Result<(), CliError> {
+    let g = match &stats_cmd.load_index {
+        Some(index) => load_graph(index)?,
+        None => build_graph(stats_cmd.common_options.graph_config())?,
+    };
+    let stats = g.stats();
+    match stats_cmd.format {
+        ClustersFormat::Json => println!("{}", serde_json::to_string(&stats).unwrap()),
+        ClustersFormat::Table => {
+            let mut table = Table::new(&["stat", "value"]);
+            table.row(vec![String::from("files"), stats.files.to_string()]);
+            table.row(vec![String::from("commits"), stats.commits.to_string()]);
+            for (kind, count) in &stats.symbols {
+                table.row(vec![format!("symbols {}", kind), count.to_string()]);
+            }
+            for (language, count) in &stats.languages {
+                table.row(vec![format!("symbols of {}", language), count.to_string()]);
+            }
+            table.row(vec![
+                String::from("symbol links"),
+                stats.symbol_links.to_string(),
+            ]);
+            table.row(vec![String::from("relations"), stats.relations.to_string()]);
+            table.row(vec![
+                String::from("average degree"),
+                format!("{:.2}", stats.average_degree),
+            ]);
+            table.row(vec![
+                String::from("largest component"),
+                stats.largest_component.to_string(),
+            ]);
+            for (stage, seconds) in &stats.build_timings {
+                table.row(vec![
+                    format!("seconds of {}", stage),
+                    format!("{:.2}", seconds),
+                ]);
+            }
+            print!("{}", table.render_plain());
+        }
+    }
+    Ok(())
+}
+
+fn handle_multi(multi_cmd: MultiCommand) -> Result<(), CliError> {
+    let mut merged = Graph::empty();
+    let mut prefixes = HashSet::new();
+    for repo in multi_cmd.repos.split(',').filter(|each| !each.is_empty()) {
+        let prefix = repo_name(repo);
+        if !prefixes.insert(prefix.clone()) {
+            return Err(CliError::new(
+                EXIT_FAILURE,
+                format!("more than one repo is named {}", prefix),
+            ));
+        }
+        let mut config = multi_cmd.common_options.graph_config();
+        config.project_path = repo.to_string();
+        merged.merge(&build_graph(config)?, &prefix);
+    }
+    if let Err(err) = merged.save(&multi_cmd.output) {
+        return Err(CliError::new(
+            EXIT_FAILURE,
+            format!("failed to save index {}: {}", multi_cmd.output, err),
+        ));
+    }
+    println!("index saved to {}", multi_cmd.output);
+    Ok(())
+}
+
+// the directory name of a repo, `.` and urls included
+fn repo_name(repo: &str) -> String {
+    let path = Path::new(repo);
+    path.canonicalize()
+        .ok()
+        .as_deref()
+        .unwrap_or(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().trim_end_matches(".git").to_string())
+        .unwrap_or_else(|| repo.to_string())
+}
+
+fn handle_cache(cache_cmd: CacheCommand) -> Result<(), CliError> {
+    let cache = CacheManager::new(&cache_cmd.project_path);
+    match cache_cmd.action {
+        CacheAction::Clear => {
+            if let Err(err) = cache.clear() {
+                return Err(CliError::new(
+                    EXIT_FAILURE,
+                    format!("failed to clear cache: {}", err),
+                ));
+            }
+            println!("cache cleared");
+        }
+        CacheAction::Stats => {
+            let stats = cache.stats();
+            println!("entries: {}", stats.entries);
+            println!("size: {} bytes", stats.bytes);
+        }
+    }
+    Ok(())
+}
+
+fn handle_doctor(doctor_cmd: DoctorCommand) -> Result<(), CliError> {
+    let config = doctor_cmd.common_options.graph_config();
+
+    let diagnosis = diagnose(&config);
+    if doctor_cmd.json {
+        println!("{}", serde_json::to_string(&diagnosis).unwrap());
+        return Ok(());
+    }
+
+    println!("project: {}", diagnosis.project_path);
+    println!("head: {}", diagnosis.head.as_deref().unwrap_or("<none>"));
+    println!("shallow: {}", diagnosis.shallow);
+    println!(
+        "history depth: {} (limit {})",
+        diagnosis.history_depth, config.depth
+    );
+    println!("collected commits: {}", diagnosis.collected_commits);
+    println!(
+        "collected files: {} (excluded {})",
+        diagnosis.collected_files, diagnosis.excluded_files
+    );
+    for (extractor, count) in &diagnosis.files_per_extractor {
+        println!("  {}: {}", extractor, count);
+    }
+    println!("  unsupported: {}", diagnosis.unsupported_files);
+
+    if diagnosis.hints.is_empty() {
+        println!("everything looks fine");
+    } else {
+        println!("hints:");
+        for hint in &diagnosis.hints {
+            println!("  - {}", hint);
+        }
     }
-    if !server_cmd.common_options.depth.is_none() {
-        config.depth = server_cmd.common_options.depth.unwrap();
+    Ok(())
+}
+
+fn handle_server(server_cmd: ServerCommand) -> Result<(), CliError> {
+    tracing_subscriber::fmt::init();
+    let mut config = server_cmd.common_options.graph_config();
+    if server_cmd.watch {
+        // edits are picked up before committed, and rebuilds only parse the changed files
+        config.source = FileSource::WorkingTree;
+        config.enable_cache = true;
     }
 
-    let g = Graph::from(config);
+    let mut g = match &server_cmd.load_index {
+        Some(index) => load_graph(index)?,
+        None => build_graph(config.clone())?,
+    };
+    g.build_index();
 
     let mut server_config = ServerConfig::new(g);
-    server_config.port = server_cmd.port.clone();
+    server_config.host = server_cmd.host.clone();
+    server_config.port = server_cmd.port;
+    server_config.token = server_cmd
+        .token
+        .clone()
+        .or_else(|| std::env::var("GOSSIPHS_TOKEN").ok())
+        .filter(|token| !token.is_empty());
+    server_config.cors_origins = server_cmd.cors_origins.clone();
+    if server_cmd.watch {
+        server_config.watch = Some(config.clone());
+    }
+    for project in &server_cmd.projects {
+        let (name, path) = project.split_once('=').ok_or_else(|| {
+            CliError::new(
+                EXIT_FAILURE,
+                format!("invalid --project {}, expected name=path", project),
+            )
+        })?;
+        let mut project_config = config.clone();
+        project_config.project_path = path.to_string();
+        let mut project_graph = build_graph(project_config)?;
+        project_graph.build_index();
+        server_config
+            .projects
+            .insert(name.to_string(), project_graph);
+    }
+    server_config.config = Some(config);
     info!("server up, port: {}", server_config.port);
     server_main(server_config);
+    Ok(())
 }
 
-fn handle_obsidian(obsidian_cmd: ObsidianCommand) {
+fn handle_daemon(daemon_cmd: DaemonCommand) -> Result<(), CliError> {
     tracing_subscriber::fmt::init();
-    let mut config = GraphConfig::default();
-    config.project_path = obsidian_cmd.common_options.project_path.clone();
-    if obsidian_cmd.common_options.strict {
-        config.def_limit = 1
-    }
-    if !obsidian_cmd.common_options.depth.is_none() {
-        config.depth = obsidian_cmd.common_options.depth.unwrap();
+    let config = daemon_cmd.common_options.graph_config();
+
+    let address = daemon_cmd
+        .socket
+        .unwrap_or_else(|| default_address(&config.project_path));
+    let mut g = match &daemon_cmd.load_index {
+        Some(index) => load_graph(index)?,
+        None => build_graph(config)?,
+    };
+    g.build_index();
+
+    info!("daemon up, address: {}", address);
+    if let Err(err) = daemon_main(DaemonConfig::new(g, address)) {
+        return Err(CliError::new(
+            EXIT_FAILURE,
+            format!("daemon failed: {}", err),
+        ));
     }
+    Ok(())
+}
+
+fn handle_mcp(mcp_cmd: StdioCommand) -> Result<(), CliError> {
+    // stdout is for the protocol only
+    tracing_subscriber::fmt().with_writer(io::stderr).init();
+    let mut g = match &mcp_cmd.load_index {
+        Some(index) => load_graph(index)?,
+        None => build_graph(mcp_cmd.common_options.graph_config())?,
+    };
+    g.build_index();
+
+    info!("mcp server up");
+    mcp::serve(&g, io::stdin().lock(), io::stdout().lock())
+        .map_err(|err| CliError::new(EXIT_FAILURE, format!("mcp server failed: {}", err)))
+}
 
-    let g = Graph::from(config);
+fn handle_lsp(lsp_cmd: StdioCommand) -> Result<(), CliError> {
+    // stdout is for the protocol only
+    tracing_subscriber::fmt().with_writer(io::stderr).init();
+    let root = Path::new(&lsp_cmd.common_options.project_path)
+        .canonicalize()
+        .map_err(|err| {
+            CliError::new(
+                EXIT_FAILURE,
+                format!(
+                    "failed to open {}: {}",
+                    lsp_cmd.common_options.project_path, err
+                ),
+            )
+        })?;
+    let mut g = match &lsp_cmd.load_index {
+        Some(index) => load_graph(index)?,
+        None => build_graph(lsp_cmd.common_options.graph_config())?,
+    };
+    g.build_index();
+
+    info!("lsp server up");
+    lsp::serve(&g, &root, io::stdin().lock(), io::stdout().lock())
+        .map_err(|err| CliError::new(EXIT_FAILURE, format!("lsp server failed: {}", err)))
+}
+
+fn handle_query(query_cmd: QueryCommand) -> Result<(), CliError> {
+    let request = match query_cmd.action {
+        QueryAction::Related { file } => Request::Related { file },
+        QueryAction::Metadata { file } => Request::Metadata { file },
+        QueryAction::Files => Request::Files,
+        QueryAction::Stop => Request::Stop,
+    };
+
+    if let Some(server) = query_cmd.server {
+        let token = query_cmd
+            .token
+            .or_else(|| std::env::var("GOSSIPHS_TOKEN").ok())
+            .filter(|token| !token.is_empty());
+        let response = query_server(
+            &server,
+            token.as_deref(),
+            query_cmd.project.as_deref(),
+            &request,
+        )
+        .map_err(|err| CliError::new(EXIT_FAILURE, format!("no server at {} ({})", server, err)))?;
+        return print_query_response(response);
+    }
 
-    // create mirror files
-    // add links to files
-    let files = g.files();
-    match fs::create_dir(&obsidian_cmd.vault_dir) {
-        Ok(_) => debug!("Directory created successfully."),
-        Err(e) => panic!("Error creating directory: {}", e),
+    let address = query_cmd
+        .socket
+        .unwrap_or_else(|| default_address(&query_cmd.project_path));
+    match query(&address, &request) {
+        Ok(response) => print_query_response(response),
+        Err(err) => Err(CliError::new(
+            EXIT_FAILURE,
+            format!(
+                "no daemon at {} ({}), start one by `gossiphs daemon`",
+                address, err
+            ),
+        )),
     }
+}
 
-    for each_file in files {
-        let related = g.related_files(each_file.clone());
-        let markdown_filename = format!("{}/{}.md", &obsidian_cmd.vault_dir, each_file);
-        let mut markdown_content = String::new();
-        for related_file in related {
-            markdown_content.push_str(&format!("[[{}]]\n", related_file.name));
+fn print_query_response(response: Response) -> Result<(), CliError> {
+    match response {
+        Response::Ok(value) => {
+            if !value.is_null() {
+                println!("{}", serde_json::to_string_pretty(&value).unwrap());
+            }
+            Ok(())
         }
+        Response::Error(err) => Err(CliError::new(
+            EXIT_FAILURE,
+            format!("query failed: {}", err),
+        )),
+    }
+}
 
-        let path = Path::new(&markdown_filename);
-        let parent = path.parent().unwrap_or_else(|| Path::new("."));
-        if let Err(why) = fs::create_dir_all(parent) {
-            panic!("couldn't create directory {}: {}", parent.display(), why);
+fn handle_export(export_cmd: ExportCommand) -> Result<(), CliError> {
+    let g = build_graph(export_cmd.common_options.graph_config())?;
+    let content = match export_cmd.format {
+        ExportFormat::Dot => to_dot(&g.list_all_relations(), export_cmd.symbols),
+        ExportFormat::Graphml => to_graphml(&g.list_all_relations(), export_cmd.symbols),
+        ExportFormat::Gexf => to_gexf(&g.list_all_relations(), export_cmd.symbols),
+        ExportFormat::D3 => to_d3(&g.list_all_relations(), export_cmd.symbols).to_string(),
+        // streamed, never hold all the relations
+        ExportFormat::Jsonl => {
+            let ret = match &export_cmd.output {
+                Some(output) => {
+                    File::create(output).and_then(|file| to_jsonl(&g, BufWriter::new(file)))
+                }
+                None => to_jsonl(&g, std::io::stdout().lock()),
+            };
+            return match ret {
+                // e.g. piped to `head`
+                Err(err) if err.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+                Err(err) => Err(CliError::new(
+                    EXIT_FAILURE,
+                    format!("failed to export: {}", err),
+                )),
+                Ok(_) => Ok(()),
+            };
         }
-        let mut file = match File::create(&markdown_filename) {
-            Err(why) => panic!("couldn't create {}: {}", markdown_filename, why),
-            Ok(file) => file,
-        };
-        match file.write_all(markdown_content.as_bytes()) {
-            Err(why) => panic!("couldn't write to {}: {}", markdown_filename, why),
-            Ok(_) => debug!("Successfully wrote to {}", markdown_filename),
+        ExportFormat::Parquet => {
+            let output = match &export_cmd.output {
+                Some(output) => output,
+                None => {
+                    return Err(CliError::new(
+                        EXIT_FAILURE,
+                        "--output is required by parquet",
+                    ))
+                }
+            };
+            return to_parquet(&g.list_all_relations(), Path::new(output)).map_err(|err| {
+                CliError::new(EXIT_FAILURE, format!("failed to write {}: {}", output, err))
+            });
+        }
+    };
+    match export_cmd.output {
+        Some(output) => {
+            if let Err(err) = fs::write(&output, content) {
+                return Err(CliError::new(
+                    EXIT_FAILURE,
+                    format!("failed to write {}: {}", output, err),
+                ));
+            }
         }
+        None => print!("{}", content),
     }
+    Ok(())
 }
-#[derive(Serialize, Deserialize)]
-struct DiffFileContext {
-    // same as git
+
+fn handle_viz(viz_cmd: VizCommand) -> Result<(), CliError> {
+    let g = build_graph(viz_cmd.common_options.graph_config())?;
+    if let Err(err) = fs::write(&viz_cmd.output, to_html(&g.list_all_relations())) {
+        return Err(CliError::new(
+            EXIT_FAILURE,
+            format!("failed to write {}: {}", viz_cmd.output, err),
+        ));
+    }
+    println!("open {} in your browser", viz_cmd.output);
+    Ok(())
+}
+
+fn handle_rank(rank_cmd: RankCommand) -> Result<(), CliError> {
+    let g = build_graph(rank_cmd.common_options.graph_config())?;
+    write_ranked(
+        g.rank_files(),
+        rank_cmd.format,
+        rank_cmd.output,
+        rank_cmd.top,
+    )
+}
+
+fn handle_hotspots(rank_cmd: RankCommand) -> Result<(), CliError> {
+    let g = build_graph(rank_cmd.common_options.graph_config())?;
+    write_ranked(g.hotspots(), rank_cmd.format, rank_cmd.output, rank_cmd.top)
+}
+
+fn handle_orphans(rank_cmd: RankCommand) -> Result<(), CliError> {
+    let g = build_graph(rank_cmd.common_options.graph_config())?;
+    write_ranked(
+        g.orphan_files(),
+        rank_cmd.format,
+        rank_cmd.output,
+        rank_cmd.top,
+    )
+}
+
+// a row of `deadsymbols`, flat for csv
+#[derive(Serialize)]
+struct SymbolRow {
+    file: String,
     name: String,
-    added: Vec<RelatedFileContext>,
-    deleted: Vec<RelatedFileContext>,
-    modified: Vec<RelatedFileContext>,
+    symbol_type: String,
+    line: usize,
 }
 
-fn is_working_directory_clean(repo: &Repository) -> bool {
-    match repo.statuses(None) {
-        Ok(statuses) => {
-            for entry in statuses.iter() {
-                let status = entry.status();
-                if status.contains(Status::WT_NEW)
-                    || status.contains(Status::WT_MODIFIED)
-                    || status.contains(Status::WT_DELETED)
-                    || status.contains(Status::WT_TYPECHANGE)
-                    || status.contains(Status::WT_RENAMED)
-                    || status.contains(Status::INDEX_NEW)
-                    || status.contains(Status::INDEX_MODIFIED)
-                    || status.contains(Status::INDEX_DELETED)
-                    || status.contains(Status::INDEX_TYPECHANGE)
-                    || status.contains(Status::INDEX_RENAMED)
-                {
-                    return false;
-                }
+fn handle_dead_symbols(rank_cmd: RankCommand) -> Result<(), CliError> {
+    let g = build_graph(rank_cmd.common_options.graph_config())?;
+    let rows: Vec<SymbolRow> = g
+        .unreferenced_definitions()
+        .into_iter()
+        .map(|def| SymbolRow {
+            file: def.file.to_string(),
+            name: def.name.to_string(),
+            symbol_type: format!("{:?}", def.symbol_type),
+            line: def.range.start_point.row + 1,
+        })
+        .collect();
+    write_ranked(rows, rank_cmd.format, rank_cmd.output, rank_cmd.top)
+}
+
+fn handle_busfactor(busfactor_cmd: BusFactorCommand) -> Result<(), CliError> {
+    let g = build_graph(busfactor_cmd.common_options.graph_config())?;
+    let risks = g.bus_factor(busfactor_cmd.min_share);
+    write_ranked(risks, busfactor_cmd.format, busfactor_cmd.output, None)
+}
+
+fn write_ranked<T: Serialize>(
+    mut rows: Vec<T>,
+    format: RankFormat,
+    output: Option<String>,
+    top: Option<usize>,
+) -> Result<(), CliError> {
+    if let Some(top) = top {
+        rows.truncate(top);
+    }
+    let content = match format {
+        RankFormat::Json => serde_json::to_string(&rows).unwrap(),
+        RankFormat::Csv => {
+            let mut wtr = Writer::from_writer(Vec::new());
+            for each in &rows {
+                wtr.serialize(each).unwrap();
             }
-            true
+            String::from_utf8(wtr.into_inner().unwrap()).unwrap()
+        }
+    };
+    match output {
+        Some(output) => fs::write(&output, content).map_err(|err| {
+            CliError::new(EXIT_FAILURE, format!("failed to write {}: {}", output, err))
+        }),
+        None => {
+            print!("{}", content);
+            Ok(())
         }
-        Err(_) => false,
     }
 }
 
-fn get_current_branch(repo: &Repository) -> Option<String> {
-    let head = repo.head().ok()?;
-    let shorthand = head.shorthand()?;
-    Some(shorthand.to_string())
+fn handle_clusters(clusters_cmd: ClustersCommand) -> Result<(), CliError> {
+    let g = build_graph(clusters_cmd.common_options.graph_config())?;
+    let mut communities = g.detect_communities();
+    communities.retain(|each| each.files.len() >= clusters_cmd.min_size);
+    let content = match clusters_cmd.format {
+        ClustersFormat::Json => serde_json::to_string(&communities).unwrap(),
+        ClustersFormat::Table => {
+            let mut table = Table::new(&["cluster", "file"]);
+            for each in &communities {
+                for file in &each.files {
+                    table.row(vec![each.id.to_string(), file.clone()]);
+                }
+            }
+            table.render_plain()
+        }
+    };
+    match clusters_cmd.output {
+        Some(output) => fs::write(&output, content).map_err(|err| {
+            CliError::new(EXIT_FAILURE, format!("failed to write {}: {}", output, err))
+        }),
+        None => {
+            print!("{}", content);
+            Ok(())
+        }
+    }
 }
 
-fn get_commit_and_object<'repo>(
-    repo: &'repo Repository,
-    rev: &str,
-) -> Result<(Commit<'repo>, Object<'repo>), Error> {
-    let obj = repo.revparse_single(rev)?;
+fn handle_cycles(cycles_cmd: CyclesCommand) -> Result<(), CliError> {
+    let g = build_graph(cycles_cmd.common_options.graph_config())?;
+    let scope = match cycles_cmd.scope {
+        CyclesScope::File => CycleScope::FILE,
+        CyclesScope::Dir => CycleScope::DIR,
+    };
+    let cycles = g.find_cycles(scope);
+    let content = match cycles_cmd.format {
+        ClustersFormat::Json => serde_json::to_string(&cycles).unwrap(),
+        ClustersFormat::Table => {
+            let mut table = Table::new(&["cycle", "src", "dst", "score"]);
+            for (i, cycle) in cycles.iter().enumerate() {
+                for (src, dst, score) in &cycle.edges {
+                    table.row(vec![
+                        i.to_string(),
+                        src.clone(),
+                        dst.clone(),
+                        score.to_string(),
+                    ]);
+                }
+            }
+            table.render_plain()
+        }
+    };
+    match cycles_cmd.output {
+        Some(output) => fs::write(&output, content).map_err(|err| {
+            CliError::new(EXIT_FAILURE, format!("failed to write {}: {}", output, err))
+        }),
+        None => {
+            print!("{}", content);
+            Ok(())
+        }
+    }
+}
 
-    // Check if the object is a commit or needs to be peeled to a commit
-    let commit = if obj.kind() == Some(ObjectType::Commit) {
-        obj.as_commit()
-            .map(|commit| (commit.clone(), obj.clone()))
-            .ok_or_else(|| Error::from_str("Object is not a commit"))
-    } else {
-        let peeled_obj = obj.peel(ObjectType::Commit)?;
-        peeled_obj
-            .as_commit()
-            .map(|commit| (commit.clone(), peeled_obj.clone()))
-            .ok_or_else(|| Error::from_str("Object could not be peeled to commit"))
+fn handle_impact(impact_cmd: ImpactCommand, tests_only: bool) -> Result<(), CliError> {
+    let changed = impact_cmd
+        .change_options
+        .changed_files(&impact_cmd.common_options.project_path)?;
+    let g = build_graph(impact_cmd.common_options.graph_config())?;
+    let impacted = match tests_only {
+        true => g.related_tests(changed),
+        false => g.impact(changed),
+    };
+    let content = match impact_cmd.format {
+        ImpactFormat::Json => serde_json::to_string(&impacted).unwrap(),
+        ImpactFormat::Md => {
+            let mut table = Table::new(&["file", "score", "changed", "symbols"]);
+            for each in &impacted {
+                table.row(vec![
+                    each.name.clone(),
+                    each.score.to_string(),
+                    each.sources.join(", "),
+                    each.symbols.join(", "),
+                ]);
+            }
+            table.render_markdown()
+        }
+        ImpactFormat::Plain => impacted
+            .iter()
+            .map(|each| format!("{}\n", each.name))
+            .collect(),
     };
+    match impact_cmd.output {
+        Some(output) => fs::write(&output, content).map_err(|err| {
+            CliError::new(EXIT_FAILURE, format!("failed to write {}: {}", output, err))
+        }),
+        None => {
+            print!("{}", content);
+            Ok(())
+        }
+    }
+}
 
-    commit
+fn handle_reviewers(reviewers_cmd: ReviewersCommand) -> Result<(), CliError> {
+    let changed = reviewers_cmd
+        .change_options
+        .changed_files(&reviewers_cmd.common_options.project_path)?;
+    let g = build_graph(reviewers_cmd.common_options.graph_config())?;
+    let mut reviewers = g.suggest_reviewers(changed);
+    if let Some(exclude) = &reviewers_cmd.exclude {
+        let excluded: Vec<&str> = exclude.split(';').filter(|each| !each.is_empty()).collect();
+        reviewers.retain(|each| !excluded.iter().any(|name| each.name.contains(name)));
+    }
+    reviewers.truncate(reviewers_cmd.top);
+    let content = serde_json::to_string(&reviewers).unwrap();
+    match reviewers_cmd.output {
+        Some(output) => fs::write(&output, content).map_err(|err| {
+            CliError::new(EXIT_FAILURE, format!("failed to write {}: {}", output, err))
+        }),
+        None => {
+            print!("{}", content);
+            Ok(())
+        }
+    }
 }
 
-fn handle_diff(diff_cmd: DiffCommand) {
-    // repo status check
-    let project_path = diff_cmd.common_options.project_path;
-    let repo = Repository::open(&project_path).unwrap();
-    if !is_working_directory_clean(&repo) {
-        println!("Working directory is dirty. Commit or stash changes first.");
-        return;
+fn handle_codeowners(codeowners_cmd: CodeownersCommand) -> Result<(), CliError> {
+    let g = build_graph(codeowners_cmd.common_options.graph_config())?;
+    let content = to_codeowners(&g.file_owners(codeowners_cmd.min_share));
+    match codeowners_cmd.output {
+        Some(output) => fs::write(&output, content).map_err(|err| {
+            CliError::new(EXIT_FAILURE, format!("failed to write {}: {}", output, err))
+        }),
+        None => {
+            print!("{}", content);
+            Ok(())
+        }
     }
-    let current_branch = get_current_branch(&repo);
-    let (target_commit, target_object) = get_commit_and_object(&repo, &diff_cmd.target).unwrap();
-    let (source_commit, source_object) = get_commit_and_object(&repo, &diff_cmd.source).unwrap();
+}
 
-    // gen graphs
-    let mut builder = CheckoutBuilder::new();
-    builder.force();
-    repo.checkout_tree(&target_object, Some(&mut builder))
-        .unwrap();
-    repo.set_head_detached(target_commit.id()).unwrap();
+fn handle_obsidian(obsidian_cmd: ObsidianCommand) -> Result<(), CliError> {
+    tracing_subscriber::fmt::init();
+    let g = build_graph(obsidian_cmd.common_options.graph_config())?;
 
-    let mut config = GraphConfig::default();
-    config.project_path = project_path;
-    if diff_cmd.common_options.strict {
-        config.def_limit = 1
-    }
-    if !diff_cmd.common_options.depth.is_none() {
-        config.depth = diff_cmd.common_options.depth.unwrap();
+    // notes mirroring the files, with links to their related files
+    let summary = sync_vault(&g, Path::new(&obsidian_cmd.vault_dir)).map_err(|err| {
+        CliError::new(
+            EXIT_FAILURE,
+            format!("failed to sync {}: {}", obsidian_cmd.vault_dir, err),
+        )
+    })?;
+    info!(
+        "vault synced: {} created, {} updated, {} removed",
+        summary.created, summary.updated, summary.removed
+    );
+
+    if obsidian_cmd.canvas {
+        let canvas_file = Path::new(&obsidian_cmd.vault_dir).join("modules.canvas");
+        fs::write(&canvas_file, module_canvas(&g).to_string()).map_err(|err| {
+            CliError::new(
+                EXIT_FAILURE,
+                format!("failed to write {}: {}", canvas_file.display(), err),
+            )
+        })?;
     }
+    Ok(())
+}
+#[derive(Serialize, Deserialize)]
+struct DiffFileContext {
+    // same as git
+    name: String,
+    added: Vec<RelatedFileContext>,
+    deleted: Vec<RelatedFileContext>,
+    modified: Vec<RelatedFileContext>,
+}
 
-    let target_graph = Graph::from(config.clone());
+fn get_commit<'repo>(repo: &'repo Repository, rev: &str) -> Result<Commit<'repo>, Error> {
+    repo.revparse_single(rev)?.peel_to_commit()
+}
 
-    repo.checkout_tree(&source_object, Some(&mut builder))
-        .unwrap();
-    repo.set_head_detached(source_commit.id()).unwrap();
-    // reset to branch
-    if !current_branch.is_none() {
-        let current_branch_str = current_branch.unwrap();
-        if let Err(e) = repo.set_head(&format!("refs/heads/{}", current_branch_str)) {
-            eprintln!(
-                "Failed to switch back to branch '{}': {}",
-                current_branch_str, e
-            );
+fn handle_diff(diff_cmd: DiffCommand) -> Result<(), CliError> {
+    let project_path = diff_cmd.common_options.project_path.clone();
+    let repo = Repository::open(&project_path).map_err(|err| {
+        CliError::new(
+            EXIT_NOT_A_REPO,
+            format!(
+                "failed to open repository {}: {}",
+                project_path,
+                err.message()
+            ),
+        )
+    })?;
+    let (target_commit, source_commit) = match (
+        get_commit(&repo, &diff_cmd.target),
+        get_commit(&repo, &diff_cmd.source),
+    ) {
+        (Ok(target_commit), Ok(source_commit)) => (target_commit, source_commit),
+        (Err(err), _) | (_, Err(err)) => {
+            return Err(CliError::new(
+                EXIT_BAD_REV,
+                format!("failed to resolve rev: {}", err.message()),
+            ))
         }
-    }
+    };
 
-    let source_graph = Graph::from(config);
+    // gen graphs, both are read from the commits directly
+    let mut config = diff_cmd.common_options.graph_config();
+
+    let mut index = diff_cmd.load_index.as_deref().map(load_graph).transpose()?;
+    let mut graph_of = |commit: &Commit| {
+        let commit_id = commit.id().to_string();
+        if index
+            .as_ref()
+            .is_some_and(|g| g.commit_id().as_ref() == Some(&commit_id))
+        {
+            return Ok(index.take().unwrap());
+        }
+        config.source = FileSource::Rev(commit_id);
+        build_graph(config.clone())
+    };
+    let target_graph = graph_of(&target_commit)?;
+    let source_graph = graph_of(&source_commit)?;
+    if index.is_some() {
+        eprintln!("index is built from neither side of the diff, ignored");
+    }
 
     // diff files
+    let diff_error =
+        |err: Error| CliError::new(EXIT_FAILURE, format!("failed to diff: {}", err.message()));
     let mut diff_options = DiffOptions::new();
     let diff = repo
         .diff_tree_to_tree(
-            Some(&target_commit.tree().unwrap()),
-            Some(&source_commit.tree().unwrap()),
+            Some(&target_commit.tree().map_err(diff_error)?),
+            Some(&source_commit.tree().map_err(diff_error)?),
             Some(&mut diff_options),
         )
-        .unwrap();
+        .map_err(diff_error)?;
 
     let mut diff_files: Vec<String> = Vec::new();
     diff.foreach(
         &mut |delta, _| {
             if let Some(new_file) = delta.new_file().path() {
-                diff_files.push(String::from(new_file.to_str().unwrap()));
+                diff_files.push(new_file.to_string_lossy().into_owned());
             }
             true
         },
@@ -644,7 +2348,7 @@ fn handle_diff(diff_cmd: DiffCommand) {
         None,
         None,
     )
-    .unwrap();
+    .map_err(diff_error)?;
 
     // diff context
     let mut ret: Vec<DiffFileContext> = Vec::new();
@@ -652,12 +2356,12 @@ fn handle_diff(diff_cmd: DiffCommand) {
         let target_related_map: HashMap<String, RelatedFileContext> = target_graph
             .related_files(each_file.clone())
             .into_iter()
-            .map(|item| return (item.name.clone(), item))
+            .map(|item| (item.name.clone(), item))
             .collect();
         let source_related_map: HashMap<String, RelatedFileContext> = source_graph
             .related_files(each_file.clone())
             .into_iter()
-            .map(|item| return (item.name.clone(), item))
+            .map(|item| (item.name.clone(), item))
             .collect();
         let mut added_links: Vec<RelatedFileContext> = Vec::new();
         let mut modified_links: Vec<RelatedFileContext> = Vec::new();
@@ -684,141 +2388,104 @@ fn handle_diff(diff_cmd: DiffCommand) {
     }
 
     // output format
-    if diff_cmd.json {
-        let json = serde_json::to_string(&ret).unwrap();
-        println!("{}", json);
-    } else {
-        for file_context in &ret {
-            let file_name = &file_context.name;
-            let mut file_node = Tree::new(file_name.as_str());
-
-            let mut names = Vec::new();
-            for link in &file_context.added {
-                names.push(format!("{} (ADDED)", link.name));
-            }
-            for link in &file_context.deleted {
-                names.push(format!("{} (DELETED)", link.name));
-            }
-            for link in &file_context.modified {
-                names.push(format!("{}", link.name));
+    let format = match diff_cmd.json {
+        true => DiffFormat::Json,
+        false => diff_cmd.format,
+    };
+    match format {
+        DiffFormat::Json => {
+            let json = serde_json::to_string(&ret).unwrap();
+            println!("{}", json);
+        }
+        DiffFormat::Mermaid => {
+            let mut mermaid = Mermaid::new();
+            for file_context in &ret {
+                let file_name = &file_context.name;
+                mermaid.node(file_name);
+                for link in &file_context.added {
+                    mermaid.edge(&link.name, file_name, "ADDED", false);
+                }
+                for link in &file_context.deleted {
+                    mermaid.edge(&link.name, file_name, "DELETED", true);
+                }
+                for link in &file_context.modified {
+                    mermaid.edge(&link.name, file_name, &link.score.to_string(), false);
+                }
             }
+            print!("{}", mermaid.render());
+        }
+        DiffFormat::Tree => {
+            for file_context in &ret {
+                let file_name = &file_context.name;
+                let mut file_node = Tree::new(file_name.as_str());
+
+                let mut names = Vec::new();
+                for link in &file_context.added {
+                    names.push(format!("{} (ADDED)", link.name));
+                }
+                for link in &file_context.deleted {
+                    names.push(format!("{} (DELETED)", link.name));
+                }
+                for link in &file_context.modified {
+                    names.push(link.name.to_string());
+                }
 
-            // Push the references of the prefixed names into the file_node
-            for prefixed_name in &names {
-                file_node.push(Tree::new(prefixed_name.as_str()));
-            }
+                // Push the references of the prefixed names into the file_node
+                for prefixed_name in &names {
+                    file_node.push(Tree::new(prefixed_name.as_str()));
+                }
 
-            println!("{}", file_node)
+                println!("{}", file_node)
+            }
         }
     }
+    Ok(())
 }
 
-#[test]
-fn test_handle_relate() {
-    let relate_cmd = RelateCommand {
-        common_options: CommonOptions::default(),
-        file: "src/extractor.rs".to_string(),
-        file_txt: "".to_string(),
-        json: None,
-        ignore_zero: true,
-    };
-    handle_relate(relate_cmd);
-}
-
-#[test]
-fn test_handle_relate_files() {
-    let relate_cmd = RelateCommand {
-        common_options: CommonOptions::default(),
-        file: "src/extractor.rs;src/main.rs;src/graph.rs".to_string(),
-        file_txt: "".to_string(),
-        json: None,
-        ignore_zero: true,
-    };
-    handle_relate(relate_cmd);
-}
-
-#[test]
-fn test_handle_relate_files_strict() {
-    let relate_cmd = RelateCommand {
-        common_options: CommonOptions::default(),
-        file: "src/extractor.rs;src/rule.rs;src/main.rs;src/graph.rs".to_string(),
-        file_txt: "".to_string(),
-        json: None,
-        ignore_zero: true,
-    };
-    handle_relate(relate_cmd);
-}
-
-#[test]
-#[ignore]
-fn test_handle_relate_file_txt() {
-    let relate_cmd = RelateCommand {
-        common_options: CommonOptions::default(),
-        file: "".to_string(),
-        file_txt: "./aa.txt".to_string(),
-        json: None,
-        ignore_zero: true,
-    };
-    handle_relate(relate_cmd);
-}
-
-#[test]
-#[ignore]
-fn server_test() {
-    handle_server(ServerCommand {
-        common_options: CommonOptions::default(),
-        port: 9411,
-    })
-}
+/// Unix timestamp of `2024-01-31`, `6 months` (ago) or `1706659200`
+fn parse_time(time: &str) -> Result<i64, String> {
+    let time = time.trim();
+    if let Ok(timestamp) = time.parse::<i64>() {
+        return Ok(timestamp);
+    }
 
-#[test]
-#[ignore]
-fn obsidian_test() {
-    handle_obsidian(ObsidianCommand {
-        common_options: CommonOptions::default(),
-        vault_dir: "./vault".to_string(),
-    })
-}
+    let date_re = Regex::new(r"^(\d{4})-(\d{2})-(\d{2})$").unwrap();
+    if let Some(caps) = date_re.captures(time) {
+        let (y, m, d) = (
+            caps[1].parse::<i64>().unwrap(),
+            caps[2].parse::<i64>().unwrap(),
+            caps[3].parse::<i64>().unwrap(),
+        );
+        if !(1..=12).contains(&m) || !(1..=31).contains(&d) {
+            return Err(format!("invalid date: {}", time));
+        }
+        // days from civil, http://howardhinnant.github.io/date_algorithms.html
+        let y = if m <= 2 { y - 1 } else { y };
+        let era = y.div_euclid(400);
+        let yoe = y - era * 400;
+        let doy = (153 * ((m + 9) % 12) + 2) / 5 + d - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        return Ok((era * 146097 + doe - 719468) * 86400);
+    }
 
-#[test]
-fn diff_test() {
-    handle_diff(DiffCommand {
-        common_options: CommonOptions::default(),
-        target: "HEAD~10".to_string(),
-        source: "HEAD".to_string(),
-        json: false,
-    });
-
-    handle_diff(DiffCommand {
-        common_options: CommonOptions::default(),
-        target: "d18a5db39752d244664a23f74e174448b66b5b7e".to_string(),
-        source: "HEAD".to_string(),
-        json: false,
-    });
-}
-
-#[test]
-fn relation_test() {
-    let mut config = CommonOptions::default();
-    config.exclude_file_regex = Some("".parse().unwrap());
-    config.project_path = ".".parse().unwrap();
-    handle_relation(RelationCommand {
-        common_options: config,
-        csv: "ok.csv".to_string(),
-        symbol_csv: "ok1.csv".to_string(),
-        index_file: "".to_string(),
-    })
-}
+    let relative_re = Regex::new(r"^(\d+)\s*(day|week|month|year)s?(\s+ago)?$").unwrap();
+    if let Some(caps) = relative_re.captures(time) {
+        let count = caps[1].parse::<i64>().unwrap();
+        let days = match &caps[2] {
+            "day" => 1,
+            "week" => 7,
+            "month" => 30,
+            _ => 365,
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        return Ok(now - count * days * 86400);
+    }
 
-#[test]
-fn relation_v2_test() {
-    let mut config = CommonOptions::default();
-    config.exclude_file_regex = Some("".parse().unwrap());
-    config.project_path = ".".parse().unwrap();
-    handle_relation_v2(RelationCommand {
-        common_options: config,
-        csv: "".to_string(),
-        symbol_csv: "".to_string(),
-        index_file: "hello.index".to_string(),
-    })
+    Err(format!(
+        "invalid time: {}, use `2024-01-31`, `6 months` or a unix timestamp",
+        time
+    ))
 }
@@ -0,0 +1,233 @@
+use crate::graph::Graph;
+use crate::server::VERSION;
+use crate::symbol::SymbolKind;
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use std::io::{BufRead, Result, Write};
+
+// the revision of the Model Context Protocol spoken here
+const PROTOCOL_VERSION: &str = "2024-11-05";
+
+// json-rpc error codes
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+fn tools() -> Value {
+    let file = json!({ "type": "string", "description": "file path relative to the repo" });
+    json!([
+        {
+            "name": "related_files",
+            "description": "Files related to a file by the symbols they share and the commits changing both, \
+                            the strongest first. Files to read before editing it.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "file": file,
+                    "top": { "type": "integer", "description": "keep the strongest ones only" },
+                },
+                "required": ["file"],
+            },
+        },
+        {
+            "name": "file_metadata",
+            "description": "Symbols defined and referenced in a file, and the commits and issues touching it.",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "file": file },
+                "required": ["file"],
+            },
+        },
+        {
+            "name": "symbol_lookup",
+            "description": "Where a symbol is defined, and the files referencing each definition.",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "name": { "type": "string", "description": "name of the symbol" } },
+                "required": ["name"],
+            },
+        },
+        {
+            "name": "impact_of_changes",
+            "description": "Files impacted by changing the given files, the most impacted first.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "files": { "type": "array", "items": { "type": "string" } },
+                    "tests_only": { "type": "boolean", "description": "only the test files" },
+                },
+                "required": ["files"],
+            },
+        },
+    ])
+}
+
+fn call_tool(g: &Graph, name: &str, args: &Value) -> std::result::Result<Value, String> {
+    let string_arg = |key: &str| {
+        args.get(key)
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| format!("missing argument {}", key))
+    };
+    let ret = match name {
+        "related_files" => {
+            let mut files = g.related_files(string_arg("file")?);
+            if let Some(top) = args.get("top").and_then(Value::as_u64) {
+                files.truncate(top as usize);
+            }
+            serde_json::to_value(files)
+        }
+        "file_metadata" => serde_json::to_value(g.file_metadata(string_arg("file")?)),
+        "symbol_lookup" => {
+            let name = string_arg("name")?;
+            let definitions: Vec<Value> = g
+                .symbol_graph
+                .g
+                .node_weights()
+                .filter_map(|node| node.get_symbol())
+                .filter(|symbol| symbol.kind == SymbolKind::DEF && *symbol.name == *name)
+                .map(|symbol| {
+                    let referenced_by: BTreeSet<String> = g
+                        .symbol_graph
+                        .list_references_by_definition(&symbol.id())
+                        .into_keys()
+                        .map(|reference| reference.file.to_string())
+                        .collect();
+                    json!({ "definition": symbol, "referenced_by": referenced_by })
+                })
+                .collect();
+            Ok(Value::from(definitions))
+        }
+        "impact_of_changes" => {
+            let files: Vec<String> = args
+                .get("files")
+                .and_then(Value::as_array)
+                .ok_or_else(|| String::from("missing argument files"))?
+                .iter()
+                .filter_map(|each| each.as_str().map(str::to_string))
+                .collect();
+            match args.get("tests_only").and_then(Value::as_bool) {
+                Some(true) => serde_json::to_value(g.related_tests(files)),
+                _ => serde_json::to_value(g.impact(files)),
+            }
+        }
+        _ => return Err(format!("unknown tool {}", name)),
+    };
+    ret.map_err(|err| err.to_string())
+}
+
+/// The response of a json-rpc message, none for notifications.
+fn handle(g: &Graph, message: &Value) -> Option<Value> {
+    // notifications have no id and get no response
+    let id = message.get("id")?.clone();
+    let params = message.get("params").cloned().unwrap_or(Value::Null);
+    let result = match message.get("method").and_then(Value::as_str) {
+        Some("initialize") => Ok(json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": { "tools": {} },
+            "serverInfo": { "name": "gossiphs", "version": VERSION },
+        })),
+        Some("ping") => Ok(json!({})),
+        Some("tools/list") => Ok(json!({ "tools": tools() })),
+        Some("tools/call") => match params.get("name").and_then(Value::as_str) {
+            Some(name) => {
+                let args = params.get("arguments").cloned().unwrap_or(json!({}));
+                // failures of a tool are shown to the model, not protocol errors
+                Ok(match call_tool(g, name, &args) {
+                    Ok(value) => json!({
+                        "content": [{ "type": "text", "text": value.to_string() }],
+                        "isError": false,
+                    }),
+                    Err(err) => json!({
+                        "content": [{ "type": "text", "text": err }],
+                        "isError": true,
+                    }),
+                })
+            }
+            None => Err((INVALID_PARAMS, String::from("missing tool name"))),
+        },
+        Some(method) => Err((METHOD_NOT_FOUND, format!("unknown method {}", method))),
+        None => Err((INVALID_PARAMS, String::from("missing method"))),
+    };
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": code, "message": message },
+        }),
+    })
+}
+
+/// Answer the Model Context Protocol messages, one json per line, until the reader is closed.
+/// Nothing else can be written to `writer`, logs go to stderr.
+pub fn serve<R: BufRead, W: Write>(g: &Graph, reader: R, mut writer: W) -> Result<()> {
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Value>(&line) {
+            Ok(message) => handle(g, &message),
+            Err(err) => Some(json!({
+                "jsonrpc": "2.0",
+                "id": Value::Null,
+                "error": { "code": PARSE_ERROR, "message": err.to_string() },
+            })),
+        };
+        if let Some(response) = response {
+            writeln!(writer, "{}", response)?;
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::{Graph, GraphConfig};
+    use crate::mcp::serve;
+    use serde_json::Value;
+
+    #[test]
+    fn mcp_session() {
+        let g = Graph::from(GraphConfig::default());
+        let input = [
+            r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#,
+            r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+            r#"{"jsonrpc":"2.0","id":2,"method":"tools/list"}"#,
+            r#"{"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"related_files","arguments":{"file":"src/symbol.rs","top":3}}}"#,
+            r#"{"jsonrpc":"2.0","id":4,"method":"tools/call","params":{"name":"symbol_lookup","arguments":{"name":"SymbolGraph"}}}"#,
+            r#"{"jsonrpc":"2.0","id":5,"method":"tools/call","params":{"name":"related_files","arguments":{}}}"#,
+            r#"{"jsonrpc":"2.0","id":6,"method":"unknown"}"#,
+        ]
+        .join("\n");
+        let mut output = Vec::new();
+        serve(&g, input.as_bytes(), &mut output).unwrap();
+        let responses: Vec<Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        // no response to the notification
+        assert_eq!(responses.len(), 6);
+        assert_eq!(responses[0]["result"]["serverInfo"]["name"], "gossiphs");
+        assert_eq!(responses[1]["result"]["tools"].as_array().unwrap().len(), 4);
+
+        let text = responses[2]["result"]["content"][0]["text"]
+            .as_str()
+            .unwrap();
+        let related: Vec<Value> = serde_json::from_str(text).unwrap();
+        assert!(!related.is_empty() && related.len() <= 3);
+
+        let text = responses[3]["result"]["content"][0]["text"]
+            .as_str()
+            .unwrap();
+        let definitions: Vec<Value> = serde_json::from_str(text).unwrap();
+        assert_eq!(definitions[0]["definition"]["file"], "src/symbol.rs");
+
+        assert_eq!(responses[4]["result"]["isError"], true);
+        assert_eq!(responses[5]["error"]["code"], -32601);
+    }
+}
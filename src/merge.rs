@@ -0,0 +1,229 @@
+use crate::graph::{resolve_definitions, FileContext, Graph};
+use crate::symbol::{Interner, NodeType, Symbol, SymbolGraph, SymbolKind};
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+impl Graph {
+    /// Add the files of `other` (another repo) under `prefix`, e.g. `payments/src/api.go`,
+    /// then link the refs of each repo to the defs of the other with the same name.
+    /// Only the names defined in a single file of the other repo and nowhere in the repo of the ref
+    /// are linked, like the calls of a client SDK to the service defining them.
+    /// An empty graph takes the settings of the first graph merged into it.
+    pub fn merge(&mut self, other: &Graph, prefix: &str) {
+        let prefix = prefix.trim_matches('/');
+        if self.file_contexts.is_empty() {
+            self.cross_language_ratio = other.cross_language_ratio;
+            self.def_limit = other.def_limit;
+            self.scoring = other.scoring.clone();
+            self.show_progress = other.show_progress;
+            self.threads = other.threads;
+        }
+
+        let mut interner = Interner::default();
+        let mut rename_symbol = |symbol: &Symbol| {
+            let mut symbol = symbol.clone();
+            symbol.file = Arc::from(prefixed(prefix, &symbol.file));
+            interner.intern_symbol(&mut symbol);
+            symbol
+        };
+
+        // symbols and their links, with the same weights
+        let mut indexes: HashMap<NodeIndex, NodeIndex> = HashMap::new();
+        for index in other.symbol_graph.g.node_indices() {
+            let node = &other.symbol_graph.g[index];
+            let new_index = match &node.node_type {
+                NodeType::File => {
+                    let name = prefixed(prefix, &node._id);
+                    self.symbol_graph.add_file(&name);
+                    self.symbol_graph.file_mapping[name.as_str()]
+                }
+                NodeType::Symbol(_) => {
+                    let symbol = rename_symbol(&node.get_symbol().unwrap());
+                    let id = symbol.id();
+                    self.symbol_graph.add_symbol(symbol);
+                    self.symbol_graph.symbol_mapping[id.as_str()]
+                }
+            };
+            indexes.insert(index, new_index);
+        }
+        for edge in other.symbol_graph.g.edge_references() {
+            let (a, b) = (indexes[&edge.source()], indexes[&edge.target()]);
+            if self.symbol_graph.g.find_edge(a, b).is_none() {
+                self.symbol_graph.g.add_edge(a, b, *edge.weight());
+            }
+        }
+
+        // the history
+        let relation_graph = &other._relation_graph;
+        for commit in relation_graph.commits() {
+            let files: Vec<String> = relation_graph
+                .commit_related_files(&commit)
+                .unwrap_or_default()
+                .iter()
+                .map(|file| prefixed(prefix, file))
+                .collect();
+            self._relation_graph.add_commit_node(&commit);
+            for file in &files {
+                self._relation_graph.add_file_node(file);
+                self._relation_graph.add_edge_file2commit(file, &commit);
+            }
+            for issue in relation_graph
+                .commit_related_issues(&commit)
+                .unwrap_or_default()
+            {
+                self._relation_graph.add_issue_node(&issue);
+                for file in &files {
+                    self._relation_graph.add_edge_file2issue(file, &issue);
+                }
+                self._relation_graph.add_edge_commit2issue(&commit, &issue);
+            }
+            for author in relation_graph
+                .commit_related_authors(&commit)
+                .unwrap_or_default()
+            {
+                self._relation_graph.add_author_node(&author);
+                self._relation_graph
+                    .add_edge_author2commit(&author, &commit);
+            }
+        }
+        for (file, co_changes) in &other.co_changes {
+            self.co_changes.insert(
+                prefixed(prefix, file),
+                co_changes
+                    .iter()
+                    .map(|(each, score)| (prefixed(prefix, each), *score))
+                    .collect(),
+            );
+        }
+        self.commit_decays.extend(other.commit_decays.clone());
+        for (name, idf) in &other.symbol_idf {
+            self.symbol_idf.entry(name.clone()).or_insert(*idf);
+        }
+        self.packages
+            .extend(other.packages.iter().map(|package| match package.as_str() {
+                "." => prefix.to_string(),
+                _ => prefixed(prefix, package),
+            }));
+        self.packages.sort();
+        self.truncated_files.extend(
+            other
+                .truncated_files
+                .iter()
+                .map(|file| prefixed(prefix, file)),
+        );
+        self.file_len += other.file_len;
+        self.commit_size_limit += other.commit_size_limit;
+
+        let file_contexts: Vec<FileContext> = other
+            .file_contexts
+            .iter()
+            .map(|file_context| FileContext {
+                path: prefixed(prefix, &file_context.path),
+                symbols: file_context
+                    .symbols
+                    .iter()
+                    .map(&mut rename_symbol)
+                    .collect(),
+            })
+            .collect();
+        link_by_names(&mut self.symbol_graph, &self.file_contexts, &file_contexts);
+        link_by_names(&mut self.symbol_graph, &file_contexts, &self.file_contexts);
+        self.file_contexts.extend(file_contexts);
+        self.related_index = None;
+    }
+}
+
+fn prefixed(prefix: &str, path: &str) -> String {
+    format!("{}/{}", prefix, path)
+}
+
+// refs of `ref_contexts` -> defs of `def_contexts`, see `Graph::merge`
+fn link_by_names(
+    symbol_graph: &mut SymbolGraph,
+    ref_contexts: &[FileContext],
+    def_contexts: &[FileContext],
+) {
+    let symbols_of = |contexts: &[FileContext], kind: SymbolKind| {
+        let mut table: HashMap<Arc<str>, Vec<Symbol>> = HashMap::new();
+        contexts
+            .iter()
+            .flat_map(|file_context| file_context.symbols.iter())
+            .filter(|symbol| symbol.kind == kind)
+            .for_each(|symbol| {
+                table
+                    .entry(symbol.name.clone())
+                    .or_default()
+                    .push(symbol.clone())
+            });
+        table
+    };
+    let local_defs = symbols_of(ref_contexts, SymbolKind::DEF);
+    let remote_defs = symbols_of(def_contexts, SymbolKind::DEF);
+
+    for (name, refs) in symbols_of(ref_contexts, SymbolKind::REF) {
+        if local_defs.contains_key(&name) {
+            continue;
+        }
+        let defs = match remote_defs.get(&name) {
+            Some(defs) => defs,
+            None => continue,
+        };
+        for reference in &refs {
+            let defs = resolve_definitions(reference, defs);
+            let files: HashSet<&Arc<str>> = defs.iter().map(|def| &def.file).collect();
+            if files.len() != 1 {
+                continue;
+            }
+            for def in defs {
+                for symbol in [reference, def] {
+                    symbol_graph.add_symbol(symbol.clone());
+                    symbol_graph.link_file_to_symbol(&symbol.file, symbol);
+                }
+                symbol_graph.link_symbol_to_symbol(reference, def);
+                symbol_graph.enhance_symbol_to_symbol(&reference.id(), &def.id(), 1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::{Graph, GraphConfig};
+
+    #[test]
+    fn merge() {
+        let g = Graph::from(GraphConfig::default());
+        let mut merged = Graph::empty();
+        merged.merge(&g, "a");
+        merged.merge(&g, "b/");
+
+        assert_eq!(merged.files().len(), g.files().len() * 2);
+        assert!(merged.files().contains("a/src/graph.rs"));
+        assert!(merged.files().contains("b/src/graph.rs"));
+        assert_eq!(merged.packages(), vec!["a", "b"]);
+        assert_eq!(
+            merged
+                .list_file_commits(String::from("b/src/graph.rs"))
+                .len(),
+            g.list_file_commits(String::from("src/graph.rs")).len()
+        );
+
+        let scores = |g: &Graph, file: &str| {
+            let mut scores: Vec<(String, usize)> = g
+                .related_files(file.to_string())
+                .into_iter()
+                .map(|each| (each.name, each.score))
+                .collect();
+            scores.sort();
+            scores
+        };
+        // every name is defined in both, nothing is linked across them
+        let expected: Vec<(String, usize)> = scores(&g, "src/symbol.rs")
+            .into_iter()
+            .map(|(name, score)| (format!("b/{}", name), score))
+            .collect();
+        assert_eq!(scores(&merged, "b/src/symbol.rs"), expected);
+    }
+}
@@ -0,0 +1,414 @@
+use crate::api::RelatedFileContext;
+use crate::graph::{extractor_of_extension, Graph};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// the part of a note rewritten by each sync, the rest of the note belongs to the user
+const BEGIN: &str = "<!-- gossiphs:begin -->";
+const END: &str = "<!-- gossiphs:end -->";
+// the keys of the frontmatter rewritten by each sync, the other keys belong to the user
+const MANAGED_KEYS: &[&str] = &["path", "language", "related_files", "score", "tags"];
+
+/// The notes changed by `sync_vault`.
+#[derive(Debug, Default, PartialEq)]
+pub struct SyncSummary {
+    pub created: usize,
+    pub updated: usize,
+    pub removed: usize,
+}
+
+fn note_path(vault: &Path, file: &str) -> PathBuf {
+    vault.join(format!("{}.md", file))
+}
+
+fn language(file: &str) -> Option<String> {
+    let (_, extension) = file.rsplit_once('.')?;
+    extractor_of_extension(&extension.to_lowercase())
+        .map(|extractor| format!("{:?}", extractor).to_lowercase())
+}
+
+// the directory as a nested tag, tags take no spaces or dots
+fn directory_tag(file: &str) -> Option<String> {
+    let (dir, _) = file.rsplit_once('/')?;
+    let tag = dir
+        .chars()
+        .map(|c| match c.is_alphanumeric() || "_-/".contains(c) {
+            true => c,
+            false => '-',
+        })
+        .collect();
+    Some(tag)
+}
+
+// the lines of MANAGED_KEYS, strings are json quoted, which yaml reads as well
+fn managed_keys(file: &str, related: &[RelatedFileContext]) -> Vec<(&'static str, String)> {
+    let mut lines = vec![("path", format!("path: {}", Value::from(file)))];
+    if let Some(language) = language(file) {
+        lines.push(("language", format!("language: {}", language)));
+    }
+    lines.push(("related_files", format!("related_files: {}", related.len())));
+    let score: usize = related.iter().map(|each| each.score).sum();
+    lines.push(("score", format!("score: {}", score)));
+    if let Some(tag) = directory_tag(file) {
+        lines.push(("tags", format!("tags: [{}]", Value::from(tag))));
+    }
+    lines
+}
+
+// the top level entries of the frontmatter, each with its nested lines, and the rest of the note
+fn split_frontmatter(note: &str) -> (Vec<String>, &str) {
+    let Some(rest) = note.strip_prefix("---\n") else {
+        return (Vec::new(), note);
+    };
+    let (yaml, body) = match rest.strip_prefix("---\n") {
+        Some(body) => ("", body),
+        None => match rest.find("\n---\n") {
+            Some(end) => (&rest[..end], &rest[end + 5..]),
+            None => return (Vec::new(), note),
+        },
+    };
+    let mut entries: Vec<String> = Vec::new();
+    for line in yaml.lines() {
+        let nested = line.starts_with(|c: char| c.is_whitespace() || c == '-');
+        match entries.last_mut() {
+            Some(entry) if nested || line.is_empty() => {
+                entry.push('\n');
+                entry.push_str(line);
+            }
+            _ => entries.push(line.to_string()),
+        }
+    }
+    (entries, body)
+}
+
+fn entry_key(entry: &str) -> &str {
+    entry.split(':').next().unwrap_or_default().trim()
+}
+
+// the managed keys replace their old entries in place, the entries of the user are kept
+fn frontmatter(entries: &[String], mut managed: Vec<(&'static str, String)>) -> String {
+    let mut lines = Vec::new();
+    for entry in entries {
+        let key = entry_key(entry);
+        if !MANAGED_KEYS.contains(&key) {
+            lines.push(entry.clone());
+        } else if let Some(index) = managed.iter().position(|(each, _)| *each == key) {
+            lines.push(managed.remove(index).1);
+        }
+    }
+    lines.extend(managed.into_iter().map(|(_, line)| line));
+    match lines.is_empty() {
+        true => String::new(),
+        false => format!("---\n{}\n---\n", lines.join("\n")),
+    }
+}
+
+// a link to each related file, with the symbols linking them and their 1-based lines
+fn managed_content(related: &[RelatedFileContext]) -> String {
+    let mut content = String::new();
+    for each in related {
+        content.push_str(&format!("- [[{}]] score {}\n", each.name, each.score));
+        let symbols: BTreeSet<(&str, &str, usize)> = each
+            .related_symbols
+            .iter()
+            .map(|related| {
+                let symbol = &related.symbol;
+                (
+                    &*symbol.name,
+                    &*symbol.file,
+                    symbol.range.start_point.row + 1,
+                )
+            })
+            .collect();
+        for (name, file, line) in symbols {
+            content.push_str(&format!("    - `{}` {}:{}\n", name, file, line));
+        }
+    }
+    content
+}
+
+// the managed block of a note with the line break after it
+fn block_range(note: &str) -> Option<(usize, usize)> {
+    let start = note.find(BEGIN)?;
+    let mut end = start + note[start..].find(END)? + END.len();
+    if note[end..].starts_with('\n') {
+        end += 1;
+    }
+    Some((start, end))
+}
+
+// a note without a block gets it at the end
+fn replace_block(note: &str, content: &str) -> String {
+    let block = format!("{}\n{}{}\n", BEGIN, content, END);
+    match block_range(note) {
+        Some((start, end)) => format!("{}{}{}", &note[..start], block, &note[end..]),
+        None if note.trim().is_empty() => block,
+        None => format!("{}\n\n{}", note.trim_end(), block),
+    }
+}
+
+fn managed_notes(dir: &Path, notes: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        // `.obsidian`, `.trash` ...
+        if path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+        {
+            continue;
+        }
+        if path.is_dir() {
+            managed_notes(&path, notes)?;
+        } else if path.extension().is_some_and(|ext| ext == "md")
+            && fs::read_to_string(&path).is_ok_and(|note| block_range(&note).is_some())
+        {
+            notes.push(path);
+        }
+    }
+    Ok(())
+}
+
+// the directories left empty by a removed note, up to the vault
+fn remove_empty_dirs(vault: &Path, note: &Path) {
+    let mut dir = note.parent();
+    while let Some(each) = dir.filter(|each| *each != vault) {
+        if fs::remove_dir(each).is_err() {
+            break;
+        }
+        dir = each.parent();
+    }
+}
+
+/// Mirror the files of the graph as the notes of an obsidian vault, linked to their related files.
+/// It can run again on the same vault: the keys of gossiphs in the frontmatter and the managed block of links
+/// of each note are rewritten, the rest of the note is left as is, and the notes of the files gone from the graph
+/// are removed unless the user wrote something in them.
+pub fn sync_vault(g: &Graph, vault: &Path) -> io::Result<SyncSummary> {
+    fs::create_dir_all(vault)?;
+    let mut summary = SyncSummary::default();
+    let files = g.files();
+    let related = g.related_files_batch(files.iter().cloned().collect());
+    for (file, related) in &related {
+        let path = note_path(vault, file);
+        let managed = managed_keys(file, related);
+        let content = managed_content(related);
+        match fs::read_to_string(&path) {
+            Ok(note) => {
+                let (entries, body) = split_frontmatter(&note);
+                let synced = frontmatter(&entries, managed) + &replace_block(body, &content);
+                if synced != note {
+                    fs::write(&path, synced)?;
+                    summary.updated += 1;
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(
+                    &path,
+                    frontmatter(&[], managed) + &replace_block("", &content),
+                )?;
+                summary.created += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    let kept: HashSet<PathBuf> = files.iter().map(|file| note_path(vault, file)).collect();
+    let mut notes = Vec::new();
+    managed_notes(vault, &mut notes)?;
+    for path in notes.into_iter().filter(|path| !kept.contains(path)) {
+        let note = fs::read_to_string(&path)?;
+        let (entries, body) = split_frontmatter(&note);
+        let Some((start, end)) = block_range(body) else {
+            continue;
+        };
+        let rest = frontmatter(&entries, Vec::new()) + &body[..start] + &body[end..];
+        if rest.trim().is_empty() {
+            fs::remove_file(&path)?;
+            remove_empty_dirs(vault, &path);
+        } else {
+            fs::write(&path, rest)?;
+        }
+        summary.removed += 1;
+    }
+    Ok(summary)
+}
+
+fn top_level(file: &str) -> &str {
+    file.split_once('/').map(|(top, _)| top).unwrap_or(".")
+}
+
+/// An obsidian canvas of the top level directories, each edge from the referencing directory
+/// labeled with the sum of the scores of the file pairs between them.
+pub fn module_canvas(g: &Graph) -> Value {
+    let related = g.related_files_batch(g.files().into_iter().collect());
+    let mut modules: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut links: BTreeMap<(&str, &str), usize> = BTreeMap::new();
+    for (file, contexts) in &related {
+        *modules.entry(top_level(file)).or_default() += 1;
+        for each in contexts {
+            let (from, to) = (top_level(&each.name), top_level(file));
+            if from != to {
+                *links.entry((from, to)).or_default() += each.score;
+            }
+        }
+    }
+
+    // a grid, to be arranged in obsidian
+    let columns = (modules.len() as f64).sqrt().ceil().max(1.0) as usize;
+    let nodes: Vec<Value> = modules
+        .iter()
+        .enumerate()
+        .map(|(i, (module, files))| {
+            json!({
+                "id": module,
+                "type": "text",
+                "text": format!("**{}**\n{} files", module, files),
+                "x": (i % columns) * 400,
+                "y": (i / columns) * 240,
+                "width": 260,
+                "height": 120,
+            })
+        })
+        .collect();
+    let edges: Vec<Value> = links
+        .iter()
+        .map(|((from, to), score)| {
+            json!({
+                "id": format!("{}->{}", from, to),
+                "fromNode": from,
+                "toNode": to,
+                "label": score.to_string(),
+            })
+        })
+        .collect();
+    json!({ "nodes": nodes, "edges": edges })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::{Graph, GraphConfig};
+    use crate::obsidian::{
+        frontmatter, module_canvas, replace_block, split_frontmatter, sync_vault, SyncSummary,
+        BEGIN, END,
+    };
+    use std::fs;
+
+    fn graph(files: &[(&str, &str)]) -> Graph {
+        let files = files
+            .iter()
+            .map(|(name, content)| (name.to_string(), content.to_string()))
+            .collect();
+        Graph::from_sources(GraphConfig::default(), files).unwrap()
+    }
+
+    #[test]
+    fn block() {
+        let block = format!("{}\n[[a.rs]]\n{}\n", BEGIN, END);
+        assert_eq!(replace_block("", "[[a.rs]]\n"), block);
+        assert_eq!(
+            replace_block("my notes\n", "[[a.rs]]\n"),
+            format!("my notes\n\n{}", block)
+        );
+        let note = format!("before\n{}after\n", block);
+        assert_eq!(
+            replace_block(&note, "[[b.rs]]\n"),
+            format!("before\n{}\n[[b.rs]]\n{}\nafter\n", BEGIN, END)
+        );
+    }
+
+    #[test]
+    fn user_keys() {
+        let note = "---\npath: \"a.rs\"\naliases:\n  - b\nscore: 1\ntags: [\"x\"]\n---\nbody\n";
+        let (entries, body) = split_frontmatter(note);
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[1], "aliases:\n  - b");
+        assert_eq!(body, "body\n");
+        let managed = vec![
+            ("path", String::from("path: \"a.rs\"")),
+            ("score", String::from("score: 2")),
+        ];
+        assert_eq!(
+            frontmatter(&entries, managed),
+            "---\npath: \"a.rs\"\naliases:\n  - b\nscore: 2\n---\n"
+        );
+        assert_eq!(frontmatter(&entries[..1], Vec::new()), "");
+        assert_eq!(split_frontmatter("---\n---\nbody"), (Vec::new(), "body"));
+    }
+
+    #[test]
+    fn sync() {
+        let vault = std::env::temp_dir().join(format!("gossiphs-vault-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&vault);
+        let helper = (
+            "src/util/helper.rs",
+            "pub fn shared_helper() -> usize { 1 }\n",
+        );
+        let main = ("src/main.rs", "fn main() { shared_helper(); }\n");
+        let other = ("src/other.rs", "fn other() {}\n");
+
+        let g = graph(&[helper, main, other]);
+        let summary = sync_vault(&g, &vault).unwrap();
+        assert_eq!(summary.created, 3);
+        let note = vault.join("src/util/helper.rs.md");
+        let content = fs::read_to_string(&note).unwrap();
+        assert!(content.starts_with("---\npath: \"src/util/helper.rs\"\nlanguage: rust\n"));
+        assert!(content.contains("tags: [\"src/util\"]\n---\n"));
+        assert!(content.contains("- [[src/main.rs]] score "));
+        assert!(content.contains("    - `shared_helper` src/main.rs:1\n"));
+        assert_eq!(sync_vault(&g, &vault).unwrap(), SyncSummary::default());
+
+        // user notes around the block and user keys survive, the notes of deleted files are removed
+        let main_note = vault.join("src/main.rs.md");
+        let edited = fs::read_to_string(&main_note)
+            .unwrap()
+            .replacen("---\n", "---\naliases: [entry]\n", 1)
+            .replacen(BEGIN, &format!("# main\n{}", BEGIN), 1);
+        fs::write(&main_note, &edited).unwrap();
+        let other_note = vault.join("src/other.rs.md");
+        let edited =
+            fs::read_to_string(&other_note)
+                .unwrap()
+                .replacen("---\n", "---\nstatus: draft\n", 1);
+        fs::write(&other_note, &edited).unwrap();
+        fs::write(vault.join("todo.md"), "not generated\n").unwrap();
+        let g = graph(&[main]);
+        let summary = sync_vault(&g, &vault).unwrap();
+        assert_eq!(summary.removed, 2);
+        assert!(!vault.join("src/util").exists());
+        assert!(vault.join("todo.md").exists());
+        let main_content = fs::read_to_string(&main_note).unwrap();
+        assert!(main_content.starts_with("---\naliases: [entry]\npath: \"src/main.rs\"\n"));
+        assert!(main_content.contains("related_files: 0\n"));
+        assert!(main_content.contains("# main\n"));
+        assert!(!main_content.contains("[[src/util/helper.rs]]"));
+        assert_eq!(
+            fs::read_to_string(&other_note).unwrap(),
+            "---\nstatus: draft\n---\n"
+        );
+        fs::remove_dir_all(&vault).unwrap();
+    }
+
+    #[test]
+    fn canvas() {
+        let g = graph(&[
+            ("lib/helper.rs", "pub fn shared_helper() -> usize { 1 }\n"),
+            ("app/main.rs", "fn main() { shared_helper(); }\n"),
+            ("app/cli.rs", "fn cli() {}\n"),
+        ]);
+        let canvas = module_canvas(&g);
+        let nodes = canvas["nodes"].as_array().unwrap();
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0]["id"], "app");
+        assert_eq!(nodes[0]["text"], "**app**\n2 files");
+        let edges = canvas["edges"].as_array().unwrap();
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0]["fromNode"], "app");
+        assert_eq!(edges[0]["toNode"], "lib");
+    }
+}
@@ -0,0 +1,565 @@
+use crate::server::VERSION;
+use serde_json::{json, Map, Value};
+
+// name, type, required, description
+type Param = (&'static str, &'static str, bool, &'static str);
+
+struct Route {
+    method: &'static str,
+    path: &'static str,
+    summary: &'static str,
+    params: &'static [Param],
+    // schema of the json body
+    body: Option<fn() -> Value>,
+    // of the successful responses, the errors are always json
+    content: &'static str,
+    response: fn() -> Value,
+}
+
+const JSON: &str = "application/json";
+// one json value per line
+const NDJSON: &str = "application/x-ndjson";
+const HTML: &str = "text/html";
+const TEXT: &str = "text/plain";
+
+const PROJECT: Param = (
+    "project",
+    "string",
+    false,
+    "name of a `--project`, the main one if not set",
+);
+const PATH: Param = ("path", "string", true, "file path relative to the repo");
+const TOP: Param = ("top", "integer", false, "keep the strongest ones only");
+const MIN_SCORE: Param = ("min_score", "integer", false, "drop the weaker ones");
+const DIRECTION: Param = ("direction", "string", false, "in (default), out or both");
+const OFFSET: Param = ("offset", "integer", false, "skip the first ones");
+const LIMIT: Param = ("limit", "integer", false, "size of the page");
+
+fn schema(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+fn array_of(item: Value) -> Value {
+    json!({ "type": "array", "items": item })
+}
+
+fn strings() -> Value {
+    array_of(json!({ "type": "string" }))
+}
+
+fn object() -> Value {
+    json!({ "type": "object" })
+}
+
+fn string() -> Value {
+    json!({ "type": "string" })
+}
+
+const ROUTES: &[Route] = &[
+    Route {
+        method: "get",
+        path: "/",
+        summary: "version of the server, and the stats of the main graph",
+        params: &[],
+        body: None,
+        content: JSON,
+        response: || schema("Desc"),
+    },
+    Route {
+        method: "get",
+        path: "/health",
+        summary: "ok once the server is up",
+        params: &[],
+        body: None,
+        content: JSON,
+        response: || schema("Health"),
+    },
+    Route {
+        method: "get",
+        path: "/projects",
+        summary: "names of the other projects",
+        params: &[],
+        body: None,
+        content: JSON,
+        response: strings,
+    },
+    Route {
+        method: "get",
+        path: "/file/list",
+        summary: "files of the graph, sorted",
+        params: &[PROJECT, OFFSET, LIMIT],
+        body: None,
+        content: JSON,
+        response: strings,
+    },
+    Route {
+        method: "get",
+        path: "/file/metadata",
+        summary: "symbols, commits and issues of a file",
+        params: &[PROJECT, PATH],
+        body: None,
+        content: JSON,
+        response: || schema("FileMetadata"),
+    },
+    Route {
+        method: "get",
+        path: "/file/commits",
+        summary: "commits changing a file, the latest first",
+        params: &[PROJECT, PATH],
+        body: None,
+        content: JSON,
+        response: || array_of(schema("CommitInfo")),
+    },
+    Route {
+        method: "get",
+        path: "/file/relation",
+        summary: "files related to a file, the strongest first",
+        params: &[
+            PROJECT,
+            PATH,
+            TOP,
+            MIN_SCORE,
+            DIRECTION,
+            OFFSET,
+            LIMIT,
+            (
+                "fields",
+                "string",
+                false,
+                "comma separated fields of each file, all of them if not set",
+            ),
+        ],
+        body: None,
+        content: JSON,
+        response: || array_of(schema("RelatedFileContext")),
+    },
+    Route {
+        method: "post",
+        path: "/file/relation/batch",
+        summary: "related files of many files, by path",
+        params: &[PROJECT, TOP, MIN_SCORE, DIRECTION],
+        body: Some(strings),
+        content: JSON,
+        response: || {
+            json!({
+                "type": "object",
+                "additionalProperties": array_of(schema("RelatedFileContext")),
+            })
+        },
+    },
+    Route {
+        method: "get",
+        path: "/file/pairs",
+        summary: "symbol pairs linking two files",
+        params: &[
+            PROJECT,
+            ("src", "string", true, "the referencing file"),
+            ("dst", "string", true, "the defining file"),
+        ],
+        body: None,
+        content: JSON,
+        response: || array_of(schema("DefRefPair")),
+    },
+    Route {
+        method: "get",
+        path: "/symbol/relation",
+        summary: "ids of the symbols linked to the symbol at a position, with the weights",
+        params: &[
+            PROJECT,
+            PATH,
+            ("start_byte", "integer", true, "start of the symbol"),
+        ],
+        body: None,
+        content: JSON,
+        response: || json!({ "type": "object", "additionalProperties": { "type": "integer" } }),
+    },
+    Route {
+        method: "get",
+        path: "/symbol/metadata",
+        summary: "a symbol by its id",
+        params: &[PROJECT, ("id", "string", true, "see `Symbol.id`")],
+        body: None,
+        content: JSON,
+        response: || schema("Symbol"),
+    },
+    Route {
+        method: "get",
+        path: "/commit/files",
+        summary: "files changed by a commit",
+        params: &[PROJECT, ("sha", "string", true, "full hash of the commit")],
+        body: None,
+        content: JSON,
+        response: strings,
+    },
+    Route {
+        method: "get",
+        path: "/issue/files",
+        summary: "files changed by the commits mentioning an issue",
+        params: &[PROJECT, ("issue", "string", true, "like `#123`")],
+        body: None,
+        content: JSON,
+        response: strings,
+    },
+    Route {
+        method: "get",
+        path: "/impact",
+        summary: "files impacted by the changes of a range of commits",
+        params: &[
+            PROJECT,
+            (
+                "range",
+                "string",
+                false,
+                "`A..B`, `A...B` or a commit, the uncommitted changes if not set",
+            ),
+            ("tests_only", "boolean", false, "only the test files"),
+        ],
+        body: None,
+        content: JSON,
+        response: || array_of(schema("ImpactContext")),
+    },
+    Route {
+        method: "post",
+        path: "/impact",
+        summary: "files impacted by the changes of the posted files",
+        params: &[
+            PROJECT,
+            ("tests_only", "boolean", false, "only the test files"),
+        ],
+        body: Some(strings),
+        content: JSON,
+        response: || array_of(schema("ImpactContext")),
+    },
+    Route {
+        method: "get",
+        path: "/relations",
+        summary: "the whole graph as newline delimited json, see `export --format jsonl`",
+        params: &[PROJECT],
+        body: None,
+        content: NDJSON,
+        // of each line
+        response: || schema("RelationLine"),
+    },
+    Route {
+        method: "get",
+        path: "/ws",
+        summary: "websocket pushing the added and removed file relations after each rebuild",
+        params: &[],
+        body: None,
+        content: JSON,
+        // of each message
+        response: || schema("GraphUpdate"),
+    },
+    Route {
+        method: "post",
+        path: "/admin/rebuild",
+        summary: "rebuild the main graph in background",
+        params: &[],
+        body: None,
+        content: JSON,
+        response: || schema("RebuildStatus"),
+    },
+    Route {
+        method: "get",
+        path: "/admin/status",
+        summary: "progress of the last rebuild",
+        params: &[],
+        body: None,
+        content: JSON,
+        response: || schema("RebuildStatus"),
+    },
+    Route {
+        method: "get",
+        path: "/viz",
+        summary: "html page drawing the file relations, see `export --format html`",
+        params: &[PROJECT],
+        body: None,
+        content: HTML,
+        response: string,
+    },
+    Route {
+        method: "get",
+        path: "/metrics",
+        summary: "prometheus metrics of the main graph and the requests",
+        params: &[],
+        body: None,
+        content: TEXT,
+        response: string,
+    },
+    Route {
+        method: "get",
+        path: "/openapi.json",
+        summary: "this document",
+        params: &[],
+        body: None,
+        content: JSON,
+        response: object,
+    },
+];
+
+#[cfg(feature = "graphql")]
+const GRAPHQL: Route = Route {
+    method: "post",
+    path: "/graphql",
+    summary: "graphql queries of the main graph, see `src/graphql.rs` for the schema",
+    params: &[],
+    body: Some(|| {
+        properties(&[
+            ("query", string()),
+            ("operationName", string()),
+            ("variables", object()),
+        ])
+    }),
+    content: JSON,
+    response: || properties(&[("data", object()), ("errors", array_of(object()))]),
+};
+
+fn properties(fields: &[(&str, Value)]) -> Value {
+    let properties: Map<String, Value> = fields
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.clone()))
+        .collect();
+    json!({ "type": "object", "properties": properties })
+}
+
+fn schemas() -> Value {
+    let integer = || json!({ "type": "integer" });
+    let nullable = |kind: &str| json!({ "type": kind, "nullable": true });
+    let counts = || json!({ "type": "object", "additionalProperties": integer() });
+    json!({
+        "Error": properties(&[("error", string()), ("message", string())]),
+        "Point": properties(&[("row", integer()), ("column", integer())]),
+        "Range": properties(&[
+            ("start_byte", integer()),
+            ("end_byte", integer()),
+            ("start_point", schema("Point")),
+            ("end_point", schema("Point")),
+        ]),
+        "Symbol": properties(&[
+            ("file", string()),
+            ("name", string()),
+            ("range", schema("Range")),
+            ("kind", json!({ "type": "string", "enum": ["DEF", "REF", "NAMESPACE"] })),
+            (
+                "symbol_type",
+                json!({
+                    "type": "string",
+                    "enum": ["FUNCTION", "METHOD", "CLASS", "TYPE", "VARIABLE", "UNKNOWN"],
+                }),
+            ),
+            ("scope", string()),
+            ("signature", nullable("string")),
+            ("doc", nullable("string")),
+            ("occurrence", integer()),
+        ]),
+        "RelatedSymbol": properties(&[("symbol", schema("Symbol")), ("weight", integer())]),
+        "RelatedFileContext": properties(&[
+            ("name", string()),
+            ("score", integer()),
+            ("defs", integer()),
+            ("refs", integer()),
+            ("related_symbols", array_of(schema("RelatedSymbol"))),
+        ]),
+        "FileMetadata": properties(&[
+            ("path", string()),
+            ("commits", strings()),
+            ("symbols", array_of(schema("Symbol"))),
+            ("issues", strings()),
+            ("truncated", json!({ "type": "boolean" })),
+        ]),
+        "CommitInfo": properties(&[
+            ("sha", string()),
+            ("author", string()),
+            ("timestamp", integer()),
+            ("subject", string()),
+        ]),
+        "DefRefPair": properties(&[
+            ("src_symbol", schema("Symbol")),
+            ("dst_symbol", schema("Symbol")),
+        ]),
+        "ImpactContext": properties(&[
+            ("name", string()),
+            ("score", integer()),
+            ("sources", strings()),
+            ("symbols", strings()),
+        ]),
+        "GraphStats": properties(&[
+            ("files", integer()),
+            ("commits", integer()),
+            ("symbols", counts()),
+            ("languages", counts()),
+            ("symbol_links", integer()),
+            ("relations", integer()),
+            ("average_degree", json!({ "type": "number" })),
+            ("largest_component", integer()),
+            // (stage, seconds)
+            ("build_timings", array_of(array_of(json!({})))),
+        ]),
+        "Desc": properties(&[
+            ("version", string()),
+            ("project_path", string()),
+            ("commit_id", nullable("string")),
+            ("built_at", integer()),
+            ("stats", schema("GraphStats")),
+        ]),
+        "Health": properties(&[("status", string()), ("files", integer())]),
+        "RebuildStatus": properties(&[
+            ("running", json!({ "type": "boolean" })),
+            ("phase", string()),
+            ("files_processed", integer()),
+            ("files_total", integer()),
+            ("started_at", nullable("integer")),
+            ("finished_at", nullable("integer")),
+            ("error", nullable("string")),
+        ]),
+        "Edge": properties(&[("src", string()), ("dst", string()), ("score", integer())]),
+        "GraphUpdate": properties(&[
+            ("built_at", integer()),
+            ("added", array_of(schema("Edge"))),
+            ("removed", array_of(schema("Edge"))),
+        ]),
+        "FileNode": properties(&[
+            ("id", integer()),
+            ("kind", json!({ "type": "string", "enum": ["FileNode"] })),
+            ("name", string()),
+            ("defs", integer()),
+            ("refs", integer()),
+            ("issues", strings()),
+        ]),
+        "FileRelation": properties(&[
+            ("id", integer()),
+            ("kind", json!({ "type": "string", "enum": ["FileRelation"] })),
+            ("src", integer()),
+            ("dst", integer()),
+            ("score", integer()),
+            ("symbols", array_of(integer())),
+        ]),
+        "SymbolNode": properties(&[
+            ("id", integer()),
+            ("kind", json!({ "type": "string", "enum": ["SymbolNode"] })),
+            ("name", string()),
+            ("range", schema("Range")),
+        ]),
+        "RelationLine": {
+            "oneOf": [schema("FileNode"), schema("FileRelation"), schema("SymbolNode")],
+            "discriminator": { "propertyName": "kind" },
+        },
+    })
+}
+
+/// OpenAPI 3 document of the routes of `server::create_router`.
+pub(crate) fn spec() -> Value {
+    let mut paths: Map<String, Value> = Map::new();
+    let routes = ROUTES.iter();
+    #[cfg(feature = "graphql")]
+    let routes = routes.chain(std::iter::once(&GRAPHQL));
+    for route in routes {
+        let parameters: Vec<Value> = route
+            .params
+            .iter()
+            .map(|(name, kind, required, description)| {
+                json!({
+                    "name": name,
+                    "in": "query",
+                    "required": required,
+                    "description": description,
+                    "schema": { "type": kind },
+                })
+            })
+            .collect();
+        let mut operation = json!({
+            "summary": route.summary,
+            "parameters": parameters,
+            "responses": {
+                "200": {
+                    "description": "ok",
+                    "content": { route.content: { "schema": (route.response)() } },
+                },
+                "default": {
+                    "description": "error",
+                    "content": { "application/json": { "schema": schema("Error") } },
+                },
+            },
+        });
+        if let Some(body) = route.body {
+            operation["requestBody"] = json!({
+                "required": true,
+                "content": { "application/json": { "schema": body() } },
+            });
+        }
+        paths
+            .entry(route.path)
+            .or_insert_with(|| json!({}))
+            .as_object_mut()
+            .unwrap()
+            .insert(route.method.to_string(), operation);
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": { "title": "gossiphs", "version": VERSION },
+        "paths": paths,
+        "components": { "schemas": schemas() },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::openapi::spec;
+    use regex::Regex;
+
+    #[test]
+    fn openapi_spec() {
+        let spec = spec();
+        assert_eq!(spec["openapi"], "3.0.3");
+        assert!(spec["paths"]["/file/relation"]["get"]["parameters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|each| each["name"] == "path" && each["required"] == true));
+        assert!(spec["paths"]["/impact"]["get"].is_object());
+        assert!(spec["paths"]["/impact"]["post"]["requestBody"].is_object());
+        assert!(spec["components"]["schemas"]["Error"].is_object());
+    }
+    #[test]
+    fn every_route() {
+        let spec = spec();
+        // read from the source, the router can not list its routes
+        let server = include_str!("server.rs");
+        let start = server.find("pub fn create_router").unwrap();
+        let end = start + server[start..].find("\n}\n").unwrap();
+        let sources = std::iter::once(&server[start..end]);
+        #[cfg(feature = "graphql")]
+        let sources = sources.chain(std::iter::once(include_str!("graphql.rs")));
+
+        let route = Regex::new(r#"\.route\("([^"]+)", ([^\n]+)"#).unwrap();
+        let method = Regex::new(r"\b(get|post)(_service)?\(").unwrap();
+        let mut count = 0;
+        for source in sources {
+            for each in route.captures_iter(source) {
+                for handler in method.captures_iter(&each[2]) {
+                    assert!(
+                        spec["paths"][&each[1]][&handler[1]].is_object(),
+                        "{} {} not in the spec",
+                        &handler[1],
+                        &each[1]
+                    );
+                    count += 1;
+                }
+            }
+        }
+        // and nothing more
+        let operations: usize = spec["paths"]
+            .as_object()
+            .unwrap()
+            .values()
+            .map(|path| path.as_object().unwrap().len())
+            .sum();
+        assert_eq!(count, operations);
+        assert_eq!(
+            spec["paths"]["/relations"]["get"]["responses"]["200"]["content"]
+                ["application/x-ndjson"]["schema"],
+            serde_json::json!({ "$ref": "#/components/schemas/RelationLine" })
+        );
+    }
+}
@@ -1,8 +1,157 @@
-use crate::graph::{Graph, GraphConfig};
+use crate::api::{
+    AmbiguousSymbol, BusFactorRisk, CommitInfo, Community, Cycle, FileMetadata, FileNode,
+    FileOwnership, FileRank, FileRelation, FunctionEdge, GraphStats, Hotspot, ImpactContext,
+    ImpactedFile, PackageRelation, RelatedDirectoryContext, RelatedFileContext,
+    RelationExplanation, RelationList, RelationPath, Reviewer, SharedCommit, SimilarFile,
+    SymbolContribution, SymbolNode,
+};
+use crate::error;
+use crate::graph::{Graph, GraphConfig, RelatedSymbol};
+use crate::symbol::{DefRefPair, Point, RangeWrapper};
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyValueError};
 use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use serde::Serialize;
+use serde_json::Value;
 
+// raised instead of aborting the interpreter
+create_exception!(gossiphs, GossiphsError, PyException);
+
+impl From<error::GossiphsError> for PyErr {
+    fn from(err: error::GossiphsError) -> PyErr {
+        GossiphsError::new_err(err.to_string())
+    }
+}
+
+// the other python threads keep running while the graph is built
 #[pyfunction]
-pub fn create_graph(config: GraphConfig) -> PyResult<Graph> {
-    let g = Graph::from(config);
+pub fn create_graph(py: Python<'_>, config: GraphConfig) -> PyResult<Graph> {
+    let g = py.allow_threads(|| Graph::try_from(config))?;
     Ok(g)
 }
+
+fn to_py(py: Python<'_>, value: &Value) -> PyResult<PyObject> {
+    Ok(match value {
+        Value::Null => py.None(),
+        Value::Bool(value) => (*value).into_py(py),
+        Value::Number(value) => match (value.as_u64(), value.as_i64()) {
+            (Some(value), _) => value.into_py(py),
+            (_, Some(value)) => value.into_py(py),
+            _ => value.as_f64().unwrap_or_default().into_py(py),
+        },
+        Value::String(value) => value.as_str().into_py(py),
+        Value::Array(items) => {
+            let items = items
+                .iter()
+                .map(|each| to_py(py, each))
+                .collect::<PyResult<Vec<PyObject>>>()?;
+            PyList::new_bound(py, items).into_py(py)
+        }
+        Value::Object(fields) => {
+            let dict = PyDict::new_bound(py);
+            for (key, value) in fields {
+                dict.set_item(key, to_py(py, value)?)?;
+            }
+            dict.into_py(py)
+        }
+    })
+}
+
+/// The fields as a dict, nested classes as dicts too, the same as the json of the server.
+pub(crate) fn to_dict<T: Serialize>(py: Python<'_>, value: &T) -> PyResult<PyObject> {
+    let value =
+        serde_json::to_value(value).map_err(|err| PyValueError::new_err(err.to_string()))?;
+    to_py(py, &value)
+}
+
+pub(crate) fn repr<T: Serialize>(name: &str, value: &T) -> String {
+    format!(
+        "{}({})",
+        name,
+        serde_json::to_string(value).unwrap_or_default()
+    )
+}
+
+pub(crate) fn same<T: Serialize>(a: &T, b: &T) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+// the classes holding plain data, compared and printed by their fields
+macro_rules! data_classes {
+    ($($name:ident),* $(,)?) => {
+        $(
+            #[pymethods]
+            impl $name {
+                fn __repr__(&self) -> String {
+                    repr(stringify!($name), self)
+                }
+
+                fn __eq__(&self, other: &Self) -> bool {
+                    same(self, other)
+                }
+
+                fn to_dict(&self, py: Python<'_>) -> PyResult<PyObject> {
+                    to_dict(py, self)
+                }
+            }
+        )*
+    };
+}
+
+data_classes!(
+    RelatedFileContext,
+    RelatedDirectoryContext,
+    PackageRelation,
+    FileRank,
+    Hotspot,
+    SimilarFile,
+    Community,
+    ImpactedFile,
+    ImpactContext,
+    Reviewer,
+    FileOwnership,
+    BusFactorRisk,
+    SymbolContribution,
+    SharedCommit,
+    RelationExplanation,
+    GraphStats,
+    AmbiguousSymbol,
+    RelationPath,
+    Cycle,
+    FileMetadata,
+    CommitInfo,
+    FunctionEdge,
+    FileNode,
+    FileRelation,
+    SymbolNode,
+    RelationList,
+    RelatedSymbol,
+    Point,
+    RangeWrapper,
+    DefRefPair,
+);
+
+#[cfg(test)]
+mod tests {
+    use crate::api::CommitInfo;
+    use crate::pyapi::{repr, same};
+
+    #[test]
+    fn data_classes() {
+        let commit = CommitInfo {
+            sha: String::from("abc"),
+            author: String::from("a <a@example.com>"),
+            timestamp: 1,
+            subject: String::from("init"),
+        };
+        assert_eq!(
+            repr("CommitInfo", &commit),
+            r#"CommitInfo({"sha":"abc","author":"a <a@example.com>","timestamp":1,"subject":"init"})"#
+        );
+        let mut other = commit.clone();
+        assert!(same(&commit, &other));
+        other.timestamp = 2;
+        assert!(!same(&commit, &other));
+    }
+}
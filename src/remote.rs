@@ -0,0 +1,140 @@
+use crate::error::GossiphsError;
+use git2::build::RepoBuilder;
+use git2::{FetchOptions, Repository};
+use std::path::PathBuf;
+use tracing::info;
+
+/// Whether the project path is the url of a remote repo instead of a local directory,
+/// like `https://github.com/org/repo.git` or `git@github.com:org/repo.git`.
+pub fn is_remote(project_path: &str) -> bool {
+    project_path.contains("://") || project_path.starts_with("git@")
+}
+
+/// Where the remote repo is cloned, reused by the later runs.
+/// It is under the cache directory of the user, which other users can not write to.
+pub fn clone_dir(url: &str) -> PathBuf {
+    let name: String = url
+        .trim_end_matches('/')
+        .trim_end_matches(".git")
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    dirs::cache_dir()
+        .map(|dir| dir.join("gossiphs"))
+        // no home, next to the cache of the local repos
+        .unwrap_or_else(|| PathBuf::from(".gossiphs"))
+        .join("remotes")
+        .join(name)
+}
+
+/// Clone the remote repo, or fetch the latest commits if it is cloned already,
+/// and return the local path. `depth` limits the fetched commits, 0 for the full history.
+/// The clone is bare, the files are read from the commits.
+pub(crate) fn clone_or_fetch(url: &str, depth: u32) -> Result<String, GossiphsError> {
+    let dir = clone_dir(url);
+    let path = dir.to_string_lossy().into_owned();
+    let mut fetch_options = FetchOptions::new();
+    if depth > 0 {
+        fetch_options.depth(depth as i32);
+    }
+
+    match Repository::open_bare(&dir) {
+        Ok(repo) => {
+            info!("fetching {} into {} ...", url, path);
+            // the refspec of origin only updates `refs/remotes/origin/*`,
+            // but HEAD of the bare clone points to the local branch
+            repo.find_remote("origin")?
+                .fetch(
+                    &["+refs/heads/*:refs/heads/*"],
+                    Some(&mut fetch_options),
+                    None,
+                )
+                .map_err(GossiphsError::open(url))?;
+        }
+        Err(_) => {
+            info!("cloning {} into {} ...", url, path);
+            RepoBuilder::new()
+                .bare(true)
+                .fetch_options(fetch_options)
+                .clone(url, &dir)
+                .map_err(GossiphsError::open(url))?;
+        }
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::{Graph, GraphConfig};
+    use crate::remote::{clone_dir, is_remote};
+    use git2::{Repository, Signature};
+    use std::path::Path;
+
+    #[test]
+    fn remote_url() {
+        assert!(is_remote("https://github.com/williamfzc/gossiphs.git"));
+        assert!(is_remote("git@github.com:williamfzc/gossiphs.git"));
+        assert!(!is_remote("."));
+        assert!(!is_remote("../gossiphs"));
+        assert_eq!(
+            clone_dir("https://github.com/williamfzc/gossiphs.git"),
+            clone_dir("https://github.com/williamfzc/gossiphs")
+        );
+    }
+
+    #[test]
+    fn clone_local() {
+        let root = Path::new(".").canonicalize().unwrap();
+        let mut config = GraphConfig::default();
+        config.project_path = format!("file://{}", root.to_string_lossy());
+        let g = Graph::from(config.clone());
+        assert!(g.files().contains("src/graph.rs"));
+        assert!(!g.related_files(String::from("src/symbol.rs")).is_empty());
+
+        // cloned already
+        let g = Graph::from(config);
+        assert!(g.files().contains("src/graph.rs"));
+    }
+
+    #[test]
+    fn fetch_new_commits() {
+        let repo_path =
+            std::env::temp_dir().join(format!("gossiphs-remote-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&repo_path);
+        let repo = Repository::init(&repo_path).unwrap();
+        let signature = Signature::now("gossiphs", "gossiphs@example.com").unwrap();
+        let commit = |file: &str, content: &str| {
+            std::fs::write(repo_path.join(file), content).unwrap();
+            let mut index = repo.index().unwrap();
+            index.add_path(Path::new(file)).unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let parents: Vec<_> = repo
+                .head()
+                .ok()
+                .and_then(|head| head.peel_to_commit().ok())
+                .into_iter()
+                .collect();
+            let parents: Vec<_> = parents.iter().collect();
+            repo.commit(Some("HEAD"), &signature, &signature, file, &tree, &parents)
+                .unwrap()
+                .to_string()
+        };
+        commit("a.rs", "pub fn shared_a() {}\n");
+
+        let url = format!("file://{}", repo_path.to_string_lossy());
+        let _ = std::fs::remove_dir_all(clone_dir(&url));
+        let mut config = GraphConfig::default();
+        config.project_path = url.clone();
+        let g = Graph::from(config.clone());
+        assert!(!g.files().contains("b.rs"));
+
+        // the second run analyzes the new commit, not the one of the first clone
+        let head = commit("b.rs", "fn b() { shared_a(); }\n");
+        let g = Graph::from(config);
+        assert!(g.files().contains("b.rs"));
+        assert_eq!(g.commit_id(), Some(head));
+
+        std::fs::remove_dir_all(clone_dir(&url)).unwrap();
+        std::fs::remove_dir_all(&repo_path).unwrap();
+    }
+}
@@ -3,6 +3,13 @@ use crate::extractor::Extractor;
 /*
 tree-sitter query syntax
 https://tree-sitter.github.io/tree-sitter/using-parsers#query-syntax
+
+capture names of export grammar decide the symbol type:
+@function / @method / @class / @type / @variable
+
+special captures:
+@default: anonymous default export, named after the file
+@module: (import grammar) path of a module, links to the whole file
  */
 pub struct Rule {
     // which symbols has been used (possibly imported) in this file
@@ -32,13 +39,24 @@ pub fn get_rule(extractor_type: &Extractor) -> Rule {
     name: (identifier) @function))
 "#,
             export_grammar: r#"
-(function_item name: (identifier) @exported_symbol)
-(function_signature_item name: (identifier) @exported_symbol)
+(source_file (function_item name: (identifier) @function))
+(mod_item body: (declaration_list (function_item name: (identifier) @function)))
+(impl_item body: (declaration_list (function_item name: (identifier) @method)))
+(trait_item body: (declaration_list (function_item name: (identifier) @method)))
+(function_signature_item name: (identifier) @method)
 (generic_function
-  function: (identifier) @exported_symbol)
+  function: (identifier) @function)
 (generic_function
   function: (scoped_identifier
-    name: (identifier) @exported_symbol))
+    name: (identifier) @function))
+(struct_item name: (type_identifier) @class)
+(union_item name: (type_identifier) @class)
+(enum_item name: (type_identifier) @type)
+(trait_item name: (type_identifier) @type)
+(type_item name: (type_identifier) @type)
+(const_item name: (identifier) @variable)
+(static_item name: (identifier) @variable)
+(macro_definition name: (identifier) @function)
 "#,
             namespace_grammar: r#"
 (function_item) @body
@@ -51,17 +69,21 @@ pub fn get_rule(extractor_type: &Extractor) -> Rule {
             import_grammar: r#"
 (identifier) @variable_name
 (type_identifier) @variable_name
+(export_statement "*" source: (string (string_fragment) @module))
 "#,
             export_grammar: r#"
-(export_statement (function_declaration name: (identifier) @exported_symbol))
-(export_statement (arrow_function (identifier) @exported_symbol))
-(export_statement (generator_function_declaration name: (identifier) @exported_symbol))
-(method_definition name: (property_identifier) @exported_symbol)
-(export_statement (type_alias_declaration name: (type_identifier) @exported_symbol))
-(export_statement (interface_declaration name: (type_identifier) @exported_symbol))
-(export_statement (class_declaration name: (type_identifier) @exported_symbol))
+(export_statement (function_declaration name: (identifier) @function))
+(export_statement (arrow_function (identifier) @function))
+(export_statement (generator_function_declaration name: (identifier) @function))
+(method_definition name: (property_identifier) @method)
+(export_statement (type_alias_declaration name: (type_identifier) @type))
+(export_statement (interface_declaration name: (type_identifier) @type))
+(export_statement (class_declaration name: (type_identifier) @class))
 (export_specifier (identifier) @exported_symbol)
-(lexical_declaration (variable_declarator name: (identifier) @lexical_symbol))
+(lexical_declaration (variable_declarator name: (identifier) @variable))
+(export_statement (enum_declaration name: (identifier) @type))
+(namespace_export (identifier) @variable)
+(export_statement value: [(arrow_function) (function_expression) (class)] @default)
 "#,
             namespace_grammar: r#"
 (class_declaration) @body
@@ -79,12 +101,16 @@ pub fn get_rule(extractor_type: &Extractor) -> Rule {
 (field_identifier) @variable_name
 "#,
             export_grammar: r#"
-(function_declaration name: (identifier) @exported_symbol)
-(method_declaration name: (field_identifier) @exported_symbol)
-(type_alias name: (type_identifier) @exported_symbol)
-(type_spec name: (type_identifier) @exported_symbol)
-(const_spec name: (identifier) @exported_symbol)
-(var_spec name: (identifier) @exported_symbol)
+(function_declaration name: (identifier) @function)
+(method_declaration name: (field_identifier) @method)
+(type_alias name: (type_identifier) @type)
+(type_spec name: (type_identifier) @type)
+(const_spec name: (identifier) @variable)
+(var_spec name: (identifier) @variable)
+(method_elem name: (field_identifier) @method)
+(field_declaration
+  name: (field_identifier) @variable
+  (#match? @variable "^[A-Z]"))
 "#,
             namespace_grammar: r#"
 (function_declaration) @body
@@ -95,11 +121,26 @@ pub fn get_rule(extractor_type: &Extractor) -> Rule {
 
         Extractor::Python => Rule {
             import_grammar: r#"
+(decorator (identifier) @function)
+(decorator (call function: (identifier) @function))
+(decorator (attribute attribute: (identifier) @function))
+(decorator (call function: (attribute attribute: (identifier) @function)))
 (identifier) @variable_name
 "#,
             export_grammar: r#"
-(function_definition name: (identifier) @exported_symbol)
-(class_definition name: (identifier) @exported_symbol)
+(function_definition name: (identifier) @function)
+(class_definition name: (identifier) @class)
+(module
+  (expression_statement
+    (assignment
+      left: (identifier) @variable
+      (#match? @variable "^[A-Z][A-Z0-9_]*$"))))
+(class_definition
+  body: (block
+    (expression_statement
+      (assignment
+        left: (identifier) @variable
+        type: (_)))))
 "#,
             namespace_grammar: r#"
 (function_definition) @body
@@ -113,8 +154,8 @@ pub fn get_rule(extractor_type: &Extractor) -> Rule {
 (identifier) @variable_name
     "#,
             export_grammar: r#"
-(function_declaration name: (identifier) @exported_symbol)
-(class_declaration name: (identifier) @exported_symbol)
+(function_declaration name: (identifier) @function)
+(class_declaration name: (identifier) @class)
     "#,
             namespace_grammar: r#"
 (function_declaration) @body
@@ -128,7 +169,7 @@ pub fn get_rule(extractor_type: &Extractor) -> Rule {
   "#,
             // todo: not enough maybe
             export_grammar: r#"
-(class_declaration name: (identifier) @exported_symbol)
+(class_declaration name: (identifier) @class)
   "#,
             namespace_grammar: r#"
 (class_declaration) @body
@@ -141,8 +182,16 @@ pub fn get_rule(extractor_type: &Extractor) -> Rule {
 (identifier (simple_identifier) @variable_name)
   "#,
             export_grammar: r#"
-(class_declaration (type_identifier) @exported_symbol)
-(function_declaration (simple_identifier) @exported_symbol)
+(class_declaration (type_identifier) @class)
+(function_declaration (simple_identifier) @function)
+(object_declaration (type_identifier) @class)
+(companion_object (type_identifier) @class)
+(source_file
+  (property_declaration
+    (variable_declaration (simple_identifier) @variable)))
+(class_body
+  (property_declaration
+    (variable_declaration (simple_identifier) @variable)))
   "#,
             namespace_grammar: "",
             namespace_filter_level: 0,
@@ -150,14 +199,68 @@ pub fn get_rule(extractor_type: &Extractor) -> Rule {
 
         Extractor::Swift => Rule {
             import_grammar: r#"
-((simple_identifier) @exported_symbol)
+(call_expression (simple_identifier) @function)
+(navigation_suffix suffix: (simple_identifier) @method)
+(user_type (type_identifier) @class)
   "#,
-            // TODO: not enough
             export_grammar: r#"
-(function_declaration (simple_identifier) @method)
+(class_declaration declaration_kind: "class" name: (type_identifier) @class)
+(class_declaration declaration_kind: "struct" name: (type_identifier) @class)
+(class_declaration declaration_kind: "actor" name: (type_identifier) @class)
+(class_declaration declaration_kind: "enum" name: (type_identifier) @type)
+(class_declaration declaration_kind: "extension" name: (user_type (type_identifier) @class))
+(protocol_declaration name: (type_identifier) @type)
+(protocol_function_declaration name: (simple_identifier) @method)
+(function_declaration name: (simple_identifier) @function)
   "#,
             namespace_grammar: "",
             namespace_filter_level: 0,
         },
+
+        // script blocks of single-file components
+        Extractor::Vue | Extractor::Svelte => get_rule(&Extractor::TypeScript),
+
+        // IDL files have no tree-sitter grammar here,
+        // their rules are regex (one per line, the first group is the symbol name)
+        Extractor::Proto => Rule {
+            import_grammar: r#"
+(?m)^\s*(?:repeated\s+|optional\s+|required\s+)?(?:\w+\.)*([A-Z]\w*)\s+\w+\s*=
+\(\s*(?:stream\s+)?(?:\w+\.)*(\w+)\s*\)
+"#,
+            export_grammar: r#"
+\bmessage\s+(?P<class>\w+)
+\bservice\s+(?P<class>\w+)
+\brpc\s+(?P<method>\w+)
+\benum\s+(?P<type>\w+)
+"#,
+            namespace_grammar: "",
+            namespace_filter_level: 0,
+        },
+
+        Extractor::Thrift => Rule {
+            import_grammar: r#"
+(?m)^\s*\d+\s*:\s*(?:required\s+|optional\s+)?(?:\w+\.)*([A-Z]\w*)\s+\w+
+(?m)^\s*(?:oneway\s+)?(?:\w+\.)*([A-Z]\w*)\s+\w+\s*\(
+"#,
+            export_grammar: r#"
+\b(?:struct|union|exception|service)\s+(?P<class>\w+)
+\benum\s+(?P<type>\w+)
+(?m)^\s*(?:oneway\s+)?[\w.<>, ]+?\s+(?P<method>\w+)\s*\(
+\bconst\s+[\w.<>, ]+?\s+(?P<variable>\w+)\s*=
+"#,
+            namespace_grammar: "",
+            namespace_filter_level: 0,
+        },
+
+        // documents only refer to code: inline code spans and relative file links
+        Extractor::Markdown => Rule {
+            import_grammar: r#"
+`(?:\w+(?:::|\.))*([A-Za-z_]\w*)(?:\(\))?`
+\[[^\]]*\]\(([^)\s#]+)(?:#[^)]*)?\)
+"#,
+            export_grammar: "",
+            namespace_grammar: "",
+            namespace_filter_level: 0,
+        },
     }
 }
@@ -1,72 +1,664 @@
-use crate::graph::{Graph};
-use crate::symbol::{Symbol, SymbolKind};
-use axum::extract::Query;
-use axum::routing::get;
+use crate::daemon::{Request as QueryRequest, Response as QueryResponse};
+use crate::graph::{BuildProgress, Graph, GraphConfig};
+use crate::history::changed_paths;
+use crate::openapi;
+use crate::symbol::{DefRefPair, Symbol, SymbolKind};
+use axum::body::Body;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{MatchedPath, Query, Request};
+use axum::http::{header, HeaderValue, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
 use axum::Router;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
-use std::sync::{Arc, RwLock};
-use crate::api::{FileMetadata, RelatedFileContext};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::Infallible;
+use std::fmt::Write;
+use std::io;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::StreamExt;
+use tower_http::compression::CompressionLayer;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tracing::warn;
+use crate::api::{
+    limit_related_files, CommitInfo, FileMetadata, GraphStats, ImpactContext, RelatedFileContext,
+    RelationDirection, RelationLine,
+};
+use crate::export::{to_html, to_jsonl};
+use crate::watch;
 
 lazy_static::lazy_static! {
     pub static ref GRAPH_INST: Arc<RwLock<Graph>> = Arc::new(RwLock::new(Graph::empty()));
+    // the other repos, queried with `project=<name>`
+    pub static ref PROJECTS: RwLock<HashMap<String, Arc<RwLock<Graph>>>> =
+        RwLock::new(HashMap::new());
+    // bearer token required by all the routes but `/health`, see `ServerConfig.token`
+    static ref TOKEN: RwLock<Option<String>> = RwLock::new(None);
+    // unix seconds of the time GRAPH_INST was (re)built
+    static ref BUILT_AT: RwLock<u64> = RwLock::new(0);
+    static ref REQUEST_METRICS: Mutex<BTreeMap<RequestKey, RequestMetric>> =
+        Mutex::new(BTreeMap::new());
+    // the graph is rebuilt with it on `/admin/rebuild`
+    static ref GRAPH_CONFIG: RwLock<Option<GraphConfig>> = RwLock::new(None);
+    static ref REBUILD: Mutex<Rebuild> = Mutex::new(Rebuild::default());
+    // json of the `GraphUpdate`s of GRAPH_INST, pushed to the `/ws` clients
+    static ref UPDATES: broadcast::Sender<Arc<str>> = broadcast::channel(16).0;
 }
 
 pub(crate) const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 #[tokio::main]
 pub async fn server_main(server_conf: ServerConfig) {
-    *GRAPH_INST.write().unwrap() = server_conf.graph;
+    set_graph(server_conf.graph);
+    *GRAPH_CONFIG.write().unwrap() = server_conf.config;
+    *TOKEN.write().unwrap() = server_conf.token;
+    *PROJECTS.write().unwrap() = server_conf
+        .projects
+        .into_iter()
+        .map(|(name, g)| (name, Arc::new(RwLock::new(g))))
+        .collect();
+    if let Some(watch_conf) = server_conf.watch {
+        watch::spawn(watch_conf, GRAPH_INST.clone(), |mut g| {
+            g.build_index();
+            set_graph(g);
+        });
+    }
 
-    let routers = create_router();
+    // gzip or brotli if the client accepts them, `/relations` and `/viz` are large
+    let mut routers = create_router().layer(CompressionLayer::new());
+    if !server_conf.cors_origins.is_empty() {
+        routers = routers.layer(cors_layer(&server_conf.cors_origins));
+    }
 
-    let listener = tokio::net::TcpListener::bind(format!("127.0.0.1:{}", server_conf.port))
-        .await
-        .unwrap();
+    let listener =
+        tokio::net::TcpListener::bind(format!("{}:{}", server_conf.host, server_conf.port))
+            .await
+            .unwrap();
     axum::serve(listener, routers).await.unwrap();
 }
 
+/// Let the pages of these origins call the api, `*` for any origin.
+fn cors_layer(origins: &[String]) -> CorsLayer {
+    let allow_origin = match origins.iter().any(|each| each == "*") {
+        true => AllowOrigin::any(),
+        false => AllowOrigin::list(
+            origins
+                .iter()
+                .filter_map(|each| HeaderValue::from_str(each).ok()),
+        ),
+    };
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::GET, Method::POST])
+        .allow_headers([header::CONTENT_TYPE, header::AUTHORIZATION])
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|each| each.as_secs())
+        .unwrap_or_default()
+}
+
+/// The graph of the project, the main one without `project`, 404 for unknown projects.
+pub(crate) fn project_graph(params: ProjectParams) -> Result<Arc<RwLock<Graph>>, ApiError> {
+    match params.project {
+        None => Ok(GRAPH_INST.clone()),
+        Some(name) => PROJECTS
+            .read()
+            .unwrap()
+            .get(&name)
+            .cloned()
+            .ok_or_else(|| ApiError::not_found("project", &name)),
+    }
+}
+
+/// The key of the file in the graph, 404 for the files not in it.
+pub(crate) fn graph_file(g: &Graph, path: &str) -> Result<String, ApiError> {
+    let path = g.normalize_path(path);
+    match g.symbol_graph.file_mapping.contains_key(path.as_str()) {
+        true => Ok(path),
+        false => Err(ApiError::not_found("file", &path)),
+    }
+}
+
+/// Body of the error responses, like `{"error": "not_found", "message": "file a.rs not in the graph"}`
+#[derive(Serialize, Debug)]
+pub(crate) struct ApiError {
+    #[serde(skip)]
+    status: StatusCode,
+    // not_found, bad_request or internal
+    error: &'static str,
+    pub(crate) message: String,
+}
+
+// `ApiError` as read by `query_server`
+#[derive(Deserialize)]
+struct ApiErrorBody {
+    message: String,
+}
+
+impl ApiError {
+    fn not_found(kind: &str, name: &str) -> ApiError {
+        ApiError {
+            status: StatusCode::NOT_FOUND,
+            error: "not_found",
+            message: format!("{} {} not in the graph", kind, name),
+        }
+    }
+
+    fn bad_request(message: String) -> ApiError {
+        ApiError {
+            status: StatusCode::BAD_REQUEST,
+            error: "bad_request",
+            message,
+        }
+    }
+
+    fn unauthorized() -> ApiError {
+        ApiError {
+            status: StatusCode::UNAUTHORIZED,
+            error: "unauthorized",
+            message: String::from("missing or invalid bearer token"),
+        }
+    }
+
+    fn internal(message: String) -> ApiError {
+        ApiError {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            error: "internal",
+            message,
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, axum::Json(self)).into_response()
+    }
+}
+
+fn set_graph(g: Graph) {
+    // walking the relations is not free, only done for the `/ws` clients
+    let changes = (UPDATES.receiver_count() > 0)
+        .then(|| diff_edges(&file_edges(&GRAPH_INST.read().unwrap()), &file_edges(&g)));
+    *GRAPH_INST.write().unwrap() = g;
+    let built_at = now();
+    *BUILT_AT.write().unwrap() = built_at;
+    if let Some((added, removed)) = changes {
+        let update = GraphUpdate {
+            built_at,
+            added,
+            removed,
+        };
+        // no error but the last client leaving meanwhile
+        let _ = UPDATES.send(serde_json::to_string(&update).unwrap().into());
+    }
+}
+
+/// The scores of the file relations of `/relations`, by (src, dst).
+fn file_edges(g: &Graph) -> HashMap<(String, String), usize> {
+    let mut names: Vec<String> = Vec::new();
+    let mut edges = HashMap::new();
+    // file nodes come first, the relations refer to them by id
+    g.for_each_relation(|line| match line {
+        RelationLine::FileNode(node) => names.push(node.name),
+        RelationLine::FileRelation(relation) => {
+            let key = (names[relation.src].clone(), names[relation.dst].clone());
+            edges.insert(key, relation.score);
+        }
+        RelationLine::SymbolNode(_) => {}
+    });
+    edges
+}
+
+/// The added and the removed edges, a changed score is both with the new and the old score.
+fn diff_edges(
+    old: &HashMap<(String, String), usize>,
+    new: &HashMap<(String, String), usize>,
+) -> (Vec<Edge>, Vec<Edge>) {
+    let only_in = |a: &HashMap<(String, String), usize>, b: &HashMap<(String, String), usize>| {
+        let mut edges: Vec<Edge> = a
+            .iter()
+            .filter(|(key, score)| b.get(*key) != Some(*score))
+            .map(|((src, dst), score)| Edge {
+                src: src.clone(),
+                dst: dst.clone(),
+                score: *score,
+            })
+            .collect();
+        edges.sort_by(|x, y| (&x.src, &x.dst).cmp(&(&y.src, &y.dst)));
+        edges
+    };
+    (only_in(new, old), only_in(old, new))
+}
+
 pub fn create_router() -> Router {
-    Router::new()
-        .nest(
-            "/file",
-            Router::new()
-                .route("/metadata", get(file_metadata_handler))
-                .route("/relation", get(file_relation_handler))
-                .route("/list", get(file_list_handler)),
-        )
-        .nest(
-            "/symbol",
-            Router::new()
-                .route("/relation", get(symbol_relation_handler))
-                .route("/metadata", get(symbol_metadata_handler)),
-        )
-        .route("/", get(root_handler))
+    // every route is listed in `openapi::ROUTES` as well, checked by `openapi::tests`
+    let router = Router::new()
+        .route("/file/metadata", get(file_metadata_handler))
+        .route("/file/commits", get(file_commits_handler))
+        .route("/file/relation", get(file_relation_handler))
+        .route("/file/relation/batch", post(file_relation_batch_handler))
+        .route("/file/pairs", get(file_pairs_handler))
+        .route("/file/list", get(file_list_handler))
+        .route("/symbol/relation", get(symbol_relation_handler))
+        .route("/symbol/metadata", get(symbol_metadata_handler))
+        .route("/commit/files", get(commit_files_handler))
+        .route("/issue/files", get(issue_files_handler))
+        .route("/admin/rebuild", post(admin_rebuild_handler))
+        .route("/admin/status", get(admin_status_handler))
+        .route("/viz", get(viz_handler))
+        .route("/projects", get(projects_handler))
+        .route("/openapi.json", get(openapi_handler))
+        .route("/relations", get(relations_handler))
+        .route("/impact", get(impact_handler).post(impact_files_handler))
+        .route("/health", get(health_handler))
+        .route("/metrics", get(metrics_handler))
+        .route("/ws", get(ws_handler))
+        .route("/", get(root_handler));
+    #[cfg(feature = "graphql")]
+    let router = router.merge(crate::graphql::router());
+    // after the merge, so that `/graphql` is guarded and measured as well
+    router
+        .layer(axum::middleware::from_fn(check_token))
+        .layer(axum::middleware::from_fn(track_request))
 }
 
 pub struct ServerConfig {
+    // 127.0.0.1 by default, 0.0.0.0 for the other machines, better with a `token`
+    pub host: String,
     pub port: u16,
     pub graph: Graph,
+    // rebuild the graph with this config when files change
+    pub watch: Option<GraphConfig>,
+    // the config the graph was built with, `/admin/rebuild` is refused without it
+    pub config: Option<GraphConfig>,
+    // more repos served by the same server, by the names given to `project=`
+    pub projects: HashMap<String, Graph>,
+    // origins allowed to call the api from browsers, like `http://localhost:3000` or `*`, none by default
+    pub cors_origins: Vec<String>,
+    // `Authorization: Bearer <token>` required by all the routes but `/health` if set
+    pub token: Option<String>,
 }
 
 impl ServerConfig {
     pub fn new(g: Graph) -> ServerConfig {
         ServerConfig {
+            host: String::from("127.0.0.1"),
             port: 9411,
             graph: g,
+            watch: None,
+            config: None,
+            projects: HashMap::new(),
+            cors_origins: Vec::new(),
+            token: None,
+        }
+    }
+}
+
+/// Answer a `query` request by a running server instead of a daemon, `server` being its url like
+/// `http://host:9411`. The errors of the server, like files not in the graph, are `Response::Error`.
+pub fn query_server(
+    server: &str,
+    token: Option<&str>,
+    project: Option<&str>,
+    request: &QueryRequest,
+) -> std::io::Result<QueryResponse> {
+    let (route, path) = match request {
+        QueryRequest::Related { file } => ("/file/relation", Some(file)),
+        QueryRequest::Metadata { file } => ("/file/metadata", Some(file)),
+        QueryRequest::Files => ("/file/list", None),
+        QueryRequest::Stop => {
+            return Ok(QueryResponse::Error(String::from(
+                "only a daemon can be stopped by a query",
+            )))
+        }
+    };
+    let mut http_request = ureq::get(&format!("{}{}", server.trim_end_matches('/'), route));
+    if let Some(path) = path {
+        http_request = http_request.query("path", path);
+    }
+    if let Some(project) = project {
+        http_request = http_request.query("project", project);
+    }
+    if let Some(token) = token {
+        http_request = http_request.set("Authorization", &format!("Bearer {}", token));
+    }
+    match http_request.call() {
+        Ok(response) => Ok(QueryResponse::Ok(response.into_json()?)),
+        Err(ureq::Error::Status(status, response)) => {
+            let message = response
+                .into_json::<ApiErrorBody>()
+                .map(|body| body.message)
+                .unwrap_or_else(|_| format!("status {}", status));
+            Ok(QueryResponse::Error(message))
         }
+        Err(err) => Err(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            err.to_string(),
+        )),
     }
 }
 
 async fn root_handler() -> axum::Json<Desc> {
+    let g = GRAPH_INST.read().unwrap();
     axum::Json(Desc {
         version: VERSION.to_string(),
+        project_path: g.project_path.clone(),
+        commit_id: g.commit_id.clone(),
+        built_at: *BUILT_AT.read().unwrap(),
+        stats: g.stats(),
+    })
+}
+
+async fn health_handler() -> axum::Json<Health> {
+    axum::Json(Health {
+        status: String::from("ok"),
+        files: GRAPH_INST.read().unwrap().file_contexts.len(),
     })
 }
 
+async fn metrics_handler() -> String {
+    let g = GRAPH_INST.read().unwrap();
+    let requests = REQUEST_METRICS.lock().unwrap();
+    render_metrics(&g, *BUILT_AT.read().unwrap(), &requests)
+}
+
+#[derive(Default)]
+struct Rebuild {
+    running: bool,
+    progress: BuildProgress,
+    // unix seconds
+    started_at: Option<u64>,
+    finished_at: Option<u64>,
+    error: Option<String>,
+}
+
 #[derive(Deserialize, Serialize, Debug)]
+struct RebuildStatus {
+    running: bool,
+    phase: String,
+    files_processed: usize,
+    files_total: usize,
+    started_at: Option<u64>,
+    finished_at: Option<u64>,
+    // of the last rebuild, the old graph is kept
+    error: Option<String>,
+}
+
+impl Rebuild {
+    fn status(&self) -> RebuildStatus {
+        let progress = self.progress.status();
+        RebuildStatus {
+            running: self.running,
+            phase: progress.phase,
+            files_processed: progress.files_processed,
+            files_total: progress.files_total,
+            started_at: self.started_at,
+            finished_at: self.finished_at,
+            error: self.error.clone(),
+        }
+    }
+}
+
+async fn ws_handler(ws: WebSocketUpgrade) -> Response {
+    ws.on_upgrade(push_updates)
+}
+
+async fn push_updates(mut socket: WebSocket) {
+    let mut updates = UPDATES.subscribe();
+    loop {
+        tokio::select! {
+            update = updates.recv() => match update {
+                Ok(update) => {
+                    if socket.send(Message::Text(update.to_string())).await.is_err() {
+                        break;
+                    }
+                }
+                // too slow to keep up, closed so that it reconnects and reloads the graph
+                Err(_) => break,
+            },
+            // pings are answered by axum, the other messages of the client are ignored
+            message = socket.recv() => {
+                if !matches!(message, Some(Ok(_))) {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+async fn admin_rebuild_handler() -> (StatusCode, axum::Json<RebuildStatus>) {
+    let mut rebuild = REBUILD.lock().unwrap();
+    let mut conf = match GRAPH_CONFIG.read().unwrap().clone() {
+        Some(conf) if !rebuild.running => conf,
+        Some(_) => return (StatusCode::CONFLICT, axum::Json(rebuild.status())),
+        None => return (StatusCode::NOT_IMPLEMENTED, axum::Json(rebuild.status())),
+    };
+    conf.progress = BuildProgress::default();
+    *rebuild = Rebuild {
+        running: true,
+        progress: conf.progress.clone(),
+        started_at: Some(now()),
+        finished_at: None,
+        error: None,
+    };
+
+    // the queries keep being served by the old graph until the new one is ready
+    std::thread::spawn(move || {
+        let error = match Graph::try_from(conf) {
+            Ok(mut g) => {
+                g.build_index();
+                set_graph(g);
+                None
+            }
+            Err(err) => {
+                warn!("Failed to rebuild graph, keep the old one: {}", err);
+                Some(err.to_string())
+            }
+        };
+        let mut rebuild = REBUILD.lock().unwrap();
+        rebuild.running = false;
+        rebuild.finished_at = Some(now());
+        rebuild.error = error;
+    });
+    (StatusCode::ACCEPTED, axum::Json(rebuild.status()))
+}
+
+async fn admin_status_handler() -> axum::Json<RebuildStatus> {
+    axum::Json(REBUILD.lock().unwrap().status())
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Debug)]
+struct RequestKey {
+    method: String,
+    // the route, not the uri, so that the query params don't add labels
+    path: String,
+    status: u16,
+}
+
+#[derive(Default, Debug)]
+struct RequestMetric {
+    count: u64,
+    seconds: f64,
+}
+
+async fn check_token(request: Request, next: Next) -> Response {
+    let token = TOKEN.read().unwrap().clone();
+    let authorized = match token {
+        None => true,
+        Some(_) if request.uri().path() == "/health" => true,
+        Some(token) => request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|each| each.to_str().ok())
+            .and_then(|each| each.strip_prefix("Bearer "))
+            .is_some_and(|each| same_token(each.trim(), &token)),
+    };
+    match authorized {
+        true => next.run(request).await,
+        false => ApiError::unauthorized().into_response(),
+    }
+}
+
+// in a time independent of where they differ, not to leak the token byte by byte
+fn same_token(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+async fn track_request(request: Request, next: Next) -> Response {
+    let method = request.method().to_string();
+    let path = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|each| each.as_str().to_string())
+        .unwrap_or_else(|| String::from("unmatched"));
+    let start = Instant::now();
+    let response = next.run(request).await;
+    let key = RequestKey {
+        method,
+        path,
+        status: response.status().as_u16(),
+    };
+    let mut metrics = REQUEST_METRICS.lock().unwrap();
+    let metric = metrics.entry(key).or_default();
+    metric.count += 1;
+    metric.seconds += start.elapsed().as_secs_f64();
+    response
+}
+
+/// Prometheus text format of the graph sizes and the requests served.
+fn render_metrics(
+    g: &Graph,
+    built_at: u64,
+    requests: &BTreeMap<RequestKey, RequestMetric>,
+) -> String {
+    let mut ret = String::new();
+    let gauges = [
+        (
+            "gossiphs_graph_files",
+            "Files in the graph.",
+            g.file_contexts.len() as u64,
+        ),
+        (
+            "gossiphs_graph_symbols",
+            "Symbols in the symbol graph.",
+            g.symbol_graph.g.node_count() as u64,
+        ),
+        (
+            "gossiphs_graph_symbol_links",
+            "Links between the symbols.",
+            g.symbol_graph.g.edge_count() as u64,
+        ),
+        (
+            "gossiphs_graph_built_timestamp_seconds",
+            "Unix time of the last build of the graph.",
+            built_at,
+        ),
+    ];
+    for (name, help, value) in gauges {
+        let _ = writeln!(
+            ret,
+            "# HELP {} {}\n# TYPE {} gauge\n{} {}",
+            name, help, name, name, value
+        );
+    }
+
+    let _ = writeln!(ret, "# HELP gossiphs_http_requests_total Requests served.");
+    let _ = writeln!(ret, "# TYPE gossiphs_http_requests_total counter");
+    for (key, metric) in requests {
+        let _ = writeln!(
+            ret,
+            "gossiphs_http_requests_total{{method=\"{}\",path=\"{}\",status=\"{}\"}} {}",
+            key.method, key.path, key.status, metric.count
+        );
+    }
+    let _ = writeln!(
+        ret,
+        "# HELP gossiphs_http_request_duration_seconds Time spent on the requests."
+    );
+    let _ = writeln!(ret, "# TYPE gossiphs_http_request_duration_seconds summary");
+    for (key, metric) in requests {
+        let labels = format!(
+            "method=\"{}\",path=\"{}\",status=\"{}\"",
+            key.method, key.path, key.status
+        );
+        let _ = writeln!(
+            ret,
+            "gossiphs_http_request_duration_seconds_sum{{{}}} {}",
+            labels, metric.seconds
+        );
+        let _ = writeln!(
+            ret,
+            "gossiphs_http_request_duration_seconds_count{{{}}} {}",
+            labels, metric.count
+        );
+    }
+    ret
+}
+
+async fn viz_handler(
+    Query(project): Query<ProjectParams>,
+) -> Result<axum::response::Html<String>, ApiError> {
+    let relations = project_graph(project)?.read().unwrap().list_all_relations();
+    Ok(axum::response::Html(to_html(&relations)))
+}
+
+async fn openapi_handler() -> axum::Json<serde_json::Value> {
+    axum::Json(openapi::spec())
+}
+
+async fn projects_handler() -> axum::Json<Vec<String>> {
+    let mut names: Vec<String> = PROJECTS.read().unwrap().keys().cloned().collect();
+    names.sort();
+    axum::Json(names)
+}
+
+/// Pushed to the `/ws` clients after each rebuild of the graph.
+#[derive(Serialize, Debug)]
+struct GraphUpdate {
+    built_at: u64,
+    added: Vec<Edge>,
+    removed: Vec<Edge>,
+}
+
+#[derive(Serialize, Debug)]
+struct Edge {
+    src: String,
+    dst: String,
+    score: usize,
+}
+
+#[derive(Serialize)]
 struct Desc {
     version: String,
+    project_path: String,
+    commit_id: Option<String>,
+    // unix seconds
+    built_at: u64,
+    stats: GraphStats,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct Health {
+    status: String,
+    files: usize,
+}
+
+// on top of the params of each route, the main project if none
+#[derive(Deserialize, Serialize, Debug)]
+pub(crate) struct ProjectParams {
+    pub project: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -74,6 +666,48 @@ struct FileParams {
     pub path: String,
 }
 
+#[derive(Deserialize, Serialize, Debug)]
+struct FileRelationParams {
+    pub path: String,
+    pub top: Option<usize>,
+    pub min_score: Option<usize>,
+    // in (default), out or both, see `RelationDirection`
+    pub direction: Option<RelationDirection>,
+    pub offset: Option<usize>,
+    pub limit: Option<usize>,
+    // comma separated fields of each file, like `name,score`, all of them by default
+    pub fields: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct FilePairParams {
+    pub src: String,
+    pub dst: String,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct ImpactParams {
+    // `A..B`, `A...B` or a single commit like `impact --range`, the uncommitted changes if none
+    pub range: Option<String>,
+    // only the test files, like `tests`
+    #[serde(default)]
+    pub tests_only: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct PageParams {
+    pub offset: Option<usize>,
+    pub limit: Option<usize>,
+}
+
+// the paths are posted as a json array
+#[derive(Deserialize, Serialize, Debug)]
+struct FileRelationBatchParams {
+    pub top: Option<usize>,
+    pub min_score: Option<usize>,
+    pub direction: Option<RelationDirection>,
+}
+
 #[derive(Deserialize, Serialize, Debug)]
 struct SymbolParams {
     pub path: String,
@@ -85,37 +719,218 @@ struct SymbolIdParams {
     pub id: String,
 }
 
-async fn file_metadata_handler(Query(params): Query<FileParams>) -> axum::Json<FileMetadata> {
-    let g = GRAPH_INST.read().unwrap();
-    axum::Json(g.file_metadata(params.path))
+#[derive(Deserialize, Serialize, Debug)]
+struct CommitParams {
+    pub sha: String,
 }
 
-async fn file_relation_handler(
+#[derive(Deserialize, Serialize, Debug)]
+struct IssueParams {
+    pub issue: String,
+}
+
+async fn file_metadata_handler(
+    Query(project): Query<ProjectParams>,
     Query(params): Query<FileParams>,
-) -> axum::Json<Vec<RelatedFileContext>> {
-    let g = GRAPH_INST.read().unwrap();
-    axum::Json(g.related_files(params.path))
+) -> Result<axum::Json<FileMetadata>, ApiError> {
+    let g = project_graph(project)?;
+    let g = g.read().unwrap();
+    let path = graph_file(&g, &params.path)?;
+    Ok(axum::Json(g.file_metadata(path)))
 }
 
-async fn file_list_handler() -> axum::Json<HashSet<String>> {
-    let g = GRAPH_INST.read().unwrap();
-    axum::Json(g.files())
+async fn file_commits_handler(
+    Query(project): Query<ProjectParams>,
+    Query(params): Query<FileParams>,
+) -> Result<axum::Json<Vec<CommitInfo>>, ApiError> {
+    let g = project_graph(project)?;
+    let g = g.read().unwrap();
+    let path = graph_file(&g, &params.path)?;
+    Ok(axum::Json(g.file_commits_detailed(path)))
+}
+
+async fn file_relation_handler(
+    Query(project): Query<ProjectParams>,
+    Query(params): Query<FileRelationParams>,
+) -> Result<axum::Json<Vec<serde_json::Value>>, ApiError> {
+    let g = project_graph(project)?;
+    let g = g.read().unwrap();
+    let path = graph_file(&g, &params.path)?;
+    let direction = params.direction.unwrap_or(RelationDirection::IN);
+    let mut files = g.related_files_towards(path, direction);
+    limit_related_files(&mut files, params.top, params.min_score);
+    let files = paginate(files, params.offset, params.limit);
+    Ok(axum::Json(select_fields(&files, params.fields.as_deref())))
+}
+
+async fn file_relation_batch_handler(
+    Query(project): Query<ProjectParams>,
+    Query(params): Query<FileRelationBatchParams>,
+    axum::Json(paths): axum::Json<Vec<String>>,
+) -> Result<axum::Json<HashMap<String, Vec<RelatedFileContext>>>, ApiError> {
+    let g = project_graph(project)?;
+    let g = g.read().unwrap();
+    let direction = params.direction.unwrap_or(RelationDirection::IN);
+    let ret = paths
+        .into_iter()
+        .map(|path| {
+            let mut files = g.related_files_towards(path.clone(), direction);
+            limit_related_files(&mut files, params.top, params.min_score);
+            (path, files)
+        })
+        .collect();
+    Ok(axum::Json(ret))
+}
+
+async fn file_pairs_handler(
+    Query(project): Query<ProjectParams>,
+    Query(params): Query<FilePairParams>,
+) -> Result<axum::Json<Vec<DefRefPair>>, ApiError> {
+    let g = project_graph(project)?;
+    let g = g.read().unwrap();
+    let src = graph_file(&g, &params.src)?;
+    let dst = graph_file(&g, &params.dst)?;
+    Ok(axum::Json(g.pairs_between_files(src, dst)))
+}
+
+// the lines of `export --format jsonl`, the whole graph at once is too large for a json document
+async fn relations_handler(
+    Query(project): Query<ProjectParams>,
+) -> Result<([(header::HeaderName, &'static str); 1], Body), ApiError> {
+    let g = project_graph(project)?;
+    // written on a blocking thread, which holds the lock of the graph instead of the runtime
+    let (tx, rx) = mpsc::channel(16);
+    tokio::task::spawn_blocking(move || {
+        let writer = io::BufWriter::with_capacity(64 * 1024, ChunkWriter(tx));
+        match to_jsonl(&g.read().unwrap(), writer) {
+            Ok(_) => {}
+            // the client went away
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => {}
+            Err(err) => warn!("failed to write the relations: {}", err),
+        }
+    });
+    let body = Body::from_stream(ReceiverStream::new(rx).map(Ok::<_, Infallible>));
+    Ok(([(header::CONTENT_TYPE, "application/x-ndjson")], body))
+}
+
+/// Sends the written bytes to the body of a streamed response.
+struct ChunkWriter(mpsc::Sender<Vec<u8>>);
+
+impl io::Write for ChunkWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .blocking_send(buf.to_vec())
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// the changed files of a range of commits, read from the repo of the graph
+async fn impact_handler(
+    Query(project): Query<ProjectParams>,
+    Query(params): Query<ImpactParams>,
+) -> Result<axum::Json<Vec<ImpactContext>>, ApiError> {
+    let g = project_graph(project)?;
+    let g = g.read().unwrap();
+    let changed = changed_paths(&g.project_path, params.range.as_deref())
+        .map_err(|err| ApiError::bad_request(err.to_string()))?;
+    Ok(axum::Json(impact_of(&g, changed, params.tests_only)))
+}
+
+// the changed files are posted as a json array
+async fn impact_files_handler(
+    Query(project): Query<ProjectParams>,
+    Query(params): Query<ImpactParams>,
+    axum::Json(changed): axum::Json<Vec<String>>,
+) -> Result<axum::Json<Vec<ImpactContext>>, ApiError> {
+    let g = project_graph(project)?;
+    let g = g.read().unwrap();
+    Ok(axum::Json(impact_of(&g, changed, params.tests_only)))
+}
+
+fn impact_of(g: &Graph, changed: Vec<String>, tests_only: bool) -> Vec<ImpactContext> {
+    match tests_only {
+        true => g.related_tests(changed),
+        false => g.impact(changed),
+    }
+}
+
+async fn file_list_handler(
+    Query(project): Query<ProjectParams>,
+    Query(params): Query<PageParams>,
+) -> Result<axum::Json<Vec<String>>, ApiError> {
+    let g = project_graph(project)?;
+    let g = g.read().unwrap();
+    // sorted for stable pages
+    let mut files: Vec<String> = g.files().into_iter().collect();
+    files.sort();
+    Ok(axum::Json(paginate(files, params.offset, params.limit)))
+}
+
+/// Skip the first `offset` items, then keep `limit` of them.
+fn paginate<T>(items: Vec<T>, offset: Option<usize>, limit: Option<usize>) -> Vec<T> {
+    items
+        .into_iter()
+        .skip(offset.unwrap_or(0))
+        .take(limit.unwrap_or(usize::MAX))
+        .collect()
+}
+
+/// Keep only the comma separated `fields` of each item, all of them if none.
+fn select_fields<T: Serialize>(items: &[T], fields: Option<&str>) -> Vec<serde_json::Value> {
+    let fields: Option<HashSet<&str>> =
+        fields.map(|fields| fields.split(',').map(str::trim).collect());
+    items
+        .iter()
+        .map(|item| {
+            let mut value = serde_json::to_value(item).unwrap();
+            if let (Some(fields), Some(object)) = (&fields, value.as_object_mut()) {
+                object.retain(|key, _| fields.contains(key.as_str()));
+            }
+            value
+        })
+        .collect()
+}
+
+async fn commit_files_handler(
+    Query(project): Query<ProjectParams>,
+    Query(params): Query<CommitParams>,
+) -> Result<axum::Json<Vec<String>>, ApiError> {
+    let g = project_graph(project)?;
+    let g = g.read().unwrap();
+    Ok(axum::Json(g.commit_related_files(params.sha)))
+}
+
+async fn issue_files_handler(
+    Query(project): Query<ProjectParams>,
+    Query(params): Query<IssueParams>,
+) -> Result<axum::Json<Vec<String>>, ApiError> {
+    let g = project_graph(project)?;
+    let g = g.read().unwrap();
+    Ok(axum::Json(g.issue_related_files(params.issue)))
 }
 
 async fn symbol_relation_handler(
+    Query(project): Query<ProjectParams>,
     Query(params): Query<SymbolParams>,
-) -> axum::Json<HashMap<String, usize>> {
-    let g = GRAPH_INST.read().unwrap();
+) -> Result<axum::Json<HashMap<String, usize>>, ApiError> {
+    let g = project_graph(project)?;
+    let g = g.read().unwrap();
+    let path = graph_file(&g, &params.path)?;
     let targets: Vec<Symbol> = g
-        .file_metadata(params.path)
+        .file_metadata(path)
         .symbols
         .into_iter()
         .filter(|each| {
-            return each.range.start_byte == params.start_byte && each.kind != SymbolKind::NAMESPACE;
+            each.range.start_byte == params.start_byte && each.kind != SymbolKind::NAMESPACE
         })
         .collect();
-    if targets.len() == 0 {
-        return axum::Json(HashMap::new());
+    if targets.is_empty() {
+        return Ok(axum::Json(HashMap::new()));
     }
     // only one
     let target = &targets[0];
@@ -128,22 +943,152 @@ async fn symbol_relation_handler(
     let str_symbol_map: HashMap<String, usize> = symbol_map
         .into_iter()
         .map(|(key, value)| {
-            return (key.id(), value);
+            (key.id(), value)
         })
         .collect();
-    axum::Json(str_symbol_map)
+    Ok(axum::Json(str_symbol_map))
 }
 
 async fn symbol_metadata_handler(
+    Query(project): Query<ProjectParams>,
     Query(params): Query<SymbolIdParams>,
-) -> axum::Json<Option<Symbol>> {
-    let g = GRAPH_INST.read().unwrap();
-    let ret = g.symbol_graph.symbol_mapping.get(&params.id);
+) -> Result<axum::Json<Symbol>, ApiError> {
+    let g = project_graph(project)?;
+    let g = g.read().unwrap();
+    let ret = g.symbol_graph.symbol_mapping.get(params.id.as_str());
     if ret.is_none() {
-        return axum::Json(None);
+        return Err(ApiError::not_found("symbol", &params.id));
     }
 
-    axum::Json(Option::from(
+    Ok(axum::Json(
         g.symbol_graph.g[*ret.unwrap()].get_symbol().unwrap(),
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::api::RelatedFileContext;
+    use crate::daemon::{Request, Response};
+    use crate::export::to_jsonl;
+    use crate::graph::{Graph, GraphConfig};
+    use crate::server::{
+        create_router, diff_edges, file_edges, paginate, query_server, render_metrics, same_token,
+        select_fields, RequestKey, RequestMetric, GRAPH_INST,
+    };
+    use std::collections::BTreeMap;
+    use std::io::Read;
+
+    #[test]
+    fn paginate_and_select() {
+        let items: Vec<usize> = (0..10).collect();
+        assert_eq!(paginate(items.clone(), Some(3), Some(2)), vec![3, 4]);
+        assert_eq!(paginate(items.clone(), None, Some(2)), vec![0, 1]);
+        assert_eq!(paginate(items.clone(), Some(8), None), vec![8, 9]);
+        assert!(paginate(items, Some(20), None).is_empty());
+
+        let files = vec![RelatedFileContext {
+            name: String::from("src/graph.rs"),
+            score: 3,
+            defs: 1,
+            refs: 2,
+            related_symbols: vec![],
+        }];
+        let all = select_fields(&files, None);
+        assert!(all[0].get("related_symbols").is_some());
+        let some = select_fields(&files, Some("name, score"));
+        assert_eq!(some[0].as_object().unwrap().len(), 2);
+        assert_eq!(some[0]["score"], 3);
+    }
+
+    #[test]
+    fn token() {
+        assert!(same_token("secret", "secret"));
+        assert!(!same_token("secreT", "secret"));
+        assert!(!same_token("secret2", "secret"));
+        assert!(!same_token("", "secret"));
+    }
+
+    #[test]
+    fn metrics() {
+        let g = Graph::from(GraphConfig::default());
+        let mut requests = BTreeMap::new();
+        requests.insert(
+            RequestKey {
+                method: String::from("GET"),
+                path: String::from("/file/relation"),
+                status: 200,
+            },
+            RequestMetric {
+                count: 3,
+                seconds: 0.5,
+            },
+        );
+        let text = render_metrics(&g, 1700000000, &requests);
+        assert!(text.contains("gossiphs_graph_built_timestamp_seconds 1700000000"));
+        assert!(text.contains(&format!("gossiphs_graph_files {}", g.files().len())));
+        assert!(text.contains(
+            "gossiphs_http_requests_total{method=\"GET\",path=\"/file/relation\",status=\"200\"} 3"
+        ));
+    }
+
+    #[test]
+    fn graph_updates() {
+        let g = Graph::from(GraphConfig::default());
+        let edges = file_edges(&g);
+        assert!(!edges.is_empty());
+        let (added, removed) = diff_edges(&edges, &edges);
+        assert!(added.is_empty() && removed.is_empty());
+
+        let mut changed = edges.clone();
+        let key = changed.keys().next().unwrap().clone();
+        *changed.get_mut(&key).unwrap() += 1;
+        changed.insert((String::from("a.rs"), String::from("b.rs")), 1);
+        let (added, removed) = diff_edges(&edges, &changed);
+        assert_eq!(added.len(), 2);
+        assert_eq!(removed.len(), 1);
+        assert_eq!((&removed[0].src, &removed[0].dst), (&key.0, &key.1));
+        assert_eq!(
+            removed[0].score + 1,
+            added
+                .iter()
+                .find(|each| each.src == key.0 && each.dst == key.1)
+                .unwrap()
+                .score
+        );
+    }
+
+    #[test]
+    fn query_by_server() {
+        *GRAPH_INST.write().unwrap() = Graph::from(GraphConfig::default());
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let listener = runtime
+            .block_on(tokio::net::TcpListener::bind("127.0.0.1:0"))
+            .unwrap();
+        let server = format!("http://{}/", listener.local_addr().unwrap());
+        runtime.spawn(async { axum::serve(listener, create_router()).await.unwrap() });
+
+        let related = Request::Related {
+            file: String::from("src/symbol.rs"),
+        };
+        match query_server(&server, None, None, &related).unwrap() {
+            Response::Ok(value) => assert!(!value.as_array().unwrap().is_empty()),
+            Response::Error(err) => panic!("{}", err),
+        }
+        let missing = Request::Metadata {
+            file: String::from("no/such.rs"),
+        };
+        match query_server(&server, None, None, &missing).unwrap() {
+            Response::Error(err) => assert!(err.contains("not in the graph")),
+            Response::Ok(value) => panic!("{}", value),
+        }
+
+        // streamed in chunks, the same lines as the export
+        let response = ureq::get(&format!("{}relations", server)).call().unwrap();
+        assert_eq!(response.content_type(), "application/x-ndjson");
+        let mut body = Vec::new();
+        response.into_reader().read_to_end(&mut body).unwrap();
+        let mut expected = Vec::new();
+        to_jsonl(&GRAPH_INST.read().unwrap(), &mut expected).unwrap();
+        assert_eq!(body, expected);
+    }
+}
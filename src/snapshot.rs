@@ -0,0 +1,281 @@
+use crate::graph::{project_roots, FileContext, Graph, ScoringConfig};
+use crate::symbol::{Interner, NodeType, Symbol, SymbolGraph};
+use cupido::relation::graph::RelationGraph as CupidoRelationGraph;
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+use std::sync::Arc;
+
+const MAGIC: &[u8; 4] = b"GSPH";
+// bump it when the layout of `Snapshot` changes
+const SNAPSHOT_VERSION: u32 = 11;
+
+#[derive(Serialize, Deserialize)]
+enum SnapshotNode {
+    File(String),
+    Symbol(Symbol),
+}
+
+#[derive(Serialize, Deserialize)]
+struct CommitRecord {
+    name: String,
+    files: Vec<String>,
+    issues: Vec<String>,
+    authors: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    project_path: String,
+    commit_id: Option<String>,
+    file_contexts: Vec<FileContext>,
+    // in the order of node indexes
+    nodes: Vec<SnapshotNode>,
+    edges: Vec<(usize, usize, usize)>,
+    commits: Vec<CommitRecord>,
+    co_changes: HashMap<String, HashMap<String, usize>>,
+    file_len: usize,
+    commit_size_limit: usize,
+    cross_language_ratio: f64,
+    def_limit: usize,
+    scoring: ScoringConfig,
+    commit_decays: HashMap<String, f64>,
+    symbol_idf: HashMap<Arc<str>, f64>,
+    packages: Vec<String>,
+    truncated_files: HashSet<String>,
+    build_timings: Vec<(String, f64)>,
+}
+
+fn commit_records(relation_graph: &CupidoRelationGraph) -> Vec<CommitRecord> {
+    relation_graph
+        .commits()
+        .into_iter()
+        .map(|name| CommitRecord {
+            files: relation_graph
+                .commit_related_files(&name)
+                .unwrap_or_default(),
+            issues: relation_graph
+                .commit_related_issues(&name)
+                .unwrap_or_default(),
+            authors: relation_graph
+                .commit_related_authors(&name)
+                .unwrap_or_default(),
+            name,
+        })
+        .collect()
+}
+
+fn relation_graph_of(commits: &[CommitRecord]) -> CupidoRelationGraph {
+    let mut relation_graph = CupidoRelationGraph::new();
+    for commit in commits {
+        relation_graph.add_commit_node(&commit.name);
+        for file in &commit.files {
+            relation_graph.add_file_node(file);
+            relation_graph.add_edge_file2commit(file, &commit.name);
+        }
+        for issue in &commit.issues {
+            relation_graph.add_issue_node(issue);
+            for file in &commit.files {
+                relation_graph.add_edge_file2issue(file, issue);
+            }
+            relation_graph.add_edge_commit2issue(&commit.name, issue);
+        }
+        for author in &commit.authors {
+            relation_graph.add_author_node(author);
+            relation_graph.add_edge_author2commit(author, &commit.name);
+        }
+    }
+    relation_graph
+}
+
+/// A copy of the history of a graph, rebuilt from its commits like an index.
+pub(crate) fn copy_relation_graph(relation_graph: &CupidoRelationGraph) -> CupidoRelationGraph {
+    relation_graph_of(&commit_records(relation_graph))
+}
+
+impl Graph {
+    /// Write this graph to a binary index file, see `Graph::load`.
+    pub fn save(&self, path: &str) -> Result<()> {
+        let content = self.to_bytes()?;
+        if let Some(parent) = Path::new(path).parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, content)
+    }
+
+    /// The content of the index file written by `Graph::save`.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let nodes = self
+            .symbol_graph
+            .g
+            .node_weights()
+            .map(|node| match &node.node_type {
+                NodeType::File => SnapshotNode::File(node._id.to_string()),
+                NodeType::Symbol(_) => SnapshotNode::Symbol(node.get_symbol().unwrap()),
+            })
+            .collect();
+        let edges = self
+            .symbol_graph
+            .g
+            .edge_references()
+            .map(|edge| (edge.source().index(), edge.target().index(), *edge.weight()))
+            .collect();
+
+        let snapshot = Snapshot {
+            project_path: self.project_path.clone(),
+            commit_id: self.commit_id.clone(),
+            file_contexts: self.file_contexts.clone(),
+            nodes,
+            edges,
+            commits: commit_records(&self._relation_graph),
+            co_changes: self.co_changes.clone(),
+            file_len: self.file_len,
+            commit_size_limit: self.commit_size_limit,
+            cross_language_ratio: self.cross_language_ratio,
+            def_limit: self.def_limit,
+            scoring: self.scoring.clone(),
+            commit_decays: self.commit_decays.clone(),
+            symbol_idf: self.symbol_idf.clone(),
+            packages: self.packages.clone(),
+            truncated_files: self.truncated_files.clone(),
+            build_timings: self.build_timings.clone(),
+        };
+        let payload =
+            bincode::serialize(&snapshot).map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
+
+        let mut content = Vec::with_capacity(MAGIC.len() + 4 + payload.len());
+        content.extend_from_slice(MAGIC);
+        content.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+        content.extend_from_slice(&payload);
+        Ok(content)
+    }
+
+    /// Read a graph written by `Graph::save`, without any analysis.
+    pub fn load(path: &str) -> Result<Graph> {
+        let content = fs::read(path)?;
+        Graph::from_bytes(&content)
+            .map_err(|err| Error::new(err.kind(), format!("{}: {}", path, err)))
+    }
+
+    /// Read a graph from the content of an index file, see `Graph::to_bytes`.
+    pub fn from_bytes(content: &[u8]) -> Result<Graph> {
+        let header_len = MAGIC.len() + 4;
+        if content.len() < header_len || &content[..MAGIC.len()] != MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "not a gossiphs index"));
+        }
+        let version = u32::from_le_bytes(content[MAGIC.len()..header_len].try_into().unwrap());
+        if version != SNAPSHOT_VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "index version {} is not supported (expect {}), rebuild it",
+                    version, SNAPSHOT_VERSION
+                ),
+            ));
+        }
+        let snapshot: Snapshot = bincode::deserialize(&content[header_len..])
+            .map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
+
+        // every deserialized string is a new copy
+        let mut interner = Interner::default();
+        let mut symbol_graph = SymbolGraph::new();
+        for node in snapshot.nodes {
+            match node {
+                SnapshotNode::File(name) => symbol_graph.add_file(&name),
+                SnapshotNode::Symbol(mut symbol) => {
+                    interner.intern_symbol(&mut symbol);
+                    symbol_graph.add_symbol(symbol)
+                }
+            }
+        }
+        for (a, b, weight) in snapshot.edges {
+            symbol_graph
+                .g
+                .add_edge(NodeIndex::new(a), NodeIndex::new(b), weight);
+        }
+
+        let relation_graph = relation_graph_of(&snapshot.commits);
+
+        let mut file_contexts = snapshot.file_contexts;
+        file_contexts
+            .iter_mut()
+            .flat_map(|file_context| file_context.symbols.iter_mut())
+            .for_each(|symbol| interner.intern_symbol(symbol));
+
+        Ok(Graph {
+            project_roots: project_roots(&snapshot.project_path),
+            project_path: snapshot.project_path,
+            commit_id: snapshot.commit_id,
+            file_contexts,
+            _relation_graph: relation_graph,
+            symbol_graph,
+            co_changes: snapshot.co_changes,
+            related_index: None,
+            file_len: snapshot.file_len,
+            commit_size_limit: snapshot.commit_size_limit,
+            cross_language_ratio: snapshot.cross_language_ratio,
+            def_limit: snapshot.def_limit,
+            scoring: snapshot.scoring,
+            commit_decays: snapshot.commit_decays,
+            symbol_idf: snapshot.symbol_idf,
+            packages: snapshot.packages,
+            truncated_files: snapshot.truncated_files,
+            build_timings: snapshot.build_timings,
+            show_progress: true,
+            threads: 0,
+        })
+    }
+
+    /// The commit this graph is built from, `None` for the working tree.
+    pub fn commit_id(&self) -> Option<String> {
+        self.commit_id.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::{Graph, GraphConfig};
+
+    #[test]
+    fn save_and_load() {
+        let g = Graph::from(GraphConfig::default());
+        let path = std::env::temp_dir()
+            .join(format!("gossiphs-index-{}.bin", std::process::id()))
+            .to_string_lossy()
+            .into_owned();
+        g.save(&path).unwrap();
+        let loaded = Graph::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.commit_id(), g.commit_id());
+        assert_eq!(loaded.files(), g.files());
+        let file = String::from("src/extractor.rs");
+        let commits = |g: &Graph| {
+            let mut commits = g.list_file_commits(file.clone());
+            commits.sort();
+            commits
+        };
+        assert_eq!(commits(&loaded), commits(&g));
+        let scores = |g: &Graph| {
+            let mut scores = g
+                .related_files(file.clone())
+                .into_iter()
+                .map(|each| (each.name, each.score))
+                .collect::<Vec<_>>();
+            scores.sort();
+            scores
+        };
+        assert_eq!(scores(&loaded), scores(&g));
+
+        let copied = Graph::from_bytes(&g.to_bytes().unwrap()).unwrap();
+        assert_eq!(scores(&copied), scores(&g));
+
+        std::fs::write(&path, b"nothing").unwrap();
+        assert!(Graph::load(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,13 +1,13 @@
 use petgraph::graph::{NodeIndex, UnGraph};
 use petgraph::prelude::EdgeRef;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::hash::{Hash, Hasher};
 use std::sync::Arc;
-use pyo3::{pyclass, pymethods};
+use pyo3::{pyclass, pymethods, PyObject, PyResult, Python};
 use tree_sitter::Range;
 
-#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize, Deserialize)]
 #[pyclass]
 pub enum SymbolKind {
     DEF,
@@ -15,26 +15,107 @@ pub enum SymbolKind {
     NAMESPACE,
 }
 
+// what a symbol actually is, comes from the capture names of rules
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[pyclass]
+pub enum SymbolType {
+    FUNCTION,
+    METHOD,
+    CLASS,
+    TYPE,
+    VARIABLE,
+    UNKNOWN,
+}
+
+impl SymbolType {
+    pub fn from_capture(capture_name: &str) -> SymbolType {
+        match capture_name {
+            "function" => SymbolType::FUNCTION,
+            "method" | "function.method" => SymbolType::METHOD,
+            "class" => SymbolType::CLASS,
+            "type" => SymbolType::TYPE,
+            "variable" => SymbolType::VARIABLE,
+            _ => SymbolType::UNKNOWN,
+        }
+    }
+}
+
+// shared by all the symbols of a file, and all the symbols with the same name, see `Interner`
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 #[pyclass]
 pub struct Symbol {
-    #[pyo3(get)]
-    pub file: String,
+    pub file: Arc<str>,
 
-    #[pyo3(get)]
-    pub name: String,
+    pub name: Arc<str>,
 
     #[pyo3(get)]
     pub range: RangeWrapper,
 
+    #[pyo3(get)]
     pub kind: SymbolKind,
+
+    #[pyo3(get)]
+    pub symbol_type: SymbolType,
+
+    // def: enclosing declarations, like `Graph` for `Graph::from`
+    // ref: qualifier written before it, like `Graph` in `Graph::from()`
+    #[pyo3(get)]
+    pub scope: String,
+
+    // only captured for defs when `GraphConfig.capture_signatures` is on
+    #[pyo3(get)]
+    #[serde(default)]
+    pub signature: Option<String>,
+
+    #[pyo3(get)]
+    #[serde(default)]
+    pub doc: Option<String>,
+
+    // index among the symbols of the file with the same name and kind, in the order of positions
+    #[pyo3(get)]
+    #[serde(default)]
+    pub occurrence: usize,
 }
 
 #[pymethods]
 impl Symbol {
+    #[getter]
+    fn file(&self) -> &str {
+        &self.file
+    }
+
+    #[getter]
+    fn name(&self) -> &str {
+        &self.name
+    }
+
     fn is_def(&self) -> bool {
         self.kind == SymbolKind::DEF
     }
+
+    #[getter(id)]
+    fn py_id(&self) -> String {
+        self.id()
+    }
+
+    pub fn qualified_name(&self) -> String {
+        if self.scope.is_empty() {
+            return self.name.to_string();
+        }
+        format!("{}::{}", self.scope, self.name)
+    }
+
+    fn __repr__(&self) -> String {
+        crate::pyapi::repr("Symbol", self)
+    }
+
+    fn __eq__(&self, other: &Self) -> bool {
+        self == other
+    }
+
+    fn to_dict(&self, py: Python<'_>) -> PyResult<PyObject> {
+        crate::pyapi::to_dict(py, self)
+    }
 }
 
 #[derive(
@@ -51,7 +132,9 @@ pub struct Point {
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 #[pyclass]
 pub struct RangeWrapper {
+    #[pyo3(get)]
     pub start_byte: usize,
+    #[pyo3(get)]
     pub end_byte: usize,
     #[pyo3(get)]
     pub start_point: Point,
@@ -77,41 +160,131 @@ impl RangeWrapper {
 }
 
 impl Symbol {
-    pub fn new_def(file: String, name: String, range: Range) -> Symbol {
+    pub fn new_def(file: Arc<str>, name: Arc<str>, range: Range) -> Symbol {
         Symbol {
             file,
             name,
             kind: SymbolKind::DEF,
             range: RangeWrapper::from(range),
+            symbol_type: SymbolType::UNKNOWN,
+            scope: String::new(),
+            signature: None,
+            doc: None,
+            occurrence: 0,
         }
     }
 
-    pub fn new_ref(file: String, name: String, range: Range) -> Symbol {
+    pub fn new_ref(file: Arc<str>, name: Arc<str>, range: Range) -> Symbol {
         Symbol {
             file,
             name,
             kind: SymbolKind::REF,
             range: RangeWrapper::from(range),
+            symbol_type: SymbolType::UNKNOWN,
+            scope: String::new(),
+            signature: None,
+            doc: None,
+            occurrence: 0,
         }
     }
 
-    pub fn new_namespace(file: String, name: String, range: Range) -> Symbol {
+    pub fn new_namespace(file: Arc<str>, name: Arc<str>, range: Range) -> Symbol {
         Symbol {
             file,
             name,
             kind: SymbolKind::NAMESPACE,
             range: RangeWrapper::from(range),
+            symbol_type: SymbolType::UNKNOWN,
+            scope: String::new(),
+            signature: None,
+            doc: None,
+            occurrence: 0,
         }
     }
 
+    /// Stays the same across runs and the edits of other symbols, unlike the position.
+    /// Hash of the file, the name, the kind and `occurrence`.
     pub fn id(&self) -> String {
-        format!("{}{}", self.file, self.range.start_byte)
+        format!("{:016x}", self.id_u64())
+    }
+
+    /// `id` as a number, without allocating.
+    pub fn id_u64(&self) -> u64 {
+        // fnv-1a, the std hashers are not guaranteed to be the same across rust versions
+        let mut hash: u64 = 0xcbf29ce484222325;
+        let kind: &[u8] = match self.kind {
+            SymbolKind::DEF => b"DEF",
+            SymbolKind::REF => b"REF",
+            SymbolKind::NAMESPACE => b"NAMESPACE",
+        };
+        // decimal digits of occurrence, as the ids have always been
+        let mut digits = [0u8; 20];
+        let mut start = digits.len();
+        let mut occurrence = self.occurrence;
+        loop {
+            start -= 1;
+            digits[start] = b'0' + (occurrence % 10) as u8;
+            occurrence /= 10;
+            if occurrence == 0 {
+                break;
+            }
+        }
+        for part in [
+            self.file.as_bytes(),
+            self.name.as_bytes(),
+            kind,
+            &digits[start..],
+        ] {
+            for &byte in part.iter().chain(std::iter::once(&0)) {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+        }
+        hash
+    }
+}
+
+/// Number the symbols of a file sharing a name and a kind by their positions, see `Symbol::id`.
+pub fn assign_occurrences(symbols: &mut [Symbol]) {
+    let mut order: Vec<usize> = (0..symbols.len()).collect();
+    order.sort_by_key(|&index| symbols[index].range.start_byte);
+    let mut counter: HashMap<(Arc<str>, SymbolKind), usize> = HashMap::new();
+    for index in order {
+        let symbol = &mut symbols[index];
+        let count = counter
+            .entry((symbol.name.clone(), symbol.kind.clone()))
+            .or_default();
+        symbol.occurrence = *count;
+        *count += 1;
+    }
+}
+
+/// Hands out one shared copy for equal strings.
+#[derive(Default)]
+pub struct Interner {
+    strings: HashSet<Arc<str>>,
+}
+
+impl Interner {
+    pub fn intern(&mut self, s: &str) -> Arc<str> {
+        if let Some(interned) = self.strings.get(s) {
+            return interned.clone();
+        }
+        let interned: Arc<str> = Arc::from(s);
+        self.strings.insert(interned.clone());
+        interned
+    }
+
+    /// Share the file and the name with the symbols interned before.
+    pub fn intern_symbol(&mut self, symbol: &mut Symbol) {
+        symbol.file = self.intern(&symbol.file);
+        symbol.name = self.intern(&symbol.name);
     }
 }
 
 impl Hash for Symbol {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.id().hash(state);
+        state.write_u64(self.id_u64());
     }
 }
 
@@ -128,7 +301,7 @@ pub struct SymbolData {
 
 #[derive(Clone)]
 pub struct NodeData {
-    pub(crate) _id: Arc<String>,
+    pub(crate) _id: Arc<str>,
     pub(crate) node_type: NodeType,
 }
 
@@ -142,11 +315,17 @@ impl NodeData {
 }
 
 pub struct SymbolGraph {
-    pub(crate) file_mapping: HashMap<Arc<String>, NodeIndex>,
-    pub(crate) symbol_mapping: HashMap<Arc<String>, NodeIndex>,
+    pub(crate) file_mapping: HashMap<Arc<str>, NodeIndex>,
+    pub(crate) symbol_mapping: HashMap<Arc<str>, NodeIndex>,
     pub(crate) g: UnGraph<NodeData, usize>,
 }
 
+impl Default for SymbolGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SymbolGraph {
     pub fn new() -> SymbolGraph {
         SymbolGraph {
@@ -156,12 +335,12 @@ impl SymbolGraph {
         }
     }
 
-    pub(crate) fn add_file(&mut self, name: &String) {
-        let id = Arc::new(name.clone());
-        if self.file_mapping.contains_key(&id) {
+    pub(crate) fn add_file(&mut self, name: &str) {
+        if self.file_mapping.contains_key(name) {
             return;
         }
 
+        let id: Arc<str> = Arc::from(name);
         let index = self.g.add_node(NodeData {
             _id: id.clone(),
             node_type: NodeType::File,
@@ -170,7 +349,7 @@ impl SymbolGraph {
     }
 
     pub(crate) fn add_symbol(&mut self, symbol: Symbol) {
-        let id = Arc::new(symbol.id());
+        let id: Arc<str> = Arc::from(symbol.id());
         if self.symbol_mapping.contains_key(&id) {
             return;
         }
@@ -182,12 +361,12 @@ impl SymbolGraph {
         self.symbol_mapping.entry(id).or_insert(index);
     }
 
-    pub(crate) fn link_file_to_symbol(&mut self, name: &String, symbol: &Symbol) {
+    pub(crate) fn link_file_to_symbol(&mut self, name: &str, symbol: &Symbol) {
         if let (Some(file_index), Some(symbol_index)) = (
             self.file_mapping.get(name),
-            self.symbol_mapping.get(&symbol.id()),
+            self.symbol_mapping.get(symbol.id().as_str()),
         ) {
-            if let Some(..) = self.g.find_edge(*file_index, *symbol_index) {
+            if self.g.find_edge(*file_index, *symbol_index).is_some() {
                 return;
             }
             self.g.add_edge(*file_index, *symbol_index, 0);
@@ -196,17 +375,17 @@ impl SymbolGraph {
 
     pub(crate) fn link_symbol_to_symbol(&mut self, a: &Symbol, b: &Symbol) {
         if let (Some(a_index), Some(b_index)) = (
-            self.symbol_mapping.get(&a.id()),
-            self.symbol_mapping.get(&b.id()),
+            self.symbol_mapping.get(a.id().as_str()),
+            self.symbol_mapping.get(b.id().as_str()),
         ) {
-            if let Some(..) = self.g.find_edge(*a_index, *b_index) {
+            if self.g.find_edge(*a_index, *b_index).is_some() {
                 return;
             }
             self.g.add_edge(*a_index, *b_index, 0);
         }
     }
 
-    pub(crate) fn enhance_symbol_to_symbol(&mut self, a: &String, b: &String, ratio: usize) {
+    pub(crate) fn enhance_symbol_to_symbol(&mut self, a: &str, b: &str, ratio: usize) {
         if let (Some(a_index), Some(b_index)) =
             (self.symbol_mapping.get(a), self.symbol_mapping.get(b))
         {
@@ -226,43 +405,37 @@ impl SymbolGraph {
             .filter_map(|edge| {
                 let target_idx = edge.target();
                 let weight = *edge.weight();
-                return if let Some(symbol) = self.g[target_idx].get_symbol() {
-                    Some((symbol.clone(), weight))
-                } else {
-                    // not a symbol node
-                    None
-                };
+                self.g[target_idx].get_symbol().map(|symbol| (symbol.clone(), weight))
             })
             .collect()
     }
 
-    pub fn list_symbols(&self, file_name: &String) -> Vec<Symbol> {
+    pub fn list_symbols(&self, file_name: &str) -> Vec<Symbol> {
         if !self.file_mapping.contains_key(file_name) {
             return Vec::new();
         }
 
         let file_index = self.file_mapping.get(file_name).unwrap();
         self.neighbor_symbols(*file_index)
-            .keys()
-            .map(|each| each.clone())
+            .keys().cloned()
             .collect()
     }
 
-    pub fn list_definitions(&self, file_name: &String) -> Vec<Symbol> {
+    pub fn list_definitions(&self, file_name: &str) -> Vec<Symbol> {
         self.list_symbols(file_name)
             .into_iter()
             .filter(|symbol| symbol.kind == SymbolKind::DEF)
             .collect()
     }
 
-    pub fn list_references(&self, file_name: &String) -> Vec<Symbol> {
+    pub fn list_references(&self, file_name: &str) -> Vec<Symbol> {
         self.list_symbols(file_name)
             .into_iter()
             .filter(|symbol| symbol.kind == SymbolKind::REF)
             .collect()
     }
 
-    pub fn list_references_by_definition(&self, symbol_id: &String) -> HashMap<Symbol, usize> {
+    pub fn list_references_by_definition(&self, symbol_id: &str) -> HashMap<Symbol, usize> {
         if !self.symbol_mapping.contains_key(symbol_id) {
             return HashMap::new();
         }
@@ -271,7 +444,7 @@ impl SymbolGraph {
         self.neighbor_symbols(*def_index)
     }
 
-    pub fn list_definitions_by_reference(&self, symbol_id: &String) -> HashMap<Symbol, usize> {
+    pub fn list_definitions_by_reference(&self, symbol_id: &str) -> HashMap<Symbol, usize> {
         // there are more than one possible definitions
         if !self.symbol_mapping.contains_key(symbol_id) {
             return HashMap::new();
@@ -281,16 +454,16 @@ impl SymbolGraph {
         self.neighbor_symbols(*ref_index)
     }
 
-    pub fn pairs_between_files(&self, src_file: &String, dst_file: &String) -> Vec<DefRefPair> {
+    pub fn pairs_between_files(&self, src_file: &str, dst_file: &str) -> Vec<DefRefPair> {
         let defs = self.list_definitions(src_file);
         let refs = self.list_references(dst_file);
 
         let mut pairs = vec![];
 
         for each_def in &defs {
-            let def_index = self.symbol_mapping[&each_def.id()];
+            let def_index = self.symbol_mapping[each_def.id().as_str()];
             for each_ref in &refs {
-                let ref_index = self.symbol_mapping[&each_ref.id()];
+                let ref_index = self.symbol_mapping[each_ref.id().as_str()];
                 if self.g.contains_edge(def_index, ref_index) {
                     pairs.push(DefRefPair {
                         src_symbol: each_def.clone(),
@@ -303,6 +476,7 @@ impl SymbolGraph {
     }
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 #[pyclass]
 pub struct DefRefPair {
     #[pyo3(get)]
@@ -310,3 +484,26 @@ pub struct DefRefPair {
     #[pyo3(get)]
     pub dst_symbol: Symbol,
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::symbol::Symbol;
+    use std::sync::Arc;
+    use tree_sitter::{Point, Range};
+
+    #[test]
+    fn stable_id() {
+        let range = Range {
+            start_byte: 0,
+            end_byte: 6,
+            start_point: Point::new(0, 0),
+            end_point: Point::new(0, 6),
+        };
+        let def = Symbol::new_def(Arc::from("src/a.rs"), Arc::from("shared"), range);
+        assert_eq!(def.id(), "38000600b18aeb98");
+        let mut reference = Symbol::new_ref(Arc::from("src/a.rs"), Arc::from("shared"), range);
+        reference.occurrence = 12;
+        assert_eq!(reference.id(), "66f1a88935ef9cc3");
+        assert_eq!(reference.id_u64(), 0x66f1a88935ef9cc3);
+    }
+}
@@ -0,0 +1,50 @@
+use crate::export::to_jsonl;
+use crate::graph::{Graph, GraphConfig};
+use std::collections::BTreeMap;
+use wasm_bindgen::prelude::*;
+
+/// The graph of the files of a page, for analyzing small repos in the browser.
+/// Results are returned as json strings, like the ones of the server.
+#[wasm_bindgen(js_name = Graph)]
+pub struct WasmGraph(Graph);
+
+#[wasm_bindgen(js_class = Graph)]
+impl WasmGraph {
+    /// `files` is a json object of the contents by path, like `{"src/a.rs": "fn a() {}"}`.
+    /// See `Graph::from_sources`, there is no history in the browser.
+    #[wasm_bindgen(constructor)]
+    pub fn new(files: &str) -> Result<WasmGraph, JsError> {
+        let files: BTreeMap<String, String> = serde_json::from_str(files)?;
+        let mut conf = GraphConfig::default();
+        conf.show_progress = false;
+        // no threads without the atomics of wasm
+        conf.threads = 1;
+        Ok(WasmGraph(Graph::from_sources(
+            conf,
+            files.into_iter().collect(),
+        )?))
+    }
+
+    pub fn files(&self) -> Result<String, JsError> {
+        let mut files: Vec<String> = self.0.files().into_iter().collect();
+        files.sort();
+        Ok(serde_json::to_string(&files)?)
+    }
+
+    #[wasm_bindgen(js_name = relatedFiles)]
+    pub fn related_files(&self, file: String) -> Result<String, JsError> {
+        Ok(serde_json::to_string(&self.0.related_files(file))?)
+    }
+
+    #[wasm_bindgen(js_name = fileMetadata)]
+    pub fn file_metadata(&self, file: String) -> Result<String, JsError> {
+        Ok(serde_json::to_string(&self.0.file_metadata(file))?)
+    }
+
+    /// The whole graph as newline delimited json, see `export --format jsonl`.
+    pub fn relations(&self) -> Result<String, JsError> {
+        let mut buffer = Vec::new();
+        to_jsonl(&self.0, &mut buffer)?;
+        Ok(String::from_utf8(buffer)?)
+    }
+}
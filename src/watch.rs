@@ -0,0 +1,174 @@
+use crate::graph::{FileSource, Graph, GraphConfig};
+use git2::Repository;
+use notify::{Event, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::{mpsc, Arc, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+// saving, checking out and rebasing all come as a burst of events
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Rebuild `graph` in background whenever the working tree or HEAD changes,
+/// and hand the new graph to `on_update`. Edits of the working tree only extract the changed files again,
+/// new commits and checkouts walk the history and rebuild everything.
+pub fn spawn<F>(
+    conf: GraphConfig,
+    graph: Arc<RwLock<Graph>>,
+    on_update: F,
+) -> thread::JoinHandle<()>
+where
+    F: Fn(Graph) + Send + 'static,
+{
+    thread::spawn(move || {
+        let repo = match Repository::open(&conf.project_path) {
+            Ok(repo) => repo,
+            Err(err) => {
+                warn!("Failed to open repository, watch disabled: {:?}", err);
+                return;
+            }
+        };
+        let workdir = match repo.workdir().map(|dir| dir.canonicalize()) {
+            Some(Ok(dir)) => dir,
+            _ => {
+                warn!("Bare repository, watch disabled");
+                return;
+            }
+        };
+
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = match notify::recommended_watcher(tx) {
+            Ok(watcher) => watcher,
+            Err(err) => {
+                warn!("Failed to create watcher: {:?}", err);
+                return;
+            }
+        };
+        if let Err(err) = watcher.watch(&workdir, RecursiveMode::Recursive) {
+            warn!("Failed to watch {:?}: {:?}", workdir, err);
+            return;
+        }
+        info!("watching {:?}", workdir);
+
+        while let Ok(event) = rx.recv() {
+            let mut changes = Changes::default();
+            changes.add(&repo, &workdir, event);
+            while let Ok(event) = rx.recv_timeout(DEBOUNCE) {
+                changes.add(&repo, &workdir, event);
+            }
+
+            let start = Instant::now();
+            let rebuilt = if changes.history {
+                // HEAD can be invalid for a moment, e.g. in the middle of a rebase
+                Graph::try_from(conf.clone())
+            } else if !changes.files.is_empty() && conf.source == FileSource::WorkingTree {
+                let changed: Vec<String> = changes.files.into_iter().collect();
+                let sources = graph.read().unwrap().sources();
+                Graph::update_sources(&conf, sources, &changed)
+            } else {
+                // nothing changed, or the files are read from a commit
+                continue;
+            };
+            match rebuilt {
+                Ok(g) => {
+                    info!("graph rebuilt in {:?}", start.elapsed());
+                    on_update(g);
+                }
+                Err(err) => warn!("Failed to rebuild graph, keep the old one: {}", err),
+            }
+        }
+    })
+}
+
+/// What a burst of events changed.
+#[derive(Default)]
+struct Changes {
+    // new commits and checkouts, the history is walked again
+    history: bool,
+    // paths relative to the working tree, files or directories
+    files: HashSet<String>,
+}
+
+impl Changes {
+    fn add(&mut self, repo: &Repository, workdir: &Path, event: notify::Result<Event>) {
+        let event = match event {
+            Ok(event) => event,
+            Err(err) => {
+                warn!("Watch error: {:?}", err);
+                return;
+            }
+        };
+        if !(event.kind.is_create() || event.kind.is_modify() || event.kind.is_remove()) {
+            return;
+        }
+        for path in &event.paths {
+            let Ok(relative) = path.strip_prefix(workdir) else {
+                continue;
+            };
+            match relative.strip_prefix(".git") {
+                // objects are written before HEAD and the refs
+                Ok(git_path) => {
+                    let git_path = git_path.to_string_lossy();
+                    if !git_path.ends_with(".lock")
+                        && (git_path == "HEAD"
+                            || git_path == "packed-refs"
+                            || git_path.starts_with("refs"))
+                    {
+                        self.history = true;
+                    }
+                }
+                // the cache of gossiphs itself is ignored as well
+                Err(_) => {
+                    if !repo.is_path_ignored(relative).unwrap_or(false) {
+                        self.files
+                            .insert(relative.to_string_lossy().replace('\\', "/"));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::{FileSource, Graph, GraphConfig};
+    use crate::watch::spawn;
+    use git2::{Repository, Signature};
+    use std::path::Path;
+    use std::sync::{mpsc, Arc, RwLock};
+    use std::time::Duration;
+
+    #[test]
+    fn rebuild_on_change() {
+        let repo_path = std::env::temp_dir().join(format!("gossiphs-watch-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&repo_path);
+        let repo = Repository::init(&repo_path).unwrap();
+        std::fs::write(repo_path.join("a.rs"), "fn a() {}\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("a.rs")).unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("gossiphs", "gossiphs@example.com").unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])
+            .unwrap();
+
+        let mut config = GraphConfig::default();
+        config.project_path = repo_path.to_string_lossy().into_owned();
+        config.source = FileSource::WorkingTree;
+        let graph = Arc::new(RwLock::new(Graph::try_from(config.clone()).unwrap()));
+        let (tx, rx) = mpsc::channel();
+        spawn(config, graph, move |g| tx.send(g).unwrap());
+        // wait for the watcher to be registered
+        std::thread::sleep(Duration::from_millis(500));
+
+        std::fs::write(repo_path.join("b.rs"), "fn b() { a() }\n").unwrap();
+        let g = rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(g.files().contains("b.rs"));
+        assert!(g.files().contains("a.rs"));
+        // only the working tree changed, the history is reused
+        assert!(g.build_timings.iter().all(|(stage, _)| stage != "history"));
+
+        std::fs::remove_dir_all(&repo_path).unwrap();
+    }
+}